@@ -661,6 +661,15 @@ public protocol HudEngineProtocol : AnyObject {
      */
     func getProjectStatus(projectPath: String)  -> ProjectStatus?
     
+    /**
+     * Time per project and local calendar day spent in each session state,
+     * plus time spent waiting on the human, for the last `days` days
+     * including today. Days are cut at the current local UTC offset.
+     *
+     * Requires the daemon; errors when it is disabled or unreachable.
+     */
+    func getProjectTime(days: UInt32) throws  -> [ProjectTimeAccounting]
+    
     /**
      * Gets the session state for a single project.
      * Uses daemon session snapshots for reliable state.
@@ -1170,6 +1179,21 @@ open func getProjectStatus(projectPath: String) -> ProjectStatus? {
         FfiConverterString.lower(projectPath),$0
     )
 })
+}
+    
+    /**
+     * Time per project and local calendar day spent in each session state,
+     * plus time spent waiting on the human, for the last `days` days
+     * including today. Days are cut at the current local UTC offset.
+     *
+     * Requires the daemon; errors when it is disabled or unreachable.
+     */
+open func getProjectTime(days: UInt32)throws  -> [ProjectTimeAccounting] {
+    return try  FfiConverterSequenceTypeProjectTimeAccounting.lift(try rustCallWithError(FfiConverterTypeHudFfiError.lift) {
+    uniffi_hud_core_fn_method_hudengine_get_project_time(self.uniffiClonePointer(),
+        FfiConverterUInt32.lower(days),$0
+    )
+})
 }
     
     /**
@@ -4585,6 +4609,136 @@ public func FfiConverterTypeProjectStatus_lower(_ value: ProjectStatus) -> RustB
 }
 
 
+/**
+ * Wall time one project's sessions spent in each state on one day.
+ * Sourced from the daemon's session transition history.
+ */
+public struct ProjectTimeAccounting {
+    public var projectPath: String
+    /**
+     * Local calendar day, `YYYY-MM-DD`.
+     */
+    public var day: String
+    public var workingSecs: UInt64
+    public var waitingSecs: UInt64
+    public var readySecs: UInt64
+    public var idleSecs: UInt64
+    public var compactingSecs: UInt64
+    /**
+     * Time from a permission prompt or waiting notification until it was answered.
+     */
+    public var waitingOnHumanSecs: UInt64
+
+    // Default memberwise initializers are never public by default, so we
+    // declare one manually.
+    public init(projectPath: String, 
+        /**
+         * Local calendar day, `YYYY-MM-DD`.
+         */day: String, workingSecs: UInt64, waitingSecs: UInt64, readySecs: UInt64, idleSecs: UInt64, compactingSecs: UInt64, 
+        /**
+         * Time from a permission prompt or waiting notification until it was answered.
+         */waitingOnHumanSecs: UInt64) {
+        self.projectPath = projectPath
+        self.day = day
+        self.workingSecs = workingSecs
+        self.waitingSecs = waitingSecs
+        self.readySecs = readySecs
+        self.idleSecs = idleSecs
+        self.compactingSecs = compactingSecs
+        self.waitingOnHumanSecs = waitingOnHumanSecs
+    }
+}
+
+
+
+extension ProjectTimeAccounting: Equatable, Hashable {
+    public static func ==(lhs: ProjectTimeAccounting, rhs: ProjectTimeAccounting) -> Bool {
+        if lhs.projectPath != rhs.projectPath {
+            return false
+        }
+        if lhs.day != rhs.day {
+            return false
+        }
+        if lhs.workingSecs != rhs.workingSecs {
+            return false
+        }
+        if lhs.waitingSecs != rhs.waitingSecs {
+            return false
+        }
+        if lhs.readySecs != rhs.readySecs {
+            return false
+        }
+        if lhs.idleSecs != rhs.idleSecs {
+            return false
+        }
+        if lhs.compactingSecs != rhs.compactingSecs {
+            return false
+        }
+        if lhs.waitingOnHumanSecs != rhs.waitingOnHumanSecs {
+            return false
+        }
+        return true
+    }
+
+    public func hash(into hasher: inout Hasher) {
+        hasher.combine(projectPath)
+        hasher.combine(day)
+        hasher.combine(workingSecs)
+        hasher.combine(waitingSecs)
+        hasher.combine(readySecs)
+        hasher.combine(idleSecs)
+        hasher.combine(compactingSecs)
+        hasher.combine(waitingOnHumanSecs)
+    }
+}
+
+
+#if swift(>=5.8)
+@_documentation(visibility: private)
+#endif
+public struct FfiConverterTypeProjectTimeAccounting: FfiConverterRustBuffer {
+    public static func read(from buf: inout (data: Data, offset: Data.Index)) throws -> ProjectTimeAccounting {
+        return
+            try ProjectTimeAccounting(
+                projectPath: FfiConverterString.read(from: &buf), 
+                day: FfiConverterString.read(from: &buf), 
+                workingSecs: FfiConverterUInt64.read(from: &buf), 
+                waitingSecs: FfiConverterUInt64.read(from: &buf), 
+                readySecs: FfiConverterUInt64.read(from: &buf), 
+                idleSecs: FfiConverterUInt64.read(from: &buf), 
+                compactingSecs: FfiConverterUInt64.read(from: &buf), 
+                waitingOnHumanSecs: FfiConverterUInt64.read(from: &buf)
+        )
+    }
+
+    public static func write(_ value: ProjectTimeAccounting, into buf: inout [UInt8]) {
+        FfiConverterString.write(value.projectPath, into: &buf)
+        FfiConverterString.write(value.day, into: &buf)
+        FfiConverterUInt64.write(value.workingSecs, into: &buf)
+        FfiConverterUInt64.write(value.waitingSecs, into: &buf)
+        FfiConverterUInt64.write(value.readySecs, into: &buf)
+        FfiConverterUInt64.write(value.idleSecs, into: &buf)
+        FfiConverterUInt64.write(value.compactingSecs, into: &buf)
+        FfiConverterUInt64.write(value.waitingOnHumanSecs, into: &buf)
+    }
+}
+
+
+#if swift(>=5.8)
+@_documentation(visibility: private)
+#endif
+public func FfiConverterTypeProjectTimeAccounting_lift(_ buf: RustBuffer) throws -> ProjectTimeAccounting {
+    return try FfiConverterTypeProjectTimeAccounting.lift(buf)
+}
+
+#if swift(>=5.8)
+@_documentation(visibility: private)
+#endif
+public func FfiConverterTypeProjectTimeAccounting_lower(_ value: ProjectTimeAccounting) -> RustBuffer {
+    return FfiConverterTypeProjectTimeAccounting.lower(value)
+}
+
+
 public struct SetupStatus {
     public var dependencies: [DependencyStatus]
     public var hooks: HookStatus
@@ -7166,6 +7320,31 @@ fileprivate struct FfiConverterSequenceTypeProject: FfiConverterRustBuffer {
     }
 }
 
+#if swift(>=5.8)
+@_documentation(visibility: private)
+#endif
+fileprivate struct FfiConverterSequenceTypeProjectTimeAccounting: FfiConverterRustBuffer {
+    typealias SwiftType = [ProjectTimeAccounting]
+
+    public static func write(_ value: [ProjectTimeAccounting], into buf: inout [UInt8]) {
+        let len = Int32(value.count)
+        writeInt(&buf, len)
+        for item in value {
+            FfiConverterTypeProjectTimeAccounting.write(item, into: &buf)
+        }
+    }
+
+    public static func read(from buf: inout (data: Data, offset: Data.Index)) throws -> [ProjectTimeAccounting] {
+        let len: Int32 = try readInt(&buf)
+        var seq = [ProjectTimeAccounting]()
+        seq.reserveCapacity(Int(len))
+        for _ in 0 ..< len {
+            seq.append(try FfiConverterTypeProjectTimeAccounting.read(from: &buf))
+        }
+        return seq
+    }
+}
+
 #if swift(>=5.8)
 @_documentation(visibility: private)
 #endif
@@ -7419,6 +7598,9 @@ private var initializationResult: InitializationResult = {
     if (uniffi_hud_core_checksum_method_hudengine_get_project_status() != 14524) {
         return InitializationResult.apiChecksumMismatch
     }
+    if (uniffi_hud_core_checksum_method_hudengine_get_project_time() != 48082) {
+        return InitializationResult.apiChecksumMismatch
+    }
     if (uniffi_hud_core_checksum_method_hudengine_get_session_state() != 61231) {
         return InitializationResult.apiChecksumMismatch
     }
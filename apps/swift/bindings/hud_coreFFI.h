@@ -352,6 +352,11 @@ RustBuffer uniffi_hud_core_fn_method_hudengine_get_primary_agent_session(void*_N
 RustBuffer uniffi_hud_core_fn_method_hudengine_get_project_status(void*_Nonnull ptr, RustBuffer project_path, RustCallStatus *_Nonnull out_status
 );
 #endif
+#ifndef UNIFFI_FFIDEF_UNIFFI_HUD_CORE_FN_METHOD_HUDENGINE_GET_PROJECT_TIME
+#define UNIFFI_FFIDEF_UNIFFI_HUD_CORE_FN_METHOD_HUDENGINE_GET_PROJECT_TIME
+RustBuffer uniffi_hud_core_fn_method_hudengine_get_project_time(void*_Nonnull ptr, uint32_t days, RustCallStatus *_Nonnull out_status
+);
+#endif
 #ifndef UNIFFI_FFIDEF_UNIFFI_HUD_CORE_FN_METHOD_HUDENGINE_GET_SESSION_STATE
 #define UNIFFI_FFIDEF_UNIFFI_HUD_CORE_FN_METHOD_HUDENGINE_GET_SESSION_STATE
 RustBuffer uniffi_hud_core_fn_method_hudengine_get_session_state(void*_Nonnull ptr, RustBuffer project_path, RustCallStatus *_Nonnull out_status
@@ -874,6 +879,12 @@ uint16_t uniffi_hud_core_checksum_method_hudengine_get_primary_agent_session(voi
 #define UNIFFI_FFIDEF_UNIFFI_HUD_CORE_CHECKSUM_METHOD_HUDENGINE_GET_PROJECT_STATUS
 uint16_t uniffi_hud_core_checksum_method_hudengine_get_project_status(void
     
+);
+#endif
+#ifndef UNIFFI_FFIDEF_UNIFFI_HUD_CORE_CHECKSUM_METHOD_HUDENGINE_GET_PROJECT_TIME
+#define UNIFFI_FFIDEF_UNIFFI_HUD_CORE_CHECKSUM_METHOD_HUDENGINE_GET_PROJECT_TIME
+uint16_t uniffi_hud_core_checksum_method_hudengine_get_project_time(void
+    
 );
 #endif
 #ifndef UNIFFI_FFIDEF_UNIFFI_HUD_CORE_CHECKSUM_METHOD_HUDENGINE_GET_SESSION_STATE
@@ -0,0 +1,120 @@
+//! Unix socket connect with a deadline.
+//!
+//! `UnixStream::connect` has no timeout, and a blocking connect waits for room
+//! in the listener backlog when the daemon is saturated. We create the socket
+//! ourselves so `SO_SNDTIMEO` bounds that wait before connecting.
+
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+pub(crate) fn connect(path: &Path, timeout: Duration) -> io::Result<UnixStream> {
+    let bytes = path.as_os_str().as_bytes();
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    if bytes.is_empty() || bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "socket path is empty or too long",
+        ));
+    }
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+    let path_offset = addr.sun_path.as_ptr() as usize - (&addr as *const _ as usize);
+    let len = (path_offset + bytes.len() + 1) as libc::socklen_t;
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    ))]
+    {
+        addr.sun_len = len as u8;
+    }
+
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Owns the fd from here on so every early return closes it.
+    let stream = unsafe { UnixStream::from_raw_fd(fd) };
+    unsafe {
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+    stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+
+    loop {
+        let rc = unsafe {
+            libc::connect(
+                stream.as_raw_fd(),
+                &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+                len,
+            )
+        };
+        if rc == 0 {
+            return Ok(stream);
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(code) if code == libc::EAGAIN || code == libc::EINPROGRESS => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out connecting to daemon socket",
+                ));
+            }
+            _ => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn connects_to_listening_socket() {
+        let dir = std::path::Path::new("/tmp").join(format!(
+            "cdc-connect-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket_path = dir.join("daemon.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        let stream = connect(&socket_path, Duration::from_millis(200)).expect("connect");
+        let (_accepted, _) = listener.accept().expect("accept");
+        drop(stream);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_socket_is_not_found() {
+        let err = connect(
+            std::path::Path::new("/tmp/cdc-definitely-missing.sock"),
+            Duration::from_millis(50),
+        )
+        .expect_err("missing socket");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn rejects_overlong_paths() {
+        let long = format!("/tmp/{}", "x".repeat(200));
+        let err =
+            connect(std::path::Path::new(&long), Duration::from_millis(50)).expect_err("long path");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
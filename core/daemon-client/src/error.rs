@@ -0,0 +1,80 @@
+//! Client error type.
+
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Home directory not found")]
+    HomeDirNotFound,
+
+    #[error("Failed to connect to daemon socket {}: {source}", path.display())]
+    Connect { path: PathBuf, source: io::Error },
+
+    #[error("Timed out waiting for daemon response")]
+    Timeout,
+
+    #[error("Daemon socket I/O failed: {0}")]
+    Io(#[source] io::Error),
+
+    #[error("Failed to encode request: {0}")]
+    Encode(String),
+
+    #[error("Daemon response was empty")]
+    EmptyResponse,
+
+    #[error("Response exceeded maximum size")]
+    ResponseTooLarge,
+
+    #[error("Invalid daemon response: {0}")]
+    InvalidResponse(String),
+
+    /// The daemon answered with `ok: false`.
+    #[error("{code}: {message}")]
+    Daemon { code: String, message: String },
+}
+
+impl ClientError {
+    /// Daemon error code, when the daemon itself rejected the request.
+    pub fn daemon_code(&self) -> Option<&str> {
+        match self {
+            ClientError::Daemon { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Whether resending the same request could succeed.
+    ///
+    /// Daemon-side errors are retried: requests are idempotent (events are
+    /// deduplicated by `event_id`) and transient failures surface that way.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            ClientError::HomeDirNotFound | ClientError::Encode(_) | ClientError::InvalidResponse(_)
+        )
+    }
+
+    /// Whether the daemon could not be reached or was too busy to answer,
+    /// as opposed to rejecting the request.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            ClientError::Connect { .. }
+            | ClientError::Timeout
+            | ClientError::Io(_)
+            | ClientError::EmptyResponse => true,
+            ClientError::Daemon { code, .. } => {
+                code == capacitor_daemon_protocol::ERROR_TOO_MANY_CONNECTIONS
+                    || code == capacitor_daemon_protocol::ERROR_WRITER_BUSY
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn from_io(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ClientError::Timeout,
+            _ => ClientError::Io(err),
+        }
+    }
+}
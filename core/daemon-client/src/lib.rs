@@ -0,0 +1,1167 @@
+//! Typed IPC client for capacitor-daemon.
+//!
+//! The daemon speaks newline-delimited JSON over a Unix socket, one request per
+//! connection (see `docs/daemon-ipc.md`). This crate owns the socket plumbing,
+//! timeouts and retries so tools don't each carry their own copy.
+//!
+//! ```no_run
+//! use capacitor_daemon_client::{ClientConfig, DaemonClient};
+//!
+//! let client = DaemonClient::new(ClientConfig::from_env()?);
+//! for session in client.sessions()? {
+//!     println!("{} {}", session.session_id, session.state.as_str());
+//! }
+//! # Ok::<(), capacitor_daemon_client::ClientError>(())
+//! ```
+
+mod connect;
+mod error;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod spool;
+
+pub use capacitor_daemon_protocol as protocol;
+pub use capacitor_daemon_protocol::{
+    ActivityEntry, ConfigChangeRecord, ConfigChangesQuery, EnrichedSession, EventAck,
+    EventBatchAck, EventBatchResult, EventStatus, EventsPage, EventsQuery, HealthResponse,
+    HelloResponse, MetricsResponse, ProcessLiveness, ProcessLivenessResponse, ProjectDayTime,
+    ProjectStateRecord, ProjectTimeQuery, RuntimeHealth, SecurityHealth, SessionState,
+    SessionTimeline, SessionTimelineRequest, SessionTransition, ShellEntry, ShellState,
+    SnapshotDelta, SubagentRecord, SubscribeAck, Tombstone, ToolCall, ToolCallOutcome,
+    ToolCallsQuery, ToolCallsResponse, ToolStats, WorktreeRecord, WorktreesQuery,
+};
+pub use error::ClientError;
+
+use capacitor_daemon_protocol::{
+    EventEnvelope, HelloRequest, Method, Request, Response, RoutingConfigView, RoutingDiagnostics,
+    RoutingSnapshot, SubscriptionMessage, SubscriptionTopic, MAX_REQUEST_BYTES,
+    MIN_PROTOCOL_VERSION,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const ENABLE_ENV: &str = "CAPACITOR_DAEMON_ENABLED";
+pub const SOCKET_ENV: &str = "CAPACITOR_DAEMON_SOCKET";
+const SOCKET_NAME: &str = "daemon.sock";
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+/// The daemon heartbeats every 15s; three missed beats means it is gone.
+const SUBSCRIPTION_IDLE_TIMEOUT_SECS: u64 = 45;
+
+/// Whether daemon IPC is enabled (`CAPACITOR_DAEMON_ENABLED`, default on).
+pub fn daemon_enabled() -> bool {
+    match env::var(ENABLE_ENV) {
+        Ok(value) => matches!(value.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
+        Err(_) => true,
+    }
+}
+
+/// `CAPACITOR_DAEMON_SOCKET`, or `~/.capacitor/daemon.sock`.
+pub fn default_socket_path() -> Result<PathBuf, ClientError> {
+    if let Ok(path) = env::var(SOCKET_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+    let home = dirs::home_dir().ok_or(ClientError::HomeDirNotFound)?;
+    Ok(home.join(".capacitor").join(SOCKET_NAME))
+}
+
+/// How many times a request is sent before giving up.
+///
+/// Retries resend the exact same bytes, so an event keeps its `event_id` and
+/// the daemon deduplicates it if the first attempt was applied but the
+/// response was lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn new(max_attempts: u32, delay: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            delay,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+    pub socket_path: PathBuf,
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub write_timeout: Duration,
+    pub retry: RetryPolicy,
+}
+
+impl ClientConfig {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        let timeout = Duration::from_millis(DEFAULT_TIMEOUT_MS);
+        Self {
+            socket_path: socket_path.into(),
+            connect_timeout: timeout,
+            read_timeout: timeout,
+            write_timeout: timeout,
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    /// Config for the socket at [`default_socket_path`].
+    pub fn from_env() -> Result<Self, ClientError> {
+        Ok(Self::new(default_socket_path()?))
+    }
+
+    /// Sets the connect, read and write timeouts at once.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self.read_timeout = timeout;
+        self.write_timeout = timeout;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DaemonClient {
+    config: ClientConfig,
+}
+
+impl DaemonClient {
+    pub fn new(config: ClientConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.config.socket_path
+    }
+
+    /// Sends a raw request and returns the daemon's successful response.
+    ///
+    /// The request is stamped with the protocol version that introduced
+    /// `method`, so daemons older than this crate still accept it. An
+    /// `ok: false` response becomes [`ClientError::Daemon`].
+    pub fn request(
+        &self,
+        method: Method,
+        params: Option<Value>,
+        id: Option<String>,
+    ) -> Result<Response, ClientError> {
+        let request = Request {
+            protocol_version: method.since_version(),
+            method,
+            id,
+            params,
+        };
+        let mut payload =
+            serde_json::to_vec(&request).map_err(|err| ClientError::Encode(err.to_string()))?;
+        payload.push(b'\n');
+
+        let retry = self.config.retry;
+        let mut attempt = 1;
+        loop {
+            match self.round_trip(&payload) {
+                Ok(response) => return Ok(response),
+                Err(err) if attempt < retry.max_attempts && err.is_retryable() => {
+                    tracing::warn!(
+                        error = %err,
+                        method = ?request.method,
+                        attempt,
+                        "Daemon request failed; retrying"
+                    );
+                    if !retry.delay.is_zero() {
+                        std::thread::sleep(retry.delay);
+                    }
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Negotiates a protocol version and learns the daemon's features.
+    ///
+    /// Daemons that predate the handshake cannot parse the method and answer
+    /// `invalid_json`; they are reported as speaking only the oldest version
+    /// with no optional features.
+    pub fn hello(&self, client: Option<&str>) -> Result<HelloResponse, ClientError> {
+        let hello = HelloRequest {
+            client: client.map(str::to_string),
+            ..HelloRequest::default()
+        };
+        let params =
+            serde_json::to_value(&hello).map_err(|err| ClientError::Encode(err.to_string()))?;
+        match self.call(Method::Hello, Some(params), "hello") {
+            Err(err) if err.daemon_code() == Some("invalid_json") => Ok(HelloResponse {
+                protocol_version: MIN_PROTOCOL_VERSION,
+                min_protocol_version: MIN_PROTOCOL_VERSION,
+                max_protocol_version: MIN_PROTOCOL_VERSION,
+                features: Vec::new(),
+                daemon_version: "unknown".to_string(),
+            }),
+            result => result,
+        }
+    }
+
+    pub fn health(&self) -> Result<HealthResponse, ClientError> {
+        self.call(Method::GetHealth, None, "health-check")
+    }
+
+    pub fn shell_state(&self) -> Result<ShellState, ClientError> {
+        self.call(Method::GetShellState, None, "shell-state")
+    }
+
+    /// Returns `None` when the daemon has never seen `pid`.
+    pub fn process_liveness(&self, pid: u32) -> Result<Option<ProcessLiveness>, ClientError> {
+        let response: ProcessLivenessResponse = self.call(
+            Method::GetProcessLiveness,
+            Some(serde_json::json!({ "pid": pid })),
+            "process-liveness",
+        )?;
+        Ok(response.into_option())
+    }
+
+    pub fn routing_snapshot(
+        &self,
+        project_path: &str,
+        workspace_id: Option<&str>,
+    ) -> Result<RoutingSnapshot, ClientError> {
+        self.call(
+            Method::GetRoutingSnapshot,
+            Some(routing_params(project_path, workspace_id)),
+            "routing-snapshot",
+        )
+    }
+
+    pub fn routing_diagnostics(
+        &self,
+        project_path: &str,
+        workspace_id: Option<&str>,
+    ) -> Result<RoutingDiagnostics, ClientError> {
+        self.call(
+            Method::GetRoutingDiagnostics,
+            Some(routing_params(project_path, workspace_id)),
+            "routing-diagnostics",
+        )
+    }
+
+    pub fn config_view(&self) -> Result<RoutingConfigView, ClientError> {
+        self.call(Method::GetConfig, None, "config")
+    }
+
+    pub fn sessions(&self) -> Result<Vec<EnrichedSession>, ClientError> {
+        self.call(Method::GetSessions, None, "sessions-snapshot")
+    }
+
+    /// Sessions changed or removed after `since_revision`.
+    pub fn sessions_delta(
+        &self,
+        since_revision: u64,
+    ) -> Result<SnapshotDelta<EnrichedSession>, ClientError> {
+        self.call(
+            Method::GetSessions,
+            Some(serde_json::json!({ "since_revision": since_revision })),
+            "sessions-delta",
+        )
+    }
+
+    pub fn project_states(&self) -> Result<Vec<ProjectStateRecord>, ClientError> {
+        self.call(Method::GetProjectStates, None, "project-states-snapshot")
+    }
+
+    /// Project states changed or removed after `since_revision`.
+    pub fn project_states_delta(
+        &self,
+        since_revision: u64,
+    ) -> Result<SnapshotDelta<ProjectStateRecord>, ClientError> {
+        self.call(
+            Method::GetProjectStates,
+            Some(serde_json::json!({ "since_revision": since_revision })),
+            "project-states-delta",
+        )
+    }
+
+    /// Recent file activity, newest first. The daemon caps `limit` at 1000.
+    pub fn activity(
+        &self,
+        session_id: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<ActivityEntry>, ClientError> {
+        let mut params = serde_json::Map::new();
+        if let Some(session_id) = session_id {
+            params.insert("session_id".to_string(), Value::from(session_id));
+        }
+        if let Some(limit) = limit {
+            params.insert("limit".to_string(), Value::from(limit));
+        }
+        self.call(
+            Method::GetActivity,
+            Some(Value::Object(params)),
+            "activity-snapshot",
+        )
+    }
+
+    pub fn tombstones(&self) -> Result<Vec<Tombstone>, ClientError> {
+        self.call(Method::GetTombstones, None, "tombstones")
+    }
+
+    /// One page of the raw event log. Pass `next_cursor` back as
+    /// `query.cursor` to continue.
+    pub fn events(&self, query: &EventsQuery) -> Result<EventsPage, ClientError> {
+        let params =
+            serde_json::to_value(query).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.call(Method::GetEvents, Some(params), "events")
+    }
+
+    /// Every subagent recorded for `session_id`, running or stopped.
+    pub fn subagents(&self, session_id: &str) -> Result<Vec<SubagentRecord>, ClientError> {
+        let params = serde_json::json!({ "session_id": session_id });
+        self.call(Method::GetSubagents, Some(params), "subagents")
+    }
+
+    /// State transitions of one session and the time spent in each state.
+    pub fn session_timeline(
+        &self,
+        request: &SessionTimelineRequest,
+    ) -> Result<SessionTimeline, ClientError> {
+        let params =
+            serde_json::to_value(request).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.call(Method::GetSessionTimeline, Some(params), "session-timeline")
+    }
+
+    /// Time per project and day spent in each state and waiting on the human.
+    pub fn project_time(
+        &self,
+        query: &ProjectTimeQuery,
+    ) -> Result<Vec<ProjectDayTime>, ClientError> {
+        let params =
+            serde_json::to_value(query).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.call(Method::GetProjectTime, Some(params), "project-time")
+    }
+
+    /// Recent tool calls with per-tool durations and failure rates.
+    pub fn tool_calls(&self, query: &ToolCallsQuery) -> Result<ToolCallsResponse, ClientError> {
+        let params =
+            serde_json::to_value(query).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.call(Method::GetToolCalls, Some(params), "tool-calls")
+    }
+
+    /// Registered git worktrees with their branch and creating session.
+    pub fn worktrees(&self, query: &WorktreesQuery) -> Result<Vec<WorktreeRecord>, ClientError> {
+        let params =
+            serde_json::to_value(query).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.call(Method::GetWorktrees, Some(params), "worktrees")
+    }
+
+    /// Settings changes reported by `config_change` hooks, newest first.
+    pub fn config_changes(
+        &self,
+        query: &ConfigChangesQuery,
+    ) -> Result<Vec<ConfigChangeRecord>, ClientError> {
+        let params =
+            serde_json::to_value(query).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.call(Method::GetConfigChanges, Some(params), "config-changes")
+    }
+
+    /// Daemon counters in OpenMetrics text format.
+    pub fn metrics(&self) -> Result<MetricsResponse, ClientError> {
+        self.call(Method::GetMetrics, None, "metrics")
+    }
+
+    /// Sends one event. The request id is the event id so retries are
+    /// recognisable in daemon logs.
+    pub fn send_event(&self, event: &EventEnvelope) -> Result<EventAck, ClientError> {
+        let params =
+            serde_json::to_value(event).map_err(|err| ClientError::Encode(err.to_string()))?;
+        let response = self.request(Method::Event, Some(params), Some(event.event_id.clone()))?;
+        response_data(response)
+    }
+
+    /// Sends up to `MAX_EVENT_BATCH` events in one request. The daemon stores
+    /// them in one transaction and reports each as accepted, duplicate or
+    /// rejected, so a retried batch is safe.
+    pub fn send_events(&self, events: &[EventEnvelope]) -> Result<EventBatchAck, ClientError> {
+        self.call(
+            Method::EventBatch,
+            Some(serde_json::json!({ "events": events })),
+            "event-batch",
+        )
+    }
+
+    /// Opens a long-lived `subscribe` stream. Empty `topics` means every topic.
+    ///
+    /// The stream is not retried; after it ends, resync with a snapshot and
+    /// subscribe again.
+    pub fn subscribe(&self, topics: &[SubscriptionTopic]) -> Result<Subscription, ClientError> {
+        let request = Request {
+            protocol_version: Method::Subscribe.since_version(),
+            method: Method::Subscribe,
+            id: Some("subscribe".to_string()),
+            params: Some(serde_json::json!({ "topics": topics })),
+        };
+        let mut payload =
+            serde_json::to_vec(&request).map_err(|err| ClientError::Encode(err.to_string()))?;
+        payload.push(b'\n');
+
+        let mut reader = BufReader::new(self.send(&payload)?);
+        let response = read_response(&mut reader)?;
+        if !response.ok {
+            return Err(daemon_error(response));
+        }
+        let ack: SubscribeAck = response_data(response)?;
+        reader
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_secs(SUBSCRIPTION_IDLE_TIMEOUT_SECS)))
+            .map_err(ClientError::Io)?;
+
+        Ok(Subscription { ack, reader })
+    }
+
+    fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        params: Option<Value>,
+        id: &str,
+    ) -> Result<T, ClientError> {
+        let response = self.request(method, params, Some(id.to_string()))?;
+        response_data(response)
+    }
+
+    fn send(&self, payload: &[u8]) -> Result<UnixStream, ClientError> {
+        let path = &self.config.socket_path;
+        let mut stream = connect::connect(path, self.config.connect_timeout).map_err(|err| {
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) {
+                ClientError::Timeout
+            } else {
+                ClientError::Connect {
+                    path: path.clone(),
+                    source: err,
+                }
+            }
+        })?;
+        stream
+            .set_read_timeout(Some(non_zero(self.config.read_timeout)))
+            .map_err(ClientError::Io)?;
+        stream
+            .set_write_timeout(Some(non_zero(self.config.write_timeout)))
+            .map_err(ClientError::Io)?;
+
+        stream.write_all(payload).map_err(ClientError::from_io)?;
+        stream.flush().map_err(ClientError::from_io)?;
+        Ok(stream)
+    }
+
+    fn round_trip(&self, payload: &[u8]) -> Result<Response, ClientError> {
+        let mut reader = BufReader::new(self.send(payload)?);
+        let response = read_response(&mut reader)?;
+        if response.ok {
+            return Ok(response);
+        }
+        Err(daemon_error(response))
+    }
+}
+
+/// Messages streamed by the daemon after a successful `subscribe`.
+///
+/// Iteration ends when the daemon closes the connection. A read timeout means
+/// heartbeats stopped and is yielded as [`ClientError::Timeout`].
+pub struct Subscription {
+    ack: SubscribeAck,
+    reader: BufReader<UnixStream>,
+}
+
+impl Subscription {
+    pub fn topics(&self) -> &[SubscriptionTopic] {
+        &self.ack.topics
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = Result<SubscriptionMessage, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(
+                serde_json::from_str(line.trim_end())
+                    .map_err(|err| ClientError::InvalidResponse(err.to_string())),
+            ),
+            Err(err) => Some(Err(ClientError::from_io(err))),
+        }
+    }
+}
+
+fn daemon_error(response: Response) -> ClientError {
+    match response.error {
+        Some(error) => ClientError::Daemon {
+            code: error.code,
+            message: error.message,
+        },
+        None => ClientError::Daemon {
+            code: "unknown".to_string(),
+            message: "Unknown daemon error".to_string(),
+        },
+    }
+}
+
+fn routing_params(project_path: &str, workspace_id: Option<&str>) -> Value {
+    match workspace_id {
+        Some(workspace_id) => serde_json::json!({
+            "project_path": project_path,
+            "workspace_id": workspace_id,
+        }),
+        None => serde_json::json!({ "project_path": project_path }),
+    }
+}
+
+fn non_zero(timeout: Duration) -> Duration {
+    timeout.max(Duration::from_millis(1))
+}
+
+fn response_data<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    let data = response
+        .data
+        .ok_or_else(|| ClientError::InvalidResponse("response has no data".to_string()))?;
+    serde_json::from_value(data).map_err(|err| ClientError::InvalidResponse(err.to_string()))
+}
+
+fn read_response(reader: &mut BufReader<UnixStream>) -> Result<Response, ClientError> {
+    let mut buffer = Vec::new();
+    let limit = MAX_REQUEST_BYTES as u64 + 1;
+    loop {
+        match reader.by_ref().take(limit).read_until(b'\n', &mut buffer) {
+            Ok(_) => break,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(ClientError::from_io(err)),
+        }
+    }
+    if buffer.last() == Some(&b'\n') {
+        buffer.pop();
+    }
+    if buffer.len() > MAX_REQUEST_BYTES {
+        return Err(ClientError::ResponseTooLarge);
+    }
+    if buffer.is_empty() {
+        return Err(ClientError::EmptyResponse);
+    }
+
+    serde_json::from_slice(&buffer).map_err(|err| ClientError::InvalidResponse(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockDaemon, MockReply};
+    use capacitor_daemon_protocol::ChangeKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+    struct EnvGuard {
+        key: &'static str,
+        prior: Option<String>,
+    }
+
+    impl EnvGuard {
+        fn set(key: &'static str, value: &str) -> Self {
+            let prior = std::env::var(key).ok();
+            std::env::set_var(key, value);
+            Self { key, prior }
+        }
+
+        fn unset(key: &'static str) -> Self {
+            let prior = std::env::var(key).ok();
+            std::env::remove_var(key);
+            Self { key, prior }
+        }
+    }
+
+    impl Drop for EnvGuard {
+        fn drop(&mut self) {
+            if let Some(value) = &self.prior {
+                std::env::set_var(self.key, value);
+            } else {
+                std::env::remove_var(self.key);
+            }
+        }
+    }
+
+    fn env_lock() -> std::sync::MutexGuard<'static, ()> {
+        ENV_LOCK
+            .get_or_init(|| Mutex::new(()))
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn session_json(session_id: &str) -> Value {
+        serde_json::json!({
+            "session_id": session_id,
+            "pid": 4242,
+            "state": "ready",
+            "cwd": "/repo",
+            "project_id": "/repo/.git",
+            "workspace_id": "ws-1",
+            "project_path": "/repo",
+            "updated_at": "2026-01-31T00:00:00Z",
+            "state_changed_at": "2026-01-31T00:00:00Z",
+            "tools_in_flight": 0,
+            "is_alive": true
+        })
+    }
+
+    #[test]
+    fn daemon_enabled_defaults_to_true_when_env_missing() {
+        let _guard = env_lock();
+        let _unset = EnvGuard::unset(ENABLE_ENV);
+        assert!(daemon_enabled());
+    }
+
+    #[test]
+    fn daemon_enabled_is_false_when_env_zero() {
+        let _guard = env_lock();
+        let _set = EnvGuard::set(ENABLE_ENV, "0");
+        assert!(!daemon_enabled());
+    }
+
+    #[test]
+    fn daemon_enabled_is_true_when_env_one() {
+        let _guard = env_lock();
+        let _set = EnvGuard::set(ENABLE_ENV, "1");
+        assert!(daemon_enabled());
+    }
+
+    #[test]
+    fn socket_env_overrides_default_path() {
+        let _guard = env_lock();
+        let _set = EnvGuard::set(SOCKET_ENV, "/tmp/custom-daemon.sock");
+        assert_eq!(
+            default_socket_path().expect("socket path"),
+            PathBuf::from("/tmp/custom-daemon.sock")
+        );
+    }
+
+    #[test]
+    fn sessions_are_decoded_into_typed_records() {
+        let daemon = MockDaemon::start(|request| {
+            assert!(matches!(request.method, Method::GetSessions));
+            MockReply::ok(serde_json::json!([session_json("session-1")]))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let sessions = client.sessions().expect("sessions");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "session-1");
+        assert_eq!(sessions[0].state, SessionState::Ready);
+
+        let request = &daemon.requests()[0];
+        assert_eq!(
+            request["protocol_version"],
+            Method::GetSessions.since_version()
+        );
+        assert_eq!(request["method"], "get_sessions");
+    }
+
+    #[test]
+    fn sessions_delta_sends_since_revision() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!({
+                "revision": 7,
+                "since_revision": 5,
+                "full": false,
+                "changed": [session_json("session-2")],
+                "removed": ["session-1"]
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let delta = client.sessions_delta(5).expect("delta");
+        assert_eq!(delta.revision, 7);
+        assert!(!delta.full);
+        assert_eq!(delta.changed[0].session_id, "session-2");
+        assert_eq!(delta.removed, vec!["session-1".to_string()]);
+        assert_eq!(daemon.requests()[0]["params"]["since_revision"], 5);
+    }
+
+    #[test]
+    fn hello_advertises_supported_range() {
+        let daemon = MockDaemon::start(|request| {
+            assert_eq!(request.protocol_version, Method::Hello.since_version());
+            MockReply::ok(serde_json::json!({
+                "protocol_version": 2,
+                "min_protocol_version": 1,
+                "max_protocol_version": 3,
+                "features": ["subscribe"],
+                "daemon_version": "0.3.0"
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let hello = client.hello(Some("test-client")).expect("hello");
+        assert_eq!(hello.protocol_version, 2);
+        assert!(hello.supports("subscribe"));
+        assert!(!hello.supports("snapshot_deltas"));
+
+        let params = &daemon.requests()[0]["params"];
+        assert_eq!(params["min_protocol_version"], MIN_PROTOCOL_VERSION);
+        assert_eq!(params["max_protocol_version"], protocol::PROTOCOL_VERSION);
+        assert_eq!(params["client"], "test-client");
+    }
+
+    #[test]
+    fn hello_treats_pre_handshake_daemons_as_oldest_version() {
+        let daemon =
+            MockDaemon::start(|_| MockReply::error("invalid_json", "unknown variant `hello`"));
+        let client = DaemonClient::new(daemon.client_config());
+
+        let hello = client.hello(None).expect("legacy hello");
+        assert_eq!(hello.protocol_version, MIN_PROTOCOL_VERSION);
+        assert!(hello.features.is_empty());
+    }
+
+    #[test]
+    fn process_liveness_maps_not_found_to_none() {
+        let daemon = MockDaemon::start(|request| {
+            let pid = request.params.as_ref().unwrap()["pid"].clone();
+            MockReply::ok(serde_json::json!({ "found": false, "pid": pid }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        assert_eq!(client.process_liveness(99).expect("liveness"), None);
+    }
+
+    #[test]
+    fn daemon_errors_surface_code_without_retry_by_default() {
+        let daemon = MockDaemon::start(|_| MockReply::error("invalid_params", "pid is required"));
+        let client = DaemonClient::new(daemon.client_config());
+
+        let err = client.process_liveness(0).expect_err("daemon error");
+        assert_eq!(err.daemon_code(), Some("invalid_params"));
+        assert_eq!(daemon.request_count(), 1);
+    }
+
+    #[test]
+    fn retry_resends_identical_request_after_lost_response() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let daemon = {
+            let attempts = Arc::clone(&attempts);
+            MockDaemon::start(move |_| {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    MockReply::Close
+                } else {
+                    MockReply::ok(serde_json::json!({ "accepted": true }))
+                }
+            })
+        };
+        let client = DaemonClient::new(
+            daemon
+                .client_config()
+                .with_retry(RetryPolicy::new(2, Duration::from_millis(10))),
+        );
+        let event: EventEnvelope = serde_json::from_value(serde_json::json!({
+            "event_id": "evt-1",
+            "recorded_at": "2026-01-31T00:00:00Z",
+            "event_type": "session_start",
+            "session_id": "session-1",
+            "pid": 4242,
+            "cwd": "/repo"
+        }))
+        .expect("event");
+
+        let ack = client.send_event(&event).expect("send event");
+        assert!(ack.accepted);
+
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+        assert_eq!(requests[0]["id"], "evt-1");
+    }
+
+    #[test]
+    fn send_events_wraps_envelopes_and_decodes_results() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!({
+                "accepted": 1,
+                "duplicates": 1,
+                "rejected": 0,
+                "results": [
+                    { "index": 0, "event_id": "evt-1", "status": "accepted" },
+                    { "index": 1, "event_id": "evt-1", "status": "duplicate" }
+                ]
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+        let event: EventEnvelope = serde_json::from_value(serde_json::json!({
+            "event_id": "evt-1",
+            "recorded_at": "2026-01-31T00:00:00Z",
+            "event_type": "session_start",
+            "session_id": "session-1",
+            "pid": 4242,
+            "cwd": "/repo"
+        }))
+        .expect("event");
+
+        let ack = client
+            .send_events(&[event.clone(), event])
+            .expect("send events");
+        assert_eq!(ack.results[1].status, EventStatus::Duplicate);
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "event_batch");
+        assert_eq!(request["params"]["events"][1]["event_id"], "evt-1");
+    }
+
+    #[test]
+    fn events_sends_filters_and_decodes_page() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!({
+                "events": [{
+                    "event_id": "evt-1",
+                    "recorded_at": "2026-01-31T00:00:00Z",
+                    "event_type": "stop",
+                    "session_id": "session-1"
+                }],
+                "next_cursor": 7
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let page = client
+            .events(&EventsQuery {
+                session_id: Some("session-1".to_string()),
+                cursor: Some(3),
+                ..EventsQuery::default()
+            })
+            .expect("events");
+        assert_eq!(page.events[0].event_id, "evt-1");
+        assert_eq!(page.next_cursor, Some(7));
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_events");
+        assert_eq!(
+            request["protocol_version"],
+            Method::GetEvents.since_version()
+        );
+        assert_eq!(
+            request["params"],
+            serde_json::json!({ "session_id": "session-1", "cursor": 3 })
+        );
+    }
+
+    #[test]
+    fn subagents_sends_session_id() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!([{
+                "session_id": "session-1",
+                "agent_id": "agent-1",
+                "agent_type": "Explore",
+                "started_at": "2026-01-31T00:00:00Z",
+                "tools": ["Read"],
+                "tool_calls": 2,
+                "updated_at": "2026-01-31T00:00:01Z"
+            }]))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let subagents = client.subagents("session-1").expect("subagents");
+        assert_eq!(subagents.len(), 1);
+        assert!(subagents[0].is_running());
+        assert_eq!(subagents[0].tools, vec!["Read".to_string()]);
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_subagents");
+        assert_eq!(request["params"]["session_id"], "session-1");
+    }
+
+    #[test]
+    fn session_timeline_sends_window_and_decodes_durations() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!({
+                "session_id": "session-1",
+                "transitions": [{
+                    "session_id": "session-1",
+                    "project_path": "/repo",
+                    "from_state": "working",
+                    "to_state": "waiting",
+                    "event_id": "evt-2",
+                    "transitioned_at": "2026-01-31T00:10:00Z"
+                }],
+                "seconds_in_state": { "working": 600, "waiting": 30 }
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let timeline = client
+            .session_timeline(&SessionTimelineRequest {
+                session_id: "session-1".to_string(),
+                since: Some("2026-01-31T00:00:00Z".to_string()),
+                until: None,
+            })
+            .expect("timeline");
+        assert_eq!(
+            timeline.transitions[0].to_state,
+            Some(SessionState::Waiting)
+        );
+        assert_eq!(timeline.seconds_in_state[&SessionState::Working], 600);
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_session_timeline");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({ "session_id": "session-1", "since": "2026-01-31T00:00:00Z" })
+        );
+    }
+
+    #[test]
+    fn project_time_sends_query() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!([{
+                "project_path": "/repo",
+                "day": "2026-01-31",
+                "seconds_in_state": { "working": 1800 },
+                "waiting_on_human_secs": 90
+            }]))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let rows = client
+            .project_time(&ProjectTimeQuery {
+                days: Some(7),
+                utc_offset_minutes: Some(-480),
+                ..ProjectTimeQuery::default()
+            })
+            .expect("project time");
+        assert_eq!(rows[0].waiting_on_human_secs, 90);
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_project_time");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({ "days": 7, "utc_offset_minutes": -480 })
+        );
+    }
+
+    #[test]
+    fn tool_calls_sends_query() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!({
+                "calls": [{
+                    "session_id": "session-1",
+                    "tool_use_id": "toolu_1",
+                    "tool_name": "Bash",
+                    "project_path": "/repo",
+                    "started_at": "2026-01-31T00:00:00Z",
+                    "ended_at": "2026-01-31T00:00:02Z",
+                    "duration_ms": 2000,
+                    "outcome": "failure"
+                }],
+                "tools": []
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let response = client
+            .tool_calls(&ToolCallsQuery {
+                session_id: Some("session-1".to_string()),
+                limit: Some(10),
+                ..ToolCallsQuery::default()
+            })
+            .expect("tool calls");
+        assert_eq!(response.calls[0].outcome, Some(ToolCallOutcome::Failure));
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_tool_calls");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({ "session_id": "session-1", "limit": 10 })
+        );
+    }
+
+    #[test]
+    fn worktrees_sends_project_filter() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!([{
+                "path": "/repo/.claude/worktrees/feature-a",
+                "project_id": "/repo/.git",
+                "project_path": "/repo",
+                "branch": "feature-a",
+                "created_by_session_id": "session-1",
+                "created_at": "2026-01-31T00:00:00Z"
+            }]))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let worktrees = client
+            .worktrees(&WorktreesQuery {
+                project_path: Some("/repo".to_string()),
+            })
+            .expect("worktrees");
+        assert_eq!(worktrees[0].branch.as_deref(), Some("feature-a"));
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_worktrees");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({ "project_path": "/repo" })
+        );
+    }
+
+    #[test]
+    fn config_changes_sends_query() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!([{
+                "event_id": "evt-1",
+                "source": "user_settings",
+                "file_path": "/Users/pete/.claude/settings.json",
+                "content_hash": "9e107d9d372bb6826bd81d3542a419d6",
+                "content_changed": true,
+                "hud_hook_changed": true,
+                "recorded_at": "2026-01-31T00:00:00Z"
+            }]))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let changes = client
+            .config_changes(&ConfigChangesQuery {
+                project_path: Some("/repo".to_string()),
+                ..ConfigChangesQuery::default()
+            })
+            .expect("config changes");
+        assert!(changes[0].hud_hook_changed);
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_config_changes");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({ "project_path": "/repo" })
+        );
+    }
+
+    #[test]
+    fn metrics_returns_exposition_text() {
+        let daemon = MockDaemon::start(|_| {
+            MockReply::ok(serde_json::json!({
+                "content_type": "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                "text": "# TYPE capacitor_daemon_connections_active gauge\ncapacitor_daemon_connections_active 1\n# EOF\n"
+            }))
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let metrics = client.metrics().expect("metrics");
+        assert!(metrics.text.ends_with("# EOF\n"));
+
+        let request = &daemon.requests()[0];
+        assert_eq!(request["method"], "get_metrics");
+        assert_eq!(request["protocol_version"], 2);
+    }
+
+    #[test]
+    fn malformed_response_is_not_retried() {
+        let daemon = MockDaemon::start(|_| MockReply::Raw(b"not json\n".to_vec()));
+        let client = DaemonClient::new(
+            daemon
+                .client_config()
+                .with_retry(RetryPolicy::new(3, Duration::ZERO)),
+        );
+
+        let err = client.health().expect_err("invalid response");
+        assert!(matches!(err, ClientError::InvalidResponse(_)));
+        assert_eq!(daemon.request_count(), 1);
+    }
+
+    #[test]
+    fn missing_socket_is_a_connect_error() {
+        let client = DaemonClient::new(ClientConfig::new("/tmp/cdc-no-such-daemon.sock"));
+        let err = client.health().expect_err("connect error");
+        assert!(matches!(err, ClientError::Connect { .. }));
+    }
+
+    #[test]
+    fn subscribe_yields_ack_topics_and_messages() {
+        let daemon = MockDaemon::start(|_| {
+            let mut payload = Vec::new();
+            let ack = Response::ok(
+                Some("subscribe".to_string()),
+                serde_json::json!({ "subscribed": true, "topics": ["sessions"] }),
+            );
+            for value in [
+                serde_json::to_value(ack).unwrap(),
+                serde_json::json!({
+                    "type": "change",
+                    "topic": "sessions",
+                    "change": "delete",
+                    "key": "session-1",
+                    "emitted_at": "2026-01-31T00:00:00Z"
+                }),
+                serde_json::json!({ "type": "heartbeat", "emitted_at": "2026-01-31T00:00:15Z" }),
+            ] {
+                payload.extend(serde_json::to_vec(&value).unwrap());
+                payload.push(b'\n');
+            }
+            MockReply::Raw(payload)
+        });
+        let client = DaemonClient::new(daemon.client_config());
+
+        let mut subscription = client
+            .subscribe(&[SubscriptionTopic::Sessions])
+            .expect("subscribe");
+        assert_eq!(subscription.topics(), &[SubscriptionTopic::Sessions]);
+
+        match subscription.next() {
+            Some(Ok(SubscriptionMessage::Change(change))) => {
+                assert_eq!(change.change, ChangeKind::Delete);
+                assert_eq!(change.key, "session-1");
+            }
+            other => panic!("expected change, got {other:?}"),
+        }
+        assert!(matches!(
+            subscription.next(),
+            Some(Ok(SubscriptionMessage::Heartbeat { .. }))
+        ));
+        assert!(subscription.next().is_none());
+        assert_eq!(daemon.requests()[0]["params"]["topics"][0], "sessions");
+    }
+}
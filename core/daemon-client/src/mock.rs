@@ -0,0 +1,185 @@
+//! In-process fake daemon for client tests (feature `mock`).
+//!
+//! ```ignore
+//! let daemon = MockDaemon::start(|request| {
+//!     MockReply::ok(serde_json::json!({ "accepted": true }))
+//! });
+//! let client = DaemonClient::new(daemon.client_config());
+//! ```
+
+use crate::ClientConfig;
+use capacitor_daemon_protocol::{Request, Response};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const ACCEPT_POLL_MS: u64 = 5;
+
+/// What the mock does with one request.
+pub enum MockReply {
+    /// Writes the response as a single JSON line.
+    Respond(Response),
+    /// Writes these bytes verbatim (malformed or oversized responses).
+    Raw(Vec<u8>),
+    /// Closes the connection without answering, like a daemon that crashed
+    /// after applying the request.
+    Close,
+}
+
+impl MockReply {
+    pub fn ok(data: Value) -> Self {
+        MockReply::Respond(Response::ok(None, data))
+    }
+
+    pub fn error(code: &str, message: &str) -> Self {
+        MockReply::Respond(Response::error(None, code, message))
+    }
+}
+
+type Handler = dyn Fn(&Request) -> MockReply + Send + Sync;
+
+/// A Unix socket server answering one request per connection, like the daemon.
+///
+/// Every request is recorded as raw JSON. The server stops when dropped.
+pub struct MockDaemon {
+    dir: PathBuf,
+    socket_path: PathBuf,
+    requests: Arc<Mutex<Vec<Value>>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockDaemon {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> MockReply + Send + Sync + 'static,
+    {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        // /tmp rather than temp_dir(): macOS temp paths overflow sun_path.
+        let dir = Path::new("/tmp").join(format!(
+            "cdc-mock-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create mock daemon dir");
+        let socket_path = dir.join("daemon.sock");
+        let listener = UnixListener::bind(&socket_path).expect("bind mock daemon socket");
+        listener
+            .set_nonblocking(true)
+            .expect("set mock listener nonblocking");
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let thread = {
+            let requests = Arc::clone(&requests);
+            let shutdown = Arc::clone(&shutdown);
+            std::thread::spawn(move || serve(listener, handler, requests, shutdown))
+        };
+
+        Self {
+            dir,
+            socket_path,
+            requests,
+            shutdown,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Client config pointing at this mock with short timeouts.
+    pub fn client_config(&self) -> ClientConfig {
+        ClientConfig::new(&self.socket_path).with_timeout(Duration::from_millis(500))
+    }
+
+    /// Raw JSON of every request received so far, in arrival order.
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+}
+
+impl Drop for MockDaemon {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn serve(
+    listener: UnixListener,
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<Value>>>,
+    shutdown: Arc<AtomicBool>,
+) {
+    while !shutdown.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => handle(stream, handler.as_ref(), &requests),
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+fn handle(stream: UnixStream, handler: &Handler, requests: &Mutex<Vec<Value>>) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line).is_err() {
+        return;
+    }
+
+    let raw: Value = match serde_json::from_slice(&line) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    requests
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(raw.clone());
+    let request: Request = match serde_json::from_value(raw) {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+
+    let mut stream = reader.into_inner();
+    let payload = match handler(&request) {
+        MockReply::Respond(mut response) => {
+            if response.id.is_none() {
+                response.id = request.id.clone();
+            }
+            let mut payload = serde_json::to_vec(&response).unwrap_or_default();
+            payload.push(b'\n');
+            payload
+        }
+        MockReply::Raw(bytes) => bytes,
+        MockReply::Close => return,
+    };
+    let _ = stream.write_all(&payload);
+    let _ = stream.flush();
+}
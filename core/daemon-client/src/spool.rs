@@ -0,0 +1,517 @@
+//! Durable, append-only spool for events the daemon could not take.
+//!
+//! Hooks append here when the daemon is unreachable and replay a bounded slice
+//! of the spool ahead of their next event; the daemon drains all of it on
+//! startup. Events keep
+//! their original `event_id`, so a replay that overlaps an earlier delivery is
+//! deduplicated by the daemon.
+//!
+//! Layout under `~/.capacitor/spool/`:
+//!
+//! - `events.ndjson`: one `EventEnvelope` per line, appended by hooks.
+//! - `events.flushing.ndjson`: the batch being replayed. Always older than
+//!   `events.ndjson` and replayed first.
+//! - `append.lock`: serializes appends with the rotation of `events.ndjson`.
+//! - `flush.lock`: held by the one process replaying the spool.
+
+use crate::{ClientError, DaemonClient};
+use capacitor_daemon_protocol::{parse_event, EventEnvelope, EventStatus, MAX_EVENT_BATCH};
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub const SPOOL_DIR: &str = "spool";
+/// Appends are refused beyond this size so a long outage cannot fill the disk.
+pub const MAX_SPOOL_BYTES: u64 = 8 * 1024 * 1024;
+
+const PENDING_FILE: &str = "events.ndjson";
+const FLUSHING_FILE: &str = "events.flushing.ndjson";
+const APPEND_LOCK: &str = "append.lock";
+const FLUSH_LOCK: &str = "flush.lock";
+
+#[derive(Debug, Error)]
+pub enum SpoolError {
+    #[error("Home directory not found")]
+    HomeDirNotFound,
+
+    #[error("Event spool I/O failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to encode spooled event: {0}")]
+    Encode(String),
+
+    #[error("Event spool is full ({limit} bytes)")]
+    Full { limit: u64 },
+
+    /// Replay stopped; the unsent events stay spooled.
+    #[error("Failed to replay spooled events: {0}")]
+    Replay(String),
+}
+
+/// Result of a flush that was not interrupted by a replay failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flush {
+    /// Another process holds the flush lock and will replay the spool.
+    Busy,
+    Done {
+        replayed: usize,
+        /// Lines that were not valid events and were discarded.
+        discarded: usize,
+    },
+}
+
+pub struct EventSpool {
+    dir: PathBuf,
+}
+
+impl EventSpool {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `<home>/.capacitor/spool`.
+    pub fn in_home(home: &Path) -> Self {
+        Self::new(home.join(".capacitor").join(SPOOL_DIR))
+    }
+
+    pub fn default_location() -> Result<Self, SpoolError> {
+        let home = dirs::home_dir().ok_or(SpoolError::HomeDirNotFound)?;
+        Ok(Self::in_home(&home))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether there is nothing to replay.
+    pub fn is_empty(&self) -> bool {
+        [PENDING_FILE, FLUSHING_FILE].iter().all(|name| {
+            fs::metadata(self.dir.join(name))
+                .map(|metadata| metadata.len() == 0)
+                .unwrap_or(true)
+        })
+    }
+
+    pub fn append(&self, event: &EventEnvelope) -> Result<(), SpoolError> {
+        let mut line =
+            serde_json::to_vec(event).map_err(|err| SpoolError::Encode(err.to_string()))?;
+        line.push(b'\n');
+
+        self.ensure_dir()?;
+        let _lock = FileLock::acquire(&self.dir.join(APPEND_LOCK), false)?;
+        let path = self.dir.join(PENDING_FILE);
+        let size = fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        if size + line.len() as u64 > MAX_SPOOL_BYTES {
+            return Err(SpoolError::Full {
+                limit: MAX_SPOOL_BYTES,
+            });
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(&path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Replays the spool through `client` using `event_batch`. Events the
+    /// daemon rejects as invalid are dropped; they can never succeed.
+    pub fn flush(&self, client: &DaemonClient) -> Result<Flush, SpoolError> {
+        self.flush_with(|events| send_batch(client, events))
+    }
+
+    /// Like [`Self::flush`], but stops after `max_events` replayed events and
+    /// leaves the rest spooled. [`Self::is_empty`] tells whether any remain.
+    pub fn flush_some(
+        &self,
+        client: &DaemonClient,
+        max_events: usize,
+    ) -> Result<Flush, SpoolError> {
+        self.flush_up_to(max_events, |events| send_batch(client, events))
+    }
+
+    /// Replays the whole spool, oldest first; see [`Self::flush_up_to`].
+    pub fn flush_with<E, F>(&self, sink: F) -> Result<Flush, SpoolError>
+    where
+        E: std::fmt::Display,
+        F: FnMut(&[EventEnvelope]) -> Result<(), E>,
+    {
+        self.flush_up_to(usize::MAX, sink)
+    }
+
+    /// Replays up to `max_events` spooled events, oldest first, in batches of
+    /// at most `MAX_EVENT_BATCH`. `sink` failing stops the replay and keeps the
+    /// unsent events, in order, for the next flush.
+    pub fn flush_up_to<E, F>(&self, max_events: usize, mut sink: F) -> Result<Flush, SpoolError>
+    where
+        E: std::fmt::Display,
+        F: FnMut(&[EventEnvelope]) -> Result<(), E>,
+    {
+        if self.is_empty() {
+            return Ok(Flush::Done {
+                replayed: 0,
+                discarded: 0,
+            });
+        }
+        self.ensure_dir()?;
+        let Some(_flush_lock) = FileLock::acquire(&self.dir.join(FLUSH_LOCK), true)? else {
+            return Ok(Flush::Busy);
+        };
+
+        let flushing = self.dir.join(FLUSHING_FILE);
+        let mut replayed = 0;
+        let mut discarded = 0;
+        while replayed < max_events {
+            if !flushing.exists() && !self.rotate_pending(&flushing)? {
+                break;
+            }
+
+            let (events, bad_lines, unread) = read_events(&flushing, max_events - replayed)?;
+            discarded += bad_lines;
+            let mut sent = 0;
+            for batch in events.chunks(MAX_EVENT_BATCH) {
+                if let Err(err) = sink(batch) {
+                    rewrite_events(&flushing, &events[sent..], &unread)?;
+                    return Err(SpoolError::Replay(err.to_string()));
+                }
+                sent += batch.len();
+                replayed += batch.len();
+            }
+            if unread.is_empty() {
+                fs::remove_file(&flushing)?;
+            } else {
+                rewrite_events(&flushing, &[], &unread)?;
+            }
+        }
+
+        Ok(Flush::Done {
+            replayed,
+            discarded,
+        })
+    }
+
+    /// Moves `events.ndjson` aside so appends start a fresh file. Returns
+    /// false when there is nothing pending.
+    fn rotate_pending(&self, flushing: &Path) -> Result<bool, SpoolError> {
+        let _lock = FileLock::acquire(&self.dir.join(APPEND_LOCK), false)?;
+        let pending = self.dir.join(PENDING_FILE);
+        match fs::metadata(&pending) {
+            Ok(metadata) if metadata.len() > 0 => {
+                fs::rename(&pending, flushing)?;
+                Ok(true)
+            }
+            Ok(_) | Err(_) => Ok(false),
+        }
+    }
+
+    fn ensure_dir(&self) -> Result<(), SpoolError> {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+        Ok(())
+    }
+}
+
+fn send_batch(client: &DaemonClient, events: &[EventEnvelope]) -> Result<(), ClientError> {
+    let ack = client.send_events(events)?;
+    for result in ack
+        .results
+        .iter()
+        .filter(|result| result.status == EventStatus::Rejected)
+    {
+        tracing::warn!(
+            event_id = ?result.event_id,
+            error = ?result.error,
+            "Daemon rejected spooled event; dropping it"
+        );
+    }
+    Ok(())
+}
+
+/// Parses lines until `max_events` events are read, using the protocol's own
+/// validation, and returns the unread remainder of the file as raw bytes. A
+/// torn final line from a crash mid-append is discarded rather than blocking
+/// the spool.
+fn read_events(
+    path: &Path,
+    max_events: usize,
+) -> Result<(Vec<EventEnvelope>, usize, Vec<u8>), SpoolError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut events = Vec::new();
+    let mut discarded = 0;
+    let mut line = String::new();
+    while events.len() < max_events {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str::<Value>(&line)
+            .map_err(|err| err.to_string())
+            .and_then(|value| parse_event(value).map_err(|err| err.message));
+        match event {
+            Ok(event) => events.push(event),
+            Err(err) => {
+                tracing::warn!(error = %err, "Discarding malformed spooled event");
+                discarded += 1;
+            }
+        }
+    }
+    let mut unread = Vec::new();
+    reader.read_to_end(&mut unread)?;
+    Ok((events, discarded, unread))
+}
+
+/// Replaces `path` with `events` followed by `unread`, the raw lines that were
+/// never parsed.
+fn rewrite_events(path: &Path, events: &[EventEnvelope], unread: &[u8]) -> Result<(), SpoolError> {
+    let temp = path.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp)?;
+    for event in events {
+        let mut line =
+            serde_json::to_vec(event).map_err(|err| SpoolError::Encode(err.to_string()))?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+    }
+    file.write_all(unread)?;
+    file.sync_data()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Advisory `flock` held until drop.
+struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// With `nonblocking`, returns `None` when another process holds the lock.
+    fn acquire(path: &Path, nonblocking: bool) -> io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(path)?;
+        let mut operation = libc::LOCK_EX;
+        if nonblocking {
+            operation |= libc::LOCK_NB;
+        }
+        loop {
+            // SAFETY: the descriptor is owned by `file` and valid for the call.
+            if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+                return Ok(Some(Self { _file: file }));
+            }
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::Interrupted => continue,
+                io::ErrorKind::WouldBlock if nonblocking => return Ok(None),
+                _ => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_id: &str) -> EventEnvelope {
+        serde_json::from_value(serde_json::json!({
+            "event_id": event_id,
+            "recorded_at": "2026-01-31T00:00:00Z",
+            "event_type": "user_prompt_submit",
+            "session_id": "session-1",
+            "pid": 4242,
+            "cwd": "/repo"
+        }))
+        .expect("event")
+    }
+
+    fn ids(events: &[EventEnvelope]) -> Vec<String> {
+        events.iter().map(|event| event.event_id.clone()).collect()
+    }
+
+    #[test]
+    fn flush_replays_events_in_append_order() {
+        let temp = tempfile::tempdir().expect("temp dir");
+        let spool = EventSpool::in_home(temp.path());
+        assert!(spool.is_empty());
+
+        spool.append(&event("evt-1")).expect("append");
+        spool.append(&event("evt-2")).expect("append");
+        assert!(!spool.is_empty());
+
+        let mut seen = Vec::new();
+        let outcome = spool
+            .flush_with(|events| {
+                seen.extend(ids(events));
+                Ok::<_, String>(())
+            })
+            .expect("flush");
+
+        assert_eq!(
+            outcome,
+            Flush::Done {
+                replayed: 2,
+                discarded: 0
+            }
+        );
+        assert_eq!(seen, vec!["evt-1", "evt-2"]);
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn failed_replay_keeps_events_ahead_of_newer_appends() {
+        let temp = tempfile::tempdir().expect("temp dir");
+        let spool = EventSpool::in_home(temp.path());
+        spool.append(&event("evt-1")).expect("append");
+
+        let err = spool
+            .flush_with(|_| Err("daemon down"))
+            .expect_err("replay fails");
+        assert!(matches!(err, SpoolError::Replay(_)));
+
+        spool.append(&event("evt-2")).expect("append after failure");
+        let mut seen = Vec::new();
+        spool
+            .flush_with(|events| {
+                seen.extend(ids(events));
+                Ok::<_, String>(())
+            })
+            .expect("flush");
+        assert_eq!(seen, vec!["evt-1", "evt-2"]);
+    }
+
+    #[test]
+    fn bounded_flush_keeps_the_rest_in_order() {
+        let temp = tempfile::tempdir().expect("temp dir");
+        let spool = EventSpool::in_home(temp.path());
+        for id in ["evt-1", "evt-2", "evt-3"] {
+            spool.append(&event(id)).expect("append");
+        }
+
+        let mut seen = Vec::new();
+        let outcome = spool
+            .flush_up_to(2, |events| {
+                seen.extend(ids(events));
+                Ok::<_, String>(())
+            })
+            .expect("flush");
+        assert_eq!(
+            outcome,
+            Flush::Done {
+                replayed: 2,
+                discarded: 0
+            }
+        );
+        assert_eq!(seen, vec!["evt-1", "evt-2"]);
+        assert!(!spool.is_empty());
+
+        spool.append(&event("evt-4")).expect("append");
+        spool
+            .flush_with(|events| {
+                seen.extend(ids(events));
+                Ok::<_, String>(())
+            })
+            .expect("flush");
+        assert_eq!(seen, vec!["evt-1", "evt-2", "evt-3", "evt-4"]);
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn malformed_lines_are_discarded() {
+        let temp = tempfile::tempdir().expect("temp dir");
+        let spool = EventSpool::in_home(temp.path());
+        spool.append(&event("evt-1")).expect("append");
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(spool.dir().join(PENDING_FILE))
+            .expect("open spool");
+        file.write_all(b"{\"event_id\":\"evt-torn")
+            .expect("torn write");
+
+        let outcome = spool.flush_with(|_| Ok::<_, String>(())).expect("flush");
+        assert_eq!(
+            outcome,
+            Flush::Done {
+                replayed: 1,
+                discarded: 1
+            }
+        );
+    }
+
+    #[test]
+    fn corrupt_line_between_events_does_not_block_later_ones() {
+        let temp = tempfile::tempdir().expect("temp dir");
+        let spool = EventSpool::in_home(temp.path());
+        spool.append(&event("evt-1")).expect("append");
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(spool.dir().join(PENDING_FILE))
+            .expect("open spool");
+        file.write_all(b"not json\n{\"event_id\":\"\"}\n")
+            .expect("corrupt lines");
+        spool.append(&event("evt-2")).expect("append");
+
+        let mut seen = Vec::new();
+        let outcome = spool
+            .flush_up_to(1, |events| {
+                seen.extend(ids(events));
+                Ok::<_, String>(())
+            })
+            .expect("flush");
+        assert_eq!(
+            outcome,
+            Flush::Done {
+                replayed: 1,
+                discarded: 0
+            }
+        );
+        let outcome = spool
+            .flush_with(|events| {
+                seen.extend(ids(events));
+                Ok::<_, String>(())
+            })
+            .expect("flush");
+        assert_eq!(
+            outcome,
+            Flush::Done {
+                replayed: 1,
+                discarded: 2
+            }
+        );
+        assert_eq!(seen, vec!["evt-1", "evt-2"]);
+    }
+
+    #[test]
+    fn concurrent_flush_reports_busy() {
+        let temp = tempfile::tempdir().expect("temp dir");
+        let spool = EventSpool::in_home(temp.path());
+        spool.append(&event("evt-1")).expect("append");
+
+        let _held = FileLock::acquire(&spool.dir().join(FLUSH_LOCK), false)
+            .expect("lock")
+            .expect("acquired");
+        let outcome = spool.flush_with(|_| Ok::<_, String>(())).expect("flush");
+        assert_eq!(outcome, Flush::Busy);
+        assert!(!spool.is_empty());
+    }
+}
@@ -0,0 +1,9 @@
+//! Prints the IPC JSON Schema; see `capacitor_daemon_protocol::schema`.
+
+fn main() {
+    let document = capacitor_daemon_protocol::schema::document();
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&document).expect("schema serializes")
+    );
+}
@@ -5,17 +5,70 @@
 //! same types to construct valid requests.
 
 use chrono::{DateTime, SecondsFormat, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
-pub const PROTOCOL_VERSION: u32 = 1;
+mod responses;
+pub mod schema;
+
+pub use responses::*;
+
+/// Newest protocol version this build speaks.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Oldest protocol version the daemon still serves. Versions in this range
+/// differ only by additive methods and fields.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
 pub const MAX_REQUEST_BYTES: usize = 1024 * 1024; // 1MB
 pub const ERROR_UNAUTHORIZED_PEER: &str = "unauthorized_peer";
 pub const ERROR_TOO_MANY_CONNECTIONS: &str = "too_many_connections";
 pub const ERROR_INVALID_PROJECT_PATH: &str = "invalid_project_path";
+pub const ERROR_TOO_MANY_SUBSCRIBERS: &str = "too_many_subscribers";
+pub const ERROR_PROTOCOL_MISMATCH: &str = "protocol_mismatch";
+pub const ERROR_WRITER_BUSY: &str = "writer_busy";
+pub const ACTIVITY_DEFAULT_LIMIT: usize = 100;
+pub const ACTIVITY_MAX_LIMIT: usize = 1000;
+pub const MAX_EVENT_BATCH: usize = 500;
+pub const EVENTS_DEFAULT_LIMIT: usize = 100;
+pub const EVENTS_MAX_LIMIT: usize = 1000;
+pub const PROJECT_TIME_MAX_DAYS: u32 = 90;
+pub const TOOL_CALLS_DEFAULT_LIMIT: usize = 100;
+pub const TOOL_CALLS_MAX_LIMIT: usize = 1000;
+/// Window `get_tool_calls` covers when `since` is omitted.
+pub const TOOL_CALLS_DEFAULT_WINDOW_HOURS: i64 = 24;
+pub const CONFIG_CHANGES_DEFAULT_LIMIT: usize = 100;
+pub const CONFIG_CHANGES_MAX_LIMIT: usize = 1000;
+/// Largest accepted `utc_offset_minutes` magnitude (UTC+14).
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Optional capabilities advertised in the `hello` response.
+pub const FEATURE_SUBSCRIBE: &str = "subscribe";
+pub const FEATURE_SNAPSHOT_DELTAS: &str = "snapshot_deltas";
+pub const FEATURE_EVENT_BATCH: &str = "event_batch";
+pub const FEATURE_EVENT_LOG: &str = "event_log";
+pub const FEATURE_SUBAGENTS: &str = "subagents";
+pub const FEATURE_SESSION_TIMELINE: &str = "session_timeline";
+pub const FEATURE_PROJECT_TIME: &str = "project_time";
+pub const FEATURE_TOOL_CALLS: &str = "tool_calls";
+pub const FEATURE_WORKTREES: &str = "worktrees";
+pub const FEATURE_CONFIG_CHANGES: &str = "config_changes";
+pub const FEATURE_METRICS: &str = "metrics";
+pub const FEATURES: &[&str] = &[
+    FEATURE_SUBSCRIBE,
+    FEATURE_SNAPSHOT_DELTAS,
+    FEATURE_EVENT_BATCH,
+    FEATURE_EVENT_LOG,
+    FEATURE_SUBAGENTS,
+    FEATURE_SESSION_TIMELINE,
+    FEATURE_PROJECT_TIME,
+    FEATURE_TOOL_CALLS,
+    FEATURE_WORKTREES,
+    FEATURE_CONFIG_CHANGES,
+    FEATURE_METRICS,
+];
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum Method {
     GetHealth,
@@ -28,10 +81,45 @@ pub enum Method {
     GetProjectStates,
     GetActivity,
     GetTombstones,
+    GetEvents,
+    GetSubagents,
+    GetSessionTimeline,
+    GetProjectTime,
+    GetToolCalls,
+    GetWorktrees,
+    GetConfigChanges,
+    GetMetrics,
     Event,
+    EventBatch,
+    Subscribe,
+    Hello,
+}
+
+impl Method {
+    /// Protocol version that introduced this method. Clients stamp requests
+    /// with it so that older daemons which know the method still accept them.
+    pub fn since_version(&self) -> u32 {
+        match self {
+            Method::Hello
+            | Method::EventBatch
+            | Method::GetEvents
+            | Method::GetSubagents
+            | Method::GetSessionTimeline
+            | Method::GetProjectTime
+            | Method::GetToolCalls
+            | Method::GetWorktrees
+            | Method::GetConfigChanges
+            | Method::GetMetrics => 2,
+            _ => 1,
+        }
+    }
+}
+
+pub fn is_supported_protocol_version(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Request {
     pub protocol_version: u32,
@@ -42,13 +130,13 @@ pub struct Request {
     pub params: Option<Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ProcessLivenessRequest {
     pub pid: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingSnapshotRequest {
     pub project_path: String,
@@ -56,7 +144,7 @@ pub struct RoutingSnapshotRequest {
     pub workspace_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingDiagnosticsRequest {
     pub project_path: String,
@@ -64,7 +152,288 @@ pub struct RoutingDiagnosticsRequest {
     pub workspace_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+/// Params for `get_subagents`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SubagentsRequest {
+    pub session_id: String,
+}
+
+/// Params for `get_session_timeline`. `since` (inclusive) and `until`
+/// (exclusive) are RFC3339 bounds on the reported window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SessionTimelineRequest {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+/// Params for `get_sessions` / `get_project_states`.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SnapshotRequest {
+    /// When set, only entries changed or removed after this revision are returned.
+    #[serde(default)]
+    pub since_revision: Option<u64>,
+}
+
+/// Params for `get_activity`. Parsed leniently: unknown keys are ignored,
+/// a blank `session_id` means all sessions and `limit` is clamped.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ActivityRequest {
+    pub session_id: Option<String>,
+    pub limit: usize,
+}
+
+impl Default for ActivityRequest {
+    fn default() -> Self {
+        Self {
+            session_id: None,
+            limit: ACTIVITY_DEFAULT_LIMIT,
+        }
+    }
+}
+
+/// Params for `get_events`. Every filter is optional; events come back in
+/// arrival (rowid) order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EventsQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Only these event types. Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<EventType>,
+    /// Events whose `cwd` is this path or lies beneath it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// Inclusive lower bound on `recorded_at` (RFC3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Exclusive upper bound on `recorded_at` (RFC3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    /// `next_cursor` from the previous page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// Params for `get_project_time`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectTimeQuery {
+    /// Only this project (exact `project_path`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// Calendar days to cover, ending today. Defaults to 1 (today only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u32>,
+    /// Offset used to cut days, e.g. `-480` for UTC-8. Defaults to UTC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset_minutes: Option<i32>,
+}
+
+impl ProjectTimeQuery {
+    /// Day count, defaulted and clamped to `PROJECT_TIME_MAX_DAYS`.
+    pub fn days(&self) -> u32 {
+        self.days.unwrap_or(1).clamp(1, PROJECT_TIME_MAX_DAYS)
+    }
+}
+
+/// Params for `get_tool_calls`. Without filters every session is covered.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ToolCallsQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Only this project (exact `project_path`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// Inclusive lower bound on `started_at` (RFC3339); defaults to
+    /// `TOOL_CALLS_DEFAULT_WINDOW_HOURS` ago.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Most recent calls to list; `0` returns only the aggregates, which
+    /// always cover every match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl ToolCallsQuery {
+    /// Call count, defaulted and clamped to `TOOL_CALLS_MAX_LIMIT`.
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(TOOL_CALLS_DEFAULT_LIMIT)
+            .min(TOOL_CALLS_MAX_LIMIT)
+    }
+}
+
+/// Params for `get_worktrees`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WorktreesQuery {
+    /// Only worktrees of this repository (exact `project_path`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+}
+
+/// Params for `get_config_changes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigChangesQuery {
+    /// Only changes seen by sessions of this project (exact `project_path`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// Inclusive lower bound on `recorded_at` (RFC3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl ConfigChangesQuery {
+    /// Change count, defaulted and clamped to `CONFIG_CHANGES_MAX_LIMIT`.
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(CONFIG_CHANGES_DEFAULT_LIMIT)
+            .min(CONFIG_CHANGES_MAX_LIMIT)
+    }
+}
+
+impl EventsQuery {
+    /// Page size, defaulted and clamped to `EVENTS_MAX_LIMIT`.
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(EVENTS_DEFAULT_LIMIT)
+            .clamp(1, EVENTS_MAX_LIMIT)
+    }
+
+    /// Whether `cwd` is the query's project path or lies beneath it.
+    pub fn matches_project(&self, cwd: Option<&str>) -> bool {
+        let Some(project_path) = self.project_path.as_deref() else {
+            return true;
+        };
+        let Some(cwd) = cwd else {
+            return false;
+        };
+        if project_path == "/" {
+            return cwd.starts_with('/');
+        }
+        let cwd = cwd.trim_end_matches('/');
+        cwd == project_path
+            || cwd
+                .strip_prefix(project_path)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeRequest {
+    /// Topics to stream. Empty means every topic.
+    #[serde(default)]
+    pub topics: Vec<SubscriptionTopic>,
+}
+
+impl SubscribeRequest {
+    pub fn wants(&self, topic: SubscriptionTopic) -> bool {
+        self.topics.is_empty() || self.topics.contains(&topic)
+    }
+}
+
+/// Params for `hello`. Parsed leniently so that newer clients can add fields
+/// to the handshake without being rejected by older daemons.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HelloRequest {
+    #[serde(default = "default_min_protocol_version")]
+    pub min_protocol_version: u32,
+    pub max_protocol_version: u32,
+    /// Optional capabilities the client understands.
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Free-form client name for daemon logs, e.g. `hud-hook/0.2.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+}
+
+impl Default for HelloRequest {
+    fn default() -> Self {
+        Self {
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION,
+            features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+            client: None,
+        }
+    }
+}
+
+impl HelloRequest {
+    /// Highest version both sides speak, or `protocol_mismatch` when the
+    /// ranges do not overlap.
+    pub fn negotiate(&self) -> Result<u32, ErrorInfo> {
+        let version = self.max_protocol_version.min(PROTOCOL_VERSION);
+        if version < self.min_protocol_version.max(MIN_PROTOCOL_VERSION) {
+            return Err(ErrorInfo::new(
+                ERROR_PROTOCOL_MISMATCH,
+                format!(
+                    "client supports protocol {}..={}, daemon supports {}..={}",
+                    self.min_protocol_version,
+                    self.max_protocol_version,
+                    MIN_PROTOCOL_VERSION,
+                    PROTOCOL_VERSION
+                ),
+            ));
+        }
+        Ok(version)
+    }
+}
+
+fn default_min_protocol_version() -> u32 {
+    MIN_PROTOCOL_VERSION
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionTopic {
+    Sessions,
+    ProjectStates,
+    Shells,
+    Routing,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Upsert,
+    Delete,
+}
+
+/// A single line streamed to `subscribe` clients after the initial ack.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SubscriptionMessage {
+    Change(StateChange),
+    Heartbeat { emitted_at: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct StateChange {
+    pub topic: SubscriptionTopic,
+    pub change: ChangeKind,
+    /// Stable identity of the changed entry (session id, project path, shell pid...).
+    pub key: String,
+    /// Full entry after the change; omitted for deletes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    pub emitted_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RoutingStatus {
     Attached,
@@ -72,7 +441,7 @@ pub enum RoutingStatus {
     Unavailable,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RoutingTargetKind {
     TmuxSession,
@@ -80,7 +449,7 @@ pub enum RoutingTargetKind {
     None,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RoutingConfidence {
     High,
@@ -88,7 +457,7 @@ pub enum RoutingConfidence {
     Low,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingTarget {
     pub kind: RoutingTargetKind,
@@ -96,7 +465,7 @@ pub struct RoutingTarget {
     pub value: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingEvidence {
     pub evidence_type: String,
@@ -105,7 +474,7 @@ pub struct RoutingEvidence {
     pub trust_rank: u8,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingSnapshot {
     pub version: u32,
@@ -120,7 +489,7 @@ pub struct RoutingSnapshot {
     pub updated_at: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingDiagnostics {
     pub snapshot: RoutingSnapshot,
@@ -130,16 +499,35 @@ pub struct RoutingDiagnostics {
     pub scope_resolution: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RoutingConfigView {
     pub tmux_signal_fresh_ms: u64,
     pub shell_signal_fresh_ms: u64,
     pub shell_retention_hours: u64,
     pub tmux_poll_interval_ms: u64,
+    /// Absent from daemons that predate `[session_policy]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_policy: Option<SessionPolicyView>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Session lifecycle timings in effect (`[session_policy]` in the daemon
+/// config, defaults filled in).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SessionPolicyView {
+    pub ttl_active_secs: u64,
+    pub ttl_ready_secs: u64,
+    pub ttl_idle_secs: u64,
+    pub inactivity_fallback: bool,
+    pub inactivity_fallback_secs: u64,
+    pub inactivity_fallback_events: Vec<String>,
+    pub post_tool_use_stale_secs: u64,
+    pub stop_gate_grace_secs: u64,
+    pub stale_event_grace_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Response {
     pub ok: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -148,9 +536,12 @@ pub struct Response {
     pub data: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<ErrorInfo>,
+    /// Daemon state revision the payload reflects (snapshot methods only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
 pub struct ErrorInfo {
     pub code: String,
     pub message: String,
@@ -172,15 +563,22 @@ impl Response {
             id,
             data: Some(data),
             error: None,
+            revision: None,
         }
     }
 
+    pub fn with_revision(mut self, revision: u64) -> Self {
+        self.revision = Some(revision);
+        self
+    }
+
     pub fn error(id: Option<String>, code: &str, message: impl Into<String>) -> Self {
         Self {
             ok: false,
             id,
             data: None,
             error: Some(ErrorInfo::new(code, message)),
+            revision: None,
         }
     }
 
@@ -190,11 +588,12 @@ impl Response {
             id,
             data: None,
             error: Some(error),
+            revision: None,
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, JsonSchema)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum EventType {
     SessionStart,
@@ -220,7 +619,7 @@ pub enum EventType {
 // IPC contract fields; not all are consumed in Phase 1, but we keep them
 // to lock the schema early and avoid churn during client integration.
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct EventEnvelope {
     pub event_id: String,
@@ -310,6 +709,52 @@ pub fn parse_event(params: Value) -> Result<EventEnvelope, ErrorInfo> {
     Ok(envelope)
 }
 
+/// One entry of an `event_batch` request, in request order.
+#[derive(Debug)]
+pub struct BatchEvent {
+    /// The raw `event_id`, when present, so rejected entries can be reported.
+    pub event_id: Option<String>,
+    pub event: Result<EventEnvelope, ErrorInfo>,
+}
+
+/// Parses `{"events": [...]}`. Each envelope is parsed and validated on its
+/// own so one bad event does not reject the whole batch.
+pub fn parse_event_batch(params: Option<Value>) -> Result<Vec<BatchEvent>, ErrorInfo> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct RawEventBatch {
+        events: Vec<Value>,
+    }
+
+    let Some(params) = params else {
+        return Err(ErrorInfo::new("invalid_params", "events is required"));
+    };
+    let parsed: RawEventBatch = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("event batch params are invalid JSON: {}", err),
+        )
+    })?;
+    if parsed.events.len() > MAX_EVENT_BATCH {
+        return Err(ErrorInfo::new(
+            "invalid_params",
+            format!("event batch exceeds {} events", MAX_EVENT_BATCH),
+        ));
+    }
+
+    Ok(parsed
+        .events
+        .into_iter()
+        .map(|raw| BatchEvent {
+            event_id: raw
+                .get("event_id")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            event: parse_event(raw),
+        })
+        .collect())
+}
+
 pub fn parse_process_liveness(params: Value) -> Result<ProcessLivenessRequest, ErrorInfo> {
     serde_json::from_value(params).map_err(|err| {
         ErrorInfo::new(
@@ -371,6 +816,201 @@ pub fn parse_routing_diagnostics(params: Value) -> Result<RoutingDiagnosticsRequ
     })
 }
 
+pub fn parse_hello(params: Option<Value>) -> Result<HelloRequest, ErrorInfo> {
+    let Some(params) = params else {
+        return Err(ErrorInfo::new(
+            "invalid_params",
+            "max_protocol_version is required",
+        ));
+    };
+    let parsed: HelloRequest = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("hello params are invalid JSON: {}", err),
+        )
+    })?;
+    if parsed.min_protocol_version > parsed.max_protocol_version {
+        return Err(ErrorInfo::new(
+            "invalid_params",
+            "min_protocol_version must not exceed max_protocol_version",
+        ));
+    }
+    Ok(parsed)
+}
+
+pub fn parse_subagents_request(params: Option<Value>) -> Result<SubagentsRequest, ErrorInfo> {
+    let Some(params) = params else {
+        return Err(ErrorInfo::new("invalid_params", "session_id is required"));
+    };
+    let mut parsed: SubagentsRequest = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("subagents params are invalid JSON: {}", err),
+        )
+    })?;
+    parsed.session_id = normalize_optional_string(Some(parsed.session_id))
+        .ok_or_else(|| ErrorInfo::new("invalid_params", "session_id is required"))?;
+    Ok(parsed)
+}
+
+pub fn parse_session_timeline_request(
+    params: Option<Value>,
+) -> Result<SessionTimelineRequest, ErrorInfo> {
+    let Some(params) = params else {
+        return Err(ErrorInfo::new("invalid_params", "session_id is required"));
+    };
+    let mut parsed: SessionTimelineRequest = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("session timeline params are invalid JSON: {}", err),
+        )
+    })?;
+    parsed.session_id = normalize_optional_string(Some(parsed.session_id))
+        .ok_or_else(|| ErrorInfo::new("invalid_params", "session_id is required"))?;
+    parsed.since = normalize_time_bound(parsed.since, "since")?;
+    parsed.until = normalize_time_bound(parsed.until, "until")?;
+    Ok(parsed)
+}
+
+pub fn parse_snapshot_request(params: Option<Value>) -> Result<SnapshotRequest, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(SnapshotRequest::default());
+    };
+    serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("snapshot params are invalid JSON: {}", err),
+        )
+    })
+}
+
+pub fn parse_activity_request(params: Option<Value>) -> Result<ActivityRequest, ErrorInfo> {
+    let mut request = ActivityRequest::default();
+    let Some(params) = params else {
+        return Ok(request);
+    };
+    if !params.is_object() {
+        return Err(ErrorInfo::new("invalid_params", "params must be an object"));
+    }
+    if let Some(value) = params.get("session_id").and_then(|v| v.as_str()) {
+        if !value.trim().is_empty() {
+            request.session_id = Some(value.to_string());
+        }
+    }
+    if let Some(value) = params.get("limit").and_then(|v| v.as_u64()) {
+        request.limit = value.min(ACTIVITY_MAX_LIMIT as u64) as usize;
+    }
+    Ok(request)
+}
+
+pub fn parse_events_query(params: Option<Value>) -> Result<EventsQuery, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(EventsQuery::default());
+    };
+    let mut query: EventsQuery = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("events params are invalid JSON: {}", err),
+        )
+    })?;
+    query.session_id = normalize_optional_string(query.session_id);
+    query.project_path = normalize_project_filter(query.project_path);
+    query.since = normalize_time_bound(query.since, "since")?;
+    query.until = normalize_time_bound(query.until, "until")?;
+    Ok(query)
+}
+
+pub fn parse_project_time_query(params: Option<Value>) -> Result<ProjectTimeQuery, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(ProjectTimeQuery::default());
+    };
+    let mut query: ProjectTimeQuery = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("project time params are invalid JSON: {}", err),
+        )
+    })?;
+    query.project_path = normalize_project_filter(query.project_path);
+    if let Some(offset) = query.utc_offset_minutes {
+        if offset.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err(ErrorInfo::new(
+                "invalid_params",
+                format!(
+                    "utc_offset_minutes must be within +/-{}",
+                    MAX_UTC_OFFSET_MINUTES
+                ),
+            ));
+        }
+    }
+    Ok(query)
+}
+
+pub fn parse_tool_calls_query(params: Option<Value>) -> Result<ToolCallsQuery, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(ToolCallsQuery::default());
+    };
+    let mut query: ToolCallsQuery = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("tool calls params are invalid JSON: {}", err),
+        )
+    })?;
+    query.session_id = normalize_optional_string(query.session_id);
+    query.project_path = normalize_project_filter(query.project_path);
+    query.since = normalize_time_bound(query.since, "since")?;
+    Ok(query)
+}
+
+pub fn parse_worktrees_query(params: Option<Value>) -> Result<WorktreesQuery, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(WorktreesQuery::default());
+    };
+    let mut query: WorktreesQuery = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("worktrees params are invalid JSON: {}", err),
+        )
+    })?;
+    query.project_path = normalize_project_filter(query.project_path);
+    Ok(query)
+}
+
+pub fn parse_config_changes_query(params: Option<Value>) -> Result<ConfigChangesQuery, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(ConfigChangesQuery::default());
+    };
+    let mut query: ConfigChangesQuery = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("config changes params are invalid JSON: {}", err),
+        )
+    })?;
+    query.project_path = normalize_project_filter(query.project_path);
+    query.since = normalize_time_bound(query.since, "since")?;
+    Ok(query)
+}
+
+pub fn parse_subscribe(params: Option<Value>) -> Result<SubscribeRequest, ErrorInfo> {
+    let Some(params) = params else {
+        return Ok(SubscribeRequest { topics: Vec::new() });
+    };
+    let mut parsed: SubscribeRequest = serde_json::from_value(params).map_err(|err| {
+        ErrorInfo::new(
+            "invalid_params",
+            format!("subscribe params are invalid JSON: {}", err),
+        )
+    })?;
+    let mut seen = Vec::with_capacity(parsed.topics.len());
+    parsed.topics.retain(|topic| {
+        if seen.contains(topic) {
+            return false;
+        }
+        seen.push(*topic);
+        true
+    });
+    Ok(parsed)
+}
+
 fn require_session_fields(event: &EventEnvelope) -> Result<(), ErrorInfo> {
     require_string(&event.session_id, "session_id")?;
     require_string(&event.cwd, "cwd")?;
@@ -385,6 +1025,27 @@ fn normalize_recorded_at(value: &str) -> Result<String, ErrorInfo> {
         .to_rfc3339_opts(SecondsFormat::Secs, true))
 }
 
+fn normalize_project_filter(value: Option<String>) -> Option<String> {
+    normalize_optional_string(value).map(|path| {
+        let trimmed = path.trim_end_matches('/');
+        if trimmed.is_empty() {
+            "/".to_string()
+        } else {
+            trimmed.to_string()
+        }
+    })
+}
+
+fn normalize_time_bound(value: Option<String>, field: &str) -> Result<Option<String>, ErrorInfo> {
+    value
+        .map(|value| {
+            normalize_recorded_at(&value).map_err(|_| {
+                ErrorInfo::new("invalid_timestamp", format!("{} must be RFC3339", field))
+            })
+        })
+        .transpose()
+}
+
 fn require_string(value: &Option<String>, field: &str) -> Result<(), ErrorInfo> {
     if let Some(candidate) = value {
         if !candidate.trim().is_empty() {
@@ -586,4 +1247,277 @@ mod tests {
         assert_eq!(diagnostics, "\"get_routing_diagnostics\"");
         assert_eq!(config, "\"get_config\"");
     }
+
+    #[test]
+    fn parse_snapshot_request_accepts_optional_since_revision() {
+        let parsed = parse_snapshot_request(None).expect("parse without params");
+        assert_eq!(parsed.since_revision, None);
+
+        let parsed = parse_snapshot_request(Some(serde_json::json!({ "since_revision": 42 })))
+            .expect("parse with since_revision");
+        assert_eq!(parsed.since_revision, Some(42));
+
+        let error = parse_snapshot_request(Some(serde_json::json!({ "since_revision": -1 })))
+            .expect_err("negative revision should fail");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn response_revision_is_omitted_unless_set() {
+        let plain = serde_json::to_value(Response::ok(None, serde_json::json!([])))
+            .expect("serialize response");
+        assert!(plain.get("revision").is_none());
+
+        let revisioned =
+            serde_json::to_value(Response::ok(None, serde_json::json!([])).with_revision(7))
+                .expect("serialize response");
+        assert_eq!(revisioned["revision"], 7);
+    }
+
+    #[test]
+    fn parse_event_batch_validates_each_event_independently() {
+        let good = serde_json::to_value(base_event(EventType::SessionStart)).unwrap();
+        let mut bad = good.clone();
+        bad["event_id"] = serde_json::json!("evt-bad");
+        bad["recorded_at"] = serde_json::json!("yesterday");
+
+        let parsed = parse_event_batch(Some(serde_json::json!({
+            "events": [good, bad, { "recorded_at": "2026-01-30T12:00:00Z" }]
+        })))
+        .expect("parse batch");
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].event.as_ref().unwrap().event_id, "evt-1");
+        assert_eq!(parsed[1].event_id.as_deref(), Some("evt-bad"));
+        assert_eq!(
+            parsed[1].event.as_ref().unwrap_err().code,
+            "invalid_timestamp"
+        );
+        assert_eq!(parsed[2].event_id, None);
+        assert!(parsed[2].event.is_err());
+    }
+
+    #[test]
+    fn parse_event_batch_rejects_oversized_or_missing_events() {
+        assert_eq!(parse_event_batch(None).unwrap_err().code, "invalid_params");
+        let events = vec![serde_json::json!({}); MAX_EVENT_BATCH + 1];
+        let error = parse_event_batch(Some(serde_json::json!({ "events": events })))
+            .expect_err("oversized batch should fail");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn parse_events_query_normalizes_filters() {
+        let query = parse_events_query(Some(serde_json::json!({
+            "session_id": "  ",
+            "event_types": ["pre_tool_use", "stop"],
+            "project_path": "/repo/",
+            "since": "2026-01-30T13:00:00+01:00",
+            "limit": 5000
+        })))
+        .expect("parse events query");
+        assert_eq!(query.session_id, None);
+        assert_eq!(
+            query.event_types,
+            vec![EventType::PreToolUse, EventType::Stop]
+        );
+        assert_eq!(query.project_path.as_deref(), Some("/repo"));
+        assert_eq!(query.since.as_deref(), Some("2026-01-30T12:00:00Z"));
+        assert_eq!(query.limit(), EVENTS_MAX_LIMIT);
+        assert_eq!(
+            parse_events_query(None).expect("defaults").limit(),
+            EVENTS_DEFAULT_LIMIT
+        );
+
+        let error = parse_events_query(Some(serde_json::json!({ "until": "tomorrow" })))
+            .expect_err("bad bound");
+        assert_eq!(error.code, "invalid_timestamp");
+        let error = parse_events_query(Some(serde_json::json!({ "event_types": ["nap"] })))
+            .expect_err("unknown event type");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn parse_subagents_request_requires_session_id() {
+        let parsed = parse_subagents_request(Some(serde_json::json!({ "session_id": " s1 " })))
+            .expect("parse subagents request");
+        assert_eq!(parsed.session_id, "s1");
+        for params in [
+            None,
+            Some(serde_json::json!({ "session_id": "  " })),
+            Some(serde_json::json!({ "session_id": "s1", "limit": 5 })),
+        ] {
+            let error = parse_subagents_request(params).expect_err("invalid params");
+            assert_eq!(error.code, "invalid_params");
+        }
+    }
+
+    #[test]
+    fn parse_session_timeline_request_normalizes_window() {
+        let parsed = parse_session_timeline_request(Some(serde_json::json!({
+            "session_id": "s1",
+            "since": "2026-01-31T01:00:00+01:00"
+        })))
+        .expect("parse timeline request");
+        assert_eq!(parsed.since.as_deref(), Some("2026-01-31T00:00:00Z"));
+        assert_eq!(parsed.until, None);
+
+        let error = parse_session_timeline_request(Some(serde_json::json!({
+            "session_id": "s1",
+            "until": "yesterday"
+        })))
+        .expect_err("invalid until");
+        assert_eq!(error.code, "invalid_timestamp");
+        let error = parse_session_timeline_request(None).expect_err("missing session");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn parse_project_time_query_clamps_days_and_checks_offset() {
+        let query = parse_project_time_query(Some(serde_json::json!({
+            "project_path": "/repo/",
+            "days": 500,
+            "utc_offset_minutes": -480
+        })))
+        .expect("parse project time");
+        assert_eq!(query.project_path.as_deref(), Some("/repo"));
+        assert_eq!(query.days(), PROJECT_TIME_MAX_DAYS);
+        assert_eq!(ProjectTimeQuery::default().days(), 1);
+
+        let error = parse_project_time_query(Some(serde_json::json!({
+            "utc_offset_minutes": 15 * 60
+        })))
+        .expect_err("offset out of range");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn parse_tool_calls_query_normalizes_filters() {
+        let query = parse_tool_calls_query(Some(serde_json::json!({
+            "session_id": " ",
+            "project_path": "/repo/",
+            "since": "2026-01-31T01:00:00+01:00",
+            "limit": 5000
+        })))
+        .expect("parse tool calls query");
+        assert_eq!(query.session_id, None);
+        assert_eq!(query.project_path.as_deref(), Some("/repo"));
+        assert_eq!(query.since.as_deref(), Some("2026-01-31T00:00:00Z"));
+        assert_eq!(query.limit(), TOOL_CALLS_MAX_LIMIT);
+        assert_eq!(ToolCallsQuery::default().limit(), TOOL_CALLS_DEFAULT_LIMIT);
+
+        let error = parse_tool_calls_query(Some(serde_json::json!({ "tool": "Read" })))
+            .expect_err("unknown field");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn events_query_matches_project_subdirectories_only() {
+        let query = EventsQuery {
+            project_path: Some("/repo".to_string()),
+            ..EventsQuery::default()
+        };
+        assert!(query.matches_project(Some("/repo")));
+        assert!(query.matches_project(Some("/repo/src/")));
+        assert!(!query.matches_project(Some("/repo-other")));
+        assert!(!query.matches_project(None));
+        assert!(EventsQuery::default().matches_project(None));
+    }
+
+    #[test]
+    fn parse_hello_tolerates_unknown_fields() {
+        let parsed = parse_hello(Some(serde_json::json!({
+            "max_protocol_version": 7,
+            "features": ["subscribe", "teleport"],
+            "field_from_newer_client": true
+        })))
+        .expect("parse hello");
+        assert_eq!(parsed.min_protocol_version, MIN_PROTOCOL_VERSION);
+        assert_eq!(parsed.negotiate().expect("negotiate"), PROTOCOL_VERSION);
+
+        let error = parse_hello(Some(serde_json::json!({
+            "min_protocol_version": 3,
+            "max_protocol_version": 2
+        })))
+        .expect_err("inverted range should fail");
+        assert_eq!(error.code, "invalid_params");
+        assert!(parse_hello(None).is_err());
+    }
+
+    #[test]
+    fn hello_negotiates_highest_shared_version() {
+        let old_client = HelloRequest {
+            min_protocol_version: 1,
+            max_protocol_version: 1,
+            ..HelloRequest::default()
+        };
+        assert_eq!(old_client.negotiate().expect("negotiate"), 1);
+
+        let future_client = HelloRequest {
+            min_protocol_version: PROTOCOL_VERSION + 1,
+            max_protocol_version: PROTOCOL_VERSION + 3,
+            ..HelloRequest::default()
+        };
+        let error = future_client.negotiate().expect_err("disjoint ranges");
+        assert_eq!(error.code, ERROR_PROTOCOL_MISMATCH);
+    }
+
+    #[test]
+    fn supported_versions_cover_the_whole_range() {
+        assert!(!is_supported_protocol_version(0));
+        assert!(is_supported_protocol_version(MIN_PROTOCOL_VERSION));
+        assert!(is_supported_protocol_version(PROTOCOL_VERSION));
+        assert!(!is_supported_protocol_version(PROTOCOL_VERSION + 1));
+        assert!(Method::GetHealth.since_version() >= MIN_PROTOCOL_VERSION);
+        assert!(Method::Hello.since_version() <= PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn parse_subscribe_defaults_to_all_topics() {
+        let parsed = parse_subscribe(None).expect("parse subscribe without params");
+        assert!(parsed.topics.is_empty());
+        assert!(parsed.wants(SubscriptionTopic::Sessions));
+        assert!(parsed.wants(SubscriptionTopic::Routing));
+    }
+
+    #[test]
+    fn parse_subscribe_filters_topics_and_dedupes() {
+        let params = serde_json::json!({
+            "topics": ["project_states", "shells", "project_states"]
+        });
+        let parsed = parse_subscribe(Some(params)).expect("parse subscribe");
+        assert_eq!(
+            parsed.topics,
+            vec![SubscriptionTopic::ProjectStates, SubscriptionTopic::Shells]
+        );
+        assert!(!parsed.wants(SubscriptionTopic::Sessions));
+    }
+
+    #[test]
+    fn parse_subscribe_rejects_unknown_topic() {
+        let params = serde_json::json!({ "topics": ["weather"] });
+        let error = parse_subscribe(Some(params)).expect_err("unknown topic should fail");
+        assert_eq!(error.code, "invalid_params");
+    }
+
+    #[test]
+    fn subscription_messages_are_tagged_by_type() {
+        let change = SubscriptionMessage::Change(StateChange {
+            topic: SubscriptionTopic::Sessions,
+            change: ChangeKind::Delete,
+            key: "session-1".to_string(),
+            data: None,
+            emitted_at: "2026-01-30T12:00:00Z".to_string(),
+        });
+        let value = serde_json::to_value(&change).expect("serialize change");
+        assert_eq!(value["type"], "change");
+        assert_eq!(value["topic"], "sessions");
+        assert_eq!(value["change"], "delete");
+        assert!(value.get("data").is_none());
+
+        let heartbeat = SubscriptionMessage::Heartbeat {
+            emitted_at: "2026-01-30T12:00:00Z".to_string(),
+        };
+        let value = serde_json::to_value(&heartbeat).expect("serialize heartbeat");
+        assert_eq!(value["type"], "heartbeat");
+    }
 }
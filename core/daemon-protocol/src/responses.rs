@@ -0,0 +1,945 @@
+//! Response payloads (`Response::data`) for each method.
+//!
+//! The daemon serializes these and clients deserialize them, so the wire shape
+//! lives in one place. Field attributes are part of the contract: an optional
+//! field that is skipped when `None` is absent on the wire, the others are
+//! sent as `null`. [`crate::schema`] derives `docs/daemon-ipc.schema.json`
+//! from these types.
+
+use crate::{
+    ErrorInfo, EventEnvelope, RoutingConfigView, RoutingDiagnostics, RoutingSnapshot,
+    SubscriptionTopic,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Session or project state as reported over IPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionState {
+    Working,
+    Ready,
+    Idle,
+    Compacting,
+    Waiting,
+    /// A state introduced by a newer daemon. Never sent by this version.
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
+}
+
+impl SessionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionState::Working => "working",
+            SessionState::Ready => "ready",
+            SessionState::Idle => "idle",
+            SessionState::Compacting => "compacting",
+            SessionState::Waiting => "waiting",
+            SessionState::Unknown => "unknown",
+        }
+    }
+}
+
+/// `get_health`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub pid: u32,
+    pub version: String,
+    pub protocol_version: u32,
+    pub dead_session_reconcile_interval_secs: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_revision: Option<u64>,
+    pub security: SecurityHealth,
+    pub runtime: RuntimeHealth,
+    // Observability snapshots, documented in `docs/daemon-ipc.md`.
+    /// Dead-session reconciliation runs, keyed by source (`startup`,
+    /// `periodic`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dead_session_reconcile: Option<HashMap<String, DeadSessionReconcileHealth>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hem_shadow: Option<HemShadowHealth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingHealth>,
+    /// Absent when the daemon has no start history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<BackoffHealth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionHealth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<IntegrityHealth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub writer: Option<WriterHealth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_view: Option<ProjectViewHealth>,
+}
+
+impl HealthResponse {
+    pub fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SecurityHealth {
+    pub peer_auth_mode: String,
+    pub rejected_connections: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RuntimeHealth {
+    pub active_connections: u64,
+    pub max_active_connections: u64,
+    #[serde(default)]
+    pub subscribers: u64,
+    #[serde(default)]
+    pub max_subscribers: u64,
+    pub build_hash: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeadSessionReconcileHealth {
+    pub runs: u64,
+    pub repaired_sessions: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_repair_at: Option<String>,
+}
+
+/// HEM shadow-mode comparison counters and cutover gates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HemShadowHealth {
+    pub enabled: bool,
+    /// `shadow` or `primary`.
+    pub mode: String,
+    pub capability_status: HemCapabilityHealth,
+    pub events_evaluated: u64,
+    pub projects_evaluated: u64,
+    pub mismatches_total: u64,
+    pub mismatches_by_category: HashMap<String, u64>,
+    pub mismatches_by_severity: HashMap<String, u64>,
+    pub gate_blocking_mismatches: u64,
+    pub gate_critical_mismatches: u64,
+    pub gate_important_mismatches: u64,
+    pub stable_state_samples: u64,
+    pub stable_state_matches: u64,
+    pub stable_state_agreement_rate: f64,
+    pub stable_state_agreement_gate_target: f64,
+    pub stable_state_agreement_gate_met: bool,
+    pub shadow_gate_ready: bool,
+    pub blocking_mismatch_rate: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_evaluated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_mismatch_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_blocking_mismatch_at: Option<String>,
+}
+
+/// Hook capability detection as seen by HEM.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HemCapabilityHealth {
+    pub strategy: String,
+    pub handshake_seen: bool,
+    pub confidence_penalty_factor: f64,
+    pub unknown_count: u64,
+    pub misdeclared_count: u64,
+    pub warning_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_warning_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<HemCapabilityWarning>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HemCapabilityWarning {
+    pub code: String,
+    pub capability: String,
+    pub declared: String,
+    pub observed: String,
+}
+
+/// ARE routing snapshot counters and the legacy comparison rollout gate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingHealth {
+    pub enabled: bool,
+    pub dual_run_enabled: bool,
+    pub snapshots_emitted: u64,
+    pub dual_run_comparisons: u64,
+    pub legacy_vs_are_status_mismatch: u64,
+    pub legacy_vs_are_target_mismatch: u64,
+    pub confidence_high: u64,
+    pub confidence_medium: u64,
+    pub confidence_low: u64,
+    pub rollout: RoutingRolloutHealth,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_snapshot_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingRolloutHealth {
+    pub agreement_gate_target: f64,
+    pub min_comparisons_required: u64,
+    pub min_window_hours_required: u64,
+    pub comparisons: u64,
+    pub volume_gate_met: bool,
+    pub window_gate_met: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_agreement_rate: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_agreement_rate: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_comparison_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_comparison_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_elapsed_hours: Option<u64>,
+    pub status_gate_met: bool,
+    pub target_gate_met: bool,
+    pub status_row_default_ready: bool,
+    pub launcher_default_ready: bool,
+}
+
+/// Startup backoff after repeated daemon restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BackoffHealth {
+    pub starts_in_window: usize,
+    pub window_secs: i64,
+    pub max_starts: usize,
+    pub backoff_step_secs: u64,
+    pub max_backoff_secs: u64,
+    pub last_start: Option<String>,
+    /// Delay the next start would wait, if it would back off.
+    pub next_backoff_secs: Option<u64>,
+}
+
+/// Retention configuration and the outcome of its runs since daemon start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RetentionHealth {
+    pub enabled: bool,
+    pub event_retention_days: u64,
+    pub activity_retention_days: u64,
+    pub history_retention_days: u64,
+    pub history_max_rows: u64,
+    pub interval_secs: u64,
+    pub runs: u64,
+    pub last_run_at: Option<String>,
+    /// Cleared by the next successful run.
+    pub last_error: Option<String>,
+    pub last_events_pruned: u64,
+    pub events_pruned_total: u64,
+    pub last_activity_pruned: u64,
+    pub activity_pruned_total: u64,
+    pub last_history_pruned: u64,
+    pub history_pruned_total: u64,
+    /// Event cursor of the latest retention snapshot.
+    pub snapshot_event_rowid: Option<i64>,
+    pub snapshot_at: Option<String>,
+    pub event_count: u64,
+    pub oldest_event_at: Option<String>,
+    pub auto_vacuum: Option<String>,
+    pub freelist_pages: Option<u64>,
+    pub last_vacuum_pages: u64,
+}
+
+/// Outcome of the startup integrity check on `state.db`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct IntegrityHealth {
+    /// `ok`, `created` (no database yet) or `repaired`.
+    pub status: String,
+    pub checked_at: String,
+    pub problems: Vec<String>,
+    pub quarantined_path: Option<String>,
+    pub events_salvaged: u64,
+    /// Rows the damaged events table returned that could not be parsed.
+    pub events_unreadable: u64,
+    pub snapshot_salvaged: bool,
+}
+
+/// Event writer queue and batch counters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WriterHealth {
+    pub queue_capacity: usize,
+    pub queued: usize,
+    pub max_batch_events: usize,
+    pub batches: u64,
+    pub events_written: u64,
+    pub largest_batch: usize,
+    pub busy_rejections: u64,
+    pub failed_batches: u64,
+    /// Events that could not be stored.
+    pub rejected_events: u64,
+    /// Events stored without their derived state.
+    pub skipped_events: u64,
+    pub last_batch_micros: u64,
+}
+
+/// Counters of the in-memory view behind `get_project_states`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectViewHealth {
+    pub projects: usize,
+    pub refresh_interval_secs: u64,
+    pub full_refreshes: u64,
+    pub partial_refreshes: u64,
+    pub reads: u64,
+    pub last_refresh_at: Option<String>,
+    pub last_refresh_micros: u64,
+    /// State revision the current contents were recorded at.
+    pub revision: Option<u64>,
+}
+
+/// `get_shell_state`.
+pub type ShellStateResponse = ShellState;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ShellState {
+    pub version: u32,
+    pub shells: HashMap<String, ShellEntry>,
+}
+
+impl Default for ShellState {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            shells: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ShellEntry {
+    pub cwd: String,
+    pub tty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_app: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmux_session: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmux_client_tty: Option<String>,
+    pub updated_at: String,
+}
+
+/// `get_process_liveness`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ProcessLivenessResponse {
+    Found(ProcessLiveness),
+    /// The daemon has no record of the pid.
+    NotFound {
+        found: bool,
+        pid: u32,
+    },
+}
+
+impl ProcessLivenessResponse {
+    pub fn not_found(pid: u32) -> Self {
+        ProcessLivenessResponse::NotFound { found: false, pid }
+    }
+
+    pub fn into_option(self) -> Option<ProcessLiveness> {
+        match self {
+            ProcessLivenessResponse::Found(liveness) => Some(liveness),
+            ProcessLivenessResponse::NotFound { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessLiveness {
+    pub pid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proc_started: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_start_time: Option<u64>,
+    pub last_seen_at: String,
+    pub is_alive: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_matches: Option<bool>,
+}
+
+/// `get_routing_snapshot`.
+pub type RoutingSnapshotResponse = RoutingSnapshot;
+
+/// `get_routing_diagnostics`.
+pub type RoutingDiagnosticsResponse = RoutingDiagnostics;
+
+/// `get_config`.
+pub type ConfigResponse = RoutingConfigView;
+
+/// `get_sessions` without `since_revision`.
+pub type SessionsResponse = Vec<EnrichedSession>;
+
+/// `get_sessions` with `since_revision`.
+pub type SessionsDeltaResponse = SnapshotDelta<EnrichedSession>;
+
+/// Session record enriched with liveness info.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EnrichedSession {
+    pub session_id: String,
+    pub pid: u32,
+    pub state: SessionState,
+    pub cwd: String,
+    #[serde(default)]
+    pub project_id: String,
+    #[serde(default)]
+    pub workspace_id: String,
+    pub project_path: String,
+    pub updated_at: String,
+    pub state_changed_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_event: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<String>,
+    #[serde(default)]
+    pub tools_in_flight: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_reason: Option<String>,
+    /// Whether the session's process is still alive.
+    /// None if pid is 0 (unknown), Some(true) if alive, Some(false) if dead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_alive: Option<bool>,
+    /// Subagents started by this session that have not stopped yet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub active_subagents: Vec<SubagentRecord>,
+    /// What the session is waiting for; only set while it is `waiting`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_request: Option<PendingRequest>,
+}
+
+/// The prompt a `waiting` session is blocked on, kept from the
+/// `permission_request` or notification that put it there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PendingRequest {
+    pub kind: PendingRequestKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    /// One line from the tool input, e.g. the Bash command or fetched URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// `permission_suggestions` exactly as the hook received them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission_suggestions: Option<Value>,
+    pub requested_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingRequestKind {
+    PermissionRequest,
+    PermissionPrompt,
+    ElicitationDialog,
+}
+
+/// `get_subagents`: every subagent of the session, oldest first.
+pub type SubagentsResponse = Vec<SubagentRecord>;
+
+/// A subagent (e.g. a Task tool child) running inside a session, tracked from
+/// `subagent_start` / `subagent_stop` and from tool events carrying its
+/// `agent_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SubagentRecord {
+    pub session_id: String,
+    pub agent_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_type: Option<String>,
+    /// `None` when the start was not observed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    /// `None` while the subagent is running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript_path: Option<String>,
+    /// Distinct tool names in first-use order.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default)]
+    pub tool_calls: u32,
+    pub updated_at: String,
+}
+
+impl SubagentRecord {
+    pub fn is_running(&self) -> bool {
+        self.stopped_at.is_none()
+    }
+}
+
+/// `get_session_timeline`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionTimeline {
+    pub session_id: String,
+    /// Transitions inside the window, oldest first.
+    pub transitions: Vec<SessionTransition>,
+    /// Whole seconds spent in each state inside the window, including the
+    /// state the session was already in when the window opened.
+    #[serde(default)]
+    pub seconds_in_state: HashMap<SessionState, u64>,
+}
+
+/// One change of a session's stored state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionTransition {
+    pub session_id: String,
+    pub project_path: String,
+    /// `None` when the session started.
+    pub from_state: Option<SessionState>,
+    /// `None` when the session ended.
+    pub to_state: Option<SessionState>,
+    /// The event that caused it; `None` when the daemon pruned the session.
+    pub event_id: Option<String>,
+    pub transitioned_at: String,
+}
+
+/// `get_project_time`: one row per project and day with any recorded time.
+pub type ProjectTimeResponse = Vec<ProjectDayTime>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectDayTime {
+    pub project_path: String,
+    /// Calendar day (`YYYY-MM-DD`) at the requested UTC offset.
+    pub day: String,
+    /// Whole seconds sessions of this project spent in each state.
+    #[serde(default)]
+    pub seconds_in_state: HashMap<SessionState, u64>,
+    /// Seconds from a permission prompt or waiting notification until the
+    /// human answered it.
+    #[serde(default)]
+    pub waiting_on_human_secs: u64,
+}
+
+/// `get_tool_calls`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolCallsResponse {
+    /// Matching calls, newest first, at most `limit`.
+    pub calls: Vec<ToolCall>,
+    /// Totals per tool over every matching call, slowest average first.
+    pub tools: Vec<ToolStats>,
+}
+
+/// One tool invocation, paired from `pre_tool_use` and its
+/// `post_tool_use` / `post_tool_use_failure` by `tool_use_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolCall {
+    pub session_id: String,
+    pub tool_use_id: String,
+    pub tool_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    pub project_path: String,
+    /// Set when a subagent made the call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    pub started_at: String,
+    /// `None` while the call is running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// `None` while the call is running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<ToolCallOutcome>,
+}
+
+impl ToolCall {
+    pub fn is_running(&self) -> bool {
+        self.outcome.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallOutcome {
+    Success,
+    Failure,
+    /// The turn ended, was interrupted or the session went away before the
+    /// call reported a result.
+    Interrupted,
+}
+
+impl ToolCallOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ToolCallOutcome::Success => "success",
+            ToolCallOutcome::Failure => "failure",
+            ToolCallOutcome::Interrupted => "interrupted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "success" => Some(ToolCallOutcome::Success),
+            "failure" => Some(ToolCallOutcome::Failure),
+            "interrupted" => Some(ToolCallOutcome::Interrupted),
+            _ => None,
+        }
+    }
+}
+
+/// Aggregates for one tool name. Durations and the failure rate only count
+/// calls that reported a result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolStats {
+    pub tool_name: String,
+    pub calls: u64,
+    pub failures: u64,
+    pub interrupted: u64,
+    /// Failures per hundred completed calls, rounded down.
+    pub failure_rate_percent: u32,
+    pub avg_duration_ms: u64,
+    pub max_duration_ms: u64,
+    /// The call behind `max_duration_ms`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slowest_tool_use_id: Option<String>,
+}
+
+/// `get_project_states` without `since_revision`.
+pub type ProjectStatesResponse = Vec<ProjectStateRecord>;
+
+/// `get_project_states` with `since_revision`.
+pub type ProjectStatesDeltaResponse = SnapshotDelta<ProjectStateRecord>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectStateRecord {
+    pub project_id: String,
+    pub workspace_id: String,
+    pub project_path: String,
+    pub state: SessionState,
+    pub state_changed_at: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub latest_session_id: Option<String>,
+    pub session_count: usize,
+    pub active_count: usize,
+    pub has_session: bool,
+    /// The representative session's pending request while the project is
+    /// `waiting`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_request: Option<PendingRequest>,
+    /// Registered worktrees of the project that have sessions, each with the
+    /// state of the sessions running in it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub worktrees: Vec<ProjectWorktreeState>,
+    /// Agent teams running in the project.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<TeamState>,
+}
+
+/// An agent team: the lead session, its teammates and the tasks they
+/// completed, from events carrying `team_name` / `teammate_name` metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TeamState {
+    pub team_name: String,
+    /// `None` until an event from the lead itself (no `teammate_name`) is seen.
+    #[serde(default)]
+    pub lead_session_id: Option<String>,
+    /// Most urgent state of the lead and its teammates.
+    pub state: SessionState,
+    pub teammates: Vec<TeammateState>,
+    /// Most recent first.
+    #[serde(default)]
+    pub completed_tasks: Vec<TeamTask>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TeammateState {
+    pub name: String,
+    /// The teammate's own session, when its hooks report under a session id
+    /// other than the lead's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub state: SessionState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_event: Option<String>,
+    pub tasks_completed: u32,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TeamTask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_subject: Option<String>,
+    /// `None` for tasks the lead completed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teammate_name: Option<String>,
+    pub completed_at: String,
+}
+
+/// Sessions of one worktree within a project state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectWorktreeState {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    pub state: SessionState,
+    /// Representative session of the worktree.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub session_ids: Vec<String>,
+    pub active_count: usize,
+}
+
+/// `get_worktrees`: registered worktrees, ordered by repository then path.
+pub type WorktreesResponse = Vec<WorktreeRecord>;
+
+/// A git worktree registered by `worktree_create` and dropped by
+/// `worktree_remove` (or once its directory is gone).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorktreeRecord {
+    pub path: String,
+    /// Repository identity shared with the main checkout's sessions.
+    pub project_id: String,
+    pub project_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by_session_id: Option<String>,
+    pub created_at: String,
+}
+
+/// `get_config_changes`: newest first.
+pub type ConfigChangesResponse = Vec<ConfigChangeRecord>;
+
+/// `get_metrics`: daemon counters rendered as an exposition text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MetricsResponse {
+    /// `application/openmetrics-text; version=1.0.0; charset=utf-8`.
+    pub content_type: String,
+    pub text: String,
+}
+
+/// A `config_change` hook event with a snapshot of the settings file it
+/// named, taken when the daemon applied the event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigChangeRecord {
+    pub event_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// Settings source from the hook, e.g. `user_settings` or `project_settings`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    /// MD5 of the file content; `None` when the file could not be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// MD5 of the file's hud-hook hook entries; `None` when it has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hud_hook_hash: Option<String>,
+    /// Whether `content_hash` differs from the previous change to this file.
+    /// `false` for the first change recorded for a file.
+    pub content_changed: bool,
+    /// Whether `hud_hook_hash` differs from the previous change to this file.
+    pub hud_hook_changed: bool,
+    pub recorded_at: String,
+}
+
+/// Revision-stamped snapshot; `full` means `changed` holds every entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "{T}Delta")]
+pub struct SnapshotDelta<T> {
+    pub revision: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since_revision: Option<u64>,
+    pub full: bool,
+    pub changed: Vec<T>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+/// `get_activity`.
+pub type ActivityResponse = Vec<ActivityEntry>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ActivityEntry {
+    pub session_id: String,
+    pub project_path: String,
+    pub file_path: String,
+    #[serde(default)]
+    pub tool_name: Option<String>,
+    pub recorded_at: String,
+}
+
+/// `get_tombstones`.
+pub type TombstonesResponse = Vec<Tombstone>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Tombstone {
+    pub session_id: String,
+    pub created_at: String,
+    pub expires_at: String,
+}
+
+/// `get_events`.
+pub type EventsResponse = EventsPage;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EventsPage {
+    /// Stored envelopes in arrival order.
+    pub events: Vec<EventEnvelope>,
+    /// Pass back as `cursor` to continue; `None` once the log is exhausted.
+    #[serde(default)]
+    pub next_cursor: Option<u64>,
+}
+
+/// `event`.
+pub type EventResponse = EventAck;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EventAck {
+    pub accepted: bool,
+}
+
+/// `event_batch`.
+pub type EventBatchResponse = EventBatchAck;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EventBatchAck {
+    pub accepted: usize,
+    pub duplicates: usize,
+    pub rejected: usize,
+    /// One entry per submitted event, in request order.
+    pub results: Vec<EventBatchResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EventBatchResult {
+    pub index: usize,
+    #[serde(default)]
+    pub event_id: Option<String>,
+    pub status: EventStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    /// Stored. `error` is set (`apply_failed`) when the event was stored but
+    /// deriving state from it failed.
+    Accepted,
+    /// Already stored (same `event_id`); nothing was applied.
+    Duplicate,
+    /// Failed validation or could not be stored; see `error`.
+    Rejected,
+}
+
+/// `subscribe`: the first line of the stream.
+pub type SubscribeResponse = SubscribeAck;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SubscribeAck {
+    pub subscribed: bool,
+    #[serde(default)]
+    pub topics: Vec<SubscriptionTopic>,
+}
+
+/// `hello`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HelloResponse {
+    /// Negotiated version: the highest one both sides speak.
+    pub protocol_version: u32,
+    pub min_protocol_version: u32,
+    pub max_protocol_version: u32,
+    /// Optional capabilities this daemon supports.
+    #[serde(default)]
+    pub features: Vec<String>,
+    pub daemon_version: String,
+}
+
+impl HelloResponse {
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|candidate| candidate == feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enriched_session_tolerates_newer_fields() {
+        let value = serde_json::json!({
+            "session_id": "session-1",
+            "pid": 123,
+            "state": "working",
+            "cwd": "/repo",
+            "project_id": "/repo/.git",
+            "workspace_id": "ws-1",
+            "project_path": "/repo",
+            "updated_at": "2026-01-31T00:00:00Z",
+            "state_changed_at": "2026-01-31T00:00:00Z",
+            "tools_in_flight": 1,
+            "is_alive": true,
+            "field_from_newer_daemon": 1
+        });
+
+        let record: EnrichedSession = serde_json::from_value(value).expect("parse session");
+        assert_eq!(record.state, SessionState::Working);
+        assert_eq!(record.tools_in_flight, 1);
+        assert_eq!(record.is_alive, Some(true));
+    }
+
+    #[test]
+    fn unknown_session_state_is_tolerated() {
+        let state: SessionState =
+            serde_json::from_value(serde_json::json!("daydreaming")).expect("parse state");
+        assert_eq!(state, SessionState::Unknown);
+    }
+
+    #[test]
+    fn process_liveness_response_distinguishes_not_found() {
+        let missing: ProcessLivenessResponse =
+            serde_json::from_value(serde_json::json!({ "found": false, "pid": 7 }))
+                .expect("not found");
+        assert_eq!(missing, ProcessLivenessResponse::not_found(7));
+
+        let found: ProcessLivenessResponse = serde_json::from_value(serde_json::json!({
+            "pid": 7,
+            "last_seen_at": "2026-01-31T00:00:00Z",
+            "is_alive": true
+        }))
+        .expect("found");
+        assert_eq!(found.into_option().map(|liveness| liveness.pid), Some(7));
+    }
+
+    #[test]
+    fn optional_fields_keep_their_wire_presence() {
+        let entry = serde_json::to_value(ActivityEntry {
+            session_id: "s".to_string(),
+            project_path: "/repo".to_string(),
+            file_path: "/repo/a.rs".to_string(),
+            tool_name: None,
+            recorded_at: "2026-01-31T00:00:00Z".to_string(),
+        })
+        .unwrap();
+        assert!(entry.get("tool_name").unwrap().is_null());
+
+        let shell = serde_json::to_value(ShellEntry {
+            cwd: "/repo".to_string(),
+            tty: "/dev/ttys001".to_string(),
+            parent_app: None,
+            tmux_session: None,
+            tmux_client_tty: None,
+            updated_at: "2026-01-31T00:00:00Z".to_string(),
+        })
+        .unwrap();
+        assert!(shell.get("parent_app").is_none());
+    }
+}
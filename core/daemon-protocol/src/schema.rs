@@ -0,0 +1,1192 @@
+//! JSON Schema (draft 2020-12) for every request and response type.
+//!
+//! Derived from the protocol types with `schemars`, which reads the same
+//! serde attributes the wire format comes from, so adding or renaming a field
+//! updates the schema with it. The exported copy lives at
+//! `docs/daemon-ipc.schema.json`; regenerate it with
+//!
+//! ```text
+//! cargo run -p capacitor-daemon-protocol --example export_schema > docs/daemon-ipc.schema.json
+//! ```
+//!
+//! Request params that reject unknown fields are closed
+//! (`additionalProperties: false`); response objects are left open because
+//! clients must tolerate fields added by newer daemons.
+
+use crate::*;
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+// `parse_activity_request` and `parse_event_batch` read their params by hand,
+// so these two shapes exist only to be described here.
+
+/// Params for `get_activity`. Unknown keys are ignored.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(rename = "ActivityRequest")]
+struct ActivityParams {
+    session_id: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Params for `event_batch`. Each entry is validated on its own.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(rename = "EventBatchRequest")]
+struct EventBatchParams {
+    events: Vec<EventEnvelope>,
+}
+
+/// The full schema document: `$defs` for every type plus a `methods` map
+/// naming each method's params and result.
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::draft2020_12().into_generator();
+    generator.subschema_for::<Request>();
+    generator.subschema_for::<Response>();
+    generator.subschema_for::<Method>();
+    let method_names = generator.definitions()["Method"]["enum"]
+        .as_array()
+        .cloned()
+        .expect("Method is a string enum");
+
+    let methods = method_names
+        .iter()
+        .map(|name| {
+            let method: Method =
+                serde_json::from_value(name.clone()).expect("Method schema lists wire names");
+            let name = name.as_str().expect("method names are strings");
+            (name.to_string(), method_schema(&mut generator, &method))
+        })
+        .collect::<Map<_, _>>();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Capacitor daemon IPC",
+        "description": "Newline-delimited JSON over a Unix socket. See docs/daemon-ipc.md.",
+        "protocol_version": PROTOCOL_VERSION,
+        "min_protocol_version": MIN_PROTOCOL_VERSION,
+        "methods": methods,
+        "$defs": generator.take_definitions(true),
+    })
+}
+
+fn method_schema(generator: &mut SchemaGenerator, method: &Method) -> Value {
+    fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+        generator.subschema_for::<T>().to_value()
+    }
+    fn snapshot<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+        json!({
+            "anyOf": [schema::<Vec<T>>(generator), schema::<SnapshotDelta<T>>(generator)]
+        })
+    }
+
+    let g = generator;
+    let (params, result) = match method {
+        Method::GetHealth => (None, schema::<HealthResponse>(g)),
+        Method::GetShellState => (None, schema::<ShellState>(g)),
+        Method::GetProcessLiveness => (
+            Some(schema::<ProcessLivenessRequest>(g)),
+            schema::<ProcessLivenessResponse>(g),
+        ),
+        Method::GetRoutingSnapshot => (
+            Some(schema::<RoutingSnapshotRequest>(g)),
+            schema::<RoutingSnapshot>(g),
+        ),
+        Method::GetRoutingDiagnostics => (
+            Some(schema::<RoutingDiagnosticsRequest>(g)),
+            schema::<RoutingDiagnostics>(g),
+        ),
+        Method::GetConfig => (None, schema::<RoutingConfigView>(g)),
+        Method::GetSessions => (
+            Some(schema::<SnapshotRequest>(g)),
+            snapshot::<EnrichedSession>(g),
+        ),
+        Method::GetProjectStates => (
+            Some(schema::<SnapshotRequest>(g)),
+            snapshot::<ProjectStateRecord>(g),
+        ),
+        Method::GetActivity => (
+            Some(schema::<ActivityParams>(g)),
+            schema::<Vec<ActivityEntry>>(g),
+        ),
+        Method::GetTombstones => (None, schema::<Vec<Tombstone>>(g)),
+        Method::GetEvents => (Some(schema::<EventsQuery>(g)), schema::<EventsPage>(g)),
+        Method::GetSubagents => (
+            Some(schema::<SubagentsRequest>(g)),
+            schema::<Vec<SubagentRecord>>(g),
+        ),
+        Method::GetSessionTimeline => (
+            Some(schema::<SessionTimelineRequest>(g)),
+            schema::<SessionTimeline>(g),
+        ),
+        Method::GetProjectTime => (
+            Some(schema::<ProjectTimeQuery>(g)),
+            schema::<Vec<ProjectDayTime>>(g),
+        ),
+        Method::GetToolCalls => (
+            Some(schema::<ToolCallsQuery>(g)),
+            schema::<ToolCallsResponse>(g),
+        ),
+        Method::GetWorktrees => (
+            Some(schema::<WorktreesQuery>(g)),
+            schema::<Vec<WorktreeRecord>>(g),
+        ),
+        Method::GetConfigChanges => (
+            Some(schema::<ConfigChangesQuery>(g)),
+            schema::<Vec<ConfigChangeRecord>>(g),
+        ),
+        Method::GetMetrics => (None, schema::<MetricsResponse>(g)),
+        Method::Event => (Some(schema::<EventEnvelope>(g)), schema::<EventAck>(g)),
+        Method::EventBatch => (
+            Some(schema::<EventBatchParams>(g)),
+            schema::<EventBatchAck>(g),
+        ),
+        Method::Subscribe => (
+            Some(schema::<SubscribeRequest>(g)),
+            schema::<SubscribeAck>(g),
+        ),
+        Method::Hello => (Some(schema::<HelloRequest>(g)), schema::<HelloResponse>(g)),
+    };
+
+    let mut described = json!({
+        "params": params.unwrap_or(Value::Null),
+        "result": result,
+    });
+    if matches!(method, Method::Subscribe) {
+        described["stream"] = schema::<SubscriptionMessage>(g);
+    }
+    described
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::collections::{BTreeSet, HashMap};
+
+    /// Validates samples against the generated document and records which
+    /// `$defs` they reached.
+    struct Checker {
+        defs: Value,
+        seen: BTreeSet<String>,
+    }
+
+    impl Checker {
+        fn new() -> Self {
+            Self {
+                defs: document()["$defs"].clone(),
+                seen: BTreeSet::new(),
+            }
+        }
+
+        /// Serializes `value`, validates it against `def` and parses it back.
+        fn value<T: Serialize + DeserializeOwned>(&mut self, def: &str, value: &T) {
+            let value = serde_json::to_value(value).expect("serialize sample");
+            self.check(def, &value);
+            let parsed: T = serde_json::from_value(value.clone())
+                .unwrap_or_else(|err| panic!("{def} sample does not parse back: {err}"));
+            assert_eq!(serde_json::to_value(parsed).unwrap(), value, "{def}");
+        }
+
+        /// Validates raw params against `def` and parses them with `parse`.
+        fn params<T>(&mut self, def: &str, params: Value, parse: impl FnOnce(Value) -> T) {
+            self.check(def, &params);
+            parse(params);
+        }
+
+        fn check(&mut self, def: &str, value: &Value) {
+            let schema = reference(def);
+            if let Err(err) = validate(&self.defs, &schema, value, def, &mut self.seen) {
+                panic!("{def} sample does not match schema: {err}");
+            }
+        }
+
+        fn rejects(&mut self, def: &str, value: &Value) -> bool {
+            validate(
+                &self.defs,
+                &reference(def),
+                value,
+                def,
+                &mut BTreeSet::new(),
+            )
+            .is_err()
+        }
+    }
+
+    fn reference(name: &str) -> Value {
+        json!({ "$ref": format!("#/$defs/{}", name) })
+    }
+
+    fn parse<T: DeserializeOwned>(params: Value) -> T {
+        serde_json::from_value(params).expect("params parse")
+    }
+
+    /// Inlines `$ref` and `allOf` so that sibling keywords (schemars puts the
+    /// tag of internally tagged variants next to the `$ref`) are checked
+    /// together with the referenced properties.
+    fn resolve(defs: &Value, schema: &Value, names: &mut Vec<String>) -> Result<Value, String> {
+        let Some(object) = schema.as_object() else {
+            return Ok(schema.clone());
+        };
+        let mut parts = Vec::new();
+        if let Some(target) = object.get("$ref").and_then(Value::as_str) {
+            let name = target.trim_start_matches("#/$defs/");
+            let target = defs
+                .get(name)
+                .ok_or_else(|| format!("unknown $ref {target}"))?;
+            names.push(name.to_string());
+            parts.push(resolve(defs, target, names)?);
+        }
+        for part in object
+            .get("allOf")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            parts.push(resolve(defs, part, names)?);
+        }
+        let mut own = object.clone();
+        own.remove("$ref");
+        own.remove("allOf");
+        parts.push(Value::Object(own));
+
+        let mut merged = Map::new();
+        for part in parts {
+            for (key, value) in part.as_object().into_iter().flatten() {
+                match (key.as_str(), merged.get_mut(key)) {
+                    ("properties", Some(Value::Object(existing))) => {
+                        existing.extend(value.as_object().unwrap().clone());
+                    }
+                    ("required", Some(Value::Array(existing))) => {
+                        existing.extend(value.as_array().unwrap().clone());
+                    }
+                    _ => {
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        Ok(Value::Object(merged))
+    }
+
+    /// Minimal validator for the keywords `schemars` emits. Unlike a real
+    /// validator it also rejects properties the schema doesn't list, so a
+    /// sample cannot carry fields the schema is silent about.
+    fn validate(
+        defs: &Value,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+        seen: &mut BTreeSet<String>,
+    ) -> Result<(), String> {
+        let mut names = Vec::new();
+        let schema = resolve(defs, schema, &mut names).map_err(|err| format!("{path}: {err}"))?;
+        validate_resolved(defs, &schema, value, path, seen)?;
+        seen.extend(names);
+        Ok(())
+    }
+
+    fn validate_resolved(
+        defs: &Value,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+        seen: &mut BTreeSet<String>,
+    ) -> Result<(), String> {
+        if let Some(options) = schema.get("anyOf").or_else(|| schema.get("oneOf")) {
+            let options = options.as_array().unwrap();
+            let mut errors = Vec::new();
+            let mut matched = 0;
+            for option in options {
+                let mut option_seen = BTreeSet::new();
+                match validate(defs, option, value, path, &mut option_seen) {
+                    Ok(()) => {
+                        matched += 1;
+                        seen.extend(option_seen);
+                    }
+                    Err(err) => errors.push(err),
+                }
+            }
+            if matched == 0 {
+                return Err(format!("{path}: no alternative matched: {errors:?}"));
+            }
+            if schema.get("oneOf").is_some() && matched > 1 {
+                return Err(format!("{path}: {matched} oneOf alternatives matched"));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if value != expected {
+                return Err(format!("{path}: expected {expected}, got {value}"));
+            }
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                return Err(format!("{path}: {value} not in enum"));
+            }
+        }
+        let types = match schema.get("type") {
+            None => Vec::new(),
+            Some(Value::String(single)) => vec![single.as_str()],
+            Some(Value::Array(many)) => many.iter().filter_map(Value::as_str).collect(),
+            Some(other) => return Err(format!("{path}: unsupported type {other}")),
+        };
+        let type_ok = types.is_empty()
+            || types.iter().any(|kind| match *kind {
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "null" => value.is_null(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "number" => value.is_number(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => false,
+            });
+        if !type_ok {
+            return Err(format!("{path}: {value} does not match {schema}"));
+        }
+        if let (Some(minimum), Some(number)) = (
+            schema.get("minimum").and_then(Value::as_i64),
+            value.as_i64(),
+        ) {
+            if number < minimum {
+                return Err(format!("{path}: {value} is below {minimum}"));
+            }
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            for (index, item) in values.iter().enumerate() {
+                validate(defs, items, item, &format!("{path}[{index}]"), seen)?;
+            }
+        }
+        let describes_fields =
+            schema.get("properties").is_some() || schema.get("additionalProperties").is_some();
+        if let (Some(object), true) = (value.as_object(), describes_fields) {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for key in required {
+                    let key = key.as_str().unwrap();
+                    if !object.contains_key(key) {
+                        return Err(format!("{path}: missing required {key}"));
+                    }
+                }
+            }
+            for (key, field) in object {
+                let field_path = format!("{path}.{key}");
+                match (
+                    properties.and_then(|props| props.get(key)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(field_schema), _) => {
+                        validate(defs, field_schema, field, &field_path, seen)?
+                    }
+                    (None, Some(extra)) if extra.is_object() => {
+                        validate(defs, extra, field, &field_path, seen)?
+                    }
+                    (None, _) => return Err(format!("{field_path}: not in schema")),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn session() -> EnrichedSession {
+        EnrichedSession {
+            session_id: "session-1".to_string(),
+            pid: 4242,
+            state: SessionState::Working,
+            cwd: "/repo".to_string(),
+            project_id: "/repo/.git".to_string(),
+            workspace_id: "ws-1".to_string(),
+            project_path: "/repo".to_string(),
+            updated_at: "2026-01-31T00:00:00Z".to_string(),
+            state_changed_at: "2026-01-31T00:00:00Z".to_string(),
+            last_event: Some("pre_tool_use".to_string()),
+            last_activity_at: Some("2026-01-31T00:00:00Z".to_string()),
+            tools_in_flight: 1,
+            ready_reason: None,
+            is_alive: Some(true),
+            active_subagents: vec![SubagentRecord {
+                session_id: "session-1".to_string(),
+                agent_id: "agent-1".to_string(),
+                agent_type: Some("general-purpose".to_string()),
+                started_at: Some("2026-01-31T00:00:00Z".to_string()),
+                stopped_at: None,
+                transcript_path: None,
+                tools: vec!["Read".to_string()],
+                tool_calls: 2,
+                updated_at: "2026-01-31T00:00:00Z".to_string(),
+            }],
+            pending_request: None,
+        }
+    }
+
+    fn pending_request() -> PendingRequest {
+        PendingRequest {
+            kind: PendingRequestKind::PermissionRequest,
+            tool_name: Some("Bash".to_string()),
+            file_path: None,
+            detail: Some("cargo publish".to_string()),
+            message: Some("Claude needs your permission to use Bash".to_string()),
+            permission_suggestions: Some(json!([{ "type": "toolAlwaysAllow", "tool": "Bash" }])),
+            requested_at: "2026-01-31T00:00:00Z".to_string(),
+        }
+    }
+
+    fn project_state() -> ProjectStateRecord {
+        ProjectStateRecord {
+            project_id: "/repo/.git".to_string(),
+            workspace_id: "ws-1".to_string(),
+            project_path: "/repo".to_string(),
+            state: SessionState::Ready,
+            state_changed_at: "2026-01-31T00:00:00Z".to_string(),
+            updated_at: "2026-01-31T00:00:00Z".to_string(),
+            session_id: None,
+            latest_session_id: Some("session-1".to_string()),
+            session_count: 1,
+            active_count: 0,
+            has_session: true,
+            pending_request: None,
+            worktrees: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    fn routing_snapshot() -> RoutingSnapshot {
+        RoutingSnapshot {
+            version: 1,
+            workspace_id: "ws-1".to_string(),
+            project_path: "/repo".to_string(),
+            status: RoutingStatus::Attached,
+            target: RoutingTarget {
+                kind: RoutingTargetKind::TmuxSession,
+                value: Some("caps".to_string()),
+            },
+            confidence: RoutingConfidence::High,
+            reason_code: "TMUX_CLIENT_ATTACHED".to_string(),
+            reason: "attached".to_string(),
+            evidence: vec![RoutingEvidence {
+                evidence_type: "tmux_client".to_string(),
+                value: "/dev/ttys001".to_string(),
+                age_ms: 10,
+                trust_rank: 1,
+            }],
+            updated_at: "2026-01-31T00:00:00Z".to_string(),
+        }
+    }
+
+    fn refs(schema: &Value, found: &mut Vec<String>) {
+        match schema {
+            Value::Object(object) => {
+                if let Some(target) = object.get("$ref").and_then(Value::as_str) {
+                    found.push(target.trim_start_matches("#/$defs/").to_string());
+                }
+                object.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_method_is_described() {
+        let document = document();
+        let methods = document["methods"].as_object().unwrap();
+        assert_eq!(
+            methods.len(),
+            document["$defs"]["Method"]["enum"]
+                .as_array()
+                .unwrap()
+                .len()
+        );
+        assert!(methods["get_health"]["params"].is_null());
+        assert!(methods["subscribe"]["stream"].is_object());
+        let mut found = Vec::new();
+        refs(&document, &mut found);
+        for def in found {
+            assert!(document["$defs"].get(&def).is_some(), "dangling $ref {def}");
+        }
+    }
+
+    fn response_samples(c: &mut Checker) {
+        c.value(
+            "HealthResponse",
+            &HealthResponse {
+                status: "ok".to_string(),
+                pid: 1,
+                version: "0.2.0".to_string(),
+                protocol_version: PROTOCOL_VERSION,
+                dead_session_reconcile_interval_secs: 15,
+                state_revision: Some(3),
+                security: SecurityHealth {
+                    peer_auth_mode: "same_user".to_string(),
+                    rejected_connections: 0,
+                },
+                runtime: RuntimeHealth {
+                    active_connections: 1,
+                    max_active_connections: 64,
+                    subscribers: 0,
+                    max_subscribers: 16,
+                    build_hash: "abc".to_string(),
+                },
+                dead_session_reconcile: Some(HashMap::from([(
+                    "startup".to_string(),
+                    DeadSessionReconcileHealth {
+                        runs: 1,
+                        repaired_sessions: 0,
+                        last_run_at: Some("2026-01-31T00:00:00Z".to_string()),
+                        last_repair_at: None,
+                    },
+                )])),
+                hem_shadow: Some(HemShadowHealth {
+                    enabled: true,
+                    mode: "shadow".to_string(),
+                    capability_status: HemCapabilityHealth {
+                        strategy: "runtime_handshake".to_string(),
+                        handshake_seen: true,
+                        confidence_penalty_factor: 0.9,
+                        unknown_count: 0,
+                        misdeclared_count: 1,
+                        warning_count: 1,
+                        last_warning_at: Some("2026-01-31T00:00:00Z".to_string()),
+                        warnings: vec![HemCapabilityWarning {
+                            code: "misdeclared_capability".to_string(),
+                            capability: "tool_use_id_consistency".to_string(),
+                            declared: "true".to_string(),
+                            observed: "false".to_string(),
+                        }],
+                    },
+                    events_evaluated: 10,
+                    projects_evaluated: 4,
+                    mismatches_total: 1,
+                    mismatches_by_category: HashMap::from([("missing_in_hem".to_string(), 1)]),
+                    mismatches_by_severity: HashMap::from([("important".to_string(), 1)]),
+                    gate_blocking_mismatches: 1,
+                    gate_critical_mismatches: 0,
+                    gate_important_mismatches: 1,
+                    stable_state_samples: 4,
+                    stable_state_matches: 3,
+                    stable_state_agreement_rate: 0.75,
+                    stable_state_agreement_gate_target: 0.995,
+                    stable_state_agreement_gate_met: false,
+                    shadow_gate_ready: false,
+                    blocking_mismatch_rate: 0.25,
+                    last_evaluated_at: Some("2026-01-31T00:00:00Z".to_string()),
+                    last_mismatch_at: None,
+                    last_blocking_mismatch_at: None,
+                }),
+                routing: Some(RoutingHealth {
+                    enabled: false,
+                    dual_run_enabled: true,
+                    snapshots_emitted: 2,
+                    dual_run_comparisons: 2,
+                    legacy_vs_are_status_mismatch: 0,
+                    legacy_vs_are_target_mismatch: 1,
+                    confidence_high: 2,
+                    confidence_medium: 0,
+                    confidence_low: 0,
+                    rollout: RoutingRolloutHealth {
+                        agreement_gate_target: 0.995,
+                        min_comparisons_required: 1_000,
+                        min_window_hours_required: 168,
+                        comparisons: 2,
+                        volume_gate_met: false,
+                        window_gate_met: false,
+                        status_agreement_rate: Some(1.0),
+                        target_agreement_rate: Some(0.5),
+                        first_comparison_at: Some("2026-01-31T00:00:00Z".to_string()),
+                        last_comparison_at: Some("2026-01-31T00:00:00Z".to_string()),
+                        window_elapsed_hours: Some(0),
+                        status_gate_met: false,
+                        target_gate_met: false,
+                        status_row_default_ready: false,
+                        launcher_default_ready: false,
+                    },
+                    last_snapshot_at: Some("2026-01-31T00:00:00Z".to_string()),
+                }),
+                backoff: Some(BackoffHealth {
+                    starts_in_window: 1,
+                    window_secs: 120,
+                    max_starts: 3,
+                    backoff_step_secs: 10,
+                    max_backoff_secs: 60,
+                    last_start: Some("2026-01-31T00:00:00Z".to_string()),
+                    next_backoff_secs: None,
+                }),
+                retention: Some(RetentionHealth {
+                    enabled: true,
+                    event_retention_days: 30,
+                    activity_retention_days: 7,
+                    history_retention_days: 90,
+                    history_max_rows: 100_000,
+                    interval_secs: 3600,
+                    runs: 1,
+                    last_run_at: Some("2026-01-31T00:00:00Z".to_string()),
+                    last_error: None,
+                    last_events_pruned: 2,
+                    events_pruned_total: 2,
+                    last_activity_pruned: 0,
+                    activity_pruned_total: 0,
+                    last_history_pruned: 0,
+                    history_pruned_total: 0,
+                    snapshot_event_rowid: Some(40),
+                    snapshot_at: Some("2026-01-31T00:00:00Z".to_string()),
+                    event_count: 12,
+                    oldest_event_at: Some("2026-01-01T00:00:00Z".to_string()),
+                    auto_vacuum: Some("incremental".to_string()),
+                    freelist_pages: Some(0),
+                    last_vacuum_pages: 0,
+                }),
+                integrity: Some(IntegrityHealth {
+                    status: "repaired".to_string(),
+                    checked_at: "2026-01-31T00:00:00Z".to_string(),
+                    problems: vec!["*** in database main ***".to_string()],
+                    quarantined_path: Some("/state.db.corrupt-20260131T000000Z".to_string()),
+                    events_salvaged: 12,
+                    events_unreadable: 1,
+                    snapshot_salvaged: true,
+                }),
+                writer: Some(WriterHealth {
+                    queue_capacity: 256,
+                    queued: 0,
+                    max_batch_events: 512,
+                    batches: 3,
+                    events_written: 12,
+                    largest_batch: 6,
+                    busy_rejections: 0,
+                    failed_batches: 0,
+                    rejected_events: 1,
+                    skipped_events: 0,
+                    last_batch_micros: 840,
+                }),
+                project_view: Some(ProjectViewHealth {
+                    projects: 2,
+                    refresh_interval_secs: 2,
+                    full_refreshes: 5,
+                    partial_refreshes: 3,
+                    reads: 9,
+                    last_refresh_at: Some("2026-01-31T00:00:00Z".to_string()),
+                    last_refresh_micros: 910,
+                    revision: Some(3),
+                }),
+            },
+        );
+        let mut shells = ShellState::default();
+        shells.shells.insert(
+            "4242".to_string(),
+            ShellEntry {
+                cwd: "/repo".to_string(),
+                tty: "/dev/ttys001".to_string(),
+                parent_app: Some("terminal".to_string()),
+                tmux_session: None,
+                tmux_client_tty: None,
+                updated_at: "2026-01-31T00:00:00Z".to_string(),
+            },
+        );
+        c.value("ShellState", &shells);
+        c.value(
+            "ProcessLivenessResponse",
+            &ProcessLivenessResponse::Found(ProcessLiveness {
+                pid: 4242,
+                proc_started: Some(1),
+                current_start_time: Some(1),
+                last_seen_at: "2026-01-31T00:00:00Z".to_string(),
+                is_alive: true,
+                identity_matches: Some(true),
+            }),
+        );
+        c.value(
+            "ProcessLivenessResponse",
+            &ProcessLivenessResponse::not_found(4242),
+        );
+        c.value("RoutingSnapshot", &routing_snapshot());
+        c.value(
+            "RoutingDiagnostics",
+            &RoutingDiagnostics {
+                snapshot: routing_snapshot(),
+                signal_ages_ms: HashMap::from([("tmux_client".to_string(), 10)]),
+                candidate_targets: vec![RoutingTarget {
+                    kind: RoutingTargetKind::None,
+                    value: None,
+                }],
+                conflicts: Vec::new(),
+                scope_resolution: "path_exact".to_string(),
+            },
+        );
+        c.value(
+            "RoutingConfigView",
+            &RoutingConfigView {
+                tmux_signal_fresh_ms: 1,
+                shell_signal_fresh_ms: 1,
+                shell_retention_hours: 1,
+                tmux_poll_interval_ms: 1,
+                session_policy: Some(SessionPolicyView {
+                    ttl_active_secs: 1200,
+                    ttl_ready_secs: 1800,
+                    ttl_idle_secs: 600,
+                    inactivity_fallback: true,
+                    inactivity_fallback_secs: 60,
+                    inactivity_fallback_events: vec!["task_completed".to_string()],
+                    post_tool_use_stale_secs: 300,
+                    stop_gate_grace_secs: 20,
+                    stale_event_grace_secs: 5,
+                }),
+            },
+        );
+        c.value("EnrichedSession", &session());
+        c.value(
+            "EnrichedSession",
+            &EnrichedSession {
+                state: SessionState::Waiting,
+                pending_request: Some(pending_request()),
+                ..session()
+            },
+        );
+        c.value(
+            "ProjectStateRecord",
+            &ProjectStateRecord {
+                state: SessionState::Waiting,
+                pending_request: Some(PendingRequest {
+                    kind: PendingRequestKind::ElicitationDialog,
+                    tool_name: None,
+                    detail: None,
+                    permission_suggestions: None,
+                    ..pending_request()
+                }),
+                ..project_state()
+            },
+        );
+        c.value(
+            "ProjectStateRecord",
+            &ProjectStateRecord {
+                state: SessionState::Working,
+                worktrees: vec![ProjectWorktreeState {
+                    path: "/repo/.claude/worktrees/feature-a".to_string(),
+                    branch: Some("feature-a".to_string()),
+                    state: SessionState::Working,
+                    session_id: Some("session-2".to_string()),
+                    session_ids: vec!["session-2".to_string()],
+                    active_count: 1,
+                }],
+                teams: vec![TeamState {
+                    team_name: "release".to_string(),
+                    lead_session_id: Some("session-1".to_string()),
+                    state: SessionState::Working,
+                    teammates: vec![TeammateState {
+                        name: "reviewer".to_string(),
+                        session_id: None,
+                        state: SessionState::Idle,
+                        last_event: Some("teammate_idle".to_string()),
+                        tasks_completed: 1,
+                        updated_at: "2026-01-31T00:00:05Z".to_string(),
+                    }],
+                    completed_tasks: vec![TeamTask {
+                        task_id: Some("3".to_string()),
+                        task_subject: Some("Review the changelog".to_string()),
+                        teammate_name: Some("reviewer".to_string()),
+                        completed_at: "2026-01-31T00:00:04Z".to_string(),
+                    }],
+                    updated_at: "2026-01-31T00:00:05Z".to_string(),
+                }],
+                ..project_state()
+            },
+        );
+        c.value(
+            "WorktreeRecord",
+            &WorktreeRecord {
+                path: "/repo/.claude/worktrees/feature-a".to_string(),
+                project_id: "/repo/.git".to_string(),
+                project_path: "/repo".to_string(),
+                name: Some("feature-a".to_string()),
+                branch: None,
+                created_by_session_id: Some("session-1".to_string()),
+                created_at: "2026-01-31T00:00:00Z".to_string(),
+            },
+        );
+        c.value(
+            "EnrichedSessionDelta",
+            &SnapshotDelta {
+                revision: 2,
+                since_revision: Some(1),
+                full: false,
+                changed: vec![session()],
+                removed: vec!["session-0".to_string()],
+            },
+        );
+        c.value(
+            "ProjectStateRecordDelta",
+            &SnapshotDelta {
+                revision: 2,
+                since_revision: None,
+                full: true,
+                changed: vec![project_state()],
+                removed: Vec::new(),
+            },
+        );
+        c.value(
+            "ActivityEntry",
+            &ActivityEntry {
+                session_id: "session-1".to_string(),
+                project_path: "/repo".to_string(),
+                file_path: "/repo/src/main.rs".to_string(),
+                tool_name: None,
+                recorded_at: "2026-01-31T00:00:00Z".to_string(),
+            },
+        );
+        c.value(
+            "Tombstone",
+            &Tombstone {
+                session_id: "session-1".to_string(),
+                created_at: "2026-01-31T00:00:00Z".to_string(),
+                expires_at: "2026-01-31T00:01:00Z".to_string(),
+            },
+        );
+        c.value("EventAck", &EventAck { accepted: true });
+        c.value(
+            "EventsPage",
+            &EventsPage {
+                events: Vec::new(),
+                next_cursor: Some(12),
+            },
+        );
+        c.value(
+            "SessionTimeline",
+            &SessionTimeline {
+                session_id: "session-1".to_string(),
+                transitions: vec![
+                    SessionTransition {
+                        session_id: "session-1".to_string(),
+                        project_path: "/repo".to_string(),
+                        from_state: None,
+                        to_state: Some(SessionState::Working),
+                        event_id: Some("evt-1".to_string()),
+                        transitioned_at: "2026-01-31T00:00:00Z".to_string(),
+                    },
+                    SessionTransition {
+                        session_id: "session-1".to_string(),
+                        project_path: "/repo".to_string(),
+                        from_state: Some(SessionState::Working),
+                        to_state: None,
+                        event_id: None,
+                        transitioned_at: "2026-01-31T00:05:00Z".to_string(),
+                    },
+                ],
+                seconds_in_state: HashMap::from([(SessionState::Working, 300)]),
+            },
+        );
+        c.value(
+            "ProjectDayTime",
+            &ProjectDayTime {
+                project_path: "/repo".to_string(),
+                day: "2026-01-31".to_string(),
+                seconds_in_state: HashMap::from([
+                    (SessionState::Working, 1800),
+                    (SessionState::Waiting, 120),
+                ]),
+                waiting_on_human_secs: 95,
+            },
+        );
+        c.value(
+            "ToolCallsResponse",
+            &ToolCallsResponse {
+                calls: vec![
+                    ToolCall {
+                        session_id: "session-1".to_string(),
+                        tool_use_id: "toolu_2".to_string(),
+                        tool_name: "Bash".to_string(),
+                        file_path: None,
+                        project_path: "/repo".to_string(),
+                        agent_id: Some("agent-1".to_string()),
+                        started_at: "2026-01-31T00:00:05Z".to_string(),
+                        ended_at: None,
+                        duration_ms: None,
+                        outcome: None,
+                    },
+                    ToolCall {
+                        session_id: "session-1".to_string(),
+                        tool_use_id: "toolu_1".to_string(),
+                        tool_name: "Read".to_string(),
+                        file_path: Some("/repo/src/lib.rs".to_string()),
+                        project_path: "/repo".to_string(),
+                        agent_id: None,
+                        started_at: "2026-01-31T00:00:00Z".to_string(),
+                        ended_at: Some("2026-01-31T00:00:01Z".to_string()),
+                        duration_ms: Some(1000),
+                        outcome: Some(ToolCallOutcome::Failure),
+                    },
+                ],
+                tools: vec![ToolStats {
+                    tool_name: "Read".to_string(),
+                    calls: 1,
+                    failures: 1,
+                    interrupted: 0,
+                    failure_rate_percent: 100,
+                    avg_duration_ms: 1000,
+                    max_duration_ms: 1000,
+                    slowest_tool_use_id: Some("toolu_1".to_string()),
+                }],
+            },
+        );
+        c.value(
+            "EventBatchAck",
+            &EventBatchAck {
+                accepted: 1,
+                duplicates: 0,
+                rejected: 1,
+                results: vec![
+                    EventBatchResult {
+                        index: 0,
+                        event_id: Some("evt-1".to_string()),
+                        status: EventStatus::Accepted,
+                        error: None,
+                    },
+                    EventBatchResult {
+                        index: 1,
+                        event_id: None,
+                        status: EventStatus::Rejected,
+                        error: Some(ErrorInfo::new("invalid_event_id", "event_id is required")),
+                    },
+                ],
+            },
+        );
+        c.value(
+            "HelloResponse",
+            &HelloResponse {
+                protocol_version: PROTOCOL_VERSION,
+                min_protocol_version: MIN_PROTOCOL_VERSION,
+                max_protocol_version: PROTOCOL_VERSION,
+                features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+                daemon_version: "0.2.0".to_string(),
+            },
+        );
+        c.value(
+            "SubscribeAck",
+            &SubscribeAck {
+                subscribed: true,
+                topics: vec![SubscriptionTopic::Sessions],
+            },
+        );
+        c.value(
+            "SubscriptionMessage",
+            &SubscriptionMessage::Change(StateChange {
+                topic: SubscriptionTopic::Sessions,
+                change: ChangeKind::Upsert,
+                key: "session-1".to_string(),
+                data: Some(serde_json::to_value(session()).unwrap()),
+                emitted_at: "2026-01-31T00:00:00Z".to_string(),
+            }),
+        );
+        c.value(
+            "SubscriptionMessage",
+            &SubscriptionMessage::Heartbeat {
+                emitted_at: "2026-01-31T00:00:00Z".to_string(),
+            },
+        );
+        c.value(
+            "Response",
+            &Response::error(Some("1".to_string()), "invalid_params", "bad"),
+        );
+    }
+
+    fn request_samples(c: &mut Checker) {
+        c.value(
+            "Request",
+            &Request {
+                protocol_version: PROTOCOL_VERSION,
+                method: Method::GetSessions,
+                id: Some("sessions".to_string()),
+                params: Some(json!({ "since_revision": 4 })),
+            },
+        );
+        c.value(
+            "EventEnvelope",
+            &EventEnvelope {
+                event_id: "evt-1".to_string(),
+                recorded_at: "2026-01-31T00:00:00Z".to_string(),
+                event_type: EventType::ShellCwd,
+                session_id: None,
+                pid: Some(4242),
+                cwd: Some("/repo".to_string()),
+                tool: None,
+                file_path: None,
+                parent_app: Some("terminal".to_string()),
+                tty: Some("/dev/ttys001".to_string()),
+                tmux_session: None,
+                tmux_client_tty: None,
+                notification_type: None,
+                stop_hook_active: None,
+                metadata: Some(json!({ "proc_start": 1 })),
+            },
+        );
+
+        c.value("HelloRequest", &HelloRequest::default());
+        c.value(
+            "SubagentsRequest",
+            &SubagentsRequest {
+                session_id: "session-1".to_string(),
+            },
+        );
+        c.value(
+            "SessionTimelineRequest",
+            &SessionTimelineRequest {
+                session_id: "session-1".to_string(),
+                since: Some("2026-01-31T00:00:00Z".to_string()),
+                until: None,
+            },
+        );
+        c.value(
+            "ProjectTimeQuery",
+            &ProjectTimeQuery {
+                project_path: Some("/repo".to_string()),
+                days: Some(7),
+                utc_offset_minutes: Some(-480),
+            },
+        );
+        c.value(
+            "ToolCallsQuery",
+            &ToolCallsQuery {
+                session_id: None,
+                project_path: Some("/repo".to_string()),
+                since: Some("2026-01-31T00:00:00Z".to_string()),
+                limit: Some(20),
+            },
+        );
+        c.value(
+            "ConfigChangesQuery",
+            &ConfigChangesQuery {
+                project_path: Some("/repo".to_string()),
+                since: Some("2026-01-31T00:00:00Z".to_string()),
+                limit: Some(10),
+            },
+        );
+        c.value(
+            "ConfigChangeRecord",
+            &ConfigChangeRecord {
+                event_id: "evt-1".to_string(),
+                session_id: Some("session-1".to_string()),
+                project_path: Some("/repo".to_string()),
+                source: Some("user_settings".to_string()),
+                file_path: Some("/Users/pete/.claude/settings.json".to_string()),
+                content_hash: Some("9e107d9d372bb6826bd81d3542a419d6".to_string()),
+                hud_hook_hash: None,
+                content_changed: true,
+                hud_hook_changed: true,
+                recorded_at: "2026-01-31T00:00:00Z".to_string(),
+            },
+        );
+        c.value(
+            "MetricsResponse",
+            &MetricsResponse {
+                content_type: "application/openmetrics-text; version=1.0.0; charset=utf-8"
+                    .to_string(),
+                text: "# EOF\n".to_string(),
+            },
+        );
+        c.value(
+            "WorktreesQuery",
+            &WorktreesQuery {
+                project_path: Some("/repo".to_string()),
+            },
+        );
+        c.value(
+            "EventsQuery",
+            &EventsQuery {
+                session_id: Some("session-1".to_string()),
+                event_types: vec![EventType::PreToolUse],
+                project_path: Some("/repo".to_string()),
+                since: Some("2026-01-31T00:00:00Z".to_string()),
+                until: None,
+                cursor: Some(12),
+                limit: Some(50),
+            },
+        );
+        c.params(
+            "SnapshotRequest",
+            json!({ "since_revision": 4 }),
+            parse::<SnapshotRequest>,
+        );
+        c.params("ProcessLivenessRequest", json!({ "pid": 4242 }), |params| {
+            parse_process_liveness(params).unwrap()
+        });
+        c.params(
+            "RoutingSnapshotRequest",
+            json!({ "project_path": "/repo", "workspace_id": "ws-1" }),
+            |params| parse_routing_snapshot(params).unwrap(),
+        );
+        c.params(
+            "RoutingDiagnosticsRequest",
+            json!({ "project_path": "/repo" }),
+            |params| parse_routing_diagnostics(params).unwrap(),
+        );
+        c.params(
+            "ActivityRequest",
+            json!({ "session_id": "session-1", "limit": 20 }),
+            |params| parse_activity_request(Some(params)).unwrap(),
+        );
+        c.params(
+            "EventBatchRequest",
+            json!({ "events": [{
+                "event_id": "evt-1",
+                "recorded_at": "2026-01-31T00:00:00Z",
+                "event_type": "session_end",
+                "session_id": "session-1",
+            }] }),
+            |params| parse_event_batch(Some(params)).unwrap(),
+        );
+        c.params(
+            "SubscribeRequest",
+            json!({ "topics": ["sessions", "project_states"] }),
+            parse::<SubscribeRequest>,
+        );
+    }
+
+    #[test]
+    fn response_samples_match_schema() {
+        response_samples(&mut Checker::new());
+    }
+
+    #[test]
+    fn request_samples_match_schema() {
+        request_samples(&mut Checker::new());
+    }
+
+    #[test]
+    fn every_definition_has_a_sample() {
+        let mut checker = Checker::new();
+        request_samples(&mut checker);
+        response_samples(&mut checker);
+        let missing = checker
+            .defs
+            .as_object()
+            .unwrap()
+            .keys()
+            .filter(|def| !checker.seen.contains(*def))
+            .collect::<Vec<_>>();
+        assert!(missing.is_empty(), "no sample reaches {missing:?}");
+    }
+
+    #[test]
+    fn request_params_are_closed() {
+        let mut checker = Checker::new();
+        let unknown = json!({ "since_revision": 1, "extra": true });
+        assert!(checker.rejects("SnapshotRequest", &unknown));
+        let unknown = json!({ "pid": 1, "extra": true });
+        assert!(checker.rejects("ProcessLivenessRequest", &unknown));
+    }
+
+    #[test]
+    fn exported_schema_is_current() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../docs/daemon-ipc.schema.json"
+        );
+        let exported = std::fs::read_to_string(path).expect("read exported schema");
+        let expected = serde_json::to_string_pretty(&document()).unwrap() + "\n";
+        assert!(
+            exported == expected,
+            "docs/daemon-ipc.schema.json is stale; regenerate it with \
+             `cargo run -p capacitor-daemon-protocol --example export_schema > docs/daemon-ipc.schema.json`"
+        );
+    }
+}
@@ -1,17 +1,9 @@
 use capacitor_daemon_protocol::{EventEnvelope, EventType};
-use serde::Serialize;
 use std::path::{Path, PathBuf};
 
 use crate::project_identity::resolve_project_identity;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub struct ActivityEntry {
-    pub session_id: String,
-    pub project_path: String,
-    pub file_path: String,
-    pub tool_name: Option<String>,
-    pub recorded_at: String,
-}
+pub use capacitor_daemon_protocol::ActivityEntry;
 
 pub fn reduce_activity(event: &EventEnvelope) -> Option<ActivityEntry> {
     if event.event_type != EventType::PostToolUse {
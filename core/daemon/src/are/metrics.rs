@@ -1,5 +1,8 @@
 use crate::are::state::RoutingConfig;
-use capacitor_daemon_protocol::{RoutingConfidence, RoutingSnapshot, RoutingStatus, RoutingTarget};
+use capacitor_daemon_protocol::{
+    RoutingConfidence, RoutingHealth, RoutingRolloutHealth, RoutingSnapshot, RoutingStatus,
+    RoutingTarget,
+};
 use chrono::{DateTime, SecondsFormat, Utc};
 use serde::Serialize;
 
@@ -23,7 +26,7 @@ pub struct PersistedRoutingRolloutState {
     pub last_snapshot_at: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone)]
 pub struct RoutingRolloutGate {
     pub agreement_gate_target: f64,
     pub min_comparisons_required: u64,
@@ -31,15 +34,10 @@ pub struct RoutingRolloutGate {
     pub comparisons: u64,
     pub volume_gate_met: bool,
     pub window_gate_met: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub status_agreement_rate: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub target_agreement_rate: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub first_comparison_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_comparison_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub window_elapsed_hours: Option<u64>,
     pub status_gate_met: bool,
     pub target_gate_met: bool,
@@ -47,6 +45,28 @@ pub struct RoutingRolloutGate {
     pub launcher_default_ready: bool,
 }
 
+impl RoutingRolloutGate {
+    fn health(&self) -> RoutingRolloutHealth {
+        RoutingRolloutHealth {
+            agreement_gate_target: self.agreement_gate_target,
+            min_comparisons_required: self.min_comparisons_required,
+            min_window_hours_required: self.min_window_hours_required,
+            comparisons: self.comparisons,
+            volume_gate_met: self.volume_gate_met,
+            window_gate_met: self.window_gate_met,
+            status_agreement_rate: self.status_agreement_rate,
+            target_agreement_rate: self.target_agreement_rate,
+            first_comparison_at: self.first_comparison_at.clone(),
+            last_comparison_at: self.last_comparison_at.clone(),
+            window_elapsed_hours: self.window_elapsed_hours,
+            status_gate_met: self.status_gate_met,
+            target_gate_met: self.target_gate_met,
+            status_row_default_ready: self.status_row_default_ready,
+            launcher_default_ready: self.launcher_default_ready,
+        }
+    }
+}
+
 impl Default for RoutingRolloutGate {
     fn default() -> Self {
         Self {
@@ -69,7 +89,7 @@ impl Default for RoutingRolloutGate {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default)]
 pub struct RoutingMetrics {
     pub enabled: bool,
     pub dual_run_enabled: bool,
@@ -81,11 +101,8 @@ pub struct RoutingMetrics {
     pub confidence_medium: u64,
     pub confidence_low: u64,
     pub rollout: RoutingRolloutGate,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_snapshot_at: Option<String>,
-    #[serde(skip)]
     first_comparison_at: Option<DateTime<Utc>>,
-    #[serde(skip)]
     last_comparison_at: Option<DateTime<Utc>>,
 }
 
@@ -110,6 +127,22 @@ impl RoutingMetrics {
         metrics
     }
 
+    pub fn health(&self) -> RoutingHealth {
+        RoutingHealth {
+            enabled: self.enabled,
+            dual_run_enabled: self.dual_run_enabled,
+            snapshots_emitted: self.snapshots_emitted,
+            dual_run_comparisons: self.dual_run_comparisons,
+            legacy_vs_are_status_mismatch: self.legacy_vs_are_status_mismatch,
+            legacy_vs_are_target_mismatch: self.legacy_vs_are_target_mismatch,
+            confidence_high: self.confidence_high,
+            confidence_medium: self.confidence_medium,
+            confidence_low: self.confidence_low,
+            rollout: self.rollout.health(),
+            last_snapshot_at: self.last_snapshot_at.clone(),
+        }
+    }
+
     pub fn record_snapshot(&mut self, snapshot: &RoutingSnapshot) {
         self.snapshots_emitted = self.snapshots_emitted.saturating_add(1);
         self.last_snapshot_at = Some(snapshot.updated_at.clone());
@@ -41,6 +41,7 @@ impl RoutingConfig {
             shell_signal_fresh_ms: self.shell_signal_fresh_ms,
             shell_retention_hours: self.shell_retention_hours,
             tmux_poll_interval_ms: self.tmux_poll_interval_ms,
+            session_policy: None,
         }
     }
 }
@@ -1,3 +1,4 @@
+use capacitor_daemon_protocol::BackoffHealth;
 use chrono::{DateTime, Utc};
 use fs_err as fs;
 use serde::{Deserialize, Serialize};
@@ -17,17 +18,6 @@ struct BackoffState {
     starts: Vec<String>,
 }
 
-#[derive(Serialize)]
-pub struct BackoffSnapshot {
-    pub starts_in_window: usize,
-    pub window_secs: i64,
-    pub max_starts: usize,
-    pub backoff_step_secs: u64,
-    pub max_backoff_secs: u64,
-    pub last_start: Option<String>,
-    pub next_backoff_secs: Option<u64>,
-}
-
 pub fn apply_startup_backoff(path: &Path) {
     let now = Utc::now();
     let mut state = load_state(path).unwrap_or_default();
@@ -47,7 +37,7 @@ pub fn apply_startup_backoff(path: &Path) {
     }
 }
 
-pub fn snapshot(path: &Path) -> Option<BackoffSnapshot> {
+pub fn snapshot(path: &Path) -> Option<BackoffHealth> {
     let now = Utc::now();
     let mut state = load_state(path).ok()?;
     retain_recent_starts(now, &mut state);
@@ -63,7 +53,7 @@ pub fn snapshot(path: &Path) -> Option<BackoffSnapshot> {
         None
     };
 
-    Some(BackoffSnapshot {
+    Some(BackoffHealth {
         starts_in_window: state.starts.len(),
         window_secs: WINDOW_SECS,
         max_starts: MAX_STARTS,
@@ -0,0 +1,410 @@
+//! Settings change audit log from `config_change` hook events.
+//!
+//! The hook names the settings source and usually the file. Each change is
+//! stored with a hash of the file as the daemon found it and a hash of the
+//! hud-hook entries in it, so a change in Claude's behaviour can be traced to
+//! the file that changed and whether our hooks were touched. The hashes only
+//! mean something when taken as the event arrives, so replays neither record
+//! nor clear audit rows.
+
+use capacitor_daemon_protocol::{ConfigChangeRecord, ConfigChangesQuery, EventEnvelope, EventType};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::db::Db;
+use crate::project_identity::resolve_project_identity;
+
+const HUD_HOOK_BINARY: &str = "hud-hook";
+/// Settings files larger than this are recorded without hashes.
+const MAX_SETTINGS_BYTES: u64 = 1024 * 1024;
+/// File names Claude reads settings from, inside a `.claude` directory.
+const SETTINGS_FILE_NAMES: [&str; 2] = ["settings.json", "settings.local.json"];
+
+/// The settings file a `config_change` event names, as read when the event
+/// arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsRead {
+    source: Option<String>,
+    file_path: Option<String>,
+    content_hash: Option<String>,
+    hud_hook_hash: Option<String>,
+}
+
+/// Reads and hashes the settings file named by a live `config_change` event;
+/// `None` for other events. Runs before the write transaction so a slow or
+/// odd file never holds it.
+pub fn read_config_change(event: &EventEnvelope) -> Option<SettingsRead> {
+    if event.event_type != EventType::ConfigChange {
+        return None;
+    }
+
+    let source = metadata_str(event, "source").map(str::to_string);
+    let file_path = settings_file_path(event, source.as_deref(), dirs::home_dir().as_deref());
+    let content = file_path.as_deref().and_then(read_settings);
+    Some(SettingsRead {
+        source,
+        file_path: file_path.map(|path| path.to_string_lossy().to_string()),
+        content_hash: content
+            .as_ref()
+            .map(|content| format!("{:x}", md5::compute(content))),
+        hud_hook_hash: content.as_deref().and_then(hud_hook_entries_hash),
+    })
+}
+
+/// Records the change described by a live `config_change` event from the
+/// settings read when it arrived. Returns whether a new audit row was written.
+pub fn record_config_change(
+    db: &Db,
+    event: &EventEnvelope,
+    settings: &SettingsRead,
+) -> Result<bool, String> {
+    if db.has_config_change(&event.event_id)? {
+        return Ok(false);
+    }
+
+    let previous = match settings.file_path.as_deref() {
+        Some(path) => db.latest_config_change(path)?,
+        None => None,
+    };
+    let (content_changed, hud_hook_changed) = match previous {
+        Some(previous) => (
+            previous.content_hash != settings.content_hash,
+            previous.hud_hook_hash != settings.hud_hook_hash,
+        ),
+        None => (false, false),
+    };
+
+    db.insert_config_change(&ConfigChangeRecord {
+        event_id: event.event_id.clone(),
+        session_id: event.session_id.clone(),
+        project_path: project_path(db, event)?,
+        source: settings.source.clone(),
+        file_path: settings.file_path.clone(),
+        content_hash: settings.content_hash.clone(),
+        hud_hook_hash: settings.hud_hook_hash.clone(),
+        content_changed,
+        hud_hook_changed,
+        recorded_at: event.recorded_at.clone(),
+    })
+}
+
+pub fn config_changes(
+    db: &Db,
+    query: &ConfigChangesQuery,
+) -> Result<Vec<ConfigChangeRecord>, String> {
+    db.list_config_changes(query)
+}
+
+/// The file named by the hook, or the one its settings source lives in.
+/// Anything but an absolute `.claude/settings*.json` path is ignored so a
+/// hook event cannot make the daemon read arbitrary files.
+fn settings_file_path(
+    event: &EventEnvelope,
+    source: Option<&str>,
+    home: Option<&Path>,
+) -> Option<PathBuf> {
+    let cwd = event.cwd.as_deref().map(Path::new);
+    let path = match metadata_str(event, "file_path").or(event.file_path.as_deref()) {
+        Some(file_path) => {
+            let path = Path::new(file_path);
+            match cwd {
+                Some(cwd) if path.is_relative() => cwd.join(path),
+                _ => path.to_path_buf(),
+            }
+        }
+        None => match source? {
+            "user_settings" => home?.join(".claude").join("settings.json"),
+            "project_settings" => cwd?.join(".claude").join("settings.json"),
+            "local_settings" => cwd?.join(".claude").join("settings.local.json"),
+            _ => return None,
+        },
+    };
+    is_settings_file_path(&path).then_some(path)
+}
+
+fn is_settings_file_path(path: &Path) -> bool {
+    let mut components = path.components().rev();
+    path.is_absolute()
+        && components
+            .next()
+            .and_then(|name| name.as_os_str().to_str())
+            .is_some_and(|name| SETTINGS_FILE_NAMES.contains(&name))
+        && components
+            .next()
+            .is_some_and(|dir| dir.as_os_str() == ".claude")
+}
+
+/// The file's contents, or `None` when it is not a regular file, is larger
+/// than `MAX_SETTINGS_BYTES` or is not UTF-8. The file is checked before it is
+/// opened, and opened non-blocking, so a FIFO in its place cannot stall us.
+fn read_settings(path: &Path) -> Option<String> {
+    if !std::fs::metadata(path).ok()?.is_file() {
+        return None;
+    }
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .ok()?;
+    if !file.metadata().ok()?.is_file() {
+        return None;
+    }
+    let mut content = String::new();
+    file.take(MAX_SETTINGS_BYTES + 1)
+        .read_to_string(&mut content)
+        .ok()?;
+    (content.len() as u64 <= MAX_SETTINGS_BYTES).then_some(content)
+}
+
+fn project_path(db: &Db, event: &EventEnvelope) -> Result<Option<String>, String> {
+    if let Some(session_id) = event.session_id.as_deref() {
+        if let Some(session) = db.get_session(session_id)? {
+            if !session.project_path.is_empty() {
+                return Ok(Some(session.project_path));
+            }
+        }
+    }
+    Ok(event
+        .cwd
+        .as_deref()
+        .and_then(resolve_project_identity)
+        .map(|identity| identity.project_path))
+}
+
+/// Hash of the `hooks` groups whose commands run hud-hook, keyed by hook
+/// event. `None` when the file is not settings JSON or has no such hooks.
+fn hud_hook_entries_hash(content: &str) -> Option<String> {
+    let settings: Value = serde_json::from_str(content).ok()?;
+    let hooks = settings.get("hooks")?.as_object()?;
+
+    let mut entries: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for (hook_event, groups) in hooks {
+        for group in groups.as_array().into_iter().flatten() {
+            let commands: Vec<&Value> = group
+                .get("hooks")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter(|hook| {
+                    hook.get("command")
+                        .and_then(Value::as_str)
+                        .is_some_and(is_hud_hook_command)
+                })
+                .collect();
+            if commands.is_empty() {
+                continue;
+            }
+            entries
+                .entry(hook_event)
+                .or_default()
+                .push(serde_json::json!({
+                    "matcher": group.get("matcher"),
+                    "hooks": commands,
+                }));
+        }
+    }
+    if entries.is_empty() {
+        return None;
+    }
+    let canonical = serde_json::to_string(&entries).ok()?;
+    Some(format!("{:x}", md5::compute(canonical)))
+}
+
+/// Whether `command` runs the hud-hook binary, after any leading `NAME=value`
+/// environment assignments.
+fn is_hud_hook_command(command: &str) -> bool {
+    command
+        .split_whitespace()
+        .find(|token| !is_env_assignment(token))
+        .map(|executable| executable.rsplit('/').next().unwrap_or(executable))
+        == Some(HUD_HOOK_BINARY)
+}
+
+fn is_env_assignment(token: &str) -> bool {
+    token.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|ch: char| ch.is_ascii_digit())
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    })
+}
+
+fn metadata_str<'a>(event: &'a EventEnvelope, key: &str) -> Option<&'a str> {
+    event
+        .metadata
+        .as_ref()
+        .and_then(|value| value.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn event(event_id: &str, recorded_at: &str, cwd: &Path) -> EventEnvelope {
+        EventEnvelope {
+            cwd: Some(cwd.to_string_lossy().to_string()),
+            metadata: Some(serde_json::json!({ "source": "project_settings" })),
+            ..test_support::event(event_id, EventType::ConfigChange, recorded_at)
+        }
+    }
+
+    fn record(db: &Db, event: &EventEnvelope) -> Result<bool, String> {
+        record_config_change(
+            db,
+            event,
+            &read_config_change(event).expect("config change"),
+        )
+    }
+
+    fn settings(hook_command: &str, model: &str) -> String {
+        serde_json::json!({
+            "model": model,
+            "hooks": {
+                "Stop": [
+                    { "hooks": [{ "type": "command", "command": hook_command }] },
+                    { "hooks": [{ "type": "command", "command": "say done" }] }
+                ]
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn records_content_and_hud_hook_changes_per_file() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let project = temp_dir.path().join("repo");
+        let settings_path = project.join(".claude").join("settings.json");
+        std::fs::create_dir_all(settings_path.parent().unwrap()).expect("settings dir");
+        let hook = "CAPACITOR_HOOK_MARKER=1 $HOME/.local/bin/hud-hook handle";
+
+        std::fs::write(&settings_path, settings(hook, "opus")).expect("write");
+        let first = event("evt-1", "2026-01-31T00:00:00Z", &project);
+        assert!(record(&db, &first).expect("first"));
+        assert!(!record(&db, &first).expect("replayed"));
+
+        std::fs::write(&settings_path, settings(hook, "sonnet")).expect("write");
+        record(&db, &event("evt-2", "2026-01-31T00:01:00Z", &project)).expect("second");
+
+        std::fs::write(
+            &settings_path,
+            settings("hud-hook handle --verbose", "sonnet"),
+        )
+        .expect("write");
+        record(&db, &event("evt-3", "2026-01-31T00:02:00Z", &project)).expect("third");
+
+        let changes = config_changes(&db, &ConfigChangesQuery::default()).expect("list");
+        let flags: Vec<(&str, bool, bool)> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.event_id.as_str(),
+                    change.content_changed,
+                    change.hud_hook_changed,
+                )
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("evt-3", true, true),
+                ("evt-2", true, false),
+                ("evt-1", false, false)
+            ]
+        );
+        assert_eq!(
+            changes[0].file_path.as_deref(),
+            Some(settings_path.to_string_lossy().as_ref())
+        );
+        assert_eq!(changes[0].source.as_deref(), Some("project_settings"));
+        assert!(changes[0].hud_hook_hash.is_some());
+    }
+
+    #[test]
+    fn resolves_settings_files_and_hud_hook_commands() {
+        let cwd = Path::new("/repo");
+        let home = Path::new("/home/pete");
+        let mut change = event("evt-1", "2026-01-31T00:00:00Z", cwd);
+        assert_eq!(
+            settings_file_path(&change, Some("local_settings"), Some(home)),
+            Some(PathBuf::from("/repo/.claude/settings.local.json"))
+        );
+        assert_eq!(
+            settings_file_path(&change, Some("user_settings"), Some(home)),
+            Some(PathBuf::from("/home/pete/.claude/settings.json"))
+        );
+        assert_eq!(
+            settings_file_path(&change, Some("policy_settings"), Some(home)),
+            None
+        );
+        change.metadata = Some(serde_json::json!({ "file_path": ".claude/settings.json" }));
+        assert_eq!(
+            settings_file_path(&change, None, Some(home)),
+            Some(PathBuf::from("/repo/.claude/settings.json"))
+        );
+
+        for file_path in ["/etc/passwd", "/repo/settings.json", "notes/.claude/x.json"] {
+            change.metadata = Some(serde_json::json!({ "file_path": file_path }));
+            assert_eq!(settings_file_path(&change, None, Some(home)), None);
+        }
+        change.cwd = None;
+        change.metadata = Some(serde_json::json!({ "file_path": ".claude/settings.json" }));
+        assert_eq!(settings_file_path(&change, None, Some(home)), None);
+
+        assert!(is_hud_hook_command(
+            "A=1 B_2=x /usr/local/bin/hud-hook handle"
+        ));
+        assert!(!is_hud_hook_command("echo hud-hook"));
+        assert_eq!(hud_hook_entries_hash(&settings("say hi", "opus")), None);
+    }
+
+    #[test]
+    fn oversized_settings_are_recorded_without_hashes() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let project = temp_dir.path().join("repo");
+        let settings_path = project.join(".claude").join("settings.json");
+        std::fs::create_dir_all(settings_path.parent().unwrap()).expect("settings dir");
+        let padding = " ".repeat(MAX_SETTINGS_BYTES as usize);
+        std::fs::write(
+            &settings_path,
+            format!("{}{}", settings("hud-hook", "opus"), padding),
+        )
+        .expect("write");
+
+        assert!(record(&db, &event("evt-1", "2026-01-31T00:00:00Z", &project)).expect("record"));
+        let changes = config_changes(&db, &ConfigChangesQuery::default()).expect("list");
+        assert_eq!(
+            changes[0].file_path.as_deref(),
+            Some(settings_path.to_string_lossy().as_ref())
+        );
+        assert_eq!(changes[0].content_hash, None);
+        assert_eq!(changes[0].hud_hook_hash, None);
+    }
+
+    #[test]
+    fn settings_fifo_is_not_opened() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let project = temp_dir.path().join("repo");
+        let settings_path = project.join(".claude").join("settings.json");
+        std::fs::create_dir_all(settings_path.parent().unwrap()).expect("settings dir");
+        let fifo = std::ffi::CString::new(settings_path.to_string_lossy().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        let settings = read_config_change(&event("evt-1", "2026-01-31T00:00:00Z", &project))
+            .expect("config change");
+        assert_eq!(
+            settings.file_path.as_deref(),
+            Some(settings_path.to_string_lossy().as_ref())
+        );
+        assert_eq!(settings.content_hash, None);
+    }
+}
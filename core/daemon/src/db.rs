@@ -1,30 +1,90 @@
 //! SQLite persistence for capacitor-daemon.
 //!
-//! This is the single-writer store backing the daemon. We keep the schema
-//! intentionally small in Phase 3: an append-only events table and a
-//! materialized shell_state table for fast reads.
-
-use capacitor_daemon_protocol::{EventEnvelope, EventType};
+//! This is the store backing the daemon. Writes are serialized by the
+//! `SharedState` mutation lock; event ingestion additionally funnels through
+//! the writer thread (`writer.rs`). We keep the schema intentionally small in
+//! Phase 3: an append-only events table and a materialized shell_state table
+//! for fast reads.
+
+use capacitor_daemon_protocol::{
+    ConfigChangeRecord, ConfigChangesQuery, EventEnvelope, EventType, EventsQuery,
+    SessionTransition, ShellEntry, ShellState, SubagentRecord, TeamTask, Tombstone, ToolCall,
+    ToolCallOutcome, ToolCallsQuery, ToolStats, WorktreeRecord,
+};
 use chrono::{DateTime, Duration, Utc};
-use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
-use std::collections::HashMap;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{
+    params, params_from_iter, Connection, OpenFlags, OptionalExtension, Savepoint, Transaction,
+    TransactionBehavior,
+};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Deref;
 use std::path::PathBuf;
 
 use crate::activity::ActivityEntry;
 use crate::are::metrics::PersistedRoutingRolloutState;
+use crate::migrations;
 use crate::process::get_process_start_time;
 use crate::reducer::{SessionRecord, SessionState};
-use crate::state::{ProcessLivenessRow, ShellEntry, ShellState};
+use crate::state::ProcessLivenessRow;
+use crate::teams::{TeamRecord, TeammateRecord};
+
+pub const SESSIONS_REVISION_KIND: &str = "sessions";
+pub const PROJECT_STATES_REVISION_KIND: &str = "project_states";
+const STATE_REVISION_KEY: &str = "state_revision";
+const STATE_REVISION_FLOOR_KEY: &str = "state_revision_floor";
+pub const SNAPSHOT_REMOVAL_RETENTION_HOURS: i64 = 24;
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
+/// Derived tables holding current state rather than history. Their rows can
+/// come from events retention prunes, so the retention snapshot keeps them.
+const SNAPSHOT_REGISTRY_TABLES: [&str; 5] =
+    ["subagents", "worktrees", "teams", "teammates", "team_tasks"];
 
 pub struct Db {
     path: PathBuf,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
-pub struct TombstoneRow {
-    pub session_id: String,
-    pub created_at: String,
-    pub expires_at: String,
+thread_local! {
+    /// Connection of the batch open on this thread (see `Db::write_batch`).
+    static BATCH_CONNECTION: RefCell<Option<(PathBuf, Connection)>> = const { RefCell::new(None) };
+}
+
+/// Transaction for a multi-statement write, or a savepoint when the
+/// connection is already inside a write batch.
+enum WriteScope<'conn> {
+    Transaction(Transaction<'conn>),
+    Savepoint(Savepoint<'conn>),
+}
+
+impl WriteScope<'_> {
+    fn commit(self) -> rusqlite::Result<()> {
+        match self {
+            WriteScope::Transaction(tx) => tx.commit(),
+            WriteScope::Savepoint(savepoint) => savepoint.commit(),
+        }
+    }
+}
+
+impl Deref for WriteScope<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            WriteScope::Transaction(tx) => tx,
+            WriteScope::Savepoint(savepoint) => savepoint,
+        }
+    }
+}
+
+fn begin_write(conn: &mut Connection) -> rusqlite::Result<WriteScope<'_>> {
+    if conn.is_autocommit() {
+        conn.transaction_with_behavior(TransactionBehavior::Immediate)
+            .map(WriteScope::Transaction)
+    } else {
+        conn.savepoint().map(WriteScope::Savepoint)
+    }
 }
 
 #[allow(dead_code)]
@@ -42,6 +102,62 @@ pub struct HemShadowMismatch {
     pub detail_json: Option<String>,
 }
 
+/// Time a session spent blocked on the human (see `time_accounting`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanWait {
+    pub session_id: String,
+    pub project_path: String,
+    pub started_at: String,
+    /// `None` while the prompt is still unanswered.
+    pub ended_at: Option<String>,
+}
+
+/// Sessions and shell state as of `event_rowid`, written before events at or
+/// below that rowid are pruned (see `retention`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionSnapshot {
+    pub event_rowid: i64,
+    pub sessions: Vec<SessionRecord>,
+    pub shell_state: ShellState,
+    pub derived: DerivedSnapshot,
+    pub created_at: String,
+}
+
+/// Derived rows a rebuild could not recreate from the retained events.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DerivedSnapshot {
+    /// Rows of each `SNAPSHOT_REGISTRY_TABLES` table by column name.
+    pub registries: BTreeMap<String, Vec<Map<String, Value>>>,
+    /// Transitions and waits up to these ids came from snapshotted events;
+    /// rebuilds keep them and replay the rest.
+    pub last_transition_id: i64,
+    pub last_human_wait_id: i64,
+    /// Waits still open at the snapshot, reopened before replay.
+    pub open_human_wait_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VacuumReport {
+    /// `none`, `full` or `incremental`, after any switch this run made.
+    pub auto_vacuum: String,
+    pub pages_vacuumed: u64,
+    /// Free pages left in the file after vacuuming.
+    pub freelist_pages: u64,
+}
+
+/// Entries of a snapshot kind that changed after some revision.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevisionDelta {
+    /// Current global state revision.
+    pub revision: u64,
+    /// Oldest revision a delta can be computed from; removals at or below it were pruned.
+    pub floor: u64,
+    /// Keys whose entry changed after the requested revision.
+    pub changed_keys: HashSet<String>,
+    /// Keys removed after the requested revision.
+    pub removed_keys: Vec<String>,
+}
+
 impl Db {
     pub fn new(path: PathBuf) -> Result<Self, String> {
         let db = Self { path };
@@ -55,51 +171,77 @@ impl Db {
             .map(|rowid| rowid.is_some())
     }
 
+    #[cfg(test)]
     pub fn insert_event_with_rowid(&self, event: &EventEnvelope) -> Result<Option<i64>, String> {
+        self.with_connection(|conn| insert_event_row(conn, event))
+    }
+
+    /// Inserts `events` in order inside one transaction. Each entry is the new
+    /// rowid, or `None` when the event id was already stored.
+    pub fn insert_events_with_rowids(
+        &self,
+        events: &[EventEnvelope],
+    ) -> Result<Vec<Option<i64>>, String> {
         self.with_connection(|conn| {
-            let payload = serde_json::to_string(event)
-                .map_err(|err| format!("Failed to serialize event payload: {}", err))?;
-            let event_type = serde_json::to_string(&event.event_type)
-                .unwrap_or_else(|_| "unknown".to_string())
-                .trim_matches('"')
-                .to_string();
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start event batch transaction: {}", err))?;
+            let rowids = events
+                .iter()
+                .map(|event| insert_event_row(&tx, event))
+                .collect::<Result<Vec<_>, _>>()?;
+            tx.commit()
+                .map_err(|err| format!("Failed to commit event batch transaction: {}", err))?;
+            Ok(rowids)
+        })
+    }
 
-            let rows_affected = conn
-                .execute(
-                    "INSERT INTO events (id, recorded_at, event_type, session_id, pid, payload)\
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)\
-                 ON CONFLICT(id) DO NOTHING",
+    /// Inserts events under the rowids they had in another database, so a
+    /// retention snapshot taken there still lines up with them.
+    pub fn insert_events_at_rowids(&self, events: &[(i64, EventEnvelope)]) -> Result<(), String> {
+        self.with_connection(|conn| {
+            let tx = conn
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|err| format!("Failed to start event restore transaction: {}", err))?;
+            for (rowid, event) in events {
+                let payload = serde_json::to_string(event)
+                    .map_err(|err| format!("Failed to serialize event payload: {}", err))?;
+                tx.execute(
+                    "INSERT INTO events (rowid, id, recorded_at, event_type, session_id, pid, payload) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) \
+                     ON CONFLICT DO NOTHING",
                     params![
+                        rowid,
                         event.event_id,
                         event.recorded_at,
-                        event_type,
+                        event_type_column(&event.event_type),
                         event.session_id,
                         event.pid,
                         payload
                     ],
                 )
-                .map_err(|err| format!("Failed to insert event: {}", err))?;
-
-            if rows_affected > 0 {
-                Ok(Some(conn.last_insert_rowid()))
-            } else {
-                Ok(None)
+                .map_err(|err| format!("Failed to restore event: {}", err))?;
             }
+            tx.commit()
+                .map_err(|err| format!("Failed to commit event restore transaction: {}", err))?;
+            Ok(())
         })
     }
 
-    #[cfg(test)]
-    pub fn list_events(&self) -> Result<Vec<EventEnvelope>, String> {
+    /// Events after `after_rowid` (all events when `None`) in recorded order.
+    pub fn list_events(&self, after_rowid: Option<i64>) -> Result<Vec<EventEnvelope>, String> {
         self.with_connection(|conn| {
             let mut stmt = conn
                 .prepare(
                     "SELECT payload FROM events \
+                     WHERE rowid > ?1 \
                      ORDER BY julianday(recorded_at) ASC, id ASC",
                 )
                 .map_err(|err| format!("Failed to prepare events query: {}", err))?;
 
             let rows = stmt
-                .query_map([], |row| row.get::<_, String>(0))
+                .query_map(params![after_rowid.unwrap_or(0)], |row| {
+                    row.get::<_, String>(0)
+                })
                 .map_err(|err| format!("Failed to read event rows: {}", err))?;
 
             let mut events = Vec::new();
@@ -199,6 +341,62 @@ impl Db {
         })
     }
 
+    /// One page of the raw event log in arrival order. Event type and project
+    /// filters are applied to decoded payloads, so the scan continues until a
+    /// full page matches; the returned cursor is the rowid of the last event
+    /// on the page when more matches remain.
+    pub fn list_events_page(
+        &self,
+        query: &EventsQuery,
+    ) -> Result<(Vec<EventEnvelope>, Option<u64>), String> {
+        let limit = query.limit();
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT rowid, payload FROM events \
+                     WHERE (?1 IS NULL OR rowid > ?1) \
+                       AND (?2 IS NULL OR session_id = ?2) \
+                       AND (?3 IS NULL OR julianday(recorded_at) >= julianday(?3)) \
+                       AND (?4 IS NULL OR julianday(recorded_at) < julianday(?4)) \
+                     ORDER BY rowid ASC",
+                )
+                .map_err(|err| format!("Failed to prepare event log query: {}", err))?;
+
+            let cursor = query.cursor.map(|cursor| cursor as i64);
+            let mut rows = stmt
+                .query(params![cursor, query.session_id, query.since, query.until])
+                .map_err(|err| format!("Failed to read event log rows: {}", err))?;
+
+            let mut events = Vec::new();
+            let mut last_rowid = None;
+            while let Some(row) = rows
+                .next()
+                .map_err(|err| format!("Failed to read event log rows: {}", err))?
+            {
+                let rowid: i64 = row
+                    .get(0)
+                    .map_err(|err| format!("Failed to decode event log row: {}", err))?;
+                let payload: String = row
+                    .get(1)
+                    .map_err(|err| format!("Failed to decode event log row: {}", err))?;
+                let event: EventEnvelope = serde_json::from_str(&payload)
+                    .map_err(|err| format!("Failed to parse event log payload: {}", err))?;
+                if !query.event_types.is_empty() && !query.event_types.contains(&event.event_type) {
+                    continue;
+                }
+                if !query.matches_project(event.cwd.as_deref()) {
+                    continue;
+                }
+                if events.len() == limit {
+                    return Ok((events, last_rowid.map(|rowid: i64| rowid as u64)));
+                }
+                events.push(event);
+                last_rowid = Some(rowid);
+            }
+            Ok((events, None))
+        })
+    }
+
     pub fn max_event_rowid(&self) -> Result<Option<i64>, String> {
         self.with_connection(|conn| {
             conn.query_row("SELECT MAX(rowid) FROM events", [], |row| {
@@ -264,6 +462,91 @@ impl Db {
         })
     }
 
+    pub fn state_revision(&self) -> Result<u64, String> {
+        self.with_connection(|conn| read_meta_u64(conn, STATE_REVISION_KEY))
+    }
+
+    /// Bumps the global state revision and stamps the `changed` and `removed`
+    /// keys of snapshot `kind` with it. Called on the write path whenever an
+    /// entry a snapshot serves is written, so reads never have to diff.
+    /// Returns the new revision, or the current one when there is nothing to
+    /// record.
+    pub fn record_entry_revisions(
+        &self,
+        kind: &str,
+        changed: &[&str],
+        removed: &[&str],
+    ) -> Result<u64, String> {
+        self.with_connection(|conn| {
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start revision transaction: {}", err))?;
+            let revision = record_revisions(&tx, kind, changed, removed)?;
+            tx.commit()
+                .map_err(|err| format!("Failed to commit revision transaction: {}", err))?;
+            Ok(revision)
+        })
+    }
+
+    /// Keys of snapshot `kind` changed or removed after `since` (and at or
+    /// before `until`, when given), without writing anything.
+    pub fn entry_revisions_since(
+        &self,
+        kind: &str,
+        since: u64,
+        until: Option<u64>,
+    ) -> Result<RevisionDelta, String> {
+        self.with_connection(|conn| {
+            let revision = read_meta_u64(conn, STATE_REVISION_KEY)?;
+            let floor = read_meta_u64(conn, STATE_REVISION_FLOOR_KEY)?;
+            let since_value = u64_to_i64(since, "since_revision")?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT entry_key, removed FROM snapshot_revisions \
+                     WHERE kind = ?1 AND revision > ?2 AND (?3 IS NULL OR revision <= ?3) \
+                     ORDER BY entry_key",
+                )
+                .map_err(|err| format!("Failed to prepare revision delta query: {}", err))?;
+            let until_value = until
+                .map(|until| u64_to_i64(until, "until_revision"))
+                .transpose()?;
+            let rows = stmt
+                .query_map(params![kind, since_value, until_value], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? != 0))
+                })
+                .map_err(|err| format!("Failed to query revision delta: {}", err))?;
+
+            let mut delta = RevisionDelta {
+                revision,
+                floor,
+                ..RevisionDelta::default()
+            };
+            for row in rows {
+                let (key, removed) =
+                    row.map_err(|err| format!("Failed to decode revision delta: {}", err))?;
+                if removed {
+                    delta.removed_keys.push(key);
+                } else {
+                    delta.changed_keys.insert(key);
+                }
+            }
+            Ok(delta)
+        })
+    }
+
+    /// Keys of snapshot `kind` that are currently recorded as present.
+    pub fn live_entry_keys(&self, kind: &str) -> Result<HashSet<String>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT entry_key FROM snapshot_revisions WHERE kind = ?1 AND removed = 0")
+                .map_err(|err| format!("Failed to prepare live entry query: {}", err))?;
+            let rows = stmt
+                .query_map(params![kind], |row| row.get::<_, String>(0))
+                .map_err(|err| format!("Failed to query live entries: {}", err))?;
+            rows.collect::<Result<HashSet<_>, _>>()
+                .map_err(|err| format!("Failed to decode live entry: {}", err))
+        })
+    }
+
     #[cfg(test)]
     pub fn latest_session_affecting_event_time(&self) -> Result<Option<DateTime<Utc>>, String> {
         self.with_connection(|conn| {
@@ -382,13 +665,13 @@ impl Db {
         })
     }
 
-    pub fn get_tombstone(&self, session_id: &str) -> Result<Option<TombstoneRow>, String> {
+    pub fn get_tombstone(&self, session_id: &str) -> Result<Option<Tombstone>, String> {
         self.with_connection(|conn| {
             conn.query_row(
                 "SELECT session_id, created_at, expires_at FROM tombstones WHERE session_id = ?1",
                 params![session_id],
                 |row| {
-                    Ok(TombstoneRow {
+                    Ok(Tombstone {
                         session_id: row.get(0)?,
                         created_at: row.get(1)?,
                         expires_at: row.get(2)?,
@@ -449,7 +732,6 @@ impl Db {
         })
     }
 
-    #[cfg(test)]
     pub fn clear_tombstones(&self) -> Result<(), String> {
         self.with_connection(|conn| {
             conn.execute("DELETE FROM tombstones", [])
@@ -472,11 +754,19 @@ impl Db {
     }
 
     pub fn upsert_session(&self, record: &SessionRecord) -> Result<(), String> {
+        let pending_request = record
+            .pending_request
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|err| format!("Failed to serialize pending request: {}", err))?;
         self.with_connection(|conn| {
-            conn.execute(
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start session transaction: {}", err))?;
+            tx.execute(
                 "INSERT INTO sessions \
-                    (session_id, pid, state, cwd, project_id, project_path, updated_at, state_changed_at, last_event, last_activity_at, tools_in_flight, ready_reason) \
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12) \
+                    (session_id, pid, state, cwd, project_id, project_path, updated_at, state_changed_at, last_event, last_activity_at, tools_in_flight, ready_reason, pending_request) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13) \
                  ON CONFLICT(session_id) DO UPDATE SET \
                     pid = excluded.pid, \
                     state = excluded.state, \
@@ -488,7 +778,8 @@ impl Db {
                     last_event = excluded.last_event, \
                     last_activity_at = excluded.last_activity_at, \
                     tools_in_flight = excluded.tools_in_flight, \
-                    ready_reason = excluded.ready_reason",
+                    ready_reason = excluded.ready_reason, \
+                    pending_request = excluded.pending_request",
                 params![
                     record.session_id,
                     record.pid,
@@ -501,99 +792,59 @@ impl Db {
                     record.last_event,
                     record.last_activity_at,
                     record.tools_in_flight,
-                    record.ready_reason
+                    record.ready_reason,
+                    pending_request
                 ],
             )
             .map_err(|err| format!("Failed to upsert session: {}", err))?;
-            Ok(())
+            record_revisions(&tx, SESSIONS_REVISION_KIND, &[&record.session_id], &[])?;
+            tx.commit()
+                .map_err(|err| format!("Failed to commit session upsert: {}", err))
         })
     }
 
     pub fn get_session(&self, session_id: &str) -> Result<Option<SessionRecord>, String> {
         self.with_connection(|conn| {
             conn.query_row(
-                "SELECT session_id, COALESCE(pid, 0), state, cwd, \
-                        COALESCE(project_id, project_path, cwd), \
-                        COALESCE(project_path, cwd), \
-                        updated_at, state_changed_at, last_event, last_activity_at, \
-                        COALESCE(tools_in_flight, 0), ready_reason \
-                 FROM sessions WHERE session_id = ?1",
+                &format!("SELECT {SESSION_COLUMNS} FROM sessions WHERE session_id = ?1"),
                 params![session_id],
-                |row| {
-                    let state_raw: String = row.get(2)?;
-                    let state = SessionState::from_str(&state_raw).ok_or_else(|| {
-                        rusqlite::Error::FromSqlConversionFailure(
-                            state_raw.len(),
-                            rusqlite::types::Type::Text,
-                            Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Unknown session state: {}", state_raw),
-                            )),
-                        )
-                    })?;
-
-                    Ok(SessionRecord {
-                        session_id: row.get(0)?,
-                        pid: row.get(1)?,
-                        state,
-                        cwd: row.get(3)?,
-                        project_id: row.get(4)?,
-                        project_path: row.get(5)?,
-                        updated_at: row.get(6)?,
-                        state_changed_at: row.get(7)?,
-                        last_event: row.get(8)?,
-                        last_activity_at: row.get(9)?,
-                        tools_in_flight: row.get(10)?,
-                        ready_reason: row.get(11)?,
-                    })
-                },
+                session_from_row,
             )
             .optional()
             .map_err(|err| format!("Failed to query session: {}", err))
         })
     }
 
+    /// The sessions among `session_ids` that exist, in one query.
+    pub fn get_sessions(&self, session_ids: &[String]) -> Result<Vec<SessionRecord>, String> {
+        if session_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {SESSION_COLUMNS} FROM sessions WHERE session_id IN ({})",
+                    sql_placeholders(session_ids.len())
+                ))
+                .map_err(|err| format!("Failed to prepare sessions query: {}", err))?;
+            let rows = stmt
+                .query_map(params_from_iter(session_ids), session_from_row)
+                .map_err(|err| format!("Failed to query sessions: {}", err))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("Failed to decode session row: {}", err))
+        })
+    }
+
     pub fn list_sessions(&self) -> Result<Vec<SessionRecord>, String> {
         self.with_connection(|conn| {
             let mut stmt = conn
-                .prepare(
-                    "SELECT session_id, COALESCE(pid, 0), state, cwd, \
-                            COALESCE(project_id, project_path, cwd), \
-                            COALESCE(project_path, cwd), \
-                            updated_at, state_changed_at, last_event, last_activity_at, \
-                            COALESCE(tools_in_flight, 0), ready_reason \
-                     FROM sessions ORDER BY updated_at DESC",
-                )
+                .prepare(&format!(
+                    "SELECT {SESSION_COLUMNS} FROM sessions ORDER BY updated_at DESC"
+                ))
                 .map_err(|err| format!("Failed to prepare sessions query: {}", err))?;
 
             let rows = stmt
-                .query_map([], |row| {
-                    let state_raw: String = row.get(2)?;
-                    let state = SessionState::from_str(&state_raw).ok_or_else(|| {
-                        rusqlite::Error::FromSqlConversionFailure(
-                            state_raw.len(),
-                            rusqlite::types::Type::Text,
-                            Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Unknown session state: {}", state_raw),
-                            )),
-                        )
-                    })?;
-                    Ok(SessionRecord {
-                        session_id: row.get(0)?,
-                        pid: row.get(1)?,
-                        state,
-                        cwd: row.get(3)?,
-                        project_id: row.get(4)?,
-                        project_path: row.get(5)?,
-                        updated_at: row.get(6)?,
-                        state_changed_at: row.get(7)?,
-                        last_event: row.get(8)?,
-                        last_activity_at: row.get(9)?,
-                        tools_in_flight: row.get(10)?,
-                        ready_reason: row.get(11)?,
-                    })
-                })
+                .query_map([], session_from_row)
                 .map_err(|err| format!("Failed to query sessions: {}", err))?;
 
             let mut sessions = Vec::new();
@@ -604,23 +855,68 @@ impl Db {
         })
     }
 
+    /// Sessions in `project_paths`, ordered like [`Self::list_sessions`].
+    pub fn list_sessions_for_projects(
+        &self,
+        project_paths: &[String],
+    ) -> Result<Vec<SessionRecord>, String> {
+        if project_paths.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {SESSION_COLUMNS} FROM sessions WHERE project_path IN ({}) \
+                     ORDER BY updated_at DESC",
+                    sql_placeholders(project_paths.len())
+                ))
+                .map_err(|err| format!("Failed to prepare sessions query: {}", err))?;
+            let rows = stmt
+                .query_map(params_from_iter(project_paths), session_from_row)
+                .map_err(|err| format!("Failed to query sessions: {}", err))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("Failed to decode session row: {}", err))
+        })
+    }
+
     pub fn delete_session(&self, session_id: &str) -> Result<(), String> {
         self.with_connection(|conn| {
-            conn.execute(
-                "DELETE FROM sessions WHERE session_id = ?1",
-                params![session_id],
-            )
-            .map_err(|err| format!("Failed to delete session: {}", err))?;
-            Ok(())
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start session transaction: {}", err))?;
+            let deleted = tx
+                .execute(
+                    "DELETE FROM sessions WHERE session_id = ?1",
+                    params![session_id],
+                )
+                .map_err(|err| format!("Failed to delete session: {}", err))?;
+            if deleted > 0 {
+                record_revisions(&tx, SESSIONS_REVISION_KIND, &[], &[session_id])?;
+            }
+            tx.commit()
+                .map_err(|err| format!("Failed to commit session delete: {}", err))
         })
     }
 
-    #[cfg(test)]
     pub fn clear_sessions(&self) -> Result<(), String> {
         self.with_connection(|conn| {
-            conn.execute("DELETE FROM sessions", [])
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start session transaction: {}", err))?;
+            let session_ids = {
+                let mut stmt = tx
+                    .prepare("SELECT session_id FROM sessions")
+                    .map_err(|err| format!("Failed to prepare session id query: {}", err))?;
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, String>(0))
+                    .map_err(|err| format!("Failed to query session ids: {}", err))?;
+                rows.collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| format!("Failed to decode session id: {}", err))?
+            };
+            tx.execute("DELETE FROM sessions", [])
                 .map_err(|err| format!("Failed to clear sessions: {}", err))?;
-            Ok(())
+            let removed: Vec<&str> = session_ids.iter().map(String::as_str).collect();
+            record_revisions(&tx, SESSIONS_REVISION_KIND, &[], &removed)?;
+            tx.commit()
+                .map_err(|err| format!("Failed to commit session clear: {}", err))
         })
     }
 
@@ -661,118 +957,1412 @@ impl Db {
                     entry.recorded_at
                 ],
             )
-            .map_err(|err| format!("Failed to insert activity: {}", err))?;
-            Ok(())
+            .map_err(|err| format!("Failed to insert activity: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn list_activity(
+        &self,
+        session_id: &str,
+        limit: usize,
+    ) -> Result<Vec<ActivityEntry>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, project_path, file_path, tool_name, recorded_at \
+                     FROM activity WHERE session_id = ?1 \
+                     ORDER BY recorded_at DESC \
+                     LIMIT ?2",
+                )
+                .map_err(|err| format!("Failed to prepare activity query: {}", err))?;
+
+            let rows = stmt
+                .query_map(params![session_id, limit as i64], |row| {
+                    Ok(ActivityEntry {
+                        session_id: row.get(0)?,
+                        project_path: row.get(1)?,
+                        file_path: row.get(2)?,
+                        tool_name: row.get(3)?,
+                        recorded_at: row.get(4)?,
+                    })
+                })
+                .map_err(|err| format!("Failed to query activity rows: {}", err))?;
+
+            let mut entries = Vec::new();
+            for row in rows {
+                entries.push(row.map_err(|err| format!("Failed to decode activity row: {}", err))?);
+            }
+            Ok(entries)
+        })
+    }
+
+    pub fn list_activity_all(&self, limit: usize) -> Result<Vec<ActivityEntry>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, project_path, file_path, tool_name, recorded_at \
+                     FROM activity ORDER BY recorded_at DESC LIMIT ?1",
+                )
+                .map_err(|err| format!("Failed to prepare activity query: {}", err))?;
+
+            let rows = stmt
+                .query_map(params![limit as i64], |row| {
+                    Ok(ActivityEntry {
+                        session_id: row.get(0)?,
+                        project_path: row.get(1)?,
+                        file_path: row.get(2)?,
+                        tool_name: row.get(3)?,
+                        recorded_at: row.get(4)?,
+                    })
+                })
+                .map_err(|err| format!("Failed to query activity rows: {}", err))?;
+
+            let mut entries = Vec::new();
+            for row in rows {
+                entries.push(row.map_err(|err| format!("Failed to decode activity row: {}", err))?);
+            }
+            Ok(entries)
+        })
+    }
+
+    pub fn list_tombstones(&self) -> Result<Vec<Tombstone>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, created_at, expires_at FROM tombstones ORDER BY created_at DESC",
+                )
+                .map_err(|err| format!("Failed to prepare tombstones query: {}", err))?;
+
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok(Tombstone {
+                        session_id: row.get(0)?,
+                        created_at: row.get(1)?,
+                        expires_at: row.get(2)?,
+                    })
+                })
+                .map_err(|err| format!("Failed to query tombstones: {}", err))?;
+
+            let mut tombstones = Vec::new();
+            for row in rows {
+                tombstones
+                    .push(row.map_err(|err| format!("Failed to decode tombstone row: {}", err))?);
+            }
+            Ok(tombstones)
+        })
+    }
+
+    pub fn delete_activity_for_session(&self, session_id: &str) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "DELETE FROM activity WHERE session_id = ?1",
+                params![session_id],
+            )
+            .map_err(|err| format!("Failed to delete activity: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn clear_activity(&self) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute("DELETE FROM activity", [])
+                .map_err(|err| format!("Failed to clear activity: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn prune_activity(&self, recorded_before: &str) -> Result<u64, String> {
+        self.with_connection(|conn| {
+            let deleted = conn
+                .execute(
+                    "DELETE FROM activity WHERE julianday(recorded_at) < julianday(?1)",
+                    params![recorded_before],
+                )
+                .map_err(|err| format!("Failed to prune activity: {}", err))?;
+            Ok(deleted as u64)
+        })
+    }
+
+    pub fn event_count(&self) -> Result<u64, String> {
+        self.with_connection(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM events", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map(|count| count as u64)
+            .map_err(|err| format!("Failed to count events: {}", err))
+        })
+    }
+
+    pub fn oldest_event_time(&self) -> Result<Option<String>, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT recorded_at FROM events ORDER BY rowid ASC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| format!("Failed to query oldest event: {}", err))
+        })
+    }
+
+    /// Events that `prune_events` would delete. The newest event is always
+    /// kept so its rowid is never handed out again after a prune.
+    pub fn count_prunable_events(
+        &self,
+        max_rowid: i64,
+        recorded_before: &str,
+    ) -> Result<u64, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM events \
+                 WHERE rowid <= ?1 \
+                   AND rowid < (SELECT MAX(rowid) FROM events) \
+                   AND julianday(recorded_at) < julianday(?2)",
+                params![max_rowid, recorded_before],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as u64)
+            .map_err(|err| format!("Failed to count prunable events: {}", err))
+        })
+    }
+
+    /// Deletes events at or below `max_rowid` recorded before the cutoff.
+    /// Callers write a retention snapshot at `max_rowid` first.
+    pub fn prune_events(&self, max_rowid: i64, recorded_before: &str) -> Result<u64, String> {
+        self.with_connection(|conn| {
+            let deleted = conn
+                .execute(
+                    "DELETE FROM events \
+                     WHERE rowid <= ?1 \
+                       AND rowid < (SELECT MAX(rowid) FROM events) \
+                       AND julianday(recorded_at) < julianday(?2)",
+                    params![max_rowid, recorded_before],
+                )
+                .map_err(|err| format!("Failed to prune events: {}", err))?;
+            Ok(deleted as u64)
+        })
+    }
+
+    /// Deletes history rows recorded before the cutoff or beyond the newest
+    /// `max_rows` of their table. Rows still in use are kept: open waits,
+    /// running tool calls, each session's latest transition (time accounting
+    /// opens windows with it) and each file's latest config change (the next
+    /// one is compared with it).
+    pub fn prune_history(&self, recorded_before: &str, max_rows: u64) -> Result<u64, String> {
+        // (table, row order, age column, rows that may go)
+        const HISTORY: [(&str, &str, &str, &str); 4] = [
+            (
+                "session_transitions",
+                "id",
+                "transitioned_at",
+                "id NOT IN (SELECT MAX(id) FROM session_transitions GROUP BY session_id)",
+            ),
+            ("human_waits", "id", "ended_at", "ended_at IS NOT NULL"),
+            ("tool_calls", "rowid", "started_at", "outcome IS NOT NULL"),
+            (
+                "config_changes",
+                "rowid",
+                "recorded_at",
+                "rowid NOT IN (SELECT MAX(rowid) FROM config_changes GROUP BY file_path)",
+            ),
+        ];
+        let max_rows = u64_to_i64(max_rows, "history_max_rows")?;
+        self.with_connection(|conn| {
+            let mut pruned = 0;
+            for (table, order, age, prunable) in HISTORY {
+                pruned += conn
+                    .execute(
+                        &format!(
+                            "DELETE FROM {table} WHERE {prunable} \
+                               AND (julianday({age}) < julianday(?1) \
+                                 OR {order} NOT IN ( \
+                                    SELECT {order} FROM {table} ORDER BY {order} DESC LIMIT ?2 \
+                                 ))"
+                        ),
+                        params![recorded_before, max_rows],
+                    )
+                    .map_err(|err| format!("Failed to prune {}: {}", table, err))?
+                    as u64;
+            }
+            Ok(pruned)
+        })
+    }
+
+    /// Forgets snapshot removals past `SNAPSHOT_REMOVAL_RETENTION_HOURS`, and
+    /// all but the newest `max_rows` of them, raising the revision floor.
+    /// Writes normally do this, but only when they remove something.
+    pub fn prune_snapshot_removals(
+        &self,
+        now: DateTime<Utc>,
+        max_rows: u64,
+    ) -> Result<u64, String> {
+        let cutoff = (now - Duration::hours(SNAPSHOT_REMOVAL_RETENTION_HOURS)).to_rfc3339();
+        let max_rows = u64_to_i64(max_rows, "history_max_rows")?;
+        self.with_connection(|conn| {
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start removal prune: {}", err))?;
+            let mut pruned = prune_revision_removals(&tx, &cutoff)?;
+            let beyond_limit: Option<i64> = tx
+                .query_row(
+                    "SELECT revision FROM snapshot_revisions WHERE removed = 1 \
+                     ORDER BY revision DESC LIMIT 1 OFFSET ?1",
+                    params![max_rows],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|err| format!("Failed to query removal limit: {}", err))?;
+            if let Some(revision) = beyond_limit {
+                pruned += forget_removals_through(&tx, revision)?;
+            }
+            tx.commit()
+                .map_err(|err| format!("Failed to commit removal prune: {}", err))?;
+            Ok(pruned)
+        })
+    }
+
+    /// Stores the current sessions, shell state and derived registries as the
+    /// replay base for events after `event_rowid`.
+    pub fn write_retention_snapshot(
+        &self,
+        event_rowid: i64,
+        created_at: &str,
+    ) -> Result<(), String> {
+        self.store_retention_snapshot(&RetentionSnapshot {
+            event_rowid,
+            sessions: self.list_sessions()?,
+            shell_state: self.load_shell_state()?,
+            derived: self.read_derived_snapshot()?,
+            created_at: created_at.to_string(),
+        })
+    }
+
+    fn read_derived_snapshot(&self) -> Result<DerivedSnapshot, String> {
+        self.with_connection(|conn| {
+            let mut registries = BTreeMap::new();
+            for table in SNAPSHOT_REGISTRY_TABLES {
+                registries.insert(table.to_string(), read_table_rows(conn, table)?);
+            }
+            let max_id = |table: &str| {
+                conn.query_row(
+                    &format!("SELECT COALESCE(MAX(id), 0) FROM {}", table),
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map_err(|err| format!("Failed to read last {} id: {}", table, err))
+            };
+            let open_human_wait_ids = {
+                let mut stmt = conn
+                    .prepare("SELECT id FROM human_waits WHERE ended_at IS NULL ORDER BY id")
+                    .map_err(|err| format!("Failed to prepare open waits query: {}", err))?;
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, i64>(0))
+                    .map_err(|err| format!("Failed to query open waits: {}", err))?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+                    .map_err(|err| format!("Failed to decode open wait: {}", err))?
+            };
+            Ok(DerivedSnapshot {
+                registries,
+                last_transition_id: max_id("session_transitions")?,
+                last_human_wait_id: max_id("human_waits")?,
+                open_human_wait_ids,
+            })
+        })
+    }
+
+    /// Puts derived tables back as of the snapshot, ready for replaying the
+    /// events after it: registries are replaced with the snapshot's rows and
+    /// transitions and waits trimmed back to it. Tool calls are left alone;
+    /// replay only inserts missing calls and finishes running ones, so the
+    /// rows already there are unaffected.
+    pub fn restore_derived_snapshot(&self, snapshot: &DerivedSnapshot) -> Result<(), String> {
+        self.with_connection(|conn| {
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start snapshot restore: {}", err))?;
+            for table in SNAPSHOT_REGISTRY_TABLES {
+                tx.execute(&format!("DELETE FROM {}", table), [])
+                    .map_err(|err| format!("Failed to clear {}: {}", table, err))?;
+                for row in snapshot.registries.get(table).into_iter().flatten() {
+                    insert_table_row(&tx, table, row)?;
+                }
+            }
+            tx.execute(
+                "DELETE FROM session_transitions WHERE id > ?1",
+                params![snapshot.last_transition_id],
+            )
+            .map_err(|err| format!("Failed to trim session transitions: {}", err))?;
+            tx.execute(
+                "DELETE FROM human_waits WHERE id > ?1",
+                params![snapshot.last_human_wait_id],
+            )
+            .map_err(|err| format!("Failed to trim human waits: {}", err))?;
+            for id in &snapshot.open_human_wait_ids {
+                tx.execute(
+                    "UPDATE human_waits SET ended_at = NULL WHERE id = ?1",
+                    params![id],
+                )
+                .map_err(|err| format!("Failed to reopen human wait: {}", err))?;
+            }
+            tx.commit()
+                .map_err(|err| format!("Failed to commit snapshot restore: {}", err))
+        })
+    }
+
+    pub fn store_retention_snapshot(&self, snapshot: &RetentionSnapshot) -> Result<(), String> {
+        let sessions = serde_json::to_string(&snapshot.sessions)
+            .map_err(|err| format!("Failed to serialize session snapshot: {}", err))?;
+        let shell_state = serde_json::to_string(&snapshot.shell_state)
+            .map_err(|err| format!("Failed to serialize shell snapshot: {}", err))?;
+        let derived = serde_json::to_string(&snapshot.derived)
+            .map_err(|err| format!("Failed to serialize derived snapshot: {}", err))?;
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO retention_snapshot \
+                    (id, event_rowid, sessions, shell_state, derived, created_at) \
+                 VALUES (1, ?1, ?2, ?3, ?4, ?5) \
+                 ON CONFLICT(id) DO UPDATE SET \
+                    event_rowid = excluded.event_rowid, \
+                    sessions = excluded.sessions, \
+                    shell_state = excluded.shell_state, \
+                    derived = excluded.derived, \
+                    created_at = excluded.created_at",
+                params![
+                    snapshot.event_rowid,
+                    sessions,
+                    shell_state,
+                    derived,
+                    snapshot.created_at
+                ],
+            )
+            .map_err(|err| format!("Failed to write retention snapshot: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn load_retention_snapshot(&self) -> Result<Option<RetentionSnapshot>, String> {
+        self.with_connection(|conn| read_retention_snapshot(conn))
+    }
+
+    /// Returns up to `max_pages` free pages to the filesystem. Databases
+    /// created before incremental auto-vacuum are switched over with a
+    /// one-time full `VACUUM`.
+    pub fn incremental_vacuum(&self, max_pages: u64) -> Result<VacuumReport, String> {
+        self.with_connection(|conn| {
+            let mode = read_pragma_i64(conn, "auto_vacuum")?;
+            if mode != AUTO_VACUUM_INCREMENTAL {
+                conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")
+                    .map_err(|err| format!("Failed to enable incremental vacuum: {}", err))?;
+                conn.execute_batch("VACUUM")
+                    .map_err(|err| format!("Failed to vacuum database: {}", err))?;
+            }
+            let mode = read_pragma_i64(conn, "auto_vacuum")?;
+            let before = read_pragma_i64(conn, "freelist_count")?;
+            conn.execute_batch(&format!("PRAGMA incremental_vacuum({})", max_pages))
+                .map_err(|err| format!("Failed to run incremental vacuum: {}", err))?;
+            let after = read_pragma_i64(conn, "freelist_count")?;
+            Ok(VacuumReport {
+                auto_vacuum: match mode {
+                    1 => "full",
+                    AUTO_VACUUM_INCREMENTAL => "incremental",
+                    _ => "none",
+                }
+                .to_string(),
+                pages_vacuumed: (before - after).max(0) as u64,
+                freelist_pages: after.max(0) as u64,
+            })
+        })
+    }
+
+    pub fn upsert_subagent(&self, record: &SubagentRecord) -> Result<(), String> {
+        let tools = serde_json::to_string(&record.tools)
+            .map_err(|err| format!("Failed to serialize subagent tools: {}", err))?;
+        self.with_connection(|conn| {
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start subagent transaction: {}", err))?;
+            tx.execute(
+                "INSERT INTO subagents \
+                    (session_id, agent_id, agent_type, started_at, stopped_at, transcript_path, tools, tool_calls, updated_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) \
+                 ON CONFLICT(session_id, agent_id) DO UPDATE SET \
+                    agent_type = excluded.agent_type, \
+                    started_at = excluded.started_at, \
+                    stopped_at = excluded.stopped_at, \
+                    transcript_path = excluded.transcript_path, \
+                    tools = excluded.tools, \
+                    tool_calls = excluded.tool_calls, \
+                    updated_at = excluded.updated_at",
+                params![
+                    record.session_id,
+                    record.agent_id,
+                    record.agent_type,
+                    record.started_at,
+                    record.stopped_at,
+                    record.transcript_path,
+                    tools,
+                    record.tool_calls,
+                    record.updated_at
+                ],
+            )
+            .map_err(|err| format!("Failed to upsert subagent: {}", err))?;
+            // Running subagents are part of the parent's session snapshot.
+            record_revisions(&tx, SESSIONS_REVISION_KIND, &[&record.session_id], &[])?;
+            tx.commit()
+                .map_err(|err| format!("Failed to commit subagent upsert: {}", err))
+        })
+    }
+
+    pub fn get_subagent(
+        &self,
+        session_id: &str,
+        agent_id: &str,
+    ) -> Result<Option<SubagentRecord>, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT session_id, agent_id, agent_type, started_at, stopped_at, \
+                        transcript_path, tools, tool_calls, updated_at \
+                 FROM subagents WHERE session_id = ?1 AND agent_id = ?2",
+                params![session_id, agent_id],
+                subagent_from_row,
+            )
+            .optional()
+            .map_err(|err| format!("Failed to query subagent: {}", err))
+        })
+    }
+
+    /// Subagents of a session, oldest first. With `running_only`, only those
+    /// without a stop.
+    pub fn list_subagents(
+        &self,
+        session_id: &str,
+        running_only: bool,
+    ) -> Result<Vec<SubagentRecord>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, agent_id, agent_type, started_at, stopped_at, \
+                            transcript_path, tools, tool_calls, updated_at \
+                     FROM subagents \
+                     WHERE session_id = ?1 AND (?2 = 0 OR stopped_at IS NULL) \
+                     ORDER BY julianday(COALESCE(started_at, updated_at)) ASC, agent_id ASC",
+                )
+                .map_err(|err| format!("Failed to prepare subagents query: {}", err))?;
+            let rows = stmt
+                .query_map(params![session_id, running_only], subagent_from_row)
+                .map_err(|err| format!("Failed to read subagent rows: {}", err))?;
+
+            let mut subagents = Vec::new();
+            for row in rows {
+                subagents
+                    .push(row.map_err(|err| format!("Failed to decode subagent row: {}", err))?);
+            }
+            Ok(subagents)
+        })
+    }
+
+    /// Running subagents of `session_ids`, or of every session when `None`,
+    /// grouped by session, oldest first. One query however many sessions.
+    pub fn list_running_subagents(
+        &self,
+        session_ids: Option<&[String]>,
+    ) -> Result<HashMap<String, Vec<SubagentRecord>>, String> {
+        if session_ids.is_some_and(<[String]>::is_empty) {
+            return Ok(HashMap::new());
+        }
+        self.with_connection(|conn| {
+            let filter = match session_ids {
+                Some(session_ids) => {
+                    format!(
+                        "AND session_id IN ({})",
+                        sql_placeholders(session_ids.len())
+                    )
+                }
+                None => String::new(),
+            };
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT session_id, agent_id, agent_type, started_at, stopped_at, \
+                            transcript_path, tools, tool_calls, updated_at \
+                     FROM subagents \
+                     WHERE stopped_at IS NULL {filter} \
+                     ORDER BY julianday(COALESCE(started_at, updated_at)) ASC, agent_id ASC"
+                ))
+                .map_err(|err| format!("Failed to prepare subagents query: {}", err))?;
+            let rows = stmt
+                .query_map(
+                    params_from_iter(session_ids.unwrap_or_default()),
+                    subagent_from_row,
+                )
+                .map_err(|err| format!("Failed to read subagent rows: {}", err))?;
+
+            let mut subagents: HashMap<String, Vec<SubagentRecord>> = HashMap::new();
+            for row in rows {
+                let subagent =
+                    row.map_err(|err| format!("Failed to decode subagent row: {}", err))?;
+                subagents
+                    .entry(subagent.session_id.clone())
+                    .or_default()
+                    .push(subagent);
+            }
+            Ok(subagents)
+        })
+    }
+
+    pub fn delete_subagents_for_session(&self, session_id: &str) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "DELETE FROM subagents WHERE session_id = ?1",
+                params![session_id],
+            )
+            .map_err(|err| format!("Failed to delete subagents: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn clear_subagents(&self) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute("DELETE FROM subagents", [])
+                .map_err(|err| format!("Failed to clear subagents: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn insert_session_transition(&self, transition: &SessionTransition) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO session_transitions \
+                    (session_id, project_path, from_state, to_state, event_id, transitioned_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    transition.session_id,
+                    transition.project_path,
+                    transition.from_state.map(|state| state.as_str()),
+                    transition.to_state.map(|state| state.as_str()),
+                    transition.event_id,
+                    transition.transitioned_at
+                ],
+            )
+            .map_err(|err| format!("Failed to insert session transition: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Transitions that shape `[since, until)`, grouped by session and in
+    /// applied order within each: the one in effect at `since` plus those
+    /// inside the window. With `project_path`, every session that was ever in
+    /// that project is returned whole so its intervals can be attributed by
+    /// the project in effect at the time.
+    pub fn list_session_transitions_in_window(
+        &self,
+        project_path: Option<&str>,
+        since: &str,
+        until: &str,
+    ) -> Result<Vec<SessionTransition>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "WITH sessions AS ( \
+                         SELECT DISTINCT session_id FROM session_transitions \
+                         WHERE ?1 IS NULL OR project_path = ?1 \
+                     ), \
+                     opening AS ( \
+                         SELECT MAX(id) AS id FROM session_transitions \
+                         WHERE session_id IN (SELECT session_id FROM sessions) \
+                           AND julianday(transitioned_at) <= julianday(?2) \
+                         GROUP BY session_id \
+                     ) \
+                     SELECT session_id, project_path, from_state, to_state, event_id, transitioned_at \
+                     FROM session_transitions \
+                     WHERE session_id IN (SELECT session_id FROM sessions) \
+                       AND ((id IN (SELECT id FROM opening) AND to_state IS NOT NULL) \
+                         OR (julianday(transitioned_at) > julianday(?2) \
+                             AND julianday(transitioned_at) < julianday(?3))) \
+                     ORDER BY session_id ASC, id ASC",
+                )
+                .map_err(|err| format!("Failed to prepare session transitions query: {}", err))?;
+            let rows = stmt
+                .query_map(params![project_path, since, until], session_transition_from_row)
+                .map_err(|err| format!("Failed to query session transitions: {}", err))?;
+
+            let mut transitions = Vec::new();
+            for row in rows {
+                transitions.push(
+                    row.map_err(|err| format!("Failed to decode session transition: {}", err))?,
+                );
+            }
+            Ok(transitions)
+        })
+    }
+
+    /// Every recorded transition of a session in the order it was applied.
+    pub fn list_session_transitions(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<SessionTransition>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, project_path, from_state, to_state, event_id, transitioned_at \
+                     FROM session_transitions WHERE session_id = ?1 ORDER BY id ASC",
+                )
+                .map_err(|err| format!("Failed to prepare session transitions query: {}", err))?;
+            let rows = stmt
+                .query_map(params![session_id], session_transition_from_row)
+                .map_err(|err| format!("Failed to query session transitions: {}", err))?;
+
+            let mut transitions = Vec::new();
+            for row in rows {
+                transitions.push(
+                    row.map_err(|err| format!("Failed to decode session transition: {}", err))?,
+                );
+            }
+            Ok(transitions)
+        })
+    }
+
+    pub fn clear_session_transitions(&self) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute("DELETE FROM session_transitions", [])
+                .map_err(|err| format!("Failed to clear session transitions: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Opens a wait for `session_id` unless one is already open. Returns
+    /// whether a wait was opened.
+    pub fn open_human_wait(
+        &self,
+        session_id: &str,
+        project_path: &str,
+        started_at: &str,
+    ) -> Result<bool, String> {
+        self.with_connection(|conn| {
+            let inserted = conn
+                .execute(
+                    "INSERT INTO human_waits (session_id, project_path, started_at) \
+                     SELECT ?1, ?2, ?3 WHERE NOT EXISTS ( \
+                        SELECT 1 FROM human_waits WHERE session_id = ?1 AND ended_at IS NULL \
+                     )",
+                    params![session_id, project_path, started_at],
+                )
+                .map_err(|err| format!("Failed to open human wait: {}", err))?;
+            Ok(inserted > 0)
+        })
+    }
+
+    pub fn close_human_wait(&self, session_id: &str, ended_at: &str) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE human_waits SET ended_at = ?2 \
+                 WHERE session_id = ?1 AND ended_at IS NULL",
+                params![session_id, ended_at],
+            )
+            .map_err(|err| format!("Failed to close human wait: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Waits that overlap `[since, until)`, optionally for one project.
+    pub fn list_human_waits(
+        &self,
+        project_path: Option<&str>,
+        since: &str,
+        until: &str,
+    ) -> Result<Vec<HumanWait>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, project_path, started_at, ended_at FROM human_waits \
+                     WHERE (?1 IS NULL OR project_path = ?1) \
+                       AND julianday(started_at) < julianday(?3) \
+                       AND (ended_at IS NULL OR julianday(ended_at) > julianday(?2)) \
+                     ORDER BY id ASC",
+                )
+                .map_err(|err| format!("Failed to prepare human waits query: {}", err))?;
+            let rows = stmt
+                .query_map(params![project_path, since, until], |row| {
+                    Ok(HumanWait {
+                        session_id: row.get(0)?,
+                        project_path: row.get(1)?,
+                        started_at: row.get(2)?,
+                        ended_at: row.get(3)?,
+                    })
+                })
+                .map_err(|err| format!("Failed to query human waits: {}", err))?;
+
+            let mut waits = Vec::new();
+            for row in rows {
+                waits.push(row.map_err(|err| format!("Failed to decode human wait: {}", err))?);
+            }
+            Ok(waits)
+        })
+    }
+
+    pub fn clear_human_waits(&self) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute("DELETE FROM human_waits", [])
+                .map_err(|err| format!("Failed to clear human waits: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Records a started call. A redelivered `pre_tool_use` is ignored.
+    pub fn start_tool_call(&self, call: &ToolCall) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO tool_calls \
+                    (session_id, tool_use_id, tool_name, file_path, project_path, agent_id, started_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    call.session_id,
+                    call.tool_use_id,
+                    call.tool_name,
+                    call.file_path,
+                    call.project_path,
+                    call.agent_id,
+                    call.started_at
+                ],
+            )
+            .map_err(|err| format!("Failed to start tool call: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Completes a running call. Returns whether one was found.
+    pub fn finish_tool_call(
+        &self,
+        session_id: &str,
+        tool_use_id: &str,
+        ended_at: &str,
+        outcome: ToolCallOutcome,
+    ) -> Result<bool, String> {
+        self.with_connection(|conn| {
+            let updated = conn
+                .execute(
+                    "UPDATE tool_calls SET ended_at = ?3, outcome = ?4, \
+                        duration_ms = MAX(0, CAST(ROUND( \
+                            (julianday(?3) - julianday(started_at)) * 86400000 \
+                        ) AS INTEGER)) \
+                     WHERE session_id = ?1 AND tool_use_id = ?2 AND outcome IS NULL",
+                    params![session_id, tool_use_id, ended_at, outcome.as_str()],
+                )
+                .map_err(|err| format!("Failed to finish tool call: {}", err))?;
+            Ok(updated > 0)
+        })
+    }
+
+    /// Marks running calls of the session, or of one of its subagents, as
+    /// interrupted.
+    pub fn interrupt_tool_calls(
+        &self,
+        session_id: &str,
+        agent_id: Option<&str>,
+        ended_at: &str,
+    ) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE tool_calls SET ended_at = ?3, outcome = ?4 \
+                 WHERE session_id = ?1 AND outcome IS NULL AND (?2 IS NULL OR agent_id = ?2)",
+                params![
+                    session_id,
+                    agent_id,
+                    ended_at,
+                    ToolCallOutcome::Interrupted.as_str()
+                ],
+            )
+            .map_err(|err| format!("Failed to interrupt tool calls: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Running calls of the session, or `None` when none of its calls were
+    /// ever tracked by `tool_use_id`.
+    pub fn tracked_tools_in_flight(&self, session_id: &str) -> Result<Option<u32>, String> {
+        self.with_connection(|conn| {
+            let (tracked, running): (i64, i64) = conn
+                .query_row(
+                    "SELECT COUNT(*), COALESCE(SUM(outcome IS NULL), 0) \
+                     FROM tool_calls WHERE session_id = ?1",
+                    params![session_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|err| format!("Failed to count tool calls: {}", err))?;
+            Ok((tracked > 0).then_some(running as u32))
+        })
+    }
+
+    /// Which of `session_ids` have tool calls tracked; see
+    /// [`Self::tracked_tools_in_flight`].
+    pub fn sessions_with_tracked_tools(
+        &self,
+        session_ids: &[String],
+    ) -> Result<HashSet<String>, String> {
+        if session_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT DISTINCT session_id FROM tool_calls WHERE session_id IN ({})",
+                    sql_placeholders(session_ids.len())
+                ))
+                .map_err(|err| format!("Failed to prepare tool calls query: {}", err))?;
+            let rows = stmt
+                .query_map(params_from_iter(session_ids), |row| row.get::<_, String>(0))
+                .map_err(|err| format!("Failed to query tool calls: {}", err))?;
+            rows.collect::<Result<HashSet<_>, _>>()
+                .map_err(|err| format!("Failed to decode tool call row: {}", err))
+        })
+    }
+
+    /// Calls matching `query`'s filters (not its limit), newest first.
+    pub fn list_tool_calls(&self, query: &ToolCallsQuery) -> Result<Vec<ToolCall>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, tool_use_id, tool_name, file_path, project_path, agent_id, \
+                        started_at, ended_at, duration_ms, outcome \
+                     FROM tool_calls \
+                     WHERE (?1 IS NULL OR session_id = ?1) \
+                       AND (?2 IS NULL OR project_path = ?2) \
+                       AND (?3 IS NULL OR julianday(started_at) >= julianday(?3)) \
+                     ORDER BY julianday(started_at) DESC, rowid DESC \
+                     LIMIT ?4",
+                )
+                .map_err(|err| format!("Failed to prepare tool calls query: {}", err))?;
+            let rows = stmt
+                .query_map(
+                    params![
+                        query.session_id,
+                        query.project_path,
+                        query.since,
+                        query.limit() as i64
+                    ],
+                    |row| {
+                        Ok(ToolCall {
+                            session_id: row.get(0)?,
+                            tool_use_id: row.get(1)?,
+                            tool_name: row.get(2)?,
+                            file_path: row.get(3)?,
+                            project_path: row.get(4)?,
+                            agent_id: row.get(5)?,
+                            started_at: row.get(6)?,
+                            ended_at: row.get(7)?,
+                            duration_ms: row.get::<_, Option<i64>>(8)?.map(|ms| ms as u64),
+                            outcome: row
+                                .get::<_, Option<String>>(9)?
+                                .as_deref()
+                                .and_then(ToolCallOutcome::parse),
+                        })
+                    },
+                )
+                .map_err(|err| format!("Failed to query tool calls: {}", err))?;
+
+            let mut calls = Vec::new();
+            for row in rows {
+                calls.push(row.map_err(|err| format!("Failed to decode tool call: {}", err))?);
+            }
+            Ok(calls)
+        })
+    }
+
+    /// Per-tool totals over every call matching `query` (its `limit` is
+    /// ignored), slowest average first. Durations and failure rates count
+    /// only calls that reported a result; ties for the slowest call go to the
+    /// newest.
+    pub fn tool_stats(&self, query: &ToolCallsQuery) -> Result<Vec<ToolStats>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "WITH matched AS ( \
+                        SELECT tool_name, tool_use_id, started_at, rowid AS row_id, \
+                            outcome IN ('success', 'failure') AS completed, \
+                            outcome = 'failure' AS failed, \
+                            outcome = 'interrupted' AS interrupted, \
+                            COALESCE(duration_ms, 0) AS duration_ms \
+                        FROM tool_calls \
+                        WHERE (?1 IS NULL OR session_id = ?1) \
+                          AND (?2 IS NULL OR project_path = ?2) \
+                          AND (?3 IS NULL OR julianday(started_at) >= julianday(?3)) \
+                     ), slowest AS ( \
+                        SELECT tool_name, tool_use_id, ROW_NUMBER() OVER ( \
+                            PARTITION BY tool_name \
+                            ORDER BY duration_ms DESC, julianday(started_at) DESC, row_id DESC \
+                        ) AS position \
+                        FROM matched WHERE completed \
+                     ), totals AS ( \
+                        SELECT tool_name, COUNT(*) AS calls, \
+                            COALESCE(SUM(failed), 0) AS failures, \
+                            COALESCE(SUM(interrupted), 0) AS interrupted, \
+                            COALESCE(SUM(completed), 0) AS completed, \
+                            COALESCE(SUM(CASE WHEN completed THEN duration_ms END), 0) AS total_ms, \
+                            COALESCE(MAX(CASE WHEN completed THEN duration_ms END), 0) AS max_ms \
+                        FROM matched GROUP BY tool_name \
+                     ) \
+                     SELECT totals.tool_name, calls, failures, interrupted, \
+                        COALESCE(failures * 100 / NULLIF(completed, 0), 0), \
+                        COALESCE(total_ms / NULLIF(completed, 0), 0) AS avg_ms, \
+                        max_ms, slowest.tool_use_id \
+                     FROM totals \
+                     LEFT JOIN slowest \
+                        ON slowest.tool_name = totals.tool_name AND slowest.position = 1 \
+                     ORDER BY avg_ms DESC, totals.tool_name ASC",
+                )
+                .map_err(|err| format!("Failed to prepare tool stats query: {}", err))?;
+            let rows = stmt
+                .query_map(
+                    params![query.session_id, query.project_path, query.since],
+                    |row| {
+                        Ok(ToolStats {
+                            tool_name: row.get(0)?,
+                            calls: row.get::<_, i64>(1)? as u64,
+                            failures: row.get::<_, i64>(2)? as u64,
+                            interrupted: row.get::<_, i64>(3)? as u64,
+                            failure_rate_percent: row.get::<_, i64>(4)? as u32,
+                            avg_duration_ms: row.get::<_, i64>(5)? as u64,
+                            max_duration_ms: row.get::<_, i64>(6)? as u64,
+                            slowest_tool_use_id: row.get(7)?,
+                        })
+                    },
+                )
+                .map_err(|err| format!("Failed to query tool stats: {}", err))?;
+
+            let mut tools = Vec::new();
+            for row in rows {
+                tools.push(row.map_err(|err| format!("Failed to decode tool stats: {}", err))?);
+            }
+            Ok(tools)
+        })
+    }
+
+    pub fn clear_tool_calls(&self) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute("DELETE FROM tool_calls", [])
+                .map_err(|err| format!("Failed to clear tool calls: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn upsert_worktree(&self, record: &WorktreeRecord) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO worktrees \
+                    (path, project_id, project_path, name, branch, created_by_session_id, created_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) \
+                 ON CONFLICT(path) DO UPDATE SET \
+                    project_id = excluded.project_id, \
+                    project_path = excluded.project_path, \
+                    name = excluded.name, \
+                    branch = excluded.branch, \
+                    created_by_session_id = excluded.created_by_session_id, \
+                    created_at = excluded.created_at",
+                params![
+                    record.path,
+                    record.project_id,
+                    record.project_path,
+                    record.name,
+                    record.branch,
+                    record.created_by_session_id,
+                    record.created_at
+                ],
+            )
+            .map_err(|err| format!("Failed to upsert worktree: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Returns whether a worktree was registered at `path`.
+    pub fn delete_worktree(&self, path: &str) -> Result<bool, String> {
+        self.with_connection(|conn| {
+            let deleted = conn
+                .execute("DELETE FROM worktrees WHERE path = ?1", params![path])
+                .map_err(|err| format!("Failed to delete worktree: {}", err))?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Registered worktrees ordered by repository then path, optionally only
+    /// those of `project_path`.
+    pub fn list_worktrees(
+        &self,
+        project_path: Option<&str>,
+    ) -> Result<Vec<WorktreeRecord>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT path, project_id, project_path, name, branch, created_by_session_id, created_at \
+                     FROM worktrees \
+                     WHERE ?1 IS NULL OR project_path = ?1 \
+                     ORDER BY project_path ASC, path ASC",
+                )
+                .map_err(|err| format!("Failed to prepare worktrees query: {}", err))?;
+            let rows = stmt
+                .query_map(params![project_path], |row| {
+                    Ok(WorktreeRecord {
+                        path: row.get(0)?,
+                        project_id: row.get(1)?,
+                        project_path: row.get(2)?,
+                        name: row.get(3)?,
+                        branch: row.get(4)?,
+                        created_by_session_id: row.get(5)?,
+                        created_at: row.get(6)?,
+                    })
+                })
+                .map_err(|err| format!("Failed to query worktrees: {}", err))?;
+
+            let mut worktrees = Vec::new();
+            for row in rows {
+                worktrees
+                    .push(row.map_err(|err| format!("Failed to decode worktree: {}", err))?);
+            }
+            Ok(worktrees)
+        })
+    }
+
+    pub fn clear_worktrees(&self) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute("DELETE FROM worktrees", [])
+                .map_err(|err| format!("Failed to clear worktrees: {}", err))?;
+            Ok(())
+        })
+    }
+
+    /// Returns whether the change was new; replayed events are ignored.
+    pub fn insert_config_change(&self, record: &ConfigChangeRecord) -> Result<bool, String> {
+        self.with_connection(|conn| {
+            let inserted = conn
+                .execute(
+                    "INSERT OR IGNORE INTO config_changes \
+                        (event_id, session_id, project_path, source, file_path, content_hash, \
+                         hud_hook_hash, content_changed, hud_hook_changed, recorded_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        record.event_id,
+                        record.session_id,
+                        record.project_path,
+                        record.source,
+                        record.file_path,
+                        record.content_hash,
+                        record.hud_hook_hash,
+                        record.content_changed,
+                        record.hud_hook_changed,
+                        record.recorded_at
+                    ],
+                )
+                .map_err(|err| format!("Failed to insert config change: {}", err))?;
+            Ok(inserted > 0)
+        })
+    }
+
+    pub fn has_config_change(&self, event_id: &str) -> Result<bool, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT 1 FROM config_changes WHERE event_id = ?1",
+                params![event_id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|err| format!("Failed to query config change: {}", err))
+        })
+    }
+
+    /// The most recent change recorded for `file_path`.
+    pub fn latest_config_change(
+        &self,
+        file_path: &str,
+    ) -> Result<Option<ConfigChangeRecord>, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT event_id, session_id, project_path, source, file_path, content_hash, \
+                        hud_hook_hash, content_changed, hud_hook_changed, recorded_at \
+                 FROM config_changes WHERE file_path = ?1 \
+                 ORDER BY julianday(recorded_at) DESC, rowid DESC LIMIT 1",
+                params![file_path],
+                config_change_from_row,
+            )
+            .optional()
+            .map_err(|err| format!("Failed to query latest config change: {}", err))
+        })
+    }
+
+    pub fn list_config_changes(
+        &self,
+        query: &ConfigChangesQuery,
+    ) -> Result<Vec<ConfigChangeRecord>, String> {
+        self.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT event_id, session_id, project_path, source, file_path, content_hash, \
+                        hud_hook_hash, content_changed, hud_hook_changed, recorded_at \
+                     FROM config_changes \
+                     WHERE (?1 IS NULL OR project_path = ?1) \
+                       AND (?2 IS NULL OR julianday(recorded_at) >= julianday(?2)) \
+                     ORDER BY julianday(recorded_at) DESC, rowid DESC \
+                     LIMIT ?3",
+                )
+                .map_err(|err| format!("Failed to prepare config changes query: {}", err))?;
+            let rows = stmt
+                .query_map(
+                    params![query.project_path, query.since, query.limit() as i64],
+                    config_change_from_row,
+                )
+                .map_err(|err| format!("Failed to query config changes: {}", err))?;
+
+            let mut changes = Vec::new();
+            for row in rows {
+                changes
+                    .push(row.map_err(|err| format!("Failed to decode config change: {}", err))?);
+            }
+            Ok(changes)
+        })
+    }
+
+    pub fn upsert_team(&self, record: &TeamRecord) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO teams (team_name, lead_session_id, project_path, updated_at) \
+                 VALUES (?1, ?2, ?3, ?4) \
+                 ON CONFLICT(team_name) DO UPDATE SET \
+                    lead_session_id = excluded.lead_session_id, \
+                    project_path = excluded.project_path, \
+                    updated_at = excluded.updated_at",
+                params![
+                    record.team_name,
+                    record.lead_session_id,
+                    record.project_path,
+                    record.updated_at
+                ],
+            )
+            .map_err(|err| format!("Failed to upsert team: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn get_team(&self, team_name: &str) -> Result<Option<TeamRecord>, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT team_name, lead_session_id, project_path, updated_at \
+                 FROM teams WHERE team_name = ?1",
+                params![team_name],
+                team_from_row,
+            )
+            .optional()
+            .map_err(|err| format!("Failed to query team: {}", err))
+        })
+    }
+
+    /// Teams, only those of `project_paths` when given.
+    pub fn list_teams(&self, project_paths: Option<&[String]>) -> Result<Vec<TeamRecord>, String> {
+        if project_paths.is_some_and(<[String]>::is_empty) {
+            return Ok(Vec::new());
+        }
+        self.with_connection(|conn| {
+            let filter = sql_in_filter("WHERE project_path", project_paths);
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT team_name, lead_session_id, project_path, updated_at \
+                     FROM teams {filter} ORDER BY team_name ASC"
+                ))
+                .map_err(|err| format!("Failed to prepare teams query: {}", err))?;
+            let rows = stmt
+                .query_map(
+                    params_from_iter(project_paths.unwrap_or_default()),
+                    team_from_row,
+                )
+                .map_err(|err| format!("Failed to query teams: {}", err))?;
+
+            let mut teams = Vec::new();
+            for row in rows {
+                teams.push(row.map_err(|err| format!("Failed to decode team: {}", err))?);
+            }
+            Ok(teams)
+        })
+    }
+
+    pub fn upsert_teammate(&self, record: &TeammateRecord) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO teammates \
+                    (team_name, name, session_id, state, last_event, updated_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+                 ON CONFLICT(team_name, name) DO UPDATE SET \
+                    session_id = excluded.session_id, \
+                    state = excluded.state, \
+                    last_event = excluded.last_event, \
+                    updated_at = excluded.updated_at",
+                params![
+                    record.team_name,
+                    record.name,
+                    record.session_id,
+                    record.state.as_str(),
+                    record.last_event,
+                    record.updated_at
+                ],
+            )
+            .map_err(|err| format!("Failed to upsert teammate: {}", err))?;
+            Ok(())
+        })
+    }
+
+    pub fn get_teammate(
+        &self,
+        team_name: &str,
+        name: &str,
+    ) -> Result<Option<TeammateRecord>, String> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT team_name, name, session_id, state, last_event, updated_at \
+                 FROM teammates WHERE team_name = ?1 AND name = ?2",
+                params![team_name, name],
+                teammate_from_row,
+            )
+            .optional()
+            .map_err(|err| format!("Failed to query teammate: {}", err))
         })
     }
 
-    pub fn list_activity(
+    /// Teammates of every team, ordered by team then name.
+    /// Teammates, only those of `team_names` when given.
+    pub fn list_teammates(
         &self,
-        session_id: &str,
-        limit: usize,
-    ) -> Result<Vec<ActivityEntry>, String> {
+        team_names: Option<&[String]>,
+    ) -> Result<Vec<TeammateRecord>, String> {
+        if team_names.is_some_and(<[String]>::is_empty) {
+            return Ok(Vec::new());
+        }
         self.with_connection(|conn| {
+            let filter = sql_in_filter("WHERE team_name", team_names);
             let mut stmt = conn
-                .prepare(
-                    "SELECT session_id, project_path, file_path, tool_name, recorded_at \
-                     FROM activity WHERE session_id = ?1 \
-                     ORDER BY recorded_at DESC \
-                     LIMIT ?2",
-                )
-                .map_err(|err| format!("Failed to prepare activity query: {}", err))?;
-
+                .prepare(&format!(
+                    "SELECT team_name, name, session_id, state, last_event, updated_at \
+                     FROM teammates {filter} ORDER BY team_name ASC, name ASC"
+                ))
+                .map_err(|err| format!("Failed to prepare teammates query: {}", err))?;
             let rows = stmt
-                .query_map(params![session_id, limit as i64], |row| {
-                    Ok(ActivityEntry {
-                        session_id: row.get(0)?,
-                        project_path: row.get(1)?,
-                        file_path: row.get(2)?,
-                        tool_name: row.get(3)?,
-                        recorded_at: row.get(4)?,
-                    })
-                })
-                .map_err(|err| format!("Failed to query activity rows: {}", err))?;
+                .query_map(
+                    params_from_iter(team_names.unwrap_or_default()),
+                    teammate_from_row,
+                )
+                .map_err(|err| format!("Failed to query teammates: {}", err))?;
 
-            let mut entries = Vec::new();
+            let mut teammates = Vec::new();
             for row in rows {
-                entries.push(row.map_err(|err| format!("Failed to decode activity row: {}", err))?);
+                teammates.push(row.map_err(|err| format!("Failed to decode teammate: {}", err))?);
             }
-            Ok(entries)
+            Ok(teammates)
         })
     }
 
-    pub fn list_activity_all(&self, limit: usize) -> Result<Vec<ActivityEntry>, String> {
+    /// Drops `session_id` as a teammate's own session once it is known to be
+    /// the team lead's.
+    pub fn clear_teammate_session(&self, team_name: &str, session_id: &str) -> Result<(), String> {
         self.with_connection(|conn| {
-            let mut stmt = conn
-                .prepare(
-                    "SELECT session_id, project_path, file_path, tool_name, recorded_at \
-                     FROM activity ORDER BY recorded_at DESC LIMIT ?1",
-                )
-                .map_err(|err| format!("Failed to prepare activity query: {}", err))?;
-
-            let rows = stmt
-                .query_map(params![limit as i64], |row| {
-                    Ok(ActivityEntry {
-                        session_id: row.get(0)?,
-                        project_path: row.get(1)?,
-                        file_path: row.get(2)?,
-                        tool_name: row.get(3)?,
-                        recorded_at: row.get(4)?,
-                    })
-                })
-                .map_err(|err| format!("Failed to query activity rows: {}", err))?;
+            conn.execute(
+                "UPDATE teammates SET session_id = NULL \
+                 WHERE team_name = ?1 AND session_id = ?2",
+                params![team_name, session_id],
+            )
+            .map_err(|err| format!("Failed to clear teammate session: {}", err))?;
+            Ok(())
+        })
+    }
 
-            let mut entries = Vec::new();
-            for row in rows {
-                entries.push(row.map_err(|err| format!("Failed to decode activity row: {}", err))?);
-            }
-            Ok(entries)
+    /// Replayed events are ignored.
+    pub fn insert_team_task(
+        &self,
+        event_id: &str,
+        team_name: &str,
+        task: &TeamTask,
+    ) -> Result<(), String> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO team_tasks \
+                    (event_id, team_name, task_id, task_subject, teammate_name, completed_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    event_id,
+                    team_name,
+                    task.task_id,
+                    task.task_subject,
+                    task.teammate_name,
+                    task.completed_at
+                ],
+            )
+            .map_err(|err| format!("Failed to insert team task: {}", err))?;
+            Ok(())
         })
     }
 
-    pub fn list_tombstones(&self) -> Result<Vec<TombstoneRow>, String> {
+    /// Completed tasks of every team with their team name, most recent first.
+    /// Completed tasks by team, only those of `team_names` when given.
+    pub fn list_team_tasks(
+        &self,
+        team_names: Option<&[String]>,
+    ) -> Result<Vec<(String, TeamTask)>, String> {
+        if team_names.is_some_and(<[String]>::is_empty) {
+            return Ok(Vec::new());
+        }
         self.with_connection(|conn| {
+            let filter = sql_in_filter("WHERE team_name", team_names);
             let mut stmt = conn
-                .prepare(
-                    "SELECT session_id, created_at, expires_at FROM tombstones ORDER BY created_at DESC",
-                )
-                .map_err(|err| format!("Failed to prepare tombstones query: {}", err))?;
-
+                .prepare(&format!(
+                    "SELECT team_name, task_id, task_subject, teammate_name, completed_at \
+                     FROM team_tasks {filter} \
+                     ORDER BY julianday(completed_at) DESC, rowid DESC"
+                ))
+                .map_err(|err| format!("Failed to prepare team tasks query: {}", err))?;
             let rows = stmt
-                .query_map([], |row| {
-                    Ok(TombstoneRow {
-                        session_id: row.get(0)?,
-                        created_at: row.get(1)?,
-                        expires_at: row.get(2)?,
-                    })
+                .query_map(params_from_iter(team_names.unwrap_or_default()), |row| {
+                    Ok((
+                        row.get(0)?,
+                        TeamTask {
+                            task_id: row.get(1)?,
+                            task_subject: row.get(2)?,
+                            teammate_name: row.get(3)?,
+                            completed_at: row.get(4)?,
+                        },
+                    ))
                 })
-                .map_err(|err| format!("Failed to query tombstones: {}", err))?;
+                .map_err(|err| format!("Failed to query team tasks: {}", err))?;
 
-            let mut tombstones = Vec::new();
+            let mut tasks = Vec::new();
             for row in rows {
-                tombstones
-                    .push(row.map_err(|err| format!("Failed to decode tombstone row: {}", err))?);
+                tasks.push(row.map_err(|err| format!("Failed to decode team task: {}", err))?);
             }
-            Ok(tombstones)
+            Ok(tasks)
         })
     }
 
-    pub fn delete_activity_for_session(&self, session_id: &str) -> Result<(), String> {
+    /// Drops the teams `session_id` leads, with their teammates and tasks.
+    pub fn delete_teams_led_by(&self, session_id: &str) -> Result<(), String> {
         self.with_connection(|conn| {
-            conn.execute(
-                "DELETE FROM activity WHERE session_id = ?1",
+            let tx = begin_write(conn)
+                .map_err(|err| format!("Failed to start team delete transaction: {}", err))?;
+            for table in ["teammates", "team_tasks"] {
+                tx.execute(
+                    &format!(
+                        "DELETE FROM {} WHERE team_name IN \
+                         (SELECT team_name FROM teams WHERE lead_session_id = ?1)",
+                        table
+                    ),
+                    params![session_id],
+                )
+                .map_err(|err| format!("Failed to delete team rows: {}", err))?;
+            }
+            tx.execute(
+                "DELETE FROM teams WHERE lead_session_id = ?1",
                 params![session_id],
             )
-            .map_err(|err| format!("Failed to delete activity: {}", err))?;
-            Ok(())
+            .map_err(|err| format!("Failed to delete teams: {}", err))?;
+            tx.commit()
+                .map_err(|err| format!("Failed to commit team delete transaction: {}", err))
         })
     }
 
-    #[cfg(test)]
-    pub fn clear_activity(&self) -> Result<(), String> {
+    pub fn clear_teams(&self) -> Result<(), String> {
         self.with_connection(|conn| {
-            conn.execute("DELETE FROM activity", [])
-                .map_err(|err| format!("Failed to clear activity: {}", err))?;
+            conn.execute_batch("DELETE FROM teams; DELETE FROM teammates; DELETE FROM team_tasks;")
+                .map_err(|err| format!("Failed to clear teams: {}", err))?;
             Ok(())
         })
     }
@@ -920,7 +2510,9 @@ impl Db {
         })
     }
 
+    /// Replays `shell_cwd` events over the retention snapshot, if any.
     pub fn rebuild_shell_state_from_events(&self) -> Result<ShellState, String> {
+        let snapshot = self.load_retention_snapshot()?;
         self.with_connection(|conn| {
             let by_pid: HashMap<i64, ShellEntry> = {
                 let shell_type = serde_json::to_string(&EventType::ShellCwd)
@@ -931,16 +2523,24 @@ impl Db {
                 let mut stmt = conn
                     .prepare(
                         "SELECT payload FROM events \
-                     WHERE event_type = ?1 \
+                     WHERE event_type = ?1 AND rowid > ?2 \
                      ORDER BY rowid ASC",
                     )
                     .map_err(|err| format!("Failed to prepare events replay query: {}", err))?;
 
+                let after_rowid = snapshot.as_ref().map_or(0, |snapshot| snapshot.event_rowid);
                 let rows = stmt
-                    .query_map(params![shell_type], |row| row.get::<_, String>(0))
+                    .query_map(params![shell_type, after_rowid], |row| {
+                        row.get::<_, String>(0)
+                    })
                     .map_err(|err| format!("Failed to read event payloads: {}", err))?;
 
-                let mut by_pid: HashMap<i64, ShellEntry> = HashMap::new();
+                let mut by_pid: HashMap<i64, ShellEntry> = snapshot
+                    .map(|snapshot| snapshot.shell_state.shells)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|(pid, entry)| pid.parse::<i64>().ok().map(|pid| (pid, entry)))
+                    .collect();
                 for row in rows {
                     let payload =
                         row.map_err(|err| format!("Failed to decode event payload: {}", err))?;
@@ -1111,98 +2711,103 @@ impl Db {
 
     fn init_schema(&self) -> Result<(), String> {
         self.with_connection(|conn| {
-            conn.execute_batch(
-                "BEGIN;
-                 CREATE TABLE IF NOT EXISTS events (
-                    id TEXT PRIMARY KEY,
-                    recorded_at TEXT NOT NULL,
-                    event_type TEXT NOT NULL,
-                    session_id TEXT,
-                    pid INTEGER,
-                    payload TEXT NOT NULL
-                 );
-                 CREATE TABLE IF NOT EXISTS shell_state (
-                    pid INTEGER PRIMARY KEY,
-                    cwd TEXT NOT NULL,
-                    tty TEXT NOT NULL,
-                    parent_app TEXT,
-                    tmux_session TEXT,
-                    tmux_client_tty TEXT,
-                    updated_at TEXT NOT NULL
-                 );
-                 CREATE TABLE IF NOT EXISTS process_liveness (
-                    pid INTEGER PRIMARY KEY,
-                    proc_started INTEGER,
-                    last_seen_at TEXT NOT NULL
-                 );
-                 CREATE TABLE IF NOT EXISTS sessions (
-                    session_id TEXT PRIMARY KEY,
-                    pid INTEGER NOT NULL DEFAULT 0,
-                    state TEXT NOT NULL,
-                    cwd TEXT NOT NULL,
-                    project_id TEXT,
-                    project_path TEXT,
-                    updated_at TEXT NOT NULL,
-                    state_changed_at TEXT NOT NULL,
-                    last_event TEXT,
-                    last_activity_at TEXT,
-                    tools_in_flight INTEGER NOT NULL DEFAULT 0,
-                    ready_reason TEXT
-                 );
-                 CREATE TABLE IF NOT EXISTS activity (
-                    session_id TEXT NOT NULL,
-                    project_path TEXT NOT NULL,
-                    file_path TEXT NOT NULL,
-                    tool_name TEXT,
-                    recorded_at TEXT NOT NULL
-                 );
-                 CREATE TABLE IF NOT EXISTS tombstones (
-                    session_id TEXT PRIMARY KEY,
-                    created_at TEXT NOT NULL,
-                    expires_at TEXT NOT NULL
-                 );
-                 CREATE TABLE IF NOT EXISTS hem_shadow_mismatches (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    observed_at TEXT NOT NULL,
-                    event_id TEXT,
-                    session_id TEXT,
-                    project_id TEXT,
-                    project_path TEXT,
-                    category TEXT NOT NULL,
-                    reducer_state TEXT,
-                    hem_state TEXT,
-                    confidence_delta REAL,
-                    detail_json TEXT
-                 );
-                 CREATE TABLE IF NOT EXISTS routing_rollout_state (
-                    id INTEGER PRIMARY KEY CHECK (id = 1),
-                    dual_run_comparisons INTEGER NOT NULL,
-                    legacy_vs_are_status_mismatch INTEGER NOT NULL,
-                    legacy_vs_are_target_mismatch INTEGER NOT NULL,
-                    first_comparison_at TEXT,
-                    last_comparison_at TEXT,
-                    last_snapshot_at TEXT
-                 );
-                 CREATE TABLE IF NOT EXISTS daemon_meta (
-                    key TEXT PRIMARY KEY,
-                    value TEXT NOT NULL
-                 );
-                 CREATE INDEX IF NOT EXISTS idx_hem_shadow_mismatches_observed_at
-                    ON hem_shadow_mismatches(observed_at);
-                 CREATE INDEX IF NOT EXISTS idx_hem_shadow_mismatches_category
-                    ON hem_shadow_mismatches(category);
-                 COMMIT;",
-            )
-            .map_err(|err| format!("Failed to initialize schema: {}", err))?;
-            ensure_sessions_columns(conn)?;
+            // Only takes effect on a new file; older databases switch on their
+            // first retention run.
+            conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")
+                .map_err(|err| format!("Failed to set auto_vacuum: {}", err))?;
+            migrations::migrate(conn, &self.path, migrations::MIGRATIONS)?;
             Ok(())
         })
     }
 
+    /// Runs `op` inside one `BEGIN IMMEDIATE` transaction: every `Db` call
+    /// it makes on this thread shares the transaction. Commits when `op`
+    /// succeeds and rolls everything back when it fails. Nested calls join
+    /// the outer batch under a savepoint, so a failing nested call undoes
+    /// only its own writes and the outer batch can carry on.
+    pub fn write_batch<T>(&self, op: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        if self.in_batch() {
+            return self.savepoint(op);
+        }
+        self.batch("BEGIN IMMEDIATE", op)
+    }
+
+    /// Runs `op` inside one read transaction, so every `Db` call it makes on
+    /// this thread sees the same snapshot of the database.
+    pub fn read_batch<T>(&self, op: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        self.batch("BEGIN DEFERRED", op)
+    }
+
+    fn in_batch(&self) -> bool {
+        BATCH_CONNECTION.with(|slot| {
+            slot.borrow()
+                .as_ref()
+                .is_some_and(|(path, _)| *path == self.path)
+        })
+    }
+
+    fn savepoint<T>(&self, op: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        self.with_connection(|conn| {
+            conn.execute_batch("SAVEPOINT write_batch")
+                .map_err(|err| format!("Failed to start savepoint: {}", err))
+        })?;
+        let result = op();
+        let end = match result {
+            Ok(_) => "RELEASE write_batch",
+            Err(_) => "ROLLBACK TO write_batch; RELEASE write_batch",
+        };
+        self.with_connection(|conn| {
+            conn.execute_batch(end)
+                .map_err(|err| format!("Failed to end savepoint: {}", err))
+        })?;
+        result
+    }
+
+    fn batch<T>(&self, begin: &str, op: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        if self.in_batch() {
+            return op();
+        }
+
+        let conn = self.open()?;
+        conn.execute_batch(begin)
+            .map_err(|err| format!("Failed to start batch: {}", err))?;
+        BATCH_CONNECTION.with(|slot| *slot.borrow_mut() = Some((self.path.clone(), conn)));
+        let result = op();
+        let (_, conn) = BATCH_CONNECTION
+            .with(|slot| slot.borrow_mut().take())
+            .ok_or_else(|| "Batch connection was lost".to_string())?;
+        match result {
+            Ok(value) => {
+                conn.execute_batch("COMMIT")
+                    .map_err(|err| format!("Failed to commit batch: {}", err))?;
+                Ok(value)
+            }
+            Err(err) => {
+                if let Err(rollback_err) = conn.execute_batch("ROLLBACK") {
+                    tracing::warn!(error = %rollback_err, "Failed to roll back batch");
+                }
+                Err(err)
+            }
+        }
+    }
+
     fn with_connection<T>(
         &self,
         op: impl FnOnce(&mut Connection) -> Result<T, String>,
     ) -> Result<T, String> {
+        let batch = BATCH_CONNECTION.with(|slot| {
+            let mut slot = slot.borrow_mut();
+            match slot.as_ref() {
+                Some((path, _)) if *path == self.path => slot.take(),
+                _ => None,
+            }
+        });
+        if let Some((path, mut conn)) = batch {
+            let result = op(&mut conn);
+            BATCH_CONNECTION.with(|slot| *slot.borrow_mut() = Some((path, conn)));
+            return result;
+        }
+
         let mut conn = self.open()?;
         op(&mut conn)
     }
@@ -1231,55 +2836,383 @@ impl Db {
     }
 }
 
-fn ensure_sessions_columns(conn: &Connection) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare("PRAGMA table_info(sessions)")
-        .map_err(|err| format!("Failed to read sessions schema: {}", err))?;
-    let rows = stmt
-        .query_map([], |row| row.get::<_, String>(1))
-        .map_err(|err| format!("Failed to read sessions schema rows: {}", err))?;
+fn insert_event_row(conn: &Connection, event: &EventEnvelope) -> Result<Option<i64>, String> {
+    let payload = serde_json::to_string(event)
+        .map_err(|err| format!("Failed to serialize event payload: {}", err))?;
+    let event_type = event_type_column(&event.event_type);
+
+    let rows_affected = conn
+        .execute(
+            "INSERT INTO events (id, recorded_at, event_type, session_id, pid, payload)\
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)\
+         ON CONFLICT(id) DO NOTHING",
+            params![
+                event.event_id,
+                event.recorded_at,
+                event_type,
+                event.session_id,
+                event.pid,
+                payload
+            ],
+        )
+        .map_err(|err| format!("Failed to insert event: {}", err))?;
 
-    let mut columns = Vec::new();
-    for row in rows {
-        columns.push(row.map_err(|err| format!("Failed to decode schema row: {}", err))?);
+    if rows_affected > 0 {
+        Ok(Some(conn.last_insert_rowid()))
+    } else {
+        Ok(None)
     }
+}
+
+fn event_type_column(event_type: &EventType) -> String {
+    serde_json::to_string(event_type)
+        .unwrap_or_else(|_| "unknown".to_string())
+        .trim_matches('"')
+        .to_string()
+}
+
+/// Columns `session_from_row` decodes.
+const SESSION_COLUMNS: &str = "session_id, COALESCE(pid, 0), state, cwd, \
+     COALESCE(project_id, project_path, cwd), COALESCE(project_path, cwd), \
+     updated_at, state_changed_at, last_event, last_activity_at, \
+     COALESCE(tools_in_flight, 0), ready_reason, pending_request";
+
+fn session_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SessionRecord> {
+    let state_raw: String = row.get(2)?;
+    let state = SessionState::from_str(&state_raw).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            state_raw.len(),
+            rusqlite::types::Type::Text,
+            Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unknown session state: {}", state_raw),
+            )),
+        )
+    })?;
+    Ok(SessionRecord {
+        session_id: row.get(0)?,
+        pid: row.get(1)?,
+        state,
+        cwd: row.get(3)?,
+        project_id: row.get(4)?,
+        project_path: row.get(5)?,
+        updated_at: row.get(6)?,
+        state_changed_at: row.get(7)?,
+        last_event: row.get(8)?,
+        last_activity_at: row.get(9)?,
+        tools_in_flight: row.get(10)?,
+        ready_reason: row.get(11)?,
+        pending_request: row
+            .get::<_, Option<String>>(12)?
+            .and_then(|raw| serde_json::from_str(&raw).ok()),
+    })
+}
+
+/// `?, ?, ...` for an `IN` list of `count` values.
+fn sql_placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
 
-    if !columns.iter().any(|name| name == "project_path") {
-        conn.execute("ALTER TABLE sessions ADD COLUMN project_path TEXT", [])
-            .map_err(|err| format!("Failed to add project_path column: {}", err))?;
+/// `"<clause> IN (?, ...)"` for `values`, or nothing when unfiltered.
+fn sql_in_filter(clause: &str, values: Option<&[String]>) -> String {
+    match values {
+        Some(values) => format!("{clause} IN ({})", sql_placeholders(values.len())),
+        None => String::new(),
     }
+}
+
+fn subagent_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SubagentRecord> {
+    let tools_raw: String = row.get(6)?;
+    let tools = serde_json::from_str(&tools_raw).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(err))
+    })?;
+    Ok(SubagentRecord {
+        session_id: row.get(0)?,
+        agent_id: row.get(1)?,
+        agent_type: row.get(2)?,
+        started_at: row.get(3)?,
+        stopped_at: row.get(4)?,
+        transcript_path: row.get(5)?,
+        tools,
+        tool_calls: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+fn config_change_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ConfigChangeRecord> {
+    Ok(ConfigChangeRecord {
+        event_id: row.get(0)?,
+        session_id: row.get(1)?,
+        project_path: row.get(2)?,
+        source: row.get(3)?,
+        file_path: row.get(4)?,
+        content_hash: row.get(5)?,
+        hud_hook_hash: row.get(6)?,
+        content_changed: row.get(7)?,
+        hud_hook_changed: row.get(8)?,
+        recorded_at: row.get(9)?,
+    })
+}
+
+fn team_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TeamRecord> {
+    Ok(TeamRecord {
+        team_name: row.get(0)?,
+        lead_session_id: row.get(1)?,
+        project_path: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+fn teammate_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TeammateRecord> {
+    let state: String = row.get(3)?;
+    Ok(TeammateRecord {
+        team_name: row.get(0)?,
+        name: row.get(1)?,
+        session_id: row.get(2)?,
+        state: SessionState::from_str(&state).unwrap_or(SessionState::Idle),
+        last_event: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+fn session_transition_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SessionTransition> {
+    Ok(SessionTransition {
+        session_id: row.get(0)?,
+        project_path: row.get(1)?,
+        from_state: parse_state_column(row.get(2)?),
+        to_state: parse_state_column(row.get(3)?),
+        event_id: row.get(4)?,
+        transitioned_at: row.get(5)?,
+    })
+}
+
+fn parse_state_column(value: Option<String>) -> Option<capacitor_daemon_protocol::SessionState> {
+    value
+        .as_deref()
+        .and_then(SessionState::from_str)
+        .map(Into::into)
+}
+
+/// Reads the retention snapshot row. Takes a bare connection so a damaged
+/// database can be salvaged without running migrations on it.
+pub fn read_retention_snapshot(conn: &Connection) -> Result<Option<RetentionSnapshot>, String> {
+    let row = conn
+        .query_row(
+            "SELECT event_rowid, sessions, shell_state, derived, created_at \
+             FROM retention_snapshot WHERE id = 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read retention snapshot: {}", err))?;
+    let Some((event_rowid, sessions, shell_state, derived, created_at)) = row else {
+        return Ok(None);
+    };
+    Ok(Some(RetentionSnapshot {
+        event_rowid,
+        sessions: serde_json::from_str(&sessions)
+            .map_err(|err| format!("Failed to parse session snapshot: {}", err))?,
+        shell_state: serde_json::from_str(&shell_state)
+            .map_err(|err| format!("Failed to parse shell snapshot: {}", err))?,
+        derived: serde_json::from_str(&derived)
+            .map_err(|err| format!("Failed to parse derived snapshot: {}", err))?,
+        created_at,
+    }))
+}
 
-    if !columns.iter().any(|name| name == "project_id") {
-        conn.execute("ALTER TABLE sessions ADD COLUMN project_id TEXT", [])
-            .map_err(|err| format!("Failed to add project_id column: {}", err))?;
+/// Every row of `table` in rowid order, column name to value.
+fn read_table_rows(conn: &Connection, table: &str) -> Result<Vec<Map<String, Value>>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT * FROM {} ORDER BY rowid", table))
+        .map_err(|err| format!("Failed to prepare {} snapshot query: {}", table, err))?;
+    let columns: Vec<String> = stmt
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let mut rows = stmt
+        .query([])
+        .map_err(|err| format!("Failed to query {} for snapshot: {}", table, err))?;
+    let mut snapshot = Vec::new();
+    while let Some(row) = rows
+        .next()
+        .map_err(|err| format!("Failed to read {} row: {}", table, err))?
+    {
+        let mut values = Map::new();
+        for (index, column) in columns.iter().enumerate() {
+            let value = match row
+                .get_ref(index)
+                .map_err(|err| format!("Failed to read {}.{}: {}", table, column, err))?
+            {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(value) => Value::from(value),
+                ValueRef::Real(value) => Value::from(value),
+                ValueRef::Text(value) => Value::from(String::from_utf8_lossy(value)),
+                ValueRef::Blob(_) => {
+                    return Err(format!("Unexpected blob in {}.{}", table, column));
+                }
+            };
+            values.insert(column.clone(), value);
+        }
+        snapshot.push(values);
     }
+    Ok(snapshot)
+}
+
+fn insert_table_row(
+    conn: &Connection,
+    table: &str,
+    row: &Map<String, Value>,
+) -> Result<(), String> {
+    let columns: Vec<&str> = row.keys().map(String::as_str).collect();
+    let placeholders: Vec<String> = (1..=columns.len())
+        .map(|index| format!("?{}", index))
+        .collect();
+    let values = row.values().map(|value| match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(value) => SqlValue::Integer(i64::from(*value)),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => SqlValue::Integer(value),
+            None => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(value) => SqlValue::Text(value.clone()),
+        other => SqlValue::Text(other.to_string()),
+    });
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            columns.join(", "),
+            placeholders.join(", ")
+        ),
+        rusqlite::params_from_iter(values),
+    )
+    .map_err(|err| format!("Failed to restore {} row: {}", table, err))?;
+    Ok(())
+}
 
-    if !columns.iter().any(|name| name == "pid") {
+/// Stamps `changed` and `removed` keys of `kind` with a new global revision.
+/// Removals older than `SNAPSHOT_REMOVAL_RETENTION_HOURS` are forgotten and
+/// the floor raised past them, since a delta across them is no longer exact.
+fn record_revisions(
+    conn: &Connection,
+    kind: &str,
+    changed: &[&str],
+    removed: &[&str],
+) -> Result<u64, String> {
+    let current = read_meta_u64(conn, STATE_REVISION_KEY)?;
+    if changed.is_empty() && removed.is_empty() {
+        return Ok(current);
+    }
+
+    let revision = current + 1;
+    let revision_value = u64_to_i64(revision, "state_revision")?;
+    let now = Utc::now().to_rfc3339();
+    for key in changed {
         conn.execute(
-            "ALTER TABLE sessions ADD COLUMN pid INTEGER NOT NULL DEFAULT 0",
-            [],
+            "INSERT INTO snapshot_revisions (kind, entry_key, revision, removed, updated_at) \
+             VALUES (?1, ?2, ?3, 0, ?4) \
+             ON CONFLICT(kind, entry_key) DO UPDATE SET \
+                revision = excluded.revision, \
+                removed = 0, \
+                updated_at = excluded.updated_at",
+            params![kind, key, revision_value, now],
+        )
+        .map_err(|err| format!("Failed to record entry revision: {}", err))?;
+    }
+    for key in removed {
+        conn.execute(
+            "INSERT INTO snapshot_revisions (kind, entry_key, revision, removed, updated_at) \
+             VALUES (?1, ?2, ?3, 1, ?4) \
+             ON CONFLICT(kind, entry_key) DO UPDATE SET \
+                revision = excluded.revision, \
+                removed = 1, \
+                updated_at = excluded.updated_at",
+            params![kind, key, revision_value, now],
         )
-        .map_err(|err| format!("Failed to add pid column: {}", err))?;
+        .map_err(|err| format!("Failed to record entry removal: {}", err))?;
     }
+    write_meta_u64(conn, STATE_REVISION_KEY, revision)?;
 
-    if !columns.iter().any(|name| name == "last_activity_at") {
-        conn.execute("ALTER TABLE sessions ADD COLUMN last_activity_at TEXT", [])
-            .map_err(|err| format!("Failed to add last_activity_at column: {}", err))?;
+    if !removed.is_empty() {
+        let cutoff = (Utc::now() - Duration::hours(SNAPSHOT_REMOVAL_RETENTION_HOURS)).to_rfc3339();
+        prune_revision_removals(conn, &cutoff)?;
     }
+    Ok(revision)
+}
 
-    if !columns.iter().any(|name| name == "tools_in_flight") {
-        conn.execute(
-            "ALTER TABLE sessions ADD COLUMN tools_in_flight INTEGER NOT NULL DEFAULT 0",
-            [],
+/// Forgets removals recorded before `cutoff` and raises the floor past them.
+fn prune_revision_removals(conn: &Connection, cutoff: &str) -> Result<u64, String> {
+    let pruned_floor: Option<i64> = conn
+        .query_row(
+            "SELECT MAX(revision) FROM snapshot_revisions \
+             WHERE removed = 1 AND julianday(updated_at) < julianday(?1)",
+            params![cutoff],
+            |row| row.get(0),
+        )
+        .map_err(|err| format!("Failed to query prunable removals: {}", err))?;
+    match pruned_floor {
+        Some(pruned_floor) => forget_removals_through(conn, pruned_floor),
+        None => Ok(0),
+    }
+}
+
+/// Deletes removals at or below `revision` and raises the floor to it, since
+/// a delta across them is no longer exact.
+fn forget_removals_through(conn: &Connection, revision: i64) -> Result<u64, String> {
+    let pruned = conn
+        .execute(
+            "DELETE FROM snapshot_revisions WHERE removed = 1 AND revision <= ?1",
+            params![revision],
         )
-        .map_err(|err| format!("Failed to add tools_in_flight column: {}", err))?;
+        .map_err(|err| format!("Failed to prune removal history: {}", err))?;
+    let floor = i64_to_u64(revision, "revision")?;
+    if floor > read_meta_u64(conn, STATE_REVISION_FLOOR_KEY)? {
+        write_meta_u64(conn, STATE_REVISION_FLOOR_KEY, floor)?;
     }
+    Ok(pruned as u64)
+}
+
+fn read_pragma_i64(conn: &Connection, pragma: &str) -> Result<i64, String> {
+    conn.pragma_query_value(None, pragma, |row| row.get(0))
+        .map_err(|err| format!("Failed to read PRAGMA {}: {}", pragma, err))
+}
 
-    if !columns.iter().any(|name| name == "ready_reason") {
-        conn.execute("ALTER TABLE sessions ADD COLUMN ready_reason TEXT", [])
-            .map_err(|err| format!("Failed to add ready_reason column: {}", err))?;
+fn read_meta_u64(conn: &Connection, key: &str) -> Result<u64, String> {
+    let raw: Option<String> = conn
+        .query_row(
+            "SELECT value FROM daemon_meta WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to query daemon_meta '{}': {}", key, err))?;
+    match raw {
+        Some(value) => value.parse::<u64>().map_err(|err| {
+            format!(
+                "Failed to parse daemon_meta '{}' value '{}': {}",
+                key, value, err
+            )
+        }),
+        None => Ok(0),
     }
+}
 
+fn write_meta_u64(conn: &Connection, key: &str, value: u64) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO daemon_meta (key, value) VALUES (?1, ?2) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value.to_string()],
+    )
+    .map_err(|err| format!("Failed to persist daemon_meta '{}': {}", key, err))?;
     Ok(())
 }
 
@@ -1528,6 +3461,120 @@ mod tests {
         assert!(db.get_process_liveness(22222).unwrap().is_some());
     }
 
+    #[test]
+    fn entry_revisions_track_changes_and_removals() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+
+        assert_eq!(db.state_revision().expect("initial revision"), 0);
+        let first = db
+            .record_entry_revisions("sessions", &["a", "b"], &[])
+            .expect("first record");
+        assert_eq!(first, 1);
+        assert_eq!(
+            db.record_entry_revisions("sessions", &[], &[])
+                .expect("empty record"),
+            1
+        );
+
+        let unchanged = db
+            .entry_revisions_since("sessions", 1, None)
+            .expect("unchanged delta");
+        assert_eq!(unchanged.revision, 1);
+        assert!(unchanged.changed_keys.is_empty());
+        assert!(unchanged.removed_keys.is_empty());
+
+        db.record_entry_revisions("sessions", &["a"], &["b"])
+            .expect("change a, remove b");
+        let changed = db
+            .entry_revisions_since("sessions", 1, None)
+            .expect("changed delta");
+        assert_eq!(changed.revision, 2);
+        assert_eq!(changed.changed_keys, HashSet::from(["a".to_string()]));
+        assert_eq!(changed.removed_keys, vec!["b".to_string()]);
+        assert_eq!(
+            db.live_entry_keys("sessions").expect("live keys"),
+            HashSet::from(["a".to_string()])
+        );
+
+        // Kinds share the global revision counter but not their entries.
+        assert_eq!(
+            db.record_entry_revisions("project_states", &["a"], &[])
+                .expect("project record"),
+            3
+        );
+        let projects = db
+            .entry_revisions_since("project_states", 2, None)
+            .expect("project delta");
+        assert_eq!(projects.changed_keys, HashSet::from(["a".to_string()]));
+        let bounded = db
+            .entry_revisions_since("project_states", 0, Some(2))
+            .expect("bounded delta");
+        assert!(bounded.changed_keys.is_empty());
+    }
+
+    #[test]
+    fn session_writes_stamp_revisions() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let record = SessionRecord {
+            session_id: "session-1".to_string(),
+            pid: 0,
+            state: SessionState::Working,
+            cwd: "/repo".to_string(),
+            project_id: "/repo".to_string(),
+            project_path: "/repo".to_string(),
+            updated_at: Utc::now().to_rfc3339(),
+            state_changed_at: Utc::now().to_rfc3339(),
+            last_event: None,
+            last_activity_at: None,
+            tools_in_flight: 0,
+            ready_reason: None,
+            pending_request: None,
+        };
+
+        db.upsert_session(&record).expect("upsert");
+        assert_eq!(db.state_revision().expect("revision"), 1);
+        db.delete_session("missing").expect("delete missing");
+        assert_eq!(db.state_revision().expect("revision"), 1);
+        db.clear_sessions().expect("clear");
+        let delta = db
+            .entry_revisions_since("sessions", 1, None)
+            .expect("delta");
+        assert_eq!(delta.revision, 2);
+        assert_eq!(delta.removed_keys, vec!["session-1".to_string()]);
+    }
+
+    #[test]
+    fn entry_revisions_prune_old_removals_and_raise_floor() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+
+        db.record_entry_revisions("sessions", &["a", "b"], &[])
+            .expect("seed");
+        db.record_entry_revisions("sessions", &[], &["b"])
+            .expect("remove b");
+        let stale =
+            (Utc::now() - Duration::hours(SNAPSHOT_REMOVAL_RETENTION_HOURS + 1)).to_rfc3339();
+        db.with_connection(|conn| {
+            conn.execute(
+                "UPDATE snapshot_revisions SET updated_at = ?1 WHERE removed = 1",
+                params![stale],
+            )
+            .map_err(|err| format!("Failed to age removal: {}", err))?;
+            Ok(())
+        })
+        .expect("age removal");
+
+        db.record_entry_revisions("sessions", &[], &["a"])
+            .expect("remove a");
+        let delta = db
+            .entry_revisions_since("sessions", 1, None)
+            .expect("delta after pruning");
+        assert_eq!(delta.floor, 2);
+        assert_eq!(delta.removed_keys, vec!["a".to_string()]);
+    }
+
     #[test]
     fn schema_includes_session_activity_and_tombstone_tables() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -1555,6 +3602,7 @@ mod tests {
         assert!(tables.contains(&"tombstones".to_string()));
         assert!(tables.contains(&"hem_shadow_mismatches".to_string()));
         assert!(tables.contains(&"routing_rollout_state".to_string()));
+        assert!(tables.contains(&"session_transitions".to_string()));
     }
 
     #[test]
@@ -1717,6 +3765,7 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         };
 
         db.upsert_session(&record).expect("upsert session");
@@ -1828,6 +3877,68 @@ mod tests {
         assert!(remaining.shells.contains_key("200"));
     }
 
+    #[test]
+    fn inserts_event_batches_in_one_transaction() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+
+        let first = session_event("evt-1", "2026-01-31T00:00:00Z", EventType::SessionStart);
+        let second = session_event("evt-2", "2026-01-31T00:00:01Z", EventType::PreToolUse);
+        assert!(db.insert_event(&first).expect("insert first"));
+
+        let rowids = db
+            .insert_events_with_rowids(&[first.clone(), second.clone(), second])
+            .expect("insert batch");
+        assert_eq!(rowids[0], None);
+        assert!(rowids[1].is_some());
+        assert_eq!(rowids[2], None);
+        assert_eq!(db.list_events(None).expect("list events").len(), 2);
+    }
+
+    #[test]
+    fn pages_through_event_log_with_filters() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+
+        let mut elsewhere = session_event("evt-3", "2026-01-31T00:00:02Z", EventType::PreToolUse);
+        elsewhere.cwd = Some("/repo-other".to_string());
+        for event in [
+            session_event("evt-1", "2026-01-31T00:00:00Z", EventType::SessionStart),
+            session_event("evt-2", "2026-01-31T00:00:01Z", EventType::PreToolUse),
+            elsewhere,
+            session_event("evt-4", "2026-01-31T00:00:03Z", EventType::PreToolUse),
+            session_event("evt-5", "2026-01-31T00:00:04Z", EventType::PreToolUse),
+        ] {
+            db.insert_event(&event).expect("insert event");
+        }
+
+        let mut query = EventsQuery {
+            event_types: vec![EventType::PreToolUse],
+            project_path: Some("/repo".to_string()),
+            limit: Some(2),
+            ..EventsQuery::default()
+        };
+        let (page, cursor) = db.list_events_page(&query).expect("first page");
+        let ids: Vec<_> = page.iter().map(|event| event.event_id.as_str()).collect();
+        assert_eq!(ids, vec!["evt-2", "evt-4"]);
+        assert!(cursor.is_some());
+
+        query.cursor = cursor;
+        let (page, cursor) = db.list_events_page(&query).expect("second page");
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].event_id, "evt-5");
+        assert_eq!(cursor, None);
+
+        let window = EventsQuery {
+            since: Some("2026-01-31T00:00:01Z".to_string()),
+            until: Some("2026-01-31T00:00:03Z".to_string()),
+            ..EventsQuery::default()
+        };
+        let (page, _) = db.list_events_page(&window).expect("time window");
+        let ids: Vec<_> = page.iter().map(|event| event.event_id.as_str()).collect();
+        assert_eq!(ids, vec!["evt-2", "evt-3"]);
+    }
+
     #[test]
     fn list_session_affecting_events_orders_equal_instants_by_id() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -0,0 +1,183 @@
+//! NDJSON export/import of the raw event log.
+//!
+//! An export is one `EventEnvelope` per line in arrival order. Importing it into
+//! an empty database and rebuilding through `replay::rebuild_from_events`
+//! reproduces the daemon state the trace produced, which makes reducer bugs
+//! reproducible from an attached file.
+
+use crate::db::Db;
+use crate::replay;
+use crate::session_policy::SessionPolicy;
+use capacitor_daemon_protocol::{parse_event, EventEnvelope, EventsQuery, EVENTS_MAX_LIMIT};
+use std::io::{BufRead, Write};
+
+/// Writes every event matching `filter` (cursor and limit are ignored) and
+/// returns how many were written.
+pub fn export_events(db: &Db, filter: &EventsQuery, out: &mut impl Write) -> Result<usize, String> {
+    let mut query = EventsQuery {
+        cursor: None,
+        limit: Some(EVENTS_MAX_LIMIT),
+        ..filter.clone()
+    };
+    let mut written = 0;
+    loop {
+        let (events, next_cursor) = db.list_events_page(&query)?;
+        for event in &events {
+            let line = serde_json::to_string(event)
+                .map_err(|err| format!("Failed to serialize event: {}", err))?;
+            writeln!(out, "{}", line).map_err(|err| format!("Failed to write event: {}", err))?;
+        }
+        written += events.len();
+        match next_cursor {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+    out.flush()
+        .map_err(|err| format!("Failed to write event: {}", err))?;
+    Ok(written)
+}
+
+/// Loads an NDJSON trace into an empty database and rebuilds sessions and
+/// activity from it. Every line is validated before anything is written.
+/// Returns how many events were stored; repeated event ids are stored once.
+pub fn import_events(
+    db: &Db,
+    input: impl BufRead,
+    policy: &SessionPolicy,
+) -> Result<usize, String> {
+    if db.max_event_rowid()?.is_some() {
+        return Err("Refusing to import into a database that already has events".to_string());
+    }
+
+    let events = read_trace(input)?;
+    let stored = db
+        .insert_events_with_rowids(&events)?
+        .into_iter()
+        .filter(Option::is_some)
+        .count();
+    replay::rebuild_from_events(db, policy)?;
+    if let Some(rowid) = db.max_event_rowid()? {
+        db.set_last_applied_event_rowid(rowid)?;
+    }
+    Ok(stored)
+}
+
+/// Parses and validates an NDJSON trace, skipping blank lines. Fails on the
+/// first invalid line.
+pub fn read_trace(input: impl BufRead) -> Result<Vec<EventEnvelope>, String> {
+    let mut events = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.map_err(|err| format!("Failed to read line {}: {}", index + 1, err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line)
+            .map_err(|err| err.to_string())
+            .and_then(|value| parse_event(value).map_err(|err| err.message))
+            .map_err(|err| format!("Invalid event on line {}: {}", index + 1, err))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use capacitor_daemon_protocol::EventType;
+
+    fn event(event_id: &str, event_type: EventType, recorded_at: &str, cwd: &str) -> EventEnvelope {
+        EventEnvelope {
+            event_id: event_id.to_string(),
+            recorded_at: recorded_at.to_string(),
+            event_type,
+            session_id: Some(format!("session-{}", cwd.trim_start_matches('/'))),
+            pid: Some(1234),
+            cwd: Some(cwd.to_string()),
+            tool: None,
+            file_path: None,
+            parent_app: None,
+            tty: None,
+            tmux_session: None,
+            tmux_client_tty: None,
+            notification_type: None,
+            stop_hook_active: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn exported_trace_rebuilds_sessions_in_a_fresh_db() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let source = Db::new(temp_dir.path().join("source.db")).expect("source db");
+        for event in [
+            event(
+                "evt-1",
+                EventType::SessionStart,
+                "2026-01-31T00:00:00Z",
+                "/repo",
+            ),
+            event(
+                "evt-2",
+                EventType::SessionStart,
+                "2026-01-31T00:00:01Z",
+                "/other",
+            ),
+            event(
+                "evt-3",
+                EventType::UserPromptSubmit,
+                "2026-01-31T00:00:02Z",
+                "/repo",
+            ),
+        ] {
+            source.insert_event(&event).expect("insert event");
+        }
+
+        let mut trace = Vec::new();
+        let filter = EventsQuery {
+            project_path: Some("/repo".to_string()),
+            ..EventsQuery::default()
+        };
+        assert_eq!(
+            export_events(&source, &filter, &mut trace).expect("export"),
+            2
+        );
+        assert_eq!(String::from_utf8_lossy(&trace).lines().count(), 2);
+
+        let target = Db::new(temp_dir.path().join("target.db")).expect("target db");
+        assert_eq!(
+            import_events(&target, trace.as_slice(), &SessionPolicy::default()).expect("import"),
+            2
+        );
+        let sessions = target.list_sessions().expect("sessions");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "session-repo");
+        assert_eq!(
+            target.last_applied_event_rowid().expect("cursor"),
+            target.max_event_rowid().expect("max rowid")
+        );
+
+        let error = import_events(&target, trace.as_slice(), &SessionPolicy::default())
+            .expect_err("non-empty db");
+        assert!(error.contains("already has events"), "{error}");
+    }
+
+    #[test]
+    fn import_rejects_invalid_lines_before_writing() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let valid = serde_json::to_string(&event(
+            "evt-1",
+            EventType::SessionStart,
+            "2026-01-31T00:00:00Z",
+            "/repo",
+        ))
+        .unwrap();
+        let trace = format!("{valid}\n\n{{\"event_id\": \"evt-2\"}}\n");
+
+        let error = import_events(&db, trace.as_bytes(), &SessionPolicy::default())
+            .expect_err("invalid line");
+        assert!(error.starts_with("Invalid event on line 3"), "{error}");
+        assert_eq!(db.max_event_rowid().expect("max rowid"), None);
+    }
+}
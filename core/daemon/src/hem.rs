@@ -1,12 +1,17 @@
-use capacitor_daemon_protocol::{EventEnvelope, EventType};
+use capacitor_daemon_protocol::{
+    EventEnvelope, EventType, HemCapabilityHealth, HemCapabilityWarning,
+};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::{Map, Value};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+use crate::metrics::MetricsConfig;
 use crate::reducer::{SessionRecord, SessionState};
+use crate::retention::RetentionConfig;
+use crate::session_policy::SessionPolicy;
 
 const DEFAULT_HEM_CONFIG_RELATIVE_PATH: &str = ".capacitor/daemon/hem-v2.toml";
 
@@ -116,6 +121,12 @@ pub struct HemRuntimeConfig {
     pub weights: HemWeightsConfig,
     #[serde(default)]
     pub routing: RoutingRuntimeConfig,
+    #[serde(default)]
+    pub session_policy: SessionPolicy,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
 }
 
 fn default_routing_tmux_signal_fresh_ms() -> u64 {
@@ -195,15 +206,7 @@ impl Default for RoutingRuntimeConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
-pub struct HemCapabilityWarning {
-    pub code: String,
-    pub capability: String,
-    pub declared: String,
-    pub observed: String,
-}
-
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct HemCapabilityStatus {
     pub strategy: String,
     pub handshake_seen: bool,
@@ -211,9 +214,7 @@ pub struct HemCapabilityStatus {
     pub unknown_count: u64,
     pub misdeclared_count: u64,
     pub warning_count: u64,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_warning_at: Option<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub warnings: Vec<HemCapabilityWarning>,
 }
 
@@ -230,6 +231,19 @@ impl HemCapabilityStatus {
             warnings: Vec::new(),
         }
     }
+
+    pub fn health(&self) -> HemCapabilityHealth {
+        HemCapabilityHealth {
+            strategy: self.strategy.clone(),
+            handshake_seen: self.handshake_seen,
+            confidence_penalty_factor: self.confidence_penalty_factor,
+            unknown_count: self.unknown_count,
+            misdeclared_count: self.misdeclared_count,
+            warning_count: self.warning_count,
+            last_warning_at: self.last_warning_at.clone(),
+            warnings: self.warnings.clone(),
+        }
+    }
 }
 
 impl Default for HemCapabilityStatus {
@@ -246,17 +260,6 @@ pub struct HemCapabilityAssessment {
     pub warnings_changed: bool,
 }
 
-impl HemCapabilityAssessment {
-    pub fn from_config(config: &HemRuntimeConfig) -> Self {
-        Self {
-            confidence_penalty_factor: 1.0,
-            notification_matcher_support: config.capabilities.notification_matcher_support,
-            status: HemCapabilityStatus::from_strategy(&config.capability_detection.strategy),
-            warnings_changed: false,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Copy)]
 pub struct HemEffectiveCapabilities {
     pub confidence_penalty_factor: f64,
@@ -681,13 +684,20 @@ pub fn load_runtime_config(path: Option<PathBuf>) -> Result<HemRuntimeConfig, St
             err
         )
     })?;
-    toml::from_str::<HemRuntimeConfig>(&content).map_err(|err| {
+    let config = toml::from_str::<HemRuntimeConfig>(&content).map_err(|err| {
         format!(
             "Failed to parse HEM config {}: {}",
             config_path.display(),
             err
         )
-    })
+    })?;
+    config
+        .session_policy
+        .validate()
+        .and_then(|()| config.retention.validate())
+        .and_then(|()| config.metrics.validate())
+        .map_err(|err| format!("Invalid HEM config {}: {}", config_path.display(), err))?;
+    Ok(config)
 }
 
 impl HemRuntimeConfig {
@@ -1435,6 +1445,7 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         }
     }
 
@@ -1566,6 +1577,66 @@ mode = "primary"
         assert!(config.routing.feature_flags.emit_diagnostics);
     }
 
+    #[test]
+    fn load_runtime_config_parses_session_policy() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("hem.toml");
+        fs_err::write(
+            &path,
+            r#"
+[session_policy]
+ttl_active_secs = 7200
+ttl_ready_secs = 14400
+inactivity_fallback_events = ["task_completed", "subagent_stop"]
+stale_event_grace_secs = 15
+        "#,
+        )
+        .expect("write config");
+
+        let config = load_runtime_config(Some(path)).expect("load config");
+        let policy = &config.session_policy;
+        assert_eq!(policy.ttl_active_secs, 7_200);
+        assert_eq!(policy.ttl_ready_secs, 14_400);
+        assert_eq!(policy.ttl_idle_secs, 600);
+        assert!(policy.inactivity_fallback);
+        assert_eq!(policy.inactivity_fallback_secs, 60);
+        assert_eq!(
+            policy.inactivity_fallback_events,
+            vec!["task_completed".to_string(), "subagent_stop".to_string()]
+        );
+        assert_eq!(policy.stop_gate_grace_secs, 20);
+        assert_eq!(policy.stale_event_grace_secs, 15);
+        assert_eq!(policy.ttl_secs(&SessionState::Waiting), 7_200);
+    }
+
+    #[test]
+    fn load_runtime_config_rejects_invalid_session_policy() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("hem.toml");
+        for (section, expected) in [
+            (
+                "ttl_idle_secs = 0",
+                "session_policy.ttl_idle_secs must be between",
+            ),
+            (
+                "inactivity_fallback_secs = 600",
+                "post_tool_use_stale_secs (300) must not be shorter",
+            ),
+            (
+                "stop_gate_grace_secs = 1800",
+                "must be shorter than ttl_ready_secs",
+            ),
+            (
+                "inactivity_fallback_events = [\"task_done\"]",
+                "unknown event type \"task_done\"",
+            ),
+        ] {
+            fs_err::write(&path, format!("[session_policy]\n{}\n", section)).expect("write config");
+            let error = load_runtime_config(Some(path.clone())).expect_err(section);
+            assert!(error.contains(expected), "{}: {}", section, error);
+        }
+    }
+
     #[test]
     fn load_runtime_config_parses_routing_bindings_and_flags() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
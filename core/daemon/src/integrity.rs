@@ -0,0 +1,366 @@
+//! Startup integrity check for the daemon database.
+//!
+//! A damaged `state.db` used to crash-loop the daemon until someone deleted it
+//! by hand. Before opening it we run `PRAGMA quick_check`; on failure the file
+//! (and its WAL) is moved aside as `state.db.corrupt-<timestamp>`, whatever
+//! events and retention snapshot can still be read are copied into a fresh
+//! database under their original rowids, and derived state is rebuilt through
+//! the normal replay path.
+
+use capacitor_daemon_protocol::{EventEnvelope, IntegrityHealth};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use std::path::{Path, PathBuf};
+
+use crate::db::{read_retention_snapshot, Db, RetentionSnapshot};
+use crate::replay;
+use crate::session_policy::SessionPolicy;
+
+/// Problems kept in the report; `quick_check` can list thousands.
+const MAX_REPORTED_PROBLEMS: usize = 10;
+
+fn new_report(status: &str, now: DateTime<Utc>) -> IntegrityHealth {
+    IntegrityHealth {
+        status: status.to_string(),
+        checked_at: now.to_rfc3339(),
+        problems: Vec::new(),
+        quarantined_path: None,
+        events_salvaged: 0,
+        events_unreadable: 0,
+        snapshot_salvaged: false,
+    }
+}
+
+/// Opens the database at `path`, quarantining and rebuilding it first when it
+/// fails the integrity check.
+pub fn open_checked(
+    path: PathBuf,
+    policy: &SessionPolicy,
+    now: DateTime<Utc>,
+) -> Result<(Db, IntegrityHealth), String> {
+    if !path.exists() {
+        return Ok((Db::new(path)?, new_report("created", now)));
+    }
+
+    let problems = quick_check(&path)?;
+    if problems.is_empty() {
+        return Ok((Db::new(path)?, new_report("ok", now)));
+    }
+
+    tracing::error!(
+        path = %path.display(),
+        problems = ?problems,
+        "Daemon database failed integrity check; rebuilding it"
+    );
+    let mut report = new_report("repaired", now);
+    report.problems = problems;
+
+    let (events, unreadable) = salvage_events(&path);
+    let snapshot = salvage_snapshot(&path);
+    let quarantined = quarantine(&path, now)?;
+    report.quarantined_path = Some(quarantined.to_string_lossy().to_string());
+
+    let db = Db::new(path)?;
+    db.insert_events_at_rowids(&events)?;
+    if let Some(snapshot) = snapshot.as_ref() {
+        db.store_retention_snapshot(snapshot)?;
+    }
+    replay::rebuild_from_events(&db, policy)?;
+    if let Some(rowid) = db.max_event_rowid()? {
+        db.set_last_applied_event_rowid(rowid)?;
+    }
+
+    report.events_salvaged = events.len() as u64;
+    report.events_unreadable = unreadable;
+    report.snapshot_salvaged = snapshot.is_some();
+    tracing::warn!(
+        quarantined = %quarantined.display(),
+        events_salvaged = report.events_salvaged,
+        events_unreadable = report.events_unreadable,
+        snapshot_salvaged = report.snapshot_salvaged,
+        "Rebuilt daemon database from salvaged events"
+    );
+    Ok((db, report))
+}
+
+/// Problems reported by `PRAGMA quick_check`; empty when the file is sound.
+/// A file SQLite reports as corrupt or not a database counts as one problem.
+/// Any other failure (locked, unreadable, out of file descriptors, I/O) says
+/// nothing about the file's contents and is returned as `Err`, so startup
+/// fails without touching it.
+fn quick_check(path: &Path) -> Result<Vec<String>, String> {
+    let result =
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE).and_then(|conn| {
+            let mut stmt = conn.prepare("PRAGMA quick_check")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<Vec<String>>>()
+        });
+    match result {
+        Ok(rows) => Ok(rows
+            .into_iter()
+            .filter(|row| row != "ok")
+            .take(MAX_REPORTED_PROBLEMS)
+            .collect()),
+        Err(err) if is_damage(&err) => Ok(vec![err.to_string()]),
+        Err(err) => Err(format!(
+            "Failed to check daemon database {}: {}",
+            path.display(),
+            err
+        )),
+    }
+}
+
+fn is_damage(err: &rusqlite::Error) -> bool {
+    matches!(
+        err.sqlite_error_code(),
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+    )
+}
+
+/// Reads events in rowid order until the table stops returning rows. Returns
+/// the parsed events and how many rows could not be parsed.
+fn salvage_events(path: &Path) -> (Vec<(i64, EventEnvelope)>, u64) {
+    let mut events = Vec::new();
+    let mut unreadable = 0;
+    let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return (events, unreadable);
+    };
+    let Ok(mut stmt) = conn.prepare("SELECT rowid, payload FROM events ORDER BY rowid ASC") else {
+        return (events, unreadable);
+    };
+    let Ok(mut rows) = stmt.query([]) else {
+        return (events, unreadable);
+    };
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::warn!(error = %err, "Stopped salvaging events at a damaged page");
+                break;
+            }
+        };
+        let parsed = row
+            .get::<_, i64>(0)
+            .ok()
+            .zip(row.get::<_, String>(1).ok())
+            .and_then(|(rowid, payload)| {
+                serde_json::from_str::<EventEnvelope>(&payload)
+                    .ok()
+                    .map(|event| (rowid, event))
+            });
+        match parsed {
+            Some(entry) => events.push(entry),
+            None => unreadable += 1,
+        }
+    }
+    (events, unreadable)
+}
+
+fn salvage_snapshot(path: &Path) -> Option<RetentionSnapshot> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    read_retention_snapshot(&conn).ok().flatten()
+}
+
+/// Moves the database and its WAL/SHM files aside and returns the new path of
+/// the database file.
+fn quarantine(path: &Path, now: DateTime<Utc>) -> Result<PathBuf, String> {
+    let suffix = format!(".corrupt-{}", now.format("%Y%m%dT%H%M%SZ"));
+    let target = with_suffix(path, &suffix);
+    std::fs::rename(path, &target).map_err(|err| {
+        format!(
+            "Failed to quarantine damaged database {}: {}",
+            path.display(),
+            err
+        )
+    })?;
+    for sidecar in ["-wal", "-shm"] {
+        let source = with_suffix(path, sidecar);
+        if source.exists() {
+            let _ = std::fs::rename(&source, with_suffix(&target, sidecar));
+        }
+    }
+    Ok(target)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::ActivityEntry;
+    use crate::test_support::event;
+    use capacitor_daemon_protocol::EventType;
+    use std::io::{Seek, SeekFrom, Write};
+
+    /// Overwrites the root page of `table` with garbage.
+    fn damage_table(path: &Path, table: &str) {
+        let (root_page, page_size): (i64, i64) = {
+            let conn = Connection::open(path).expect("open");
+            let root_page = conn
+                .query_row(
+                    "SELECT rootpage FROM sqlite_master WHERE name = ?1",
+                    [table],
+                    |row| row.get(0),
+                )
+                .expect("root page");
+            let page_size = conn
+                .pragma_query_value(None, "page_size", |row| row.get(0))
+                .expect("page size");
+            (root_page, page_size)
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .expect("open file");
+        file.seek(SeekFrom::Start(((root_page - 1) * page_size) as u64))
+            .expect("seek");
+        file.write_all(&vec![0xA5; page_size as usize])
+            .expect("write garbage");
+    }
+
+    fn quarantined_files(dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .expect("read dir")
+            .map(|entry| {
+                entry
+                    .expect("entry")
+                    .file_name()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .filter(|name| name.contains(".corrupt-"))
+            .collect()
+    }
+
+    #[test]
+    fn sound_database_opens_unchanged() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let now = Utc::now();
+
+        let (_, report) = open_checked(path.clone(), &SessionPolicy::default(), now).expect("new");
+        assert_eq!(report.status, "created");
+        let (_, report) = open_checked(path, &SessionPolicy::default(), now).expect("reopen");
+        assert_eq!(report.status, "ok");
+        assert!(report.problems.is_empty());
+    }
+
+    #[test]
+    fn damaged_database_is_quarantined_and_rebuilt_from_events() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let db = Db::new(path.clone()).expect("db");
+        for (event_id, event_type, recorded_at) in [
+            ("evt-1", EventType::SessionStart, "2026-01-31T00:00:00Z"),
+            ("evt-2", EventType::UserPromptSubmit, "2026-01-31T00:00:05Z"),
+        ] {
+            db.insert_event(&event(event_id, event_type, recorded_at))
+                .expect("insert");
+        }
+        replay::rebuild_from_events(&db, &SessionPolicy::default()).expect("rebuild");
+        db.insert_activity(&ActivityEntry {
+            session_id: "session-1".to_string(),
+            project_path: "/repo".to_string(),
+            file_path: "src/main.rs".to_string(),
+            tool_name: Some("Edit".to_string()),
+            recorded_at: "2026-01-31T00:00:06Z".to_string(),
+        })
+        .expect("activity");
+        drop(db);
+        damage_table(&path, "activity");
+
+        let now = Utc::now();
+        let (db, report) =
+            open_checked(path.clone(), &SessionPolicy::default(), now).expect("repair damaged db");
+        assert_eq!(report.status, "repaired");
+        assert!(!report.problems.is_empty());
+        assert_eq!(report.events_salvaged, 2);
+        let quarantined = PathBuf::from(report.quarantined_path.expect("quarantined"));
+        assert!(quarantined.exists());
+        assert!(quarantined
+            .to_string_lossy()
+            .ends_with(&format!(".corrupt-{}", now.format("%Y%m%dT%H%M%SZ"))));
+
+        let session = db
+            .get_session("session-1")
+            .expect("session")
+            .expect("session rebuilt");
+        assert_eq!(session.state, crate::reducer::SessionState::Working);
+        assert_eq!(db.last_applied_event_rowid().expect("cursor"), Some(2));
+        assert!(quick_check(&path).expect("check").is_empty());
+    }
+
+    #[test]
+    fn unreadable_file_is_replaced_with_empty_database() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        std::fs::write(&path, b"this is not a sqlite database at all, just bytes").expect("write");
+
+        let (db, report) =
+            open_checked(path, &SessionPolicy::default(), Utc::now()).expect("replace");
+        assert_eq!(report.status, "repaired");
+        assert_eq!(report.events_salvaged, 0);
+        assert!(!report.snapshot_salvaged);
+        assert!(db.list_sessions().expect("sessions").is_empty());
+    }
+
+    #[test]
+    fn locked_database_fails_without_quarantine() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let db = Db::new(path.clone()).expect("db");
+        db.insert_event(&event(
+            "evt-1",
+            EventType::SessionStart,
+            "2026-01-31T00:00:00Z",
+        ))
+        .expect("insert");
+        drop(db);
+
+        let holder = Connection::open(&path).expect("open");
+        holder
+            .execute_batch("PRAGMA locking_mode = EXCLUSIVE; BEGIN EXCLUSIVE;")
+            .expect("lock");
+        holder
+            .execute("DELETE FROM activity", [])
+            .expect("take write lock");
+
+        let err = open_checked(path.clone(), &SessionPolicy::default(), Utc::now())
+            .err()
+            .expect("locked database fails");
+        assert!(err.contains("locked") || err.contains("busy"), "{}", err);
+        drop(holder);
+
+        assert!(quarantined_files(temp_dir.path()).is_empty());
+        let db = Db::new(path).expect("reopen");
+        assert_eq!(db.list_events(None).expect("events").len(), 1);
+    }
+
+    #[test]
+    fn unreadable_permissions_fail_without_quarantine() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        drop(Db::new(path.clone()).expect("db"));
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).expect("chmod");
+        if std::fs::File::open(&path).is_ok() {
+            eprintln!("Skipping permission test: file permissions are not enforced for this user.");
+            return;
+        }
+
+        let result = open_checked(path.clone(), &SessionPolicy::default(), Utc::now());
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .expect("restore permissions");
+        assert!(result.is_err());
+        assert!(quarantined_files(temp_dir.path()).is_empty());
+    }
+}
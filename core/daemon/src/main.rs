@@ -1,8 +1,12 @@
 //! Capacitor daemon entrypoint.
 //!
-//! This is a small, single-writer service that owns state updates for the app.
+//! This is a small service that owns state updates for the app.
 //! Phase 3 keeps it minimal: a socket listener, strict request validation, and
 //! a SQLite-backed event log with a materialized shell state view.
+//!
+//! Run without arguments to serve. `export-events` / `import-events` move the
+//! event log in and out as NDJSON for bug reports (see `event_log`), and
+//! `replay` prints the state timeline a trace produces (see `timeline`).
 
 use fs_err as fs;
 use std::env;
@@ -12,41 +16,75 @@ use std::os::unix::fs::PermissionsExt;
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
+use capacitor_daemon_client::spool::{EventSpool, Flush};
 use capacitor_daemon_protocol::{
-    parse_event, parse_process_liveness, parse_routing_diagnostics, parse_routing_snapshot,
-    ErrorInfo, Method, Request, Response, ERROR_INVALID_PROJECT_PATH, ERROR_TOO_MANY_CONNECTIONS,
-    ERROR_UNAUTHORIZED_PEER, MAX_REQUEST_BYTES, PROTOCOL_VERSION,
+    is_supported_protocol_version, parse_activity_request, parse_config_changes_query, parse_event,
+    parse_event_batch, parse_events_query, parse_hello, parse_process_liveness,
+    parse_project_time_query, parse_routing_diagnostics, parse_routing_snapshot,
+    parse_session_timeline_request, parse_snapshot_request, parse_subagents_request,
+    parse_subscribe, parse_tool_calls_query, parse_worktrees_query, ActivityRequest, ErrorInfo,
+    EventAck, EventBatchAck, EventBatchResult, EventStatus, EventsPage, HealthResponse,
+    HelloResponse, Method, MetricsResponse, ProcessLivenessResponse, Request, Response,
+    RuntimeHealth, SecurityHealth, SnapshotDelta, SubscribeAck, SubscriptionMessage,
+    ERROR_INVALID_PROJECT_PATH, ERROR_PROTOCOL_MISMATCH, ERROR_TOO_MANY_CONNECTIONS,
+    ERROR_TOO_MANY_SUBSCRIBERS, ERROR_UNAUTHORIZED_PEER, ERROR_WRITER_BUSY, FEATURES,
+    MAX_REQUEST_BYTES, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
-use serde_json::Value;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
 
 mod activity;
 mod are;
 mod backoff;
 mod boundaries;
+mod config_changes;
 mod db;
+mod event_log;
 mod hem;
+mod integrity;
+mod metrics;
+mod migrations;
+mod pending_request;
 mod process;
 mod project_identity;
 mod project_state_policy;
+mod project_view;
 mod reducer;
 mod replay;
+mod retention;
+mod session_policy;
 mod session_store;
 mod state;
+mod subagents;
+mod subscriptions;
+mod teams;
+#[cfg(test)]
+mod test_support;
+mod time_accounting;
+mod timeline;
+mod tool_calls;
+mod transitions;
+mod worktrees;
+mod writer;
 
 use db::Db;
-use state::SharedState;
+use state::{EventOutcome, SharedState};
+use writer::{EventWriter, WriteError};
 
 const SOCKET_NAME: &str = "daemon.sock";
 const READ_TIMEOUT_SECS: u64 = 2;
 const READ_CHUNK_SIZE: usize = 4096;
 const DEAD_SESSION_RECONCILE_INTERVAL_SECS: u64 = 15;
 const MAX_ACTIVE_CONNECTIONS: usize = 64;
+const SUBSCRIPTION_HEARTBEAT_SECS: u64 = 15;
+const SUBSCRIPTION_WRITE_TIMEOUT_SECS: u64 = 5;
 
 #[derive(Default)]
 struct RuntimeStats {
@@ -76,7 +114,85 @@ impl Drop for ConnectionGuard {
     }
 }
 
+#[derive(Parser)]
+#[command(name = "capacitor-daemon")]
+#[command(about = "Capacitor state daemon; serves the local socket when run without a command")]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write the event log as NDJSON, one event envelope per line
+    ExportEvents {
+        /// Database to read (defaults to the daemon's)
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+
+        /// Only events for this session
+        #[arg(long, value_name = "ID")]
+        session: Option<String>,
+
+        /// Only events whose cwd is this path or lies under it
+        #[arg(long, value_name = "PATH")]
+        project: Option<String>,
+
+        /// Only events of this type (repeatable)
+        #[arg(long = "event-type", value_name = "TYPE")]
+        event_types: Vec<String>,
+
+        /// Only events recorded at or after this RFC3339 time
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+
+        /// Only events recorded before this RFC3339 time
+        #[arg(long, value_name = "TIME")]
+        until: Option<String>,
+
+        /// Write here instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Load an NDJSON event trace into an empty database and rebuild state from it
+    ImportEvents {
+        /// NDJSON file produced by export-events
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Database to create (defaults to the daemon's; must have no events)
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+    },
+
+    /// Replay an NDJSON event trace offline and print the per-event state timeline
+    Replay {
+        /// NDJSON file produced by export-events
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Print one JSON object per event instead of text
+        #[arg(long)]
+        json: bool,
+
+        /// HEM config to evaluate with (defaults to the daemon's)
+        #[arg(long, value_name = "PATH")]
+        hem_config: Option<PathBuf>,
+    },
+}
+
 fn main() {
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        if let Err(err) = run_command(command) {
+            eprintln!("capacitor-daemon: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     init_logging();
 
     if let Ok(path) = daemon_backoff_path() {
@@ -125,14 +241,6 @@ fn main() {
         }
     };
 
-    let db = match Db::new(db_path) {
-        Ok(db) => db,
-        Err(err) => {
-            error!(error = %err, "Failed to initialize daemon database");
-            std::process::exit(1);
-        }
-    };
-
     let hem_config = match hem::load_runtime_config(None) {
         Ok(config) => config,
         Err(err) => {
@@ -140,7 +248,18 @@ fn main() {
             hem::HemRuntimeConfig::default()
         }
     };
+
+    let (db, integrity_report) =
+        match integrity::open_checked(db_path, &hem_config.session_policy, chrono::Utc::now()) {
+            Ok(opened) => opened,
+            Err(err) => {
+                error!(error = %err, "Failed to initialize daemon database");
+                std::process::exit(1);
+            }
+        };
+
     let shared_state = Arc::new(SharedState::new_with_hem_config(db, hem_config.clone()));
+    shared_state.record_integrity_report(integrity_report);
     info!(
         hem_enabled = hem_config.engine.enabled,
         hem_mode = ?hem_config.engine.mode,
@@ -151,9 +270,24 @@ fn main() {
         routing_emit_diagnostics = hem_config.routing.feature_flags.emit_diagnostics,
         "HEM runtime config loaded"
     );
+    let writer = match EventWriter::spawn(Arc::clone(&shared_state)) {
+        Ok(writer) => writer,
+        Err(err) => {
+            error!(error = %err, "Failed to start event writer");
+            std::process::exit(1);
+        }
+    };
+    spawn_event_spool_drain(writer.clone());
     spawn_dead_session_reconciler(Arc::clone(&shared_state));
+    spawn_project_view_refresher(Arc::clone(&shared_state));
+    spawn_retention(Arc::clone(&shared_state));
     spawn_routing_tmux_poller(Arc::clone(&shared_state));
     let runtime = Arc::new(RuntimeStats::default());
+    spawn_metrics_textfile(
+        Arc::clone(&shared_state),
+        writer.clone(),
+        Arc::clone(&runtime),
+    );
     let expected_uid = unsafe { libc::geteuid() as u32 };
 
     for stream in listener.incoming() {
@@ -173,9 +307,10 @@ fn main() {
                 };
                 let state = Arc::clone(&shared_state);
                 let runtime_stats = Arc::clone(&runtime);
+                let writer = writer.clone();
                 thread::spawn(move || {
                     let _connection_guard = connection_guard;
-                    handle_connection(stream, state, runtime_stats, expected_uid)
+                    handle_connection(stream, state, writer, runtime_stats, expected_uid)
                 });
             }
             Err(err) => {
@@ -185,6 +320,110 @@ fn main() {
     }
 }
 
+fn run_command(command: Command) -> Result<(), String> {
+    match command {
+        Command::ExportEvents {
+            db,
+            session,
+            project,
+            event_types,
+            since,
+            until,
+            output,
+        } => {
+            let db = open_db(db)?;
+            let filter = parse_events_query(Some(serde_json::json!({
+                "session_id": session,
+                "project_path": project,
+                "event_types": event_types,
+                "since": since,
+                "until": until,
+            })))
+            .map_err(|err| err.message)?;
+            let mut out: Box<dyn Write> = match output {
+                Some(path) => Box::new(std::io::BufWriter::new(
+                    fs::File::create(path).map_err(|err| err.to_string())?,
+                )),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            let count = event_log::export_events(&db, &filter, &mut out)?;
+            eprintln!("Exported {} events", count);
+        }
+        Command::ImportEvents { input, db } => {
+            let file = fs::File::open(&input).map_err(|err| err.to_string())?;
+            let db = open_db(db)?;
+            let policy = hem::load_runtime_config(None)?.session_policy;
+            let count = event_log::import_events(&db, std::io::BufReader::new(file), &policy)?;
+            eprintln!("Imported {} events from {}", count, input.display());
+        }
+        Command::Replay {
+            input,
+            json,
+            hem_config,
+        } => {
+            let file = fs::File::open(&input).map_err(|err| err.to_string())?;
+            let events = event_log::read_trace(std::io::BufReader::new(file))?;
+            let hem_config = hem::load_runtime_config(hem_config)?;
+            let scratch_dir =
+                env::temp_dir().join(format!("capacitor-replay-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&scratch_dir);
+            let entries = timeline::replay_timeline(&events, hem_config, &scratch_dir);
+            let _ = fs::remove_dir_all(&scratch_dir);
+            let entries = entries?;
+            let mut out = std::io::stdout().lock();
+            if json {
+                timeline::write_json(&entries, &mut out)?;
+            } else {
+                timeline::write_text(&entries, &mut out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_db(path: Option<PathBuf>) -> Result<Db, String> {
+    let path = match path {
+        Some(path) => path,
+        None => daemon_db_path()?,
+    };
+    Db::new(path)
+}
+
+/// Applies hook events spooled while the daemon was down. Runs off the accept
+/// loop; a hook that finds the flush lock held spools behind it instead.
+fn spawn_event_spool_drain(writer: EventWriter) {
+    let spool = match EventSpool::default_location() {
+        Ok(spool) => spool,
+        Err(err) => {
+            warn!(error = %err, "Failed to resolve event spool path");
+            return;
+        }
+    };
+    if spool.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let drained = spool.flush_with(|events| {
+            let outcomes = writer.submit(events.to_vec())?;
+            for (event, outcome) in events.iter().zip(&outcomes) {
+                if let EventOutcome::Rejected(err) = outcome {
+                    warn!(event_id = %event.event_id, error = %err, "Dropped spooled event the daemon could not store");
+                }
+            }
+            Ok::<_, WriteError>(())
+        });
+        match drained {
+            Ok(Flush::Done {
+                replayed,
+                discarded,
+            }) => info!(replayed, discarded, "Drained event spool"),
+            Ok(Flush::Busy) => info!("Event spool is being replayed by another process"),
+            Err(err) => warn!(error = %err, "Failed to drain event spool"),
+        }
+    });
+}
+
 fn spawn_dead_session_reconciler(state: Arc<SharedState>) {
     thread::spawn(move || loop {
         thread::sleep(Duration::from_secs(DEAD_SESSION_RECONCILE_INTERVAL_SECS));
@@ -194,6 +433,60 @@ fn spawn_dead_session_reconciler(state: Arc<SharedState>) {
                 "Periodic dead-session reconciliation failed"
             );
         }
+        if let Err(err) = state.prune_missing_worktrees() {
+            warn!(error = %err, "Periodic worktree pruning failed");
+        }
+    });
+}
+
+fn spawn_project_view_refresher(state: Arc<SharedState>) {
+    thread::spawn(move || loop {
+        state.wait_and_refresh_project_view(Duration::from_secs(
+            project_view::PROJECT_VIEW_REFRESH_SECS,
+        ));
+    });
+}
+
+fn spawn_retention(state: Arc<SharedState>) {
+    if !state.retention_enabled() {
+        info!("Event retention disabled by config");
+        return;
+    }
+
+    let interval_secs = state.retention_interval_secs();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(interval_secs));
+        match state.run_retention() {
+            Ok(run) if run.events_pruned > 0 || run.activity_pruned > 0 => info!(
+                events_pruned = run.events_pruned,
+                activity_pruned = run.activity_pruned,
+                vacuumed_pages = run.vacuum.pages_vacuumed,
+                "Pruned events past the retention horizon"
+            ),
+            Ok(_) => {}
+            Err(err) => warn!(error = %err, "Periodic event retention failed"),
+        }
+    });
+}
+
+fn spawn_metrics_textfile(
+    state: Arc<SharedState>,
+    writer: EventWriter,
+    runtime: Arc<RuntimeStats>,
+) {
+    let config = state.metrics_config().clone();
+    let Some(path) = config.textfile_path else {
+        return;
+    };
+
+    info!(path = %path.display(), "Writing metrics textfile");
+    thread::spawn(move || loop {
+        let snapshot = collect_metrics(&state, &writer, &runtime);
+        let text = metrics::render(&snapshot, metrics::Format::Prometheus);
+        if let Err(err) = metrics::write_textfile(&path, &text) {
+            warn!(error = %err, "Failed to write metrics textfile");
+        }
+        thread::sleep(Duration::from_secs(config.textfile_interval_secs));
     });
 }
 
@@ -273,6 +566,7 @@ fn set_socket_permissions(socket_path: &Path) -> Result<(), String> {
 fn handle_connection(
     mut stream: UnixStream,
     state: Arc<SharedState>,
+    writer: EventWriter,
     runtime: Arc<RuntimeStats>,
     expected_uid: u32,
 ) {
@@ -314,10 +608,73 @@ fn handle_connection(
     };
 
     tracing::debug!(method = ?request.method, id = ?request.id, "Daemon request received");
-    let response = handle_request(request, state, runtime);
+    if matches!(request.method, Method::Subscribe) {
+        stream_subscription(stream, request, state);
+        return;
+    }
+    let response = handle_request(request, state, &writer, runtime);
     let _ = write_response(&mut stream, response);
 }
 
+/// Serves a `subscribe` request: acks, then streams change notifications until
+/// the client disconnects or falls too far behind.
+fn stream_subscription(mut stream: UnixStream, request: Request, state: Arc<SharedState>) {
+    if let Some(response) = check_protocol_version(&request) {
+        let _ = write_response(&mut stream, response);
+        return;
+    }
+    let parsed = match parse_subscribe(request.params) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            let _ = write_response(&mut stream, Response::error_with_info(request.id, err));
+            return;
+        }
+    };
+    let topics = parsed.topics.clone();
+    let Some(subscription) = state.subscribe(parsed) else {
+        let _ = write_response(
+            &mut stream,
+            Response::error(
+                request.id,
+                ERROR_TOO_MANY_SUBSCRIBERS,
+                "daemon subscriber limit reached",
+            ),
+        );
+        return;
+    };
+
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(SUBSCRIPTION_WRITE_TIMEOUT_SECS)));
+    info!(subscriber_id = subscription.id, topics = ?topics, "Subscriber attached");
+    let ack = data_response(
+        request.id,
+        &SubscribeAck {
+            subscribed: true,
+            topics,
+        },
+        "subscribe ack",
+    );
+    if write_response(&mut stream, ack).is_ok() {
+        loop {
+            let message = match subscription
+                .receiver
+                .recv_timeout(Duration::from_secs(SUBSCRIPTION_HEARTBEAT_SECS))
+            {
+                Ok(change) => SubscriptionMessage::Change(change),
+                Err(RecvTimeoutError::Timeout) => SubscriptionMessage::Heartbeat {
+                    emitted_at: chrono::Utc::now().to_rfc3339(),
+                },
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if write_line(&mut stream, &message).is_err() {
+                break;
+            }
+        }
+    }
+
+    state.unsubscribe(subscription.id);
+    info!(subscriber_id = subscription.id, "Subscriber detached");
+}
+
 #[cfg(any(
     target_os = "macos",
     target_os = "ios",
@@ -432,60 +789,108 @@ fn read_request(stream: &mut UnixStream) -> Result<Request, ErrorInfo> {
     })
 }
 
+fn collect_metrics(
+    state: &SharedState,
+    writer: &EventWriter,
+    runtime: &RuntimeStats,
+) -> metrics::MetricsSnapshot {
+    metrics::MetricsSnapshot {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        build_hash: daemon_build_hash(),
+        protocol_version: PROTOCOL_VERSION,
+        active_connections: runtime.active_connections.load(Ordering::SeqCst) as u64,
+        max_active_connections: MAX_ACTIVE_CONNECTIONS as u64,
+        rejected_connections: runtime.rejected_connections.load(Ordering::SeqCst),
+        subscribers: state.subscriber_count() as u64,
+        max_subscribers: subscriptions::MAX_SUBSCRIBERS as u64,
+        state_revision: state.state_revision().ok(),
+        dead_session_reconcile: state.dead_session_reconcile_snapshot(),
+        hem_shadow: state.hem_shadow_metrics_snapshot(),
+        routing: state.routing_metrics_snapshot(),
+        backoff: daemon_backoff_path()
+            .ok()
+            .and_then(|path| backoff::snapshot(&path)),
+        retention: state.retention_status_snapshot(),
+        writer: writer.snapshot(),
+        project_view: state.project_view_status(),
+    }
+}
+
 fn daemon_build_hash() -> String {
     option_env!("CAPACITOR_DAEMON_BUILD_HASH")
         .unwrap_or(env!("CARGO_PKG_VERSION"))
         .to_string()
 }
 
+/// Accepts any version in the supported range; newer versions only add
+/// methods and fields, so older clients keep working. `hello` is exempt
+/// because its purpose is to find a version both sides speak.
+fn check_protocol_version(request: &Request) -> Option<Response> {
+    if matches!(request.method, Method::Hello)
+        || is_supported_protocol_version(request.protocol_version)
+    {
+        return None;
+    }
+    Some(Response::error(
+        request.id.clone(),
+        ERROR_PROTOCOL_MISMATCH,
+        format!(
+            "unsupported protocol version {} (supported: {}..={})",
+            request.protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+        ),
+    ))
+}
+
 fn handle_request(
     request: Request,
     state: Arc<SharedState>,
+    writer: &EventWriter,
     runtime: Arc<RuntimeStats>,
 ) -> Response {
-    if request.protocol_version != PROTOCOL_VERSION {
-        return Response::error(
-            request.id,
-            "protocol_mismatch",
-            "unsupported protocol version",
-        );
+    if let Some(response) = check_protocol_version(&request) {
+        return response;
     }
 
     match request.method {
         Method::GetHealth => {
-            let mut data = serde_json::json!({
-                "status": "ok",
-                "pid": std::process::id(),
-                "version": env!("CARGO_PKG_VERSION"),
-                "protocol_version": PROTOCOL_VERSION,
-                "dead_session_reconcile_interval_secs": DEAD_SESSION_RECONCILE_INTERVAL_SECS,
-                "security": {
-                    "peer_auth_mode": "same_user",
-                    "rejected_connections": runtime.rejected_connections.load(Ordering::SeqCst),
+            let state_revision = match state.state_revision() {
+                Ok(revision) => Some(revision),
+                Err(err) => {
+                    warn!(error = %err, "Failed to read state revision for health");
+                    None
+                }
+            };
+            let backoff = daemon_backoff_path()
+                .ok()
+                .and_then(|path| backoff::snapshot(&path));
+            let health = HealthResponse {
+                status: "ok".to_string(),
+                pid: std::process::id(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                protocol_version: PROTOCOL_VERSION,
+                dead_session_reconcile_interval_secs: DEAD_SESSION_RECONCILE_INTERVAL_SECS,
+                state_revision,
+                security: SecurityHealth {
+                    peer_auth_mode: "same_user".to_string(),
+                    rejected_connections: runtime.rejected_connections.load(Ordering::SeqCst),
                 },
-                "runtime": {
-                    "active_connections": runtime.active_connections.load(Ordering::SeqCst),
-                    "max_active_connections": MAX_ACTIVE_CONNECTIONS,
-                    "build_hash": daemon_build_hash(),
+                runtime: RuntimeHealth {
+                    active_connections: runtime.active_connections.load(Ordering::SeqCst) as u64,
+                    max_active_connections: MAX_ACTIVE_CONNECTIONS as u64,
+                    subscribers: state.subscriber_count() as u64,
+                    max_subscribers: subscriptions::MAX_SUBSCRIBERS as u64,
+                    build_hash: daemon_build_hash(),
                 },
-            });
-            if let Ok(value) = serde_json::to_value(state.dead_session_reconcile_snapshot()) {
-                data["dead_session_reconcile"] = value;
-            }
-            if let Ok(value) = serde_json::to_value(state.hem_shadow_metrics_snapshot()) {
-                data["hem_shadow"] = value;
-            }
-            if let Ok(value) = serde_json::to_value(state.routing_metrics_snapshot()) {
-                data["routing"] = value;
-            }
-            if let Ok(path) = daemon_backoff_path() {
-                if let Some(snapshot) = backoff::snapshot(&path) {
-                    if let Ok(value) = serde_json::to_value(snapshot) {
-                        data["backoff"] = value;
-                    }
-                }
-            }
-            Response::ok(request.id, data)
+                dead_session_reconcile: Some(state.dead_session_reconcile_snapshot()),
+                hem_shadow: Some(state.hem_shadow_metrics_snapshot().health()),
+                routing: Some(state.routing_metrics_snapshot().health()),
+                backoff,
+                retention: Some(state.retention_status_snapshot().health()),
+                writer: Some(writer.snapshot()),
+                project_view: Some(state.project_view_status()),
+                integrity: state.integrity_report(),
+            };
+            data_response(request.id, &health, "health")
         }
         Method::GetShellState => {
             let snapshot = state.shell_state_snapshot();
@@ -510,18 +915,13 @@ fn handle_request(
             };
 
             match state.process_liveness_snapshot(parsed.pid) {
-                Ok(Some(snapshot)) => match serde_json::to_value(snapshot) {
-                    Ok(value) => Response::ok(request.id, value),
-                    Err(err) => Response::error(
-                        request.id,
-                        "serialization_error",
-                        format!("Failed to serialize process liveness: {}", err),
-                    ),
-                },
-                Ok(None) => Response::ok(
-                    request.id,
-                    serde_json::json!({ "found": false, "pid": parsed.pid }),
-                ),
+                Ok(snapshot) => {
+                    let response = match snapshot {
+                        Some(snapshot) => ProcessLivenessResponse::Found(snapshot),
+                        None => ProcessLivenessResponse::not_found(parsed.pid),
+                    };
+                    data_response(request.id, &response, "process liveness")
+                }
                 Err(err) => Response::error(
                     request.id,
                     "liveness_error",
@@ -611,51 +1011,54 @@ fn handle_request(
                 format!("Failed to serialize runtime config: {}", err),
             ),
         },
-        Method::GetSessions => match state.sessions_snapshot() {
-            Ok(sessions) => {
-                let count = sessions.len();
-                match serde_json::to_value(&sessions) {
-                    Ok(value) => {
-                        tracing::debug!(sessions = count, "Sessions snapshot");
-                        Response::ok(request.id, value)
-                    }
-                    Err(err) => Response::error(
-                        request.id,
-                        "serialization_error",
-                        format!("Failed to serialize sessions: {}", err),
-                    ),
+        Method::GetSessions => {
+            let params = match parse_snapshot_request(request.params) {
+                Ok(params) => params,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.sessions_snapshot_since(params.since_revision) {
+                Ok(delta) => {
+                    tracing::debug!(
+                        sessions = delta.changed.len(),
+                        removed = delta.removed.len(),
+                        revision = delta.revision,
+                        "Sessions snapshot"
+                    );
+                    snapshot_response(request.id, params.since_revision, delta, "sessions")
                 }
+                Err(err) => Response::error(
+                    request.id,
+                    "sessions_error",
+                    format!("Failed to fetch sessions: {}", err),
+                ),
             }
-            Err(err) => Response::error(
-                request.id,
-                "sessions_error",
-                format!("Failed to fetch sessions: {}", err),
-            ),
-        },
-        Method::GetProjectStates => match state.project_states_snapshot() {
-            Ok(projects) => {
-                let count = projects.len();
-                match serde_json::to_value(&projects) {
-                    Ok(value) => {
-                        tracing::debug!(projects = count, "Project states snapshot");
-                        Response::ok(request.id, value)
-                    }
-                    Err(err) => Response::error(
-                        request.id,
-                        "serialization_error",
-                        format!("Failed to serialize project states: {}", err),
-                    ),
+        }
+        Method::GetProjectStates => {
+            let params = match parse_snapshot_request(request.params) {
+                Ok(params) => params,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.project_states_snapshot_since(params.since_revision) {
+                Ok(delta) => {
+                    tracing::debug!(
+                        projects = delta.changed.len(),
+                        removed = delta.removed.len(),
+                        revision = delta.revision,
+                        "Project states snapshot"
+                    );
+                    snapshot_response(request.id, params.since_revision, delta, "project states")
                 }
+                Err(err) => Response::error(
+                    request.id,
+                    "project_states_error",
+                    format!("Failed to fetch project states: {}", err),
+                ),
             }
-            Err(err) => Response::error(
-                request.id,
-                "project_states_error",
-                format!("Failed to fetch project states: {}", err),
-            ),
-        },
+        }
         Method::GetActivity => {
-            let (session_id, limit) = match parse_activity_params(request.params) {
-                Ok(values) => values,
+            let ActivityRequest { session_id, limit } = match parse_activity_request(request.params)
+            {
+                Ok(params) => params,
                 Err(err) => return Response::error_with_info(request.id, err),
             };
             tracing::debug!(
@@ -700,11 +1103,166 @@ fn handle_request(
                 format!("Failed to fetch tombstones: {}", err),
             ),
         },
-        Method::Event => handle_event(request, state),
+        Method::GetEvents => {
+            let query = match parse_events_query(request.params) {
+                Ok(query) => query,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            tracing::debug!(query = ?query, "Event log request");
+            match state.events_page(&query) {
+                Ok((events, next_cursor)) => data_response(
+                    request.id,
+                    &EventsPage {
+                        events,
+                        next_cursor,
+                    },
+                    "events",
+                ),
+                Err(err) => Response::error(
+                    request.id,
+                    "events_error",
+                    format!("Failed to fetch events: {}", err),
+                ),
+            }
+        }
+        Method::GetSubagents => {
+            let parsed = match parse_subagents_request(request.params) {
+                Ok(parsed) => parsed,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.subagents_snapshot(&parsed.session_id) {
+                Ok(subagents) => data_response(request.id, &subagents, "subagents"),
+                Err(err) => Response::error(
+                    request.id,
+                    "subagents_error",
+                    format!("Failed to fetch subagents: {}", err),
+                ),
+            }
+        }
+        Method::GetSessionTimeline => {
+            let parsed = match parse_session_timeline_request(request.params) {
+                Ok(parsed) => parsed,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.session_timeline(&parsed) {
+                Ok(timeline) => data_response(request.id, &timeline, "session timeline"),
+                Err(err) => Response::error(
+                    request.id,
+                    "session_timeline_error",
+                    format!("Failed to fetch session timeline: {}", err),
+                ),
+            }
+        }
+        Method::GetProjectTime => {
+            let query = match parse_project_time_query(request.params) {
+                Ok(query) => query,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.project_time(&query) {
+                Ok(rows) => data_response(request.id, &rows, "project time"),
+                Err(err) => Response::error(
+                    request.id,
+                    "project_time_error",
+                    format!("Failed to compute project time: {}", err),
+                ),
+            }
+        }
+        Method::GetToolCalls => {
+            let query = match parse_tool_calls_query(request.params) {
+                Ok(query) => query,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.tool_calls(&query) {
+                Ok(response) => data_response(request.id, &response, "tool calls"),
+                Err(err) => Response::error(
+                    request.id,
+                    "tool_calls_error",
+                    format!("Failed to read tool calls: {}", err),
+                ),
+            }
+        }
+        Method::GetWorktrees => {
+            let query = match parse_worktrees_query(request.params) {
+                Ok(query) => query,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.worktrees(&query) {
+                Ok(worktrees) => data_response(request.id, &worktrees, "worktrees"),
+                Err(err) => Response::error(
+                    request.id,
+                    "worktrees_error",
+                    format!("Failed to read worktrees: {}", err),
+                ),
+            }
+        }
+        Method::GetConfigChanges => {
+            let query = match parse_config_changes_query(request.params) {
+                Ok(query) => query,
+                Err(err) => return Response::error_with_info(request.id, err),
+            };
+            match state.config_changes(&query) {
+                Ok(changes) => data_response(request.id, &changes, "config changes"),
+                Err(err) => Response::error(
+                    request.id,
+                    "config_changes_error",
+                    format!("Failed to read config changes: {}", err),
+                ),
+            }
+        }
+        Method::GetMetrics => {
+            let snapshot = collect_metrics(&state, writer, &runtime);
+            let response = MetricsResponse {
+                content_type: metrics::OPENMETRICS_CONTENT_TYPE.to_string(),
+                text: metrics::render(&snapshot, metrics::Format::OpenMetrics),
+            };
+            data_response(request.id, &response, "metrics")
+        }
+        Method::Event => handle_event(request, writer),
+        Method::EventBatch => handle_event_batch(request, writer),
+        Method::Hello => handle_hello(request),
+        Method::Subscribe => Response::error(
+            request.id,
+            "invalid_params",
+            "subscribe is only available as the first request on a connection",
+        ),
     }
 }
 
-fn handle_event(request: Request, state: Arc<SharedState>) -> Response {
+fn handle_hello(request: Request) -> Response {
+    let hello = match parse_hello(request.params) {
+        Ok(hello) => hello,
+        Err(err) => return Response::error_with_info(request.id, err),
+    };
+    let protocol_version = match hello.negotiate() {
+        Ok(version) => version,
+        Err(err) => {
+            warn!(
+                client = hello.client.as_deref().unwrap_or("unknown"),
+                min = hello.min_protocol_version,
+                max = hello.max_protocol_version,
+                "Rejected client with no shared protocol version"
+            );
+            return Response::error_with_info(request.id, err);
+        }
+    };
+    tracing::debug!(
+        client = hello.client.as_deref().unwrap_or("unknown"),
+        protocol_version,
+        features = ?hello.features,
+        "Client handshake"
+    );
+
+    let response = HelloResponse {
+        protocol_version,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        max_protocol_version: PROTOCOL_VERSION,
+        features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    data_response(request.id, &response, "hello")
+}
+
+fn handle_event(request: Request, writer: &EventWriter) -> Response {
     let params = match request.params {
         Some(params) => params,
         None => return Response::error(request.id, "invalid_params", "event payload is required"),
@@ -727,34 +1285,168 @@ fn handle_event(request: Request, state: Arc<SharedState>) -> Response {
         "Received event"
     );
 
-    state.update_from_event(&event);
+    match writer.submit(vec![event]) {
+        Ok(outcomes) => {
+            if let Some(EventOutcome::Rejected(err)) = outcomes.first() {
+                return Response::error(
+                    request.id,
+                    "event_error",
+                    format!("Failed to persist event: {}", err),
+                );
+            }
+        }
+        Err(err) => return write_error_response(request.id, "event_error", err),
+    }
 
-    Response::ok(request.id, serde_json::json!({"accepted": true}))
+    data_response(request.id, &EventAck { accepted: true }, "event ack")
 }
 
-fn parse_activity_params(params: Option<Value>) -> Result<(Option<String>, usize), ErrorInfo> {
-    let mut session_id = None;
-    let mut limit = 100usize;
-
-    if let Some(params) = params {
-        if !params.is_object() {
-            return Err(ErrorInfo::new("invalid_params", "params must be an object"));
+/// A full writer queue is reported as `writer_busy` so hooks spool the event
+/// and retry later; other failures use `code`.
+fn write_error_response(id: Option<String>, code: &str, err: WriteError) -> Response {
+    match err {
+        WriteError::Busy => Response::error(id, ERROR_WRITER_BUSY, err.to_string()),
+        WriteError::Stopped | WriteError::Failed(_) => {
+            Response::error(id, code, format!("Failed to persist events: {}", err))
         }
-        if let Some(value) = params.get("session_id").and_then(|v| v.as_str()) {
-            if !value.trim().is_empty() {
-                session_id = Some(value.to_string());
+    }
+}
+
+/// Valid events are stored in one transaction and applied in request order;
+/// invalid ones, and ones that cannot be stored, are reported without
+/// affecting the rest of the batch.
+fn handle_event_batch(request: Request, writer: &EventWriter) -> Response {
+    let batch = match parse_event_batch(request.params) {
+        Ok(batch) => batch,
+        Err(err) => return Response::error_with_info(request.id, err),
+    };
+
+    let mut results = Vec::with_capacity(batch.len());
+    let mut events = Vec::with_capacity(batch.len());
+    for (index, entry) in batch.into_iter().enumerate() {
+        match entry.event {
+            Ok(event) => {
+                results.push(EventBatchResult {
+                    index,
+                    event_id: Some(event.event_id.clone()),
+                    status: EventStatus::Accepted,
+                    error: None,
+                });
+                events.push(event);
             }
+            Err(err) => results.push(EventBatchResult {
+                index,
+                event_id: entry.event_id,
+                status: EventStatus::Rejected,
+                error: Some(err),
+            }),
         }
-        if let Some(value) = params.get("limit").and_then(|v| v.as_u64()) {
-            limit = value.min(1000) as usize;
+    }
+
+    let submitted = events.len();
+    let outcomes = match writer.submit(events) {
+        Ok(outcomes) => outcomes,
+        Err(err) => return write_error_response(request.id, "event_batch_error", err),
+    };
+    if outcomes.len() != submitted {
+        return Response::error(
+            request.id,
+            "internal_error",
+            format!(
+                "Event writer reported {} outcomes for {} events",
+                outcomes.len(),
+                submitted
+            ),
+        );
+    }
+    let mut outcomes = outcomes.into_iter();
+    for (result, outcome) in results
+        .iter_mut()
+        .filter(|result| result.status == EventStatus::Accepted)
+        .zip(&mut outcomes)
+    {
+        match outcome {
+            EventOutcome::Applied => {}
+            EventOutcome::Skipped(err) => {
+                result.error = Some(ErrorInfo::new(
+                    "apply_failed",
+                    format!("Stored, but failed to apply: {}", err),
+                ));
+            }
+            EventOutcome::Duplicate => result.status = EventStatus::Duplicate,
+            EventOutcome::Rejected(err) => {
+                result.status = EventStatus::Rejected;
+                result.error = Some(ErrorInfo::new(
+                    "event_error",
+                    format!("Failed to persist event: {}", err),
+                ));
+            }
         }
     }
 
-    Ok((session_id, limit))
+    let count = |status: EventStatus| {
+        results
+            .iter()
+            .filter(|result| result.status == status)
+            .count()
+    };
+    let ack = EventBatchAck {
+        accepted: count(EventStatus::Accepted),
+        duplicates: count(EventStatus::Duplicate),
+        rejected: count(EventStatus::Rejected),
+        results,
+    };
+    info!(
+        accepted = ack.accepted,
+        duplicates = ack.duplicates,
+        rejected = ack.rejected,
+        "Received event batch"
+    );
+
+    data_response(request.id, &ack, "event batch ack")
+}
+
+/// Full snapshots keep the legacy top-level array payload; delta requests get
+/// the `SnapshotDelta` object. Both carry the revision on the envelope.
+fn snapshot_response<T: Serialize>(
+    id: Option<String>,
+    since_revision: Option<u64>,
+    delta: SnapshotDelta<T>,
+    label: &str,
+) -> Response {
+    let revision = delta.revision;
+    let value = if since_revision.is_some() {
+        serde_json::to_value(&delta)
+    } else {
+        serde_json::to_value(&delta.changed)
+    };
+    match value {
+        Ok(value) => Response::ok(id, value).with_revision(revision),
+        Err(err) => Response::error(
+            id,
+            "serialization_error",
+            format!("Failed to serialize {}: {}", label, err),
+        ),
+    }
+}
+
+fn data_response<T: Serialize>(id: Option<String>, data: &T, label: &str) -> Response {
+    match serde_json::to_value(data) {
+        Ok(value) => Response::ok(id, value),
+        Err(err) => Response::error(
+            id,
+            "serialization_error",
+            format!("Failed to serialize {}: {}", label, err),
+        ),
+    }
 }
 
 fn write_response(stream: &mut UnixStream, response: Response) -> std::io::Result<()> {
-    serde_json::to_writer(&mut *stream, &response)?;
+    write_line(stream, &response)
+}
+
+fn write_line<T: Serialize>(stream: &mut UnixStream, value: &T) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *stream, value)?;
     stream.write_all(b"\n")?;
     stream.flush()?;
     Ok(())
@@ -0,0 +1,751 @@
+//! OpenMetrics export of the daemon's counters.
+//!
+//! `get_metrics` renders the same counters `get_health` reports as nested
+//! JSON, under stable `capacitor_daemon_*` names. With `[metrics]
+//! textfile_path` set, the daemon also rewrites a `.prom` file on an interval
+//! for node_exporter's textfile collector. That collector parses the older
+//! Prometheus text format, so the file differs from the OpenMetrics response
+//! in two places: counter `TYPE` lines carry the `_total` suffix, and the
+//! build info is a gauge with no `# EOF` trailer.
+
+use capacitor_daemon_protocol::{
+    BackoffHealth, DeadSessionReconcileHealth, ProjectViewHealth, WriterHealth,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::are::metrics::RoutingMetrics;
+use crate::retention::RetentionStatus;
+use crate::state::HemShadowMetrics;
+
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+fn default_textfile_interval_secs() -> u64 {
+    15
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct MetricsConfig {
+    /// Absolute path of the `.prom` file to keep current; unset disables it.
+    #[serde(default)]
+    pub textfile_path: Option<PathBuf>,
+    #[serde(default = "default_textfile_interval_secs")]
+    pub textfile_interval_secs: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            textfile_path: None,
+            textfile_interval_secs: default_textfile_interval_secs(),
+        }
+    }
+}
+
+impl MetricsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=3_600).contains(&self.textfile_interval_secs) {
+            return Err(format!(
+                "metrics.textfile_interval_secs must be between 1 and 3600, got {}",
+                self.textfile_interval_secs
+            ));
+        }
+        if let Some(path) = &self.textfile_path {
+            if !path.is_absolute() {
+                return Err(format!(
+                    "metrics.textfile_path must be absolute, got {}",
+                    path.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    OpenMetrics,
+    /// Prometheus text format 0.0.4, as read by node_exporter.
+    Prometheus,
+}
+
+/// Everything rendered, captured at one point in time.
+pub struct MetricsSnapshot {
+    pub version: String,
+    pub build_hash: String,
+    pub protocol_version: u32,
+    pub active_connections: u64,
+    pub max_active_connections: u64,
+    pub rejected_connections: u64,
+    pub subscribers: u64,
+    pub max_subscribers: u64,
+    pub state_revision: Option<u64>,
+    pub dead_session_reconcile: HashMap<String, DeadSessionReconcileHealth>,
+    pub hem_shadow: HemShadowMetrics,
+    pub routing: RoutingMetrics,
+    pub backoff: Option<BackoffHealth>,
+    pub retention: RetentionStatus,
+    pub writer: WriterHealth,
+    pub project_view: ProjectViewHealth,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Counter,
+    Gauge,
+    Info,
+}
+
+type Labels<'a> = Vec<(&'a str, String)>;
+
+struct Renderer {
+    format: Format,
+    out: String,
+}
+
+impl Renderer {
+    fn family(&mut self, name: &str, kind: Kind, help: &str, samples: &[(Labels<'_>, f64)]) {
+        if samples.is_empty() {
+            return;
+        }
+        let (family, sample, type_name) = match (kind, self.format) {
+            (Kind::Counter, Format::OpenMetrics) => {
+                (name.to_string(), format!("{}_total", name), "counter")
+            }
+            (Kind::Counter, Format::Prometheus) => {
+                let total = format!("{}_total", name);
+                (total.clone(), total, "counter")
+            }
+            (Kind::Gauge, _) => (name.to_string(), name.to_string(), "gauge"),
+            (Kind::Info, Format::OpenMetrics) => {
+                (name.to_string(), format!("{}_info", name), "info")
+            }
+            (Kind::Info, Format::Prometheus) => {
+                let info = format!("{}_info", name);
+                (info.clone(), info, "gauge")
+            }
+        };
+        let _ = writeln!(self.out, "# TYPE {} {}", family, type_name);
+        let _ = writeln!(self.out, "# HELP {} {}", family, escape_help(help));
+        for (labels, value) in samples {
+            self.out.push_str(&sample);
+            if !labels.is_empty() {
+                let rendered = labels
+                    .iter()
+                    .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = write!(self.out, "{{{}}}", rendered);
+            }
+            let _ = writeln!(self.out, " {}", value);
+        }
+    }
+
+    fn counter(&mut self, name: &str, help: &str, value: u64) {
+        self.family(name, Kind::Counter, help, &[(Vec::new(), value as f64)]);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, Kind::Gauge, help, &[(Vec::new(), value)]);
+    }
+
+    /// Unix time of an RFC3339 timestamp; skipped when absent.
+    fn timestamp(&mut self, name: &str, help: &str, value: Option<&str>) {
+        if let Some(seconds) = value.and_then(unix_seconds) {
+            self.gauge(name, help, seconds);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.format == Format::OpenMetrics {
+            self.out.push_str("# EOF\n");
+        }
+        self.out
+    }
+}
+
+pub fn render(snapshot: &MetricsSnapshot, format: Format) -> String {
+    let mut r = Renderer {
+        format,
+        out: String::new(),
+    };
+
+    r.family(
+        "capacitor_daemon_build",
+        Kind::Info,
+        "Daemon build identity.",
+        &[(
+            vec![
+                ("version", snapshot.version.clone()),
+                ("build_hash", snapshot.build_hash.clone()),
+                ("protocol_version", snapshot.protocol_version.to_string()),
+            ],
+            1.0,
+        )],
+    );
+    r.gauge(
+        "capacitor_daemon_connections_active",
+        "Socket requests currently being handled.",
+        snapshot.active_connections as f64,
+    );
+    r.gauge(
+        "capacitor_daemon_connections_max",
+        "Concurrent socket request limit.",
+        snapshot.max_active_connections as f64,
+    );
+    r.counter(
+        "capacitor_daemon_connections_rejected",
+        "Connections rejected by peer auth or the connection limit.",
+        snapshot.rejected_connections,
+    );
+    r.gauge(
+        "capacitor_daemon_subscribers",
+        "Attached subscribe streams.",
+        snapshot.subscribers as f64,
+    );
+    r.gauge(
+        "capacitor_daemon_subscribers_max",
+        "Subscribe stream limit.",
+        snapshot.max_subscribers as f64,
+    );
+    if let Some(revision) = snapshot.state_revision {
+        r.gauge(
+            "capacitor_daemon_state_revision",
+            "Current snapshot state revision.",
+            revision as f64,
+        );
+    }
+
+    render_dead_session_reconcile(&mut r, &snapshot.dead_session_reconcile);
+    render_hem_shadow(&mut r, &snapshot.hem_shadow);
+    render_routing(&mut r, &snapshot.routing);
+    if let Some(backoff) = &snapshot.backoff {
+        render_backoff(&mut r, backoff);
+    }
+    render_retention(&mut r, &snapshot.retention);
+    render_writer(&mut r, &snapshot.writer);
+    render_project_view(&mut r, &snapshot.project_view);
+
+    r.finish()
+}
+
+fn render_dead_session_reconcile(
+    r: &mut Renderer,
+    metrics: &HashMap<String, DeadSessionReconcileHealth>,
+) {
+    let mut sources = metrics.iter().collect::<Vec<_>>();
+    sources.sort_by(|left, right| left.0.cmp(right.0));
+    let by_source = |value: &dyn Fn(&DeadSessionReconcileHealth) -> Option<f64>| {
+        sources
+            .iter()
+            .filter_map(|(source, metrics)| {
+                value(metrics).map(|value| (vec![("source", source.to_string())], value))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    r.family(
+        "capacitor_daemon_dead_session_reconcile_runs",
+        Kind::Counter,
+        "Dead-session reconciliation passes, by trigger.",
+        &by_source(&|metrics| Some(metrics.runs as f64)),
+    );
+    r.family(
+        "capacitor_daemon_dead_session_reconcile_repaired_sessions",
+        Kind::Counter,
+        "Sessions of dead processes reset to idle, by trigger.",
+        &by_source(&|metrics| Some(metrics.repaired_sessions as f64)),
+    );
+    r.family(
+        "capacitor_daemon_dead_session_reconcile_last_run_timestamp_seconds",
+        Kind::Gauge,
+        "Time of the last reconciliation pass, by trigger.",
+        &by_source(&|metrics| metrics.last_run_at.as_deref().and_then(unix_seconds)),
+    );
+}
+
+fn render_hem_shadow(r: &mut Renderer, metrics: &HemShadowMetrics) {
+    r.gauge(
+        "capacitor_daemon_hem_shadow_enabled",
+        "Whether HEM shadow evaluation is running.",
+        bool_value(metrics.enabled),
+    );
+    r.counter(
+        "capacitor_daemon_hem_shadow_events_evaluated",
+        "Events evaluated by the HEM shadow comparison.",
+        metrics.events_evaluated,
+    );
+    r.counter(
+        "capacitor_daemon_hem_shadow_projects_evaluated",
+        "Project states compared by the HEM shadow comparison.",
+        metrics.projects_evaluated,
+    );
+    r.counter(
+        "capacitor_daemon_hem_shadow_mismatches",
+        "HEM shadow mismatches.",
+        metrics.mismatches_total,
+    );
+    r.family(
+        "capacitor_daemon_hem_shadow_mismatches_by_category",
+        Kind::Counter,
+        "HEM shadow mismatches, by category.",
+        &sorted_counts("category", &metrics.mismatches_by_category),
+    );
+    r.family(
+        "capacitor_daemon_hem_shadow_mismatches_by_severity",
+        Kind::Counter,
+        "HEM shadow mismatches, by severity.",
+        &sorted_counts("severity", &metrics.mismatches_by_severity),
+    );
+    r.family(
+        "capacitor_daemon_hem_shadow_gate_mismatches",
+        Kind::Counter,
+        "HEM shadow mismatches counted against the cutover gate, by gate.",
+        &[
+            (
+                vec![("gate", "blocking".to_string())],
+                metrics.gate_blocking_mismatches as f64,
+            ),
+            (
+                vec![("gate", "critical".to_string())],
+                metrics.gate_critical_mismatches as f64,
+            ),
+            (
+                vec![("gate", "important".to_string())],
+                metrics.gate_important_mismatches as f64,
+            ),
+        ],
+    );
+    r.counter(
+        "capacitor_daemon_hem_shadow_stable_state_samples",
+        "Stable-state samples compared between HEM and the reducer.",
+        metrics.stable_state_samples,
+    );
+    r.counter(
+        "capacitor_daemon_hem_shadow_stable_state_matches",
+        "Stable-state samples where HEM and the reducer agreed.",
+        metrics.stable_state_matches,
+    );
+    r.gauge(
+        "capacitor_daemon_hem_shadow_stable_state_agreement_ratio",
+        "Share of stable-state samples that agreed.",
+        metrics.stable_state_agreement_rate,
+    );
+    r.gauge(
+        "capacitor_daemon_hem_shadow_blocking_mismatch_ratio",
+        "Blocking mismatches per evaluated project.",
+        metrics.blocking_mismatch_rate,
+    );
+    r.gauge(
+        "capacitor_daemon_hem_shadow_gate_ready",
+        "Whether the HEM shadow cutover gate is met.",
+        bool_value(metrics.shadow_gate_ready),
+    );
+    r.counter(
+        "capacitor_daemon_hem_capability_warnings",
+        "HEM capability detection warnings.",
+        metrics.capability_status.warning_count,
+    );
+    r.timestamp(
+        "capacitor_daemon_hem_shadow_last_mismatch_timestamp_seconds",
+        "Time of the last HEM shadow mismatch.",
+        metrics.last_mismatch_at.as_deref(),
+    );
+}
+
+fn render_routing(r: &mut Renderer, metrics: &RoutingMetrics) {
+    r.gauge(
+        "capacitor_daemon_routing_enabled",
+        "Whether the routing engine is enabled.",
+        bool_value(metrics.enabled),
+    );
+    r.gauge(
+        "capacitor_daemon_routing_dual_run_enabled",
+        "Whether legacy and engine routing are compared.",
+        bool_value(metrics.dual_run_enabled),
+    );
+    r.counter(
+        "capacitor_daemon_routing_snapshots",
+        "Routing snapshots emitted.",
+        metrics.snapshots_emitted,
+    );
+    r.family(
+        "capacitor_daemon_routing_snapshots_by_confidence",
+        Kind::Counter,
+        "Routing snapshots emitted, by confidence.",
+        &[
+            (
+                vec![("confidence", "high".to_string())],
+                metrics.confidence_high as f64,
+            ),
+            (
+                vec![("confidence", "medium".to_string())],
+                metrics.confidence_medium as f64,
+            ),
+            (
+                vec![("confidence", "low".to_string())],
+                metrics.confidence_low as f64,
+            ),
+        ],
+    );
+    r.counter(
+        "capacitor_daemon_routing_dual_run_comparisons",
+        "Legacy versus engine routing comparisons.",
+        metrics.dual_run_comparisons,
+    );
+    r.family(
+        "capacitor_daemon_routing_dual_run_mismatches",
+        Kind::Counter,
+        "Legacy versus engine routing disagreements, by field.",
+        &[
+            (
+                vec![("field", "status".to_string())],
+                metrics.legacy_vs_are_status_mismatch as f64,
+            ),
+            (
+                vec![("field", "target".to_string())],
+                metrics.legacy_vs_are_target_mismatch as f64,
+            ),
+        ],
+    );
+    let rollout = &metrics.rollout;
+    r.family(
+        "capacitor_daemon_routing_rollout_agreement_ratio",
+        Kind::Gauge,
+        "Legacy versus engine routing agreement, by field.",
+        &[
+            ("status", rollout.status_agreement_rate),
+            ("target", rollout.target_agreement_rate),
+        ]
+        .into_iter()
+        .filter_map(|(field, rate)| rate.map(|rate| (vec![("field", field.to_string())], rate)))
+        .collect::<Vec<_>>(),
+    );
+    if let Some(hours) = rollout.window_elapsed_hours {
+        r.gauge(
+            "capacitor_daemon_routing_rollout_window_elapsed_hours",
+            "Hours since the first routing comparison.",
+            hours as f64,
+        );
+    }
+    r.family(
+        "capacitor_daemon_routing_rollout_ready",
+        Kind::Gauge,
+        "Whether a surface meets its routing cutover gate.",
+        &[
+            (
+                vec![("surface", "status_row".to_string())],
+                bool_value(rollout.status_row_default_ready),
+            ),
+            (
+                vec![("surface", "launcher".to_string())],
+                bool_value(rollout.launcher_default_ready),
+            ),
+        ],
+    );
+}
+
+fn render_backoff(r: &mut Renderer, backoff: &BackoffHealth) {
+    r.gauge(
+        "capacitor_daemon_backoff_starts_in_window",
+        "Daemon starts within the backoff window.",
+        backoff.starts_in_window as f64,
+    );
+    r.gauge(
+        "capacitor_daemon_backoff_window_seconds",
+        "Length of the startup backoff window.",
+        backoff.window_secs as f64,
+    );
+    r.gauge(
+        "capacitor_daemon_backoff_next_seconds",
+        "Delay the next start would wait; 0 when no backoff applies.",
+        backoff.next_backoff_secs.unwrap_or(0) as f64,
+    );
+}
+
+fn render_retention(r: &mut Renderer, status: &RetentionStatus) {
+    r.gauge(
+        "capacitor_daemon_retention_enabled",
+        "Whether event retention runs.",
+        bool_value(status.enabled),
+    );
+    r.counter(
+        "capacitor_daemon_retention_runs",
+        "Retention runs since the daemon started.",
+        status.runs,
+    );
+    r.counter(
+        "capacitor_daemon_retention_events_pruned",
+        "Events pruned since the daemon started.",
+        status.events_pruned_total,
+    );
+    r.counter(
+        "capacitor_daemon_retention_activity_pruned",
+        "Activity rows pruned since the daemon started.",
+        status.activity_pruned_total,
+    );
+    r.counter(
+        "capacitor_daemon_retention_history_pruned",
+        "History rows pruned since the daemon started.",
+        status.history_pruned_total,
+    );
+    r.gauge(
+        "capacitor_daemon_events_stored",
+        "Events in the event log.",
+        status.event_count as f64,
+    );
+    r.timestamp(
+        "capacitor_daemon_retention_last_run_timestamp_seconds",
+        "Time of the last retention run.",
+        status.last_run_at.as_deref(),
+    );
+}
+
+fn render_writer(r: &mut Renderer, writer: &WriterHealth) {
+    r.gauge(
+        "capacitor_daemon_writer_queued",
+        "Event submissions waiting for the writer.",
+        writer.queued as f64,
+    );
+    r.gauge(
+        "capacitor_daemon_writer_queue_capacity",
+        "Event submissions the writer queue holds.",
+        writer.queue_capacity as f64,
+    );
+    r.counter(
+        "capacitor_daemon_writer_batches",
+        "Write transactions committed or attempted by the writer.",
+        writer.batches,
+    );
+    r.counter(
+        "capacitor_daemon_writer_events_written",
+        "New events stored by the writer.",
+        writer.events_written,
+    );
+    r.counter(
+        "capacitor_daemon_writer_busy_rejections",
+        "Submissions rejected with writer_busy.",
+        writer.busy_rejections,
+    );
+    r.counter(
+        "capacitor_daemon_writer_failed_batches",
+        "Writer transactions that rolled back.",
+        writer.failed_batches,
+    );
+    r.counter(
+        "capacitor_daemon_writer_rejected_events",
+        "Events rolled back alone because they could not be stored.",
+        writer.rejected_events,
+    );
+    r.counter(
+        "capacitor_daemon_writer_skipped_events",
+        "Stored events whose derived state failed to apply and was skipped.",
+        writer.skipped_events,
+    );
+    r.gauge(
+        "capacitor_daemon_writer_largest_batch_events",
+        "Most events committed in one writer transaction.",
+        writer.largest_batch as f64,
+    );
+    r.gauge(
+        "capacitor_daemon_writer_last_batch_seconds",
+        "Duration of the last writer transaction.",
+        writer.last_batch_micros as f64 / 1_000_000.0,
+    );
+}
+
+fn render_project_view(r: &mut Renderer, status: &ProjectViewHealth) {
+    r.gauge(
+        "capacitor_daemon_projects",
+        "Projects in the project state view.",
+        status.projects as f64,
+    );
+    r.family(
+        "capacitor_daemon_project_view_refreshes",
+        Kind::Counter,
+        "Project view refreshes, by scope.",
+        &[
+            (
+                vec![("scope", "full".to_string())],
+                status.full_refreshes as f64,
+            ),
+            (
+                vec![("scope", "partial".to_string())],
+                status.partial_refreshes as f64,
+            ),
+        ],
+    );
+    r.counter(
+        "capacitor_daemon_project_view_reads",
+        "Project state requests served from the view.",
+        status.reads,
+    );
+    r.gauge(
+        "capacitor_daemon_project_view_last_refresh_seconds",
+        "Duration of the last project view refresh.",
+        status.last_refresh_micros as f64 / 1_000_000.0,
+    );
+}
+
+/// Writes `text` to `path` through a temporary file, so a scrape never reads
+/// a half-written file.
+pub fn write_textfile(path: &Path, text: &str) -> Result<(), String> {
+    let mut temp_name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    std::fs::write(&temp_path, text).map_err(|err| {
+        format!(
+            "Failed to write metrics file {}: {}",
+            temp_path.display(),
+            err
+        )
+    })?;
+    std::fs::rename(&temp_path, path)
+        .map_err(|err| format!("Failed to replace metrics file {}: {}", path.display(), err))
+}
+
+fn sorted_counts<'a>(label: &'a str, counts: &HashMap<String, u64>) -> Vec<(Labels<'a>, f64)> {
+    let mut entries = counts.iter().collect::<Vec<_>>();
+    entries.sort();
+    entries
+        .into_iter()
+        .map(|(key, value)| (vec![(label, key.clone())], *value as f64))
+        .collect()
+}
+
+fn bool_value(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn unix_seconds(value: &str) -> Option<f64> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc).timestamp_millis() as f64 / 1_000.0)
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_help(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use crate::state::SharedState;
+
+    fn snapshot() -> MetricsSnapshot {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let state = SharedState::new(Db::new(temp_dir.path().join("state.db")).expect("db"));
+        let mut dead_session_reconcile = HashMap::new();
+        dead_session_reconcile.insert(
+            "periodic".to_string(),
+            DeadSessionReconcileHealth {
+                runs: 3,
+                repaired_sessions: 1,
+                last_run_at: Some("2026-02-14T15:00:00Z".to_string()),
+                last_repair_at: None,
+            },
+        );
+        MetricsSnapshot {
+            version: "0.2.0".to_string(),
+            build_hash: "abc\"1".to_string(),
+            protocol_version: 2,
+            active_connections: 1,
+            max_active_connections: 64,
+            rejected_connections: 4,
+            subscribers: 0,
+            max_subscribers: 16,
+            state_revision: Some(42),
+            dead_session_reconcile,
+            hem_shadow: state.hem_shadow_metrics_snapshot(),
+            routing: state.routing_metrics_snapshot(),
+            backoff: None,
+            retention: state.retention_status_snapshot(),
+            writer: WriterHealth {
+                queue_capacity: 256,
+                queued: 0,
+                max_batch_events: 512,
+                batches: 10,
+                events_written: 25,
+                largest_batch: 7,
+                busy_rejections: 0,
+                failed_batches: 0,
+                rejected_events: 0,
+                skipped_events: 0,
+                last_batch_micros: 1_500,
+            },
+            project_view: state.project_view_status(),
+        }
+    }
+
+    #[test]
+    fn renders_openmetrics_with_counter_suffixes_and_eof() {
+        let text = render(&snapshot(), Format::OpenMetrics);
+
+        assert!(text.contains("# TYPE capacitor_daemon_connections_rejected counter\n"));
+        assert!(text.contains("\ncapacitor_daemon_connections_rejected_total 4\n"));
+        assert!(text.contains("# TYPE capacitor_daemon_build info\n"));
+        assert!(text.contains(
+            "capacitor_daemon_build_info{version=\"0.2.0\",build_hash=\"abc\\\"1\",protocol_version=\"2\"} 1\n"
+        ));
+        assert!(text.contains(
+            "capacitor_daemon_dead_session_reconcile_runs_total{source=\"periodic\"} 3\n"
+        ));
+        assert!(text.contains(
+            "capacitor_daemon_dead_session_reconcile_last_run_timestamp_seconds{source=\"periodic\"} 1771081200\n"
+        ));
+        assert!(text.contains("capacitor_daemon_writer_last_batch_seconds 0.0015\n"));
+        assert!(text.ends_with("\n# EOF\n"));
+
+        // Every family is declared once, before its samples.
+        let families = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE "))
+            .map(|line| line.split(' ').next().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let unique = families.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(families.len(), unique.len());
+    }
+
+    #[test]
+    fn renders_prometheus_text_for_the_textfile_collector() {
+        let text = render(&snapshot(), Format::Prometheus);
+
+        assert!(text.contains("# TYPE capacitor_daemon_connections_rejected_total counter\n"));
+        assert!(text.contains("# TYPE capacitor_daemon_build_info gauge\n"));
+        assert!(!text.contains("# EOF"));
+
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("capacitor.prom");
+        write_textfile(&path, &text).expect("write textfile");
+        assert_eq!(std::fs::read_to_string(&path).expect("read"), text);
+        assert!(!temp_dir.path().join("capacitor.prom.tmp").exists());
+
+        let config = MetricsConfig {
+            textfile_path: Some(PathBuf::from("relative.prom")),
+            ..MetricsConfig::default()
+        };
+        assert!(config
+            .validate()
+            .expect_err("relative path")
+            .contains("metrics.textfile_path must be absolute"));
+    }
+}
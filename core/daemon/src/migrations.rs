@@ -0,0 +1,674 @@
+//! Versioned schema migrations for the daemon database.
+//!
+//! Migrations run in order, each in its own transaction that also records the
+//! new version under `schema_version` in `daemon_meta`. A database from a newer
+//! daemon is refused rather than written to, and an existing database is
+//! copied to `<db>.v<version>.bak` before its first pending migration runs.
+//!
+//! Migration 1 is the schema as it stood before versioning. Databases from
+//! then have no `schema_version`, so it is written to be idempotent and brings
+//! any of them up to the same shape. Each later migration adds one feature's
+//! tables or columns and can assume the schema of the one before it.
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use std::path::{Path, PathBuf};
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub apply: fn(&Transaction<'_>) -> Result<(), String>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        apply: initial_schema,
+    },
+    Migration {
+        version: 2,
+        name: "snapshot_revisions",
+        apply: snapshot_revisions,
+    },
+    Migration {
+        version: 3,
+        name: "subagents",
+        apply: subagents,
+    },
+    Migration {
+        version: 4,
+        name: "session_transitions",
+        apply: session_transitions,
+    },
+    Migration {
+        version: 5,
+        name: "human_waits",
+        apply: human_waits,
+    },
+    Migration {
+        version: 6,
+        name: "tool_calls",
+        apply: tool_calls,
+    },
+    Migration {
+        version: 7,
+        name: "sessions_pending_request",
+        apply: sessions_pending_request,
+    },
+    Migration {
+        version: 8,
+        name: "worktrees",
+        apply: worktrees,
+    },
+    Migration {
+        version: 9,
+        name: "config_changes",
+        apply: config_changes,
+    },
+    Migration {
+        version: 10,
+        name: "teams",
+        apply: teams,
+    },
+    Migration {
+        version: 11,
+        name: "retention_snapshot",
+        apply: retention_snapshot,
+    },
+];
+
+/// Applies every migration newer than the database's recorded version and
+/// returns the resulting version.
+pub fn migrate(
+    conn: &mut Connection,
+    db_path: &Path,
+    migrations: &[Migration],
+) -> Result<u32, String> {
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    let mut version = schema_version(conn)?;
+    if version > latest {
+        return Err(format!(
+            "Database {} has schema version {}, newer than the {} this daemon supports; \
+             refusing to open it",
+            db_path.display(),
+            version,
+            latest
+        ));
+    }
+
+    let pending: Vec<&Migration> = migrations
+        .iter()
+        .filter(|migration| migration.version > version)
+        .collect();
+    if pending.is_empty() {
+        return Ok(version);
+    }
+    if has_tables(conn)? {
+        let backup = backup_path(db_path, version);
+        backup_database(conn, &backup)?;
+        tracing::info!(
+            backup = %backup.display(),
+            from_version = version,
+            to_version = latest,
+            "Backed up daemon database before migrating"
+        );
+    }
+
+    for migration in pending {
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|err| format!("Failed to start migration transaction: {}", err))?;
+        (migration.apply)(&tx).map_err(|err| {
+            format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.name, err
+            )
+        })?;
+        set_schema_version(&tx, migration.version)?;
+        tx.commit().map_err(|err| {
+            format!(
+                "Failed to commit migration {} ({}): {}",
+                migration.version, migration.name, err
+            )
+        })?;
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+pub fn schema_version(conn: &Connection) -> Result<u32, String> {
+    let has_meta: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'daemon_meta')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|err| format!("Failed to inspect daemon_meta: {}", err))?;
+    if !has_meta {
+        return Ok(0);
+    }
+
+    let raw: Option<String> = conn
+        .query_row(
+            "SELECT value FROM daemon_meta WHERE key = ?1",
+            params![SCHEMA_VERSION_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read schema version: {}", err))?;
+    match raw {
+        Some(value) => value
+            .parse::<u32>()
+            .map_err(|err| format!("Failed to parse schema version '{}': {}", value, err)),
+        None => Ok(0),
+    }
+}
+
+fn set_schema_version(conn: &Connection, version: u32) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO daemon_meta (key, value) VALUES (?1, ?2) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![SCHEMA_VERSION_KEY, version.to_string()],
+    )
+    .map_err(|err| format!("Failed to record schema version: {}", err))?;
+    Ok(())
+}
+
+fn has_tables(conn: &Connection) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|err| format!("Failed to inspect database tables: {}", err))
+}
+
+fn backup_path(db_path: &Path, version: u32) -> PathBuf {
+    let mut file_name = db_path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    file_name.push(format!(".v{}.bak", version));
+    db_path.with_file_name(file_name)
+}
+
+/// Writes a consistent copy of the database, including WAL contents.
+fn backup_database(conn: &Connection, backup: &Path) -> Result<(), String> {
+    if backup.exists() {
+        std::fs::remove_file(backup)
+            .map_err(|err| format!("Failed to replace old backup {}: {}", backup.display(), err))?;
+    }
+    conn.execute(
+        "VACUUM INTO ?1",
+        params![backup.to_string_lossy().to_string()],
+    )
+    .map_err(|err| {
+        format!(
+            "Failed to back up database to {} before migrating: {}",
+            backup.display(),
+            err
+        )
+    })?;
+    Ok(())
+}
+
+fn initial_schema(tx: &Transaction<'_>) -> Result<(), String> {
+    tx.execute_batch(INITIAL_SCHEMA)
+        .map_err(|err| format!("Failed to create tables: {}", err))?;
+    ensure_sessions_columns(tx)
+}
+
+/// The schema as it stood when versioning was introduced.
+const INITIAL_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+       id TEXT PRIMARY KEY,
+       recorded_at TEXT NOT NULL,
+       event_type TEXT NOT NULL,
+       session_id TEXT,
+       pid INTEGER,
+       payload TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS shell_state (
+       pid INTEGER PRIMARY KEY,
+       cwd TEXT NOT NULL,
+       tty TEXT NOT NULL,
+       parent_app TEXT,
+       tmux_session TEXT,
+       tmux_client_tty TEXT,
+       updated_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS process_liveness (
+       pid INTEGER PRIMARY KEY,
+       proc_started INTEGER,
+       last_seen_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sessions (
+       session_id TEXT PRIMARY KEY,
+       pid INTEGER NOT NULL DEFAULT 0,
+       state TEXT NOT NULL,
+       cwd TEXT NOT NULL,
+       project_id TEXT,
+       project_path TEXT,
+       updated_at TEXT NOT NULL,
+       state_changed_at TEXT NOT NULL,
+       last_event TEXT,
+       last_activity_at TEXT,
+       tools_in_flight INTEGER NOT NULL DEFAULT 0,
+       ready_reason TEXT
+    );
+    CREATE TABLE IF NOT EXISTS activity (
+       session_id TEXT NOT NULL,
+       project_path TEXT NOT NULL,
+       file_path TEXT NOT NULL,
+       tool_name TEXT,
+       recorded_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tombstones (
+       session_id TEXT PRIMARY KEY,
+       created_at TEXT NOT NULL,
+       expires_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS hem_shadow_mismatches (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       observed_at TEXT NOT NULL,
+       event_id TEXT,
+       session_id TEXT,
+       project_id TEXT,
+       project_path TEXT,
+       category TEXT NOT NULL,
+       reducer_state TEXT,
+       hem_state TEXT,
+       confidence_delta REAL,
+       detail_json TEXT
+    );
+    CREATE TABLE IF NOT EXISTS routing_rollout_state (
+       id INTEGER PRIMARY KEY CHECK (id = 1),
+       dual_run_comparisons INTEGER NOT NULL,
+       legacy_vs_are_status_mismatch INTEGER NOT NULL,
+       legacy_vs_are_target_mismatch INTEGER NOT NULL,
+       first_comparison_at TEXT,
+       last_comparison_at TEXT,
+       last_snapshot_at TEXT
+    );
+    CREATE TABLE IF NOT EXISTS daemon_meta (
+       key TEXT PRIMARY KEY,
+       value TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_hem_shadow_mismatches_observed_at
+       ON hem_shadow_mismatches(observed_at);
+    CREATE INDEX IF NOT EXISTS idx_hem_shadow_mismatches_category
+       ON hem_shadow_mismatches(category);
+";
+
+/// Revision each snapshot entry last changed at, behind `since_revision`
+/// deltas.
+fn snapshot_revisions(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "snapshot_revisions",
+        "CREATE TABLE snapshot_revisions (
+            kind TEXT NOT NULL,
+            entry_key TEXT NOT NULL,
+            revision INTEGER NOT NULL,
+            removed INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (kind, entry_key)
+         );",
+    )
+}
+
+fn subagents(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "subagents",
+        "CREATE TABLE subagents (
+            session_id TEXT NOT NULL,
+            agent_id TEXT NOT NULL,
+            agent_type TEXT,
+            started_at TEXT,
+            stopped_at TEXT,
+            transcript_path TEXT,
+            tools TEXT NOT NULL DEFAULT '[]',
+            tool_calls INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (session_id, agent_id)
+         );",
+    )
+}
+
+fn session_transitions(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "session_transitions",
+        "CREATE TABLE session_transitions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            from_state TEXT,
+            to_state TEXT,
+            event_id TEXT,
+            transitioned_at TEXT NOT NULL
+         );
+         CREATE INDEX idx_session_transitions_session
+            ON session_transitions(session_id, id);",
+    )
+}
+
+fn human_waits(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "human_waits",
+        "CREATE TABLE human_waits (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            started_at TEXT NOT NULL,
+            ended_at TEXT
+         );
+         CREATE INDEX idx_human_waits_session
+            ON human_waits(session_id, ended_at);",
+    )
+}
+
+fn tool_calls(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "tool_calls",
+        "CREATE TABLE tool_calls (
+            session_id TEXT NOT NULL,
+            tool_use_id TEXT NOT NULL,
+            tool_name TEXT NOT NULL,
+            file_path TEXT,
+            project_path TEXT NOT NULL,
+            agent_id TEXT,
+            started_at TEXT NOT NULL,
+            ended_at TEXT,
+            duration_ms INTEGER,
+            outcome TEXT,
+            PRIMARY KEY (session_id, tool_use_id)
+         );
+         CREATE INDEX idx_tool_calls_project
+            ON tool_calls(project_path, started_at);",
+    )
+}
+
+/// The permission request a waiting session is blocked on.
+fn sessions_pending_request(tx: &Transaction<'_>) -> Result<(), String> {
+    tx.execute_batch("ALTER TABLE sessions ADD COLUMN pending_request TEXT;")
+        .map_err(|err| format!("Failed to add pending_request column: {}", err))
+}
+
+fn worktrees(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "worktrees",
+        "CREATE TABLE worktrees (
+            path TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            name TEXT,
+            branch TEXT,
+            created_by_session_id TEXT,
+            created_at TEXT NOT NULL
+         );",
+    )
+}
+
+fn config_changes(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "config_changes",
+        "CREATE TABLE config_changes (
+            event_id TEXT PRIMARY KEY,
+            session_id TEXT,
+            project_path TEXT,
+            source TEXT,
+            file_path TEXT,
+            content_hash TEXT,
+            hud_hook_hash TEXT,
+            content_changed INTEGER NOT NULL DEFAULT 0,
+            hud_hook_changed INTEGER NOT NULL DEFAULT 0,
+            recorded_at TEXT NOT NULL
+         );
+         CREATE INDEX idx_config_changes_file
+            ON config_changes(file_path, recorded_at);",
+    )
+}
+
+/// Agent teams, their teammates and completed tasks.
+fn teams(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "team tables",
+        "CREATE TABLE teams (
+            team_name TEXT PRIMARY KEY,
+            lead_session_id TEXT,
+            project_path TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+         );
+         CREATE TABLE teammates (
+            team_name TEXT NOT NULL,
+            name TEXT NOT NULL,
+            session_id TEXT,
+            state TEXT NOT NULL,
+            last_event TEXT,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (team_name, name)
+         );
+         CREATE TABLE team_tasks (
+            event_id TEXT PRIMARY KEY,
+            team_name TEXT NOT NULL,
+            task_id TEXT,
+            task_subject TEXT,
+            teammate_name TEXT,
+            completed_at TEXT NOT NULL
+         );",
+    )
+}
+
+/// Replay base written before retention prunes events.
+fn retention_snapshot(tx: &Transaction<'_>) -> Result<(), String> {
+    create(
+        tx,
+        "retention_snapshot",
+        "CREATE TABLE retention_snapshot (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            event_rowid INTEGER NOT NULL,
+            sessions TEXT NOT NULL,
+            shell_state TEXT NOT NULL,
+            derived TEXT NOT NULL,
+            created_at TEXT NOT NULL
+         );",
+    )
+}
+
+fn create(tx: &Transaction<'_>, what: &str, sql: &str) -> Result<(), String> {
+    tx.execute_batch(sql)
+        .map_err(|err| format!("Failed to create {}: {}", what, err))
+}
+
+/// Columns added to `sessions` before migrations existed.
+fn ensure_sessions_columns(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("PRAGMA table_info(sessions)")
+        .map_err(|err| format!("Failed to read sessions schema: {}", err))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|err| format!("Failed to read sessions schema rows: {}", err))?;
+
+    let mut columns = Vec::new();
+    for row in rows {
+        columns.push(row.map_err(|err| format!("Failed to decode schema row: {}", err))?);
+    }
+
+    if !columns.iter().any(|name| name == "project_path") {
+        conn.execute("ALTER TABLE sessions ADD COLUMN project_path TEXT", [])
+            .map_err(|err| format!("Failed to add project_path column: {}", err))?;
+    }
+
+    if !columns.iter().any(|name| name == "project_id") {
+        conn.execute("ALTER TABLE sessions ADD COLUMN project_id TEXT", [])
+            .map_err(|err| format!("Failed to add project_id column: {}", err))?;
+    }
+
+    if !columns.iter().any(|name| name == "pid") {
+        conn.execute(
+            "ALTER TABLE sessions ADD COLUMN pid INTEGER NOT NULL DEFAULT 0",
+            [],
+        )
+        .map_err(|err| format!("Failed to add pid column: {}", err))?;
+    }
+
+    if !columns.iter().any(|name| name == "last_activity_at") {
+        conn.execute("ALTER TABLE sessions ADD COLUMN last_activity_at TEXT", [])
+            .map_err(|err| format!("Failed to add last_activity_at column: {}", err))?;
+    }
+
+    if !columns.iter().any(|name| name == "tools_in_flight") {
+        conn.execute(
+            "ALTER TABLE sessions ADD COLUMN tools_in_flight INTEGER NOT NULL DEFAULT 0",
+            [],
+        )
+        .map_err(|err| format!("Failed to add tools_in_flight column: {}", err))?;
+    }
+
+    if !columns.iter().any(|name| name == "ready_reason") {
+        conn.execute("ALTER TABLE sessions ADD COLUMN ready_reason TEXT", [])
+            .map_err(|err| format!("Failed to add ready_reason column: {}", err))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_notes(tx: &Transaction<'_>) -> Result<(), String> {
+        tx.execute_batch("CREATE TABLE notes (body TEXT NOT NULL);")
+            .map_err(|err| err.to_string())
+    }
+
+    fn broken(tx: &Transaction<'_>) -> Result<(), String> {
+        tx.execute_batch("CREATE TABLE half_done (id INTEGER); SELECT * FROM missing_table;")
+            .map_err(|err| err.to_string())
+    }
+
+    fn table_names(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .expect("prepare");
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .expect("query")
+            .collect::<Result<Vec<_>, _>>()
+            .expect("names");
+        names
+    }
+
+    #[test]
+    fn migrates_legacy_database_after_backing_it_up() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let mut conn = Connection::open(&path).expect("open");
+        conn.execute_batch(
+            "CREATE TABLE sessions (
+                session_id TEXT PRIMARY KEY,
+                state TEXT NOT NULL,
+                cwd TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                state_changed_at TEXT NOT NULL,
+                last_event TEXT
+             );
+             INSERT INTO sessions VALUES ('s1', 'ready', '/repo', 't', 't', NULL);",
+        )
+        .expect("seed legacy schema");
+
+        assert_eq!(migrate(&mut conn, &path, MIGRATIONS).expect("migrate"), 11);
+        assert_eq!(schema_version(&conn).expect("version"), 11);
+        let pid: i64 = conn
+            .query_row(
+                "SELECT pid FROM sessions WHERE session_id = 's1'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("patched column");
+        assert_eq!(pid, 0);
+        let pending_request: Option<String> = conn
+            .query_row(
+                "SELECT pending_request FROM sessions WHERE session_id = 's1'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("column from a later migration");
+        assert_eq!(pending_request, None);
+        let tables = table_names(&conn);
+        assert!(tables.contains(&"events".to_string()));
+        assert!(tables.contains(&"retention_snapshot".to_string()));
+
+        let backup = Connection::open(temp_dir.path().join("state.db.v0.bak")).expect("backup");
+        assert_eq!(table_names(&backup), vec!["sessions".to_string()]);
+
+        // Already current: nothing runs and no new backup is written.
+        std::fs::remove_file(temp_dir.path().join("state.db.v0.bak")).expect("remove");
+        assert_eq!(migrate(&mut conn, &path, MIGRATIONS).expect("rerun"), 11);
+        assert!(!temp_dir.path().join("state.db.v0.bak").exists());
+    }
+
+    #[test]
+    fn runs_pending_migrations_in_order_and_rolls_back_failures() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let mut conn = Connection::open(&path).expect("open");
+        let with_notes = [
+            Migration {
+                version: 1,
+                name: "initial_schema",
+                apply: initial_schema,
+            },
+            Migration {
+                version: 2,
+                name: "add_notes",
+                apply: add_notes,
+            },
+            Migration {
+                version: 3,
+                name: "broken",
+                apply: broken,
+            },
+        ];
+        assert_eq!(
+            migrate(&mut conn, &path, &with_notes[..1]).expect("fresh"),
+            1
+        );
+        assert!(!temp_dir.path().join("state.db.v0.bak").exists());
+
+        let error = migrate(&mut conn, &path, &with_notes).expect_err("broken migration");
+        assert!(
+            error.starts_with("Migration 3 (broken) failed"),
+            "{}",
+            error
+        );
+        assert_eq!(schema_version(&conn).expect("version"), 2);
+        let tables = table_names(&conn);
+        assert!(tables.contains(&"notes".to_string()));
+        assert!(!tables.contains(&"half_done".to_string()));
+        assert!(temp_dir.path().join("state.db.v1.bak").exists());
+    }
+
+    #[test]
+    fn refuses_database_from_newer_daemon() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let mut conn = Connection::open(&path).expect("open");
+        migrate(&mut conn, &path, MIGRATIONS).expect("migrate");
+        set_schema_version(&conn, 99).expect("bump version");
+
+        let error = migrate(&mut conn, &path, MIGRATIONS).expect_err("newer schema");
+        assert!(error.contains("schema version 99"), "{}", error);
+        assert_eq!(schema_version(&conn).expect("version"), 99);
+    }
+}
@@ -0,0 +1,214 @@
+//! What a `waiting` session is waiting for.
+//!
+//! The hook input behind `permission_request` and `permission_prompt` /
+//! `elicitation_dialog` notifications names the tool, its input, a message and
+//! Claude's permission suggestions. hud-hook forwards those as event metadata;
+//! this module keeps them on the session until it leaves `Waiting`.
+
+use capacitor_daemon_protocol::{EventEnvelope, EventType, PendingRequest, PendingRequestKind};
+use serde_json::Value;
+
+use crate::reducer::{SessionRecord, SessionState};
+
+const MAX_DETAIL_CHARS: usize = 200;
+/// Tool input fields worth showing, most specific first.
+const DETAIL_FIELDS: &[&str] = &[
+    "command",
+    "url",
+    "pattern",
+    "query",
+    "file_path",
+    "path",
+    "description",
+    "prompt",
+];
+
+/// The pending request for a session moving to `new_state` on `event`. A
+/// permission request and the notification that usually follows it describe
+/// the same prompt, so they are merged rather than replaced.
+pub fn reduce_pending_request(
+    current: Option<&SessionRecord>,
+    event: &EventEnvelope,
+    new_state: &SessionState,
+) -> Option<PendingRequest> {
+    if *new_state != SessionState::Waiting {
+        return None;
+    }
+    let existing = current
+        .filter(|record| record.state == SessionState::Waiting)
+        .and_then(|record| record.pending_request.clone());
+    let Some(incoming) = pending_request_from_event(event) else {
+        return existing;
+    };
+    let Some(existing) = existing else {
+        return Some(incoming);
+    };
+    Some(PendingRequest {
+        kind: existing.kind,
+        tool_name: existing.tool_name.or(incoming.tool_name),
+        file_path: existing.file_path.or(incoming.file_path),
+        detail: existing.detail.or(incoming.detail),
+        message: incoming.message.or(existing.message),
+        permission_suggestions: existing
+            .permission_suggestions
+            .or(incoming.permission_suggestions),
+        requested_at: existing.requested_at,
+    })
+}
+
+fn pending_request_from_event(event: &EventEnvelope) -> Option<PendingRequest> {
+    let kind = match (event.event_type, event.notification_type.as_deref()) {
+        (EventType::PermissionRequest, _) => PendingRequestKind::PermissionRequest,
+        (EventType::Notification, Some("permission_prompt")) => {
+            PendingRequestKind::PermissionPrompt
+        }
+        (EventType::Notification, Some("elicitation_dialog")) => {
+            PendingRequestKind::ElicitationDialog
+        }
+        _ => return None,
+    };
+    let metadata = event.metadata.as_ref();
+    let tool_name = event
+        .tool
+        .as_deref()
+        .or_else(|| metadata_str(metadata, "tool_name"))
+        .map(str::trim)
+        .filter(|tool| !tool.is_empty())
+        .map(str::to_string);
+    let detail = metadata
+        .and_then(|value| value.get("tool_input"))
+        .and_then(tool_input_detail);
+
+    Some(PendingRequest {
+        kind,
+        tool_name,
+        file_path: event.file_path.clone(),
+        detail,
+        message: metadata_str(metadata, "message").map(str::to_string),
+        permission_suggestions: metadata
+            .and_then(|value| value.get("permission_suggestions"))
+            .filter(|value| !value.is_null())
+            .cloned(),
+        requested_at: event.recorded_at.clone(),
+    })
+}
+
+/// First line of the most telling tool input field, shortened for display.
+fn tool_input_detail(tool_input: &Value) -> Option<String> {
+    let value = DETAIL_FIELDS
+        .iter()
+        .filter_map(|field| tool_input.get(field).and_then(Value::as_str))
+        .map(str::trim)
+        .find(|value| !value.is_empty())?;
+    let line = value.lines().next().unwrap_or_default().trim_end();
+    let mut detail: String = line.chars().take(MAX_DETAIL_CHARS).collect();
+    if detail.len() < value.len() {
+        detail.push('…');
+    }
+    Some(detail)
+}
+
+fn metadata_str<'a>(metadata: Option<&'a Value>, key: &str) -> Option<&'a str> {
+    metadata
+        .and_then(|value| value.get(key))
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(
+        event_type: EventType,
+        notification_type: Option<&str>,
+        metadata: Value,
+    ) -> EventEnvelope {
+        EventEnvelope {
+            event_id: "evt-1".to_string(),
+            recorded_at: "2026-01-31T00:00:00Z".to_string(),
+            event_type,
+            session_id: Some("session-1".to_string()),
+            pid: Some(1234),
+            cwd: Some("/repo".to_string()),
+            tool: None,
+            file_path: None,
+            parent_app: None,
+            tty: None,
+            tmux_session: None,
+            tmux_client_tty: None,
+            notification_type: notification_type.map(str::to_string),
+            stop_hook_active: None,
+            metadata: Some(metadata),
+        }
+    }
+
+    #[test]
+    fn merges_permission_request_with_following_prompt() {
+        let mut request = event(
+            EventType::PermissionRequest,
+            None,
+            serde_json::json!({
+                "tool_input": { "command": "cargo publish\ncargo yank", "description": "Publish" },
+                "permission_suggestions": [{ "type": "toolAlwaysAllow", "tool": "Bash" }]
+            }),
+        );
+        request.tool = Some("Bash".to_string());
+        let pending = reduce_pending_request(None, &request, &SessionState::Waiting)
+            .expect("pending request");
+        assert_eq!(pending.kind, PendingRequestKind::PermissionRequest);
+        assert_eq!(pending.tool_name.as_deref(), Some("Bash"));
+        assert_eq!(pending.detail.as_deref(), Some("cargo publish…"));
+        assert!(pending.permission_suggestions.is_some());
+
+        let current = SessionRecord {
+            session_id: "session-1".to_string(),
+            pid: 1234,
+            state: SessionState::Waiting,
+            cwd: "/repo".to_string(),
+            project_id: "/repo".to_string(),
+            project_path: "/repo".to_string(),
+            updated_at: "2026-01-31T00:00:00Z".to_string(),
+            state_changed_at: "2026-01-31T00:00:00Z".to_string(),
+            last_event: Some("permission_request".to_string()),
+            last_activity_at: None,
+            tools_in_flight: 0,
+            ready_reason: None,
+            pending_request: Some(pending),
+        };
+        let mut prompt = event(
+            EventType::Notification,
+            Some("permission_prompt"),
+            serde_json::json!({ "message": "Claude needs your permission to use Bash" }),
+        );
+        prompt.recorded_at = "2026-01-31T00:00:01Z".to_string();
+        let merged = reduce_pending_request(Some(&current), &prompt, &SessionState::Waiting)
+            .expect("merged request");
+        assert_eq!(merged.kind, PendingRequestKind::PermissionRequest);
+        assert_eq!(merged.detail.as_deref(), Some("cargo publish…"));
+        assert_eq!(
+            merged.message.as_deref(),
+            Some("Claude needs your permission to use Bash")
+        );
+        assert_eq!(merged.requested_at, "2026-01-31T00:00:00Z");
+
+        let resumed = event(EventType::PostToolUse, None, serde_json::json!({}));
+        assert!(reduce_pending_request(Some(&current), &resumed, &SessionState::Working).is_none());
+    }
+
+    #[test]
+    fn elicitation_dialog_keeps_message_only() {
+        let dialog = event(
+            EventType::Notification,
+            Some("elicitation_dialog"),
+            serde_json::json!({ "message": "Pick a deployment target" }),
+        );
+        let pending =
+            reduce_pending_request(None, &dialog, &SessionState::Waiting).expect("pending request");
+        assert_eq!(pending.kind, PendingRequestKind::ElicitationDialog);
+        assert_eq!(pending.tool_name, None);
+        assert_eq!(pending.detail, None);
+        assert_eq!(pending.message.as_deref(), Some("Pick a deployment target"));
+    }
+}
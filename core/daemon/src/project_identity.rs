@@ -47,6 +47,27 @@ pub fn workspace_id(project_id: &str, project_path: &str) -> String {
     format!("{:x}", md5::compute(source))
 }
 
+/// Root of the main checkout of the repository containing `path`.
+pub fn repo_root(path: &str) -> Option<String> {
+    resolve_git_info(Path::new(path)).map(|info| path_to_string(&info.repo_root))
+}
+
+/// Branch checked out in the worktree (or main checkout) rooted at `path`;
+/// `None` for a detached HEAD.
+pub fn worktree_branch(path: &str) -> Option<String> {
+    let root = Path::new(path);
+    let git_entry = root.join(".git");
+    let git_dir = if git_entry.is_dir() {
+        git_entry
+    } else {
+        parse_gitdir(&git_entry, root)?
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(str::to_string)
+}
+
 fn workspace_relative_path(project_id: &Path, project_path: &Path) -> String {
     let repo_root = repo_root_from_project_id(project_id);
     if let Some(repo_root) = repo_root {
@@ -158,6 +179,10 @@ fn canonicalize_worktree_path(path: &Path, git_info: &GitInfo) -> PathBuf {
     let normalized_path = canonicalize_path(path);
     let worktree_root = canonicalize_path(&git_info.worktree_root);
     if let Ok(relative) = normalized_path.strip_prefix(&worktree_root) {
+        // `join("")` would leave a trailing slash on the worktree root itself.
+        if relative.as_os_str().is_empty() {
+            return git_info.repo_root.clone();
+        }
         return git_info.repo_root.join(relative);
     }
     normalized_path
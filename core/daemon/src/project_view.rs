@@ -0,0 +1,394 @@
+//! Materialized project states served to `get_project_states`.
+//!
+//! Aggregating projects means reading every session together with its tool
+//! and liveness rows, which used to happen on every request. The view keeps
+//! the last aggregates in memory instead: mutations mark the projects they
+//! touched and refresh just those before releasing the mutation lock, and a
+//! timer refreshes everything every `PROJECT_VIEW_REFRESH_SECS` so process
+//! liveness and time-based fallbacks (inactivity, TTL expiry) are picked up
+//! without an event. Reads clone the view without touching the database.
+//!
+//! The view also remembers the revision each project last changed or was
+//! removed at, so `since_revision` deltas are answered from memory too.
+//! Removals are forgotten after `SNAPSHOT_REMOVAL_RETENTION_HOURS`, like the
+//! database's, raising the oldest revision a delta can start from.
+
+use capacitor_daemon_protocol::ProjectViewHealth;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use crate::db::SNAPSHOT_REMOVAL_RETENTION_HOURS;
+use crate::state::ProjectState;
+
+/// How often liveness and time-based fallbacks are re-evaluated.
+pub const PROJECT_VIEW_REFRESH_SECS: u64 = 2;
+
+/// What the next refresh has to recompute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PendingRefresh {
+    All,
+    Projects(HashSet<String>),
+}
+
+/// Projects whose aggregate changed in a refresh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectViewChanges {
+    pub upserted: Vec<ProjectState>,
+    pub removed: Vec<String>,
+}
+
+impl ProjectViewChanges {
+    pub fn is_empty(&self) -> bool {
+        self.upserted.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Projects changed or removed after a revision; see
+/// [`ProjectStatesView::read_since`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectViewDelta {
+    /// Revision the view's contents were recorded at.
+    pub revision: u64,
+    /// `changed` holds every project and `removed` is empty.
+    pub full: bool,
+    pub changed: Vec<ProjectState>,
+    pub removed: Vec<String>,
+}
+
+/// A refresh worked out by [`ProjectStatesView::plan`], not yet stored.
+#[derive(Debug, Clone)]
+pub struct ProjectViewUpdate {
+    refreshed: PendingRefresh,
+    next: BTreeMap<String, ProjectState>,
+    pub changes: ProjectViewChanges,
+}
+
+#[derive(Debug, Default)]
+pub struct ProjectStatesView {
+    states: BTreeMap<String, ProjectState>,
+    /// Revision each project in `states` last changed at.
+    changed_at: BTreeMap<String, u64>,
+    /// Revision and time each project was removed at.
+    removed_at: BTreeMap<String, (u64, DateTime<Utc>)>,
+    /// Deltas can start from this revision or later.
+    floor: Option<u64>,
+    populated: bool,
+    dirty_all: bool,
+    dirty_projects: HashSet<String>,
+    revision: Option<u64>,
+    status: ProjectViewHealth,
+}
+
+impl ProjectStatesView {
+    pub fn mark_project(&mut self, project_path: &str) {
+        if !project_path.trim().is_empty() {
+            self.dirty_projects.insert(project_path.to_string());
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        self.dirty_all = true;
+    }
+
+    /// `None` when the view is current.
+    pub fn pending(&self) -> Option<PendingRefresh> {
+        if !self.populated || self.dirty_all {
+            Some(PendingRefresh::All)
+        } else if self.dirty_projects.is_empty() {
+            None
+        } else {
+            Some(PendingRefresh::Projects(self.dirty_projects.clone()))
+        }
+    }
+
+    /// Works out what storing `states`, the fresh aggregates for what
+    /// `refreshed` covers, would change. Projects in `refreshed` without a
+    /// fresh aggregate are dropped. Nothing changes until [`Self::apply`].
+    pub fn plan(&self, refreshed: &PendingRefresh, states: Vec<ProjectState>) -> ProjectViewUpdate {
+        let mut next: BTreeMap<String, ProjectState> = match refreshed {
+            PendingRefresh::All => BTreeMap::new(),
+            PendingRefresh::Projects(paths) => self
+                .states
+                .iter()
+                .filter(|(path, _)| !paths.contains(*path))
+                .map(|(path, state)| (path.clone(), state.clone()))
+                .collect(),
+        };
+        for state in states {
+            next.insert(state.project_path.clone(), state);
+        }
+
+        let mut changes = ProjectViewChanges::default();
+        for (path, state) in &next {
+            if self.states.get(path) != Some(state) {
+                changes.upserted.push(state.clone());
+            }
+        }
+        for path in self.states.keys() {
+            if !next.contains_key(path) {
+                changes.removed.push(path.clone());
+            }
+        }
+        ProjectViewUpdate {
+            refreshed: refreshed.clone(),
+            next,
+            changes,
+        }
+    }
+
+    /// Stores a planned update, recorded at state `revision`.
+    pub fn apply(
+        &mut self,
+        update: ProjectViewUpdate,
+        revision: u64,
+        now: DateTime<Utc>,
+        elapsed: Duration,
+    ) -> ProjectViewChanges {
+        self.states = update.next;
+        for state in &update.changes.upserted {
+            self.removed_at.remove(&state.project_path);
+            self.changed_at.insert(state.project_path.clone(), revision);
+        }
+        for project_path in &update.changes.removed {
+            self.changed_at.remove(project_path);
+            self.removed_at
+                .insert(project_path.clone(), (revision, now));
+        }
+        // Changes before the first refresh were never seen.
+        let floor = self.floor.get_or_insert(revision);
+        let cutoff = now - ChronoDuration::hours(SNAPSHOT_REMOVAL_RETENTION_HOURS);
+        self.removed_at.retain(|_, (removed_revision, removed_at)| {
+            let keep = *removed_at >= cutoff;
+            if !keep {
+                *floor = (*floor).max(*removed_revision);
+            }
+            keep
+        });
+        self.populated = true;
+        self.revision = Some(revision);
+        match &update.refreshed {
+            PendingRefresh::All => {
+                self.dirty_all = false;
+                self.dirty_projects.clear();
+                self.status.full_refreshes += 1;
+            }
+            PendingRefresh::Projects(paths) => {
+                self.dirty_projects.retain(|path| !paths.contains(path));
+                self.status.partial_refreshes += 1;
+            }
+        }
+        self.status.last_refresh_at = Some(now.to_rfc3339());
+        self.status.last_refresh_micros = elapsed.as_micros() as u64;
+        update.changes
+    }
+
+    pub fn is_populated(&self) -> bool {
+        self.populated
+    }
+
+    /// Projects changed or removed after revision `since`, sorted by path.
+    /// Every project comes back (`full`) without `since`, or when the view
+    /// cannot tell what changed: `since` is older than the floor or newer
+    /// than the view. `None` while a refresh is pending.
+    pub fn read_since(&mut self, since: Option<u64>) -> Option<ProjectViewDelta> {
+        if self.pending().is_some() {
+            return None;
+        }
+        self.status.reads += 1;
+        let revision = self.revision.unwrap_or_default();
+        let tracked = since
+            .filter(|since| self.floor.is_some_and(|floor| *since >= floor) && *since <= revision);
+        let Some(since) = tracked else {
+            return Some(ProjectViewDelta {
+                revision,
+                full: true,
+                changed: self.states.values().cloned().collect(),
+                removed: Vec::new(),
+            });
+        };
+        Some(ProjectViewDelta {
+            revision,
+            full: false,
+            changed: self
+                .states
+                .values()
+                .filter(|state| {
+                    self.changed_at
+                        .get(&state.project_path)
+                        .is_some_and(|changed| *changed > since)
+                })
+                .cloned()
+                .collect(),
+            removed: self
+                .removed_at
+                .iter()
+                .filter(|(_, (removed, _))| *removed > since)
+                .map(|(project_path, _)| project_path.clone())
+                .collect(),
+        })
+    }
+
+    pub fn status(&self) -> ProjectViewHealth {
+        ProjectViewHealth {
+            projects: self.states.len(),
+            refresh_interval_secs: PROJECT_VIEW_REFRESH_SECS,
+            revision: self.revision,
+            ..self.status.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducer::SessionState;
+
+    fn project(path: &str, state: SessionState) -> ProjectState {
+        ProjectState {
+            project_id: path.to_string(),
+            workspace_id: path.to_string(),
+            project_path: path.to_string(),
+            state,
+            state_changed_at: "2026-02-14T09:00:00Z".to_string(),
+            updated_at: "2026-02-14T09:00:00Z".to_string(),
+            session_id: Some(format!("{}-session", path)),
+            latest_session_id: None,
+            session_count: 1,
+            active_count: 1,
+            has_session: true,
+            pending_request: None,
+            worktrees: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    fn refresh(
+        view: &mut ProjectStatesView,
+        refreshed: &PendingRefresh,
+        states: Vec<ProjectState>,
+    ) -> ProjectViewChanges {
+        let update = view.plan(refreshed, states);
+        view.apply(update, 1, Utc::now(), Duration::ZERO)
+    }
+
+    #[test]
+    fn partial_refresh_replaces_only_marked_projects() {
+        let mut view = ProjectStatesView::default();
+        assert_eq!(view.pending(), Some(PendingRefresh::All));
+        assert!(view.read_since(None).is_none());
+
+        let changes = refresh(
+            &mut view,
+            &PendingRefresh::All,
+            vec![
+                project("/b", SessionState::Working),
+                project("/a", SessionState::Ready),
+            ],
+        );
+        assert_eq!(changes.upserted.len(), 2);
+        assert_eq!(view.pending(), None);
+        let read = view.read_since(None).expect("view populated");
+        assert!(read.full);
+        assert_eq!(
+            read.changed
+                .iter()
+                .map(|state| state.project_path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/a", "/b"]
+        );
+
+        view.mark_project("/b");
+        view.mark_project("/c");
+        let Some(pending @ PendingRefresh::Projects(_)) = view.pending() else {
+            panic!("expected partial refresh");
+        };
+        let changes = refresh(
+            &mut view,
+            &pending,
+            vec![project("/c", SessionState::Waiting)],
+        );
+        assert_eq!(changes.upserted, vec![project("/c", SessionState::Waiting)]);
+        assert_eq!(changes.removed, vec!["/b".to_string()]);
+        assert_eq!(view.pending(), None);
+        assert_eq!(view.status().projects, 2);
+
+        let changes = refresh(
+            &mut view,
+            &PendingRefresh::All,
+            vec![
+                project("/a", SessionState::Ready),
+                project("/c", SessionState::Waiting),
+            ],
+        );
+        assert!(changes.is_empty());
+        let status = view.status();
+        assert_eq!(status.full_refreshes, 2);
+        assert_eq!(status.partial_refreshes, 1);
+        assert_eq!(status.reads, 1);
+    }
+
+    #[test]
+    fn deltas_come_from_per_project_revisions() {
+        let mut view = ProjectStatesView::default();
+        let start = Utc::now();
+        let apply = |view: &mut ProjectStatesView,
+                     refreshed: PendingRefresh,
+                     states: Vec<ProjectState>,
+                     revision: u64,
+                     now: DateTime<Utc>| {
+            let update = view.plan(&refreshed, states);
+            view.apply(update, revision, now, Duration::ZERO);
+        };
+        let marked = |paths: &[&str]| {
+            PendingRefresh::Projects(paths.iter().map(|path| path.to_string()).collect())
+        };
+
+        apply(
+            &mut view,
+            PendingRefresh::All,
+            vec![
+                project("/a", SessionState::Ready),
+                project("/b", SessionState::Working),
+            ],
+            5,
+            start,
+        );
+        apply(
+            &mut view,
+            marked(&["/a"]),
+            vec![project("/a", SessionState::Working)],
+            7,
+            start,
+        );
+        apply(&mut view, marked(&["/b"]), Vec::new(), 9, start);
+
+        let delta = view.read_since(Some(5)).expect("view current");
+        assert!(!delta.full);
+        assert_eq!(delta.revision, 9);
+        assert_eq!(delta.changed, vec![project("/a", SessionState::Working)]);
+        assert_eq!(delta.removed, vec!["/b".to_string()]);
+
+        let delta = view.read_since(Some(7)).expect("view current");
+        assert!(delta.changed.is_empty());
+        assert_eq!(delta.removed, vec!["/b".to_string()]);
+        assert!(view.read_since(Some(4)).expect("before first refresh").full);
+        assert!(view.read_since(Some(10)).expect("ahead of view").full);
+
+        // An expired removal is forgotten, so deltas from before it are not
+        // exact any more.
+        let later = start + ChronoDuration::hours(SNAPSHOT_REMOVAL_RETENTION_HOURS + 1);
+        apply(
+            &mut view,
+            marked(&["/c"]),
+            vec![project("/c", SessionState::Ready)],
+            11,
+            later,
+        );
+        assert!(view.read_since(Some(7)).expect("view current").full);
+        let delta = view.read_since(Some(9)).expect("view current");
+        assert!(!delta.full);
+        assert_eq!(delta.changed, vec![project("/c", SessionState::Ready)]);
+        assert!(delta.removed.is_empty());
+    }
+}
@@ -1,11 +1,11 @@
-use capacitor_daemon_protocol::{EventEnvelope, EventType};
-use serde::Serialize;
+use capacitor_daemon_protocol::{EventEnvelope, EventType, PendingRequest};
+use serde::{Deserialize, Serialize};
 
+use crate::pending_request::reduce_pending_request;
 use crate::project_identity::resolve_project_identity;
+use crate::session_policy::SessionPolicy;
 
-const STALE_EVENT_GRACE_SECS: i64 = 5;
-
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SessionState {
     Working,
@@ -15,6 +15,18 @@ pub enum SessionState {
     Waiting,
 }
 
+impl From<SessionState> for capacitor_daemon_protocol::SessionState {
+    fn from(state: SessionState) -> Self {
+        match state {
+            SessionState::Working => Self::Working,
+            SessionState::Ready => Self::Ready,
+            SessionState::Idle => Self::Idle,
+            SessionState::Compacting => Self::Compacting,
+            SessionState::Waiting => Self::Waiting,
+        }
+    }
+}
+
 impl SessionState {
     fn is_active(&self) -> bool {
         matches!(
@@ -45,7 +57,7 @@ impl SessionState {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SessionRecord {
     pub session_id: String,
     pub pid: u32,
@@ -59,6 +71,7 @@ pub struct SessionRecord {
     pub last_activity_at: Option<String>,
     pub tools_in_flight: u32,
     pub ready_reason: Option<String>,
+    pub pending_request: Option<PendingRequest>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -69,7 +82,11 @@ pub enum SessionUpdate {
     Skip,
 }
 
-pub fn reduce_session(current: Option<&SessionRecord>, event: &EventEnvelope) -> SessionUpdate {
+pub fn reduce_session(
+    current: Option<&SessionRecord>,
+    event: &EventEnvelope,
+    policy: &SessionPolicy,
+) -> SessionUpdate {
     if event.event_type == EventType::ShellCwd {
         return SessionUpdate::Skip;
     }
@@ -79,7 +96,7 @@ pub fn reduce_session(current: Option<&SessionRecord>, event: &EventEnvelope) ->
         None => return SessionUpdate::Skip,
     };
 
-    if is_event_stale(current, event) {
+    if is_event_stale(current, event, policy) {
         return SessionUpdate::Skip;
     }
 
@@ -219,7 +236,11 @@ fn should_skip_stop(current: Option<&SessionRecord>, event: &EventEnvelope) -> b
     false
 }
 
-fn is_event_stale(current: Option<&SessionRecord>, event: &EventEnvelope) -> bool {
+fn is_event_stale(
+    current: Option<&SessionRecord>,
+    event: &EventEnvelope,
+    policy: &SessionPolicy,
+) -> bool {
     let Some(current) = current else { return false };
     let Some(event_time) = parse_rfc3339(&event.recorded_at) else {
         return false;
@@ -228,7 +249,8 @@ fn is_event_stale(current: Option<&SessionRecord>, event: &EventEnvelope) -> boo
         return false;
     };
 
-    current_time.signed_duration_since(event_time).num_seconds() > STALE_EVENT_GRACE_SECS
+    current_time.signed_duration_since(event_time).num_seconds()
+        > policy.stale_event_grace_secs as i64
 }
 
 fn upsert_session(
@@ -319,6 +341,7 @@ fn upsert_session(
     } else {
         None
     };
+    let pending_request = reduce_pending_request(current, event, &new_state);
 
     SessionUpdate::Upsert(SessionRecord {
         session_id,
@@ -333,6 +356,7 @@ fn upsert_session(
         last_activity_at,
         tools_in_flight,
         ready_reason,
+        pending_request,
     })
 }
 
@@ -407,7 +431,7 @@ fn resolve_file_path(cwd: &str, file_path: &str) -> Option<String> {
     combined.to_str().map(|value| value.to_string())
 }
 
-fn event_type_string(event_type: &EventType) -> String {
+pub(crate) fn event_type_string(event_type: &EventType) -> String {
     serde_json::to_string(event_type)
         .unwrap_or_else(|_| "unknown".to_string())
         .trim_matches('"')
@@ -458,13 +482,14 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         }
     }
 
     #[test]
     fn session_start_sets_ready_when_not_active() {
         let event = event_base(EventType::SessionStart);
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -482,7 +507,7 @@ mod tests {
     fn session_start_skips_when_active() {
         let event = event_base(EventType::SessionStart);
         let current = record_with_state(SessionState::Working, "2026-01-30T23:59:00Z");
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -492,7 +517,7 @@ mod tests {
         event.cwd = Some("/does/not/exist".to_string());
         let current = record_with_state(SessionState::Ready, "2026-01-30T23:50:00Z");
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -518,7 +543,7 @@ mod tests {
         event.cwd = Some(repo_root.to_string_lossy().to_string());
         event.file_path = Some(src_dir.join("index.ts").to_string_lossy().to_string());
 
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -556,7 +581,7 @@ mod tests {
         event.cwd = Some(repo_root.to_string_lossy().to_string());
         event.file_path = Some(claude_root.join("AGENTS.md").to_string_lossy().to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -608,7 +633,7 @@ mod tests {
                 .to_string(),
         );
 
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -663,12 +688,13 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         };
 
         let mut event = event_base(EventType::PreCompact);
         event.cwd = Some(repo_root.to_string_lossy().to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -687,7 +713,7 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-31T00:00:00Z");
         current.tools_in_flight = 2;
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -706,16 +732,17 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-31T00:00:00Z");
         current.tools_in_flight = 1;
 
-        let after_compact = match reduce_session(Some(&current), &pre_compact) {
-            SessionUpdate::Upsert(record) => record,
-            _ => panic!("expected upsert"),
-        };
+        let after_compact =
+            match reduce_session(Some(&current), &pre_compact, &SessionPolicy::default()) {
+                SessionUpdate::Upsert(record) => record,
+                _ => panic!("expected upsert"),
+            };
 
         let mut stop = event_base(EventType::Stop);
         stop.stop_hook_active = Some(false);
         stop.recorded_at = "2026-01-31T00:00:20Z".to_string();
 
-        let update = reduce_session(Some(&after_compact), &stop);
+        let update = reduce_session(Some(&after_compact), &stop, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -727,16 +754,17 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-31T00:00:00Z");
         current.tools_in_flight = 1;
 
-        let after_compact = match reduce_session(Some(&current), &pre_compact) {
-            SessionUpdate::Upsert(record) => record,
-            _ => panic!("expected upsert"),
-        };
+        let after_compact =
+            match reduce_session(Some(&current), &pre_compact, &SessionPolicy::default()) {
+                SessionUpdate::Upsert(record) => record,
+                _ => panic!("expected upsert"),
+            };
         assert_eq!(after_compact.state, SessionState::Compacting);
 
         let mut post_tool = event_base(EventType::PostToolUse);
         post_tool.recorded_at = "2026-01-31T00:00:11Z".to_string();
 
-        let update = reduce_session(Some(&after_compact), &post_tool);
+        let update = reduce_session(Some(&after_compact), &post_tool, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Compacting);
@@ -750,7 +778,7 @@ mod tests {
     fn user_prompt_sets_working_and_updates_state_changed_at() {
         let event = event_base(EventType::UserPromptSubmit);
         let current = record_with_state(SessionState::Ready, "2026-01-30T23:50:00Z");
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -765,7 +793,7 @@ mod tests {
     fn pre_tool_use_heartbeat_when_already_working() {
         let event = event_base(EventType::PreToolUse);
         let current = record_with_state(SessionState::Working, "2026-01-30T23:55:00Z");
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -781,7 +809,7 @@ mod tests {
     fn post_tool_use_sets_working_when_not_working() {
         let event = event_base(EventType::PostToolUse);
         let current = record_with_state(SessionState::Ready, "2026-01-30T23:55:00Z");
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -795,7 +823,7 @@ mod tests {
     #[test]
     fn permission_request_sets_waiting() {
         let event = event_base(EventType::PermissionRequest);
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -809,7 +837,7 @@ mod tests {
     fn notification_idle_prompt_sets_ready() {
         let mut event = event_base(EventType::Notification);
         event.notification_type = Some("idle_prompt".to_string());
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -824,7 +852,7 @@ mod tests {
     fn notification_auth_success_sets_ready() {
         let mut event = event_base(EventType::Notification);
         event.notification_type = Some("auth_success".to_string());
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -839,7 +867,7 @@ mod tests {
     fn notification_permission_prompt_sets_waiting() {
         let mut event = event_base(EventType::Notification);
         event.notification_type = Some("permission_prompt".to_string());
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -854,7 +882,7 @@ mod tests {
     fn notification_elicitation_dialog_sets_waiting() {
         let mut event = event_base(EventType::Notification);
         event.notification_type = Some("elicitation_dialog".to_string());
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -869,7 +897,7 @@ mod tests {
     fn notification_non_idle_is_skipped() {
         let mut event = event_base(EventType::Notification);
         event.notification_type = Some("other".to_string());
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -877,7 +905,7 @@ mod tests {
     fn stop_hook_active_skips() {
         let mut event = event_base(EventType::Stop);
         event.stop_hook_active = Some(true);
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -891,7 +919,7 @@ mod tests {
         current.tools_in_flight = 1;
         current.last_activity_at = Some("2026-01-31T00:00:05Z".to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -905,7 +933,7 @@ mod tests {
     fn post_tool_use_failure_clears_tools_and_allows_stop() {
         let mut pre_tool = event_base(EventType::PreToolUse);
         pre_tool.recorded_at = "2026-01-31T00:00:00Z".to_string();
-        let after_pre = match reduce_session(None, &pre_tool) {
+        let after_pre = match reduce_session(None, &pre_tool, &SessionPolicy::default()) {
             SessionUpdate::Upsert(record) => record,
             _ => panic!("expected upsert"),
         };
@@ -913,16 +941,17 @@ mod tests {
 
         let mut failure = event_base(EventType::PostToolUseFailure);
         failure.recorded_at = "2026-01-31T00:00:02Z".to_string();
-        let after_failure = match reduce_session(Some(&after_pre), &failure) {
-            SessionUpdate::Upsert(record) => record,
-            _ => panic!("expected upsert"),
-        };
+        let after_failure =
+            match reduce_session(Some(&after_pre), &failure, &SessionPolicy::default()) {
+                SessionUpdate::Upsert(record) => record,
+                _ => panic!("expected upsert"),
+            };
         assert_eq!(after_failure.tools_in_flight, 0);
 
         let mut stop = event_base(EventType::Stop);
         stop.stop_hook_active = Some(false);
         stop.recorded_at = "2026-01-31T00:00:10Z".to_string();
-        let update = reduce_session(Some(&after_failure), &stop);
+        let update = reduce_session(Some(&after_failure), &stop, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -941,7 +970,7 @@ mod tests {
         current.tools_in_flight = 0;
         current.last_activity_at = Some("2026-01-31T00:00:08Z".to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -960,7 +989,7 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-31T00:00:00Z");
         current.last_activity_at = Some("not-a-timestamp".to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -979,7 +1008,7 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-31T00:00:00Z");
         current.last_activity_at = Some("2026-01-31T00:00:05Z".to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -993,7 +1022,7 @@ mod tests {
     fn task_completed_sets_ready() {
         let event = event_base(EventType::TaskCompleted);
 
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -1007,7 +1036,7 @@ mod tests {
     fn task_completed_resets_tools_in_flight() {
         let mut pre_tool = event_base(EventType::PreToolUse);
         pre_tool.recorded_at = "2026-01-31T00:00:00Z".to_string();
-        let after_pre = match reduce_session(None, &pre_tool) {
+        let after_pre = match reduce_session(None, &pre_tool, &SessionPolicy::default()) {
             SessionUpdate::Upsert(record) => record,
             _ => panic!("expected upsert"),
         };
@@ -1015,7 +1044,7 @@ mod tests {
 
         let mut task = event_base(EventType::TaskCompleted);
         task.recorded_at = "2026-01-31T00:00:05Z".to_string();
-        let update = reduce_session(Some(&after_pre), &task);
+        let update = reduce_session(Some(&after_pre), &task, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -1029,7 +1058,7 @@ mod tests {
     fn task_completed_with_agent_id_metadata_skips() {
         let mut event = event_base(EventType::TaskCompleted);
         event.metadata = Some(serde_json::json!({ "agent_id": "agent-1" }));
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -1040,7 +1069,7 @@ mod tests {
             "teammate_name": "implementer",
             "team_name": "my-project"
         }));
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -1049,7 +1078,7 @@ mod tests {
         let mut event = event_base(EventType::TaskCompleted);
         event.metadata = Some(serde_json::json!({ "team_name": "my-project" }));
 
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -1064,7 +1093,7 @@ mod tests {
         let mut event = event_base(EventType::Stop);
         event.stop_hook_active = Some(false);
         event.metadata = Some(serde_json::json!({ "agent_id": "agent-1" }));
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -1072,7 +1101,7 @@ mod tests {
     fn stop_hook_inactive_sets_ready() {
         let mut event = event_base(EventType::Stop);
         event.stop_hook_active = Some(false);
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -1088,7 +1117,7 @@ mod tests {
         let mut event = event_base(EventType::Stop);
         event.stop_hook_active = Some(false);
         event.metadata = Some(serde_json::json!({ "note": "no-agent" }));
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -1102,7 +1131,7 @@ mod tests {
     #[test]
     fn session_end_deletes() {
         let event = event_base(EventType::SessionEnd);
-        let update = reduce_session(None, &event);
+        let update = reduce_session(None, &event, &SessionPolicy::default());
         assert_eq!(
             update,
             SessionUpdate::Delete {
@@ -1128,10 +1157,18 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         };
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
+
+        let lenient = SessionPolicy {
+            stale_event_grace_secs: 30,
+            ..SessionPolicy::default()
+        };
+        let update = reduce_session(Some(&current), &event, &lenient);
+        assert!(matches!(update, SessionUpdate::Upsert(_)));
     }
 
     #[test]
@@ -1151,9 +1188,10 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         };
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         assert!(matches!(update, SessionUpdate::Upsert(_)));
     }
 
@@ -1163,7 +1201,7 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-30T23:55:00Z");
         current.tools_in_flight = 1;
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -1180,7 +1218,7 @@ mod tests {
         let mut current = record_with_state(SessionState::Working, "2026-01-30T23:55:00Z");
         current.tools_in_flight = 1;
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -1196,7 +1234,7 @@ mod tests {
         let event = event_base(EventType::UserPromptSubmit);
         let current = record_with_state(SessionState::Ready, "2026-01-30T23:55:00Z");
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
 
         match update {
             SessionUpdate::Upsert(record) => {
@@ -1216,7 +1254,7 @@ mod tests {
         current.tools_in_flight = 0;
         current.last_activity_at = Some("2026-01-31T00:00:08Z".to_string());
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         match update {
             SessionUpdate::Upsert(record) => {
                 assert_eq!(record.state, SessionState::Ready);
@@ -1244,9 +1282,10 @@ mod tests {
             last_activity_at: Some("2026-01-31T00:00:10Z".to_string()),
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         };
 
-        let update = reduce_session(Some(&current), &event);
+        let update = reduce_session(Some(&current), &event, &SessionPolicy::default());
         assert_eq!(update, SessionUpdate::Skip);
     }
 }
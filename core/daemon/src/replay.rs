@@ -1,55 +1,100 @@
 use crate::activity::reduce_activity;
 use crate::db::Db;
 use crate::reducer::SessionUpdate;
+use crate::session_policy::SessionPolicy;
 use crate::session_store::handle_session_event;
-
-#[cfg(test)]
-pub fn rebuild_from_events(db: &Db) -> Result<(), String> {
+use crate::subagents::apply_subagent_event;
+use crate::teams;
+use crate::time_accounting;
+use crate::tool_calls;
+use crate::transitions;
+use crate::worktrees;
+
+/// Rebuilds derived state from the event log. After retention has pruned old
+/// events, sessions and registries start from the retention snapshot, history
+/// recorded up to it is kept, and only later events are replayed. The config
+/// change audit log is live-only and kept as is.
+pub fn rebuild_from_events(db: &Db, policy: &SessionPolicy) -> Result<(), String> {
     db.clear_sessions()?;
     db.clear_activity()?;
     db.clear_tombstones()?;
 
+    let snapshot = db.load_retention_snapshot()?;
+    match snapshot.as_ref() {
+        Some(snapshot) => db.restore_derived_snapshot(&snapshot.derived)?,
+        None => {
+            db.clear_subagents()?;
+            db.clear_session_transitions()?;
+            db.clear_human_waits()?;
+            db.clear_tool_calls()?;
+            db.clear_worktrees()?;
+            db.clear_teams()?;
+        }
+    }
+    if let Some(snapshot) = snapshot.as_ref() {
+        for session in &snapshot.sessions {
+            db.upsert_session(session)?;
+        }
+    }
     let events = db
-        .list_events()?
+        .list_events(snapshot.map(|snapshot| snapshot.event_rowid))?
         .into_iter()
         .enumerate()
         .map(|(index, event)| ((index + 1) as i64, event))
         .collect();
-    apply_events(db, events, false)
+    apply_events(db, events, false, policy)
 }
 
-pub fn catch_up_sessions_from_events(db: &Db) -> Result<(), String> {
+pub fn catch_up_sessions_from_events(db: &Db, policy: &SessionPolicy) -> Result<(), String> {
     let after_rowid = db.last_applied_event_rowid()?;
     let events = db.list_session_affecting_events_after_rowid(after_rowid)?;
-    apply_events(db, events, true)
+    apply_events(db, events, true, policy)
 }
 
 fn apply_events(
     db: &Db,
     events: Vec<(i64, capacitor_daemon_protocol::EventEnvelope)>,
     persist_cursor: bool,
+    policy: &SessionPolicy,
 ) -> Result<(), String> {
     for (rowid, event) in events {
+        apply_subagent_event(db, &event)?;
+        tool_calls::record_tool_call(db, &event)?;
+        worktrees::apply_worktree_event(db, &event)?;
+        teams::apply_team_event(db, &event)?;
         let current = match event.session_id.as_ref() {
             Some(session_id) => db.get_session(session_id)?,
             None => None,
         };
 
-        let update = handle_session_event(db, current.as_ref(), &event)?;
+        let update = handle_session_event(db, current.as_ref(), &event, policy)?;
 
         match update {
-            SessionUpdate::Upsert(record) => {
+            SessionUpdate::Upsert(mut record) => {
+                tool_calls::sync_tools_in_flight(db, &mut record)?;
                 db.upsert_session(&record)?;
+                transitions::record_upsert(db, current.as_ref(), &record, Some(&event.event_id))?;
                 if let Some(entry) = reduce_activity(&event) {
                     db.insert_activity(&entry)?;
                 }
             }
             SessionUpdate::Delete { session_id } => {
                 db.delete_session(&session_id)?;
+                if let Some(current) = current.as_ref() {
+                    transitions::record_end(
+                        db,
+                        current,
+                        Some(&event.event_id),
+                        &event.recorded_at,
+                    )?;
+                }
                 db.delete_activity_for_session(&session_id)?;
+                db.delete_subagents_for_session(&session_id)?;
+                db.delete_teams_led_by(&session_id)?;
             }
             SessionUpdate::Skip => {}
         }
+        time_accounting::record_human_wait(db, &event)?;
 
         if persist_cursor {
             db.set_last_applied_event_rowid(rowid)?;
@@ -100,7 +145,7 @@ mod tests {
         db.insert_event(&post).expect("insert post");
         db.insert_event(&end).expect("insert end");
 
-        rebuild_from_events(&db).expect("rebuild");
+        rebuild_from_events(&db, &SessionPolicy::default()).expect("rebuild");
 
         let session = db.get_session("session-1").expect("fetch session");
         assert!(session.is_none());
@@ -127,7 +172,7 @@ mod tests {
         db.insert_event(&start).expect("insert start");
         db.insert_event(&post).expect("insert post");
 
-        rebuild_from_events(&db).expect("rebuild");
+        rebuild_from_events(&db, &SessionPolicy::default()).expect("rebuild");
 
         let session = db
             .get_session("session-1")
@@ -155,7 +200,7 @@ mod tests {
         db.insert_event(&older_start).expect("insert start");
         db.insert_event(&newer_end).expect("insert end");
 
-        catch_up_sessions_from_events(&db).expect("catch up");
+        catch_up_sessions_from_events(&db, &SessionPolicy::default()).expect("catch up");
 
         let session = db.get_session("session-1").expect("get session");
         assert!(
@@ -175,14 +220,14 @@ mod tests {
         db.insert_event(&start).expect("insert start");
         db.insert_event(&pre_tool).expect("insert pre_tool");
 
-        catch_up_sessions_from_events(&db).expect("first catch-up");
+        catch_up_sessions_from_events(&db, &SessionPolicy::default()).expect("first catch-up");
         let first_cursor = db
             .last_applied_event_rowid()
             .expect("read first cursor")
             .expect("cursor exists after first catch-up");
         assert!(first_cursor > 0);
 
-        catch_up_sessions_from_events(&db).expect("second catch-up");
+        catch_up_sessions_from_events(&db, &SessionPolicy::default()).expect("second catch-up");
         let second_cursor = db
             .last_applied_event_rowid()
             .expect("read second cursor")
@@ -210,12 +255,13 @@ mod tests {
 
         let start = make_event("evt-1", EventType::SessionStart, "2026-02-01T00:00:00Z");
         db.insert_event(&start).expect("insert start");
-        catch_up_sessions_from_events(&db).expect("initial catch-up");
+        catch_up_sessions_from_events(&db, &SessionPolicy::default()).expect("initial catch-up");
 
         let out_of_order = make_event("evt-2", EventType::PreToolUse, "2026-01-31T23:59:59Z");
         db.insert_event(&out_of_order)
             .expect("insert out-of-order timestamp event");
-        catch_up_sessions_from_events(&db).expect("catch-up with older timestamp");
+        catch_up_sessions_from_events(&db, &SessionPolicy::default())
+            .expect("catch-up with older timestamp");
 
         let session = db
             .get_session("session-1")
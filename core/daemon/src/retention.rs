@@ -0,0 +1,513 @@
+//! Event, activity and history retention from the `[retention]` config
+//! section.
+//!
+//! Sessions and shell state are derived from the append-only `events` table,
+//! so old events cannot simply be dropped: each run first snapshots derived
+//! state at the `last_applied_event_rowid` cursor, then deletes events older
+//! than the horizon at or before that cursor. Rebuilds start from the
+//! snapshot and replay only the events after it. Catch-up replay only reads
+//! events after the cursor, so it never sees the gap. History tables
+//! (transitions, human waits, tool calls, config changes and snapshot
+//! removals) are trimmed by age and row count. Freed pages are returned to
+//! the filesystem with incremental vacuum.
+
+use capacitor_daemon_protocol::RetentionHealth;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::db::{Db, VacuumReport};
+
+fn default_retention_enabled() -> bool {
+    true
+}
+
+fn default_event_retention_days() -> u64 {
+    30
+}
+
+fn default_activity_retention_days() -> u64 {
+    7
+}
+
+fn default_history_retention_days() -> u64 {
+    90
+}
+
+fn default_history_max_rows() -> u64 {
+    100_000
+}
+
+fn default_retention_interval_secs() -> u64 {
+    60 * 60
+}
+
+fn default_vacuum_pages() -> u64 {
+    1_000
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct RetentionConfig {
+    #[serde(default = "default_retention_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_event_retention_days")]
+    pub event_retention_days: u64,
+    #[serde(default = "default_activity_retention_days")]
+    pub activity_retention_days: u64,
+    /// Age limit for history rows; `get_project_time` can look back 90 days.
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u64,
+    /// Row limit per history table.
+    #[serde(default = "default_history_max_rows")]
+    pub history_max_rows: u64,
+    /// How often the daemon runs retention.
+    #[serde(default = "default_retention_interval_secs")]
+    pub interval_secs: u64,
+    /// Free pages returned per run by `PRAGMA incremental_vacuum`.
+    #[serde(default = "default_vacuum_pages")]
+    pub vacuum_pages: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_retention_enabled(),
+            event_retention_days: default_event_retention_days(),
+            activity_retention_days: default_activity_retention_days(),
+            history_retention_days: default_history_retention_days(),
+            history_max_rows: default_history_max_rows(),
+            interval_secs: default_retention_interval_secs(),
+            vacuum_pages: default_vacuum_pages(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value, min, max) in [
+            ("event_retention_days", self.event_retention_days, 1, 3_650),
+            (
+                "activity_retention_days",
+                self.activity_retention_days,
+                1,
+                3_650,
+            ),
+            (
+                "history_retention_days",
+                self.history_retention_days,
+                1,
+                3_650,
+            ),
+            (
+                "history_max_rows",
+                self.history_max_rows,
+                1_000,
+                100_000_000,
+            ),
+            ("interval_secs", self.interval_secs, 60, 7 * 24 * 60 * 60),
+            ("vacuum_pages", self.vacuum_pages, 1, 1_000_000),
+        ] {
+            if !(min..=max).contains(&value) {
+                return Err(format!(
+                    "retention.{} must be between {} and {}, got {}",
+                    name, min, max, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of one retention run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionRun {
+    pub events_pruned: u64,
+    pub activity_pruned: u64,
+    pub history_pruned: u64,
+    /// Cursor the snapshot was taken at, when events were pruned.
+    pub snapshot_event_rowid: Option<i64>,
+    pub vacuum: VacuumReport,
+}
+
+/// Retention status reported by `get_health`.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionStatus {
+    pub enabled: bool,
+    pub event_retention_days: u64,
+    pub activity_retention_days: u64,
+    pub history_retention_days: u64,
+    pub history_max_rows: u64,
+    pub interval_secs: u64,
+    pub runs: u64,
+    pub last_run_at: Option<String>,
+    pub last_error: Option<String>,
+    pub last_events_pruned: u64,
+    pub events_pruned_total: u64,
+    pub last_activity_pruned: u64,
+    pub activity_pruned_total: u64,
+    pub last_history_pruned: u64,
+    pub history_pruned_total: u64,
+    pub snapshot_event_rowid: Option<i64>,
+    pub snapshot_at: Option<String>,
+    pub event_count: u64,
+    pub oldest_event_at: Option<String>,
+    pub auto_vacuum: Option<String>,
+    pub freelist_pages: Option<u64>,
+    pub last_vacuum_pages: u64,
+}
+
+impl RetentionStatus {
+    pub fn new(config: &RetentionConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            event_retention_days: config.event_retention_days,
+            activity_retention_days: config.activity_retention_days,
+            history_retention_days: config.history_retention_days,
+            history_max_rows: config.history_max_rows,
+            interval_secs: config.interval_secs,
+            ..Self::default()
+        }
+    }
+
+    pub fn record_run(&mut self, run: &RetentionRun, now: DateTime<Utc>) {
+        self.runs += 1;
+        self.last_run_at = Some(now.to_rfc3339());
+        self.last_error = None;
+        self.last_events_pruned = run.events_pruned;
+        self.events_pruned_total += run.events_pruned;
+        self.last_activity_pruned = run.activity_pruned;
+        self.activity_pruned_total += run.activity_pruned;
+        self.last_history_pruned = run.history_pruned;
+        self.history_pruned_total += run.history_pruned;
+        if run.snapshot_event_rowid.is_some() {
+            self.snapshot_event_rowid = run.snapshot_event_rowid;
+            self.snapshot_at = self.last_run_at.clone();
+        }
+        self.auto_vacuum = Some(run.vacuum.auto_vacuum.clone());
+        self.freelist_pages = Some(run.vacuum.freelist_pages);
+        self.last_vacuum_pages = run.vacuum.pages_vacuumed;
+    }
+
+    pub fn record_error(&mut self, error: &str, now: DateTime<Utc>) {
+        self.runs += 1;
+        self.last_run_at = Some(now.to_rfc3339());
+        self.last_error = Some(error.to_string());
+    }
+
+    pub fn health(&self) -> RetentionHealth {
+        RetentionHealth {
+            enabled: self.enabled,
+            event_retention_days: self.event_retention_days,
+            activity_retention_days: self.activity_retention_days,
+            history_retention_days: self.history_retention_days,
+            history_max_rows: self.history_max_rows,
+            interval_secs: self.interval_secs,
+            runs: self.runs,
+            last_run_at: self.last_run_at.clone(),
+            last_error: self.last_error.clone(),
+            last_events_pruned: self.last_events_pruned,
+            events_pruned_total: self.events_pruned_total,
+            last_activity_pruned: self.last_activity_pruned,
+            activity_pruned_total: self.activity_pruned_total,
+            last_history_pruned: self.last_history_pruned,
+            history_pruned_total: self.history_pruned_total,
+            snapshot_event_rowid: self.snapshot_event_rowid,
+            snapshot_at: self.snapshot_at.clone(),
+            event_count: self.event_count,
+            oldest_event_at: self.oldest_event_at.clone(),
+            auto_vacuum: self.auto_vacuum.clone(),
+            freelist_pages: self.freelist_pages,
+            last_vacuum_pages: self.last_vacuum_pages,
+        }
+    }
+}
+
+/// Snapshots derived state and prunes events, activity and history past the
+/// configured limits. The caller holds the mutation lock, so the cursor
+/// matches the derived tables being snapshotted.
+pub fn run_retention(
+    db: &Db,
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> Result<RetentionRun, String> {
+    let event_cutoff = (now - Duration::days(config.event_retention_days as i64)).to_rfc3339();
+    let activity_cutoff =
+        (now - Duration::days(config.activity_retention_days as i64)).to_rfc3339();
+
+    let mut events_pruned = 0;
+    let mut snapshot_event_rowid = None;
+    // Without a cursor nothing says which events the derived tables reflect.
+    if let Some(cursor) = db.last_applied_event_rowid()? {
+        if db.count_prunable_events(cursor, &event_cutoff)? > 0 {
+            db.write_retention_snapshot(cursor, &now.to_rfc3339())?;
+            events_pruned = db.prune_events(cursor, &event_cutoff)?;
+            snapshot_event_rowid = Some(cursor);
+        }
+    }
+    let activity_pruned = db.prune_activity(&activity_cutoff)?;
+    let history_cutoff = (now - Duration::days(config.history_retention_days as i64)).to_rfc3339();
+    let history_pruned = db.prune_history(&history_cutoff, config.history_max_rows)?
+        + db.prune_snapshot_removals(now, config.history_max_rows)?;
+    let vacuum = db.incremental_vacuum(config.vacuum_pages)?;
+
+    Ok(RetentionRun {
+        events_pruned,
+        activity_pruned,
+        history_pruned,
+        snapshot_event_rowid,
+        vacuum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::ActivityEntry;
+    use crate::replay;
+    use crate::session_policy::SessionPolicy;
+    use crate::test_support;
+    use capacitor_daemon_protocol::{
+        EventEnvelope, EventType, SessionState, SessionTransition, ToolCall, ToolCallOutcome,
+    };
+
+    fn event(event_id: &str, event_type: EventType, recorded_at: DateTime<Utc>) -> EventEnvelope {
+        EventEnvelope {
+            tty: Some("/dev/ttys001".to_string()),
+            ..test_support::event(event_id, event_type, &recorded_at.to_rfc3339())
+        }
+    }
+
+    fn insert_and_apply(db: &Db, events: &[EventEnvelope]) {
+        for event in events {
+            let rowid = db
+                .insert_event_with_rowid(event)
+                .expect("insert")
+                .expect("new event");
+            if event.event_type == EventType::ShellCwd {
+                db.upsert_shell_state(event).expect("shell state");
+            }
+            db.set_last_applied_event_rowid(rowid).expect("cursor");
+        }
+        replay::rebuild_from_events(db, &SessionPolicy::default()).expect("rebuild");
+    }
+
+    #[test]
+    fn prunes_only_applied_events_and_rebuilds_from_snapshot() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let now = Utc::now();
+        let old = now - Duration::days(40);
+
+        let mut shell = event("evt-shell", EventType::ShellCwd, old);
+        shell.session_id = None;
+        insert_and_apply(
+            &db,
+            &[
+                shell,
+                event("evt-start", EventType::SessionStart, old),
+                event("evt-prompt", EventType::UserPromptSubmit, old),
+                event("evt-stop", EventType::Stop, now),
+            ],
+        );
+        // Stored but not yet applied, so it stays even though it is old.
+        db.insert_event(&event("evt-late", EventType::PreToolUse, old))
+            .expect("insert late");
+
+        let run = run_retention(&db, &RetentionConfig::default(), now).expect("retention");
+        assert_eq!(run.events_pruned, 3);
+        assert_eq!(run.snapshot_event_rowid, Some(4));
+        assert_eq!(db.event_count().expect("count"), 2);
+        assert_eq!(run.vacuum.auto_vacuum, "incremental");
+
+        db.clear_sessions().expect("clear sessions");
+        replay::rebuild_from_events(&db, &SessionPolicy::default()).expect("rebuild");
+        let session = db
+            .get_session("session-1")
+            .expect("session")
+            .expect("session restored from snapshot");
+        assert_eq!(session.last_event.as_deref(), Some("stop"));
+
+        let shells = db.rebuild_shell_state_from_events().expect("shells");
+        assert_eq!(shells.shells["1234"].cwd, "/repo");
+
+        let again = run_retention(&db, &RetentionConfig::default(), now).expect("second run");
+        assert_eq!(again.events_pruned, 0);
+        assert_eq!(again.snapshot_event_rowid, None);
+    }
+
+    #[test]
+    fn rebuild_after_retention_keeps_registries_and_history() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let now = Utc::now();
+        let old = |secs: i64| now - Duration::days(40) + Duration::seconds(secs);
+        let with_metadata =
+            |event_id: &str, event_type: EventType, secs: i64, metadata| EventEnvelope {
+                metadata: Some(metadata),
+                ..event(event_id, event_type, old(secs))
+            };
+
+        insert_and_apply(
+            &db,
+            &[
+                event("evt-start", EventType::SessionStart, old(0)),
+                with_metadata(
+                    "evt-team",
+                    EventType::UserPromptSubmit,
+                    1,
+                    serde_json::json!({ "team_name": "team-a" }),
+                ),
+                with_metadata(
+                    "evt-agent",
+                    EventType::SubagentStart,
+                    2,
+                    serde_json::json!({ "agent_id": "agent-1", "agent_type": "Explore" }),
+                ),
+                with_metadata(
+                    "evt-worktree",
+                    EventType::WorktreeCreate,
+                    3,
+                    serde_json::json!({ "worktree_path": "/repo/.claude/worktrees/a" }),
+                ),
+                event("evt-permission", EventType::PermissionRequest, old(4)),
+                event("evt-newest", EventType::Notification, old(5)),
+            ],
+        );
+        let transitions = db
+            .list_session_transitions("session-1")
+            .expect("transitions");
+        assert!(!transitions.is_empty());
+
+        let run = run_retention(&db, &RetentionConfig::default(), now).expect("retention");
+        assert_eq!(run.events_pruned, 5);
+        // Newer than the snapshot, so it is replayed; it closes the open wait.
+        let rowid = db
+            .insert_event_with_rowid(&event("evt-prompt", EventType::UserPromptSubmit, now))
+            .expect("insert")
+            .expect("new event");
+        db.set_last_applied_event_rowid(rowid).expect("cursor");
+        replay::rebuild_from_events(&db, &SessionPolicy::default()).expect("rebuild");
+
+        let subagents = db.list_subagents("session-1", true).expect("subagents");
+        assert_eq!(subagents.len(), 1);
+        assert_eq!(subagents[0].agent_id, "agent-1");
+        assert_eq!(subagents[0].agent_type.as_deref(), Some("Explore"));
+        let teams = db.list_teams(None).expect("teams");
+        assert_eq!(teams.len(), 1);
+        assert_eq!(teams[0].lead_session_id.as_deref(), Some("session-1"));
+        let worktrees = db.list_worktrees(None).expect("worktrees");
+        assert_eq!(worktrees.len(), 1);
+        assert_eq!(worktrees[0].path, "/repo/.claude/worktrees/a");
+
+        let rebuilt = db
+            .list_session_transitions("session-1")
+            .expect("transitions");
+        assert_eq!(rebuilt[..transitions.len()], transitions[..]);
+        assert_eq!(rebuilt.len(), transitions.len() + 1);
+        let waits = db
+            .list_human_waits(
+                None,
+                &old(0).to_rfc3339(),
+                &(now + Duration::hours(1)).to_rfc3339(),
+            )
+            .expect("waits");
+        assert_eq!(waits.len(), 1);
+        assert_eq!(waits[0].ended_at, Some(now.to_rfc3339()));
+    }
+
+    #[test]
+    fn prunes_history_by_age_and_row_count_but_keeps_rows_in_use() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let now = Utc::now();
+        let old = (now - Duration::days(100)).to_rfc3339();
+        let recent = (now - Duration::days(1)).to_rfc3339();
+        let transition = |session_id: &str, at: &str| SessionTransition {
+            session_id: session_id.to_string(),
+            project_path: "/repo".to_string(),
+            from_state: None,
+            to_state: Some(SessionState::Working),
+            event_id: None,
+            transitioned_at: at.to_string(),
+        };
+        let call = |tool_use_id: &str| ToolCall {
+            session_id: "session-1".to_string(),
+            tool_use_id: tool_use_id.to_string(),
+            tool_name: "Bash".to_string(),
+            file_path: None,
+            project_path: "/repo".to_string(),
+            agent_id: None,
+            started_at: old.clone(),
+            ended_at: None,
+            duration_ms: None,
+            outcome: None,
+        };
+
+        for (session_id, at) in [
+            ("session-1", &old),
+            ("session-1", &recent),
+            ("session-2", &old),
+        ] {
+            db.insert_session_transition(&transition(session_id, at))
+                .expect("transition");
+        }
+        db.open_human_wait("session-1", "/repo", &old)
+            .expect("wait");
+        db.close_human_wait("session-1", &old).expect("close");
+        db.open_human_wait("session-2", "/repo", &old)
+            .expect("open wait");
+        db.start_tool_call(&call("done")).expect("call");
+        db.finish_tool_call("session-1", "done", &old, ToolCallOutcome::Success)
+            .expect("finish");
+        db.start_tool_call(&call("running")).expect("running call");
+
+        let run = run_retention(&db, &RetentionConfig::default(), now).expect("retention");
+        assert_eq!(run.history_pruned, 3);
+        // session-2's only transition is its latest, so it stays.
+        assert_eq!(db.list_session_transitions("session-1").unwrap().len(), 1);
+        assert_eq!(db.list_session_transitions("session-2").unwrap().len(), 1);
+        let waits = db.list_human_waits(None, &old, &now.to_rfc3339()).unwrap();
+        assert_eq!(waits.len(), 1);
+        assert_eq!(waits[0].session_id, "session-2");
+        assert_eq!(db.tracked_tools_in_flight("session-1").unwrap(), Some(1));
+
+        for _ in 0..3 {
+            db.insert_session_transition(&transition("session-3", &recent))
+                .expect("transition");
+        }
+        assert_eq!(db.prune_history(&old, 1).expect("row limit"), 2);
+        assert_eq!(db.list_session_transitions("session-3").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn prunes_old_activity_and_validates_config() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let now = Utc::now();
+        for (file, age_days) in [("old.rs", 8), ("new.rs", 1)] {
+            db.insert_activity(&ActivityEntry {
+                session_id: "session-1".to_string(),
+                project_path: "/repo".to_string(),
+                file_path: file.to_string(),
+                tool_name: Some("Edit".to_string()),
+                recorded_at: (now - Duration::days(age_days)).to_rfc3339(),
+            })
+            .expect("activity");
+        }
+
+        let run = run_retention(&db, &RetentionConfig::default(), now).expect("retention");
+        assert_eq!(run.activity_pruned, 1);
+        assert_eq!(run.events_pruned, 0);
+        let remaining = db.list_activity("session-1", 10).expect("activity");
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].file_path, "new.rs");
+
+        let config = RetentionConfig {
+            event_retention_days: 0,
+            ..RetentionConfig::default()
+        };
+        assert!(config
+            .validate()
+            .expect_err("zero days")
+            .contains("retention.event_retention_days must be between 1"));
+    }
+}
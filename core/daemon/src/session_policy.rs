@@ -0,0 +1,176 @@
+//! Session lifecycle timings from the `[session_policy]` config section.
+//!
+//! Covers how long sessions live in each state before expiring, when a
+//! quiet `working` session falls back to `ready`, and the grace windows for
+//! out-of-order events and premature stops. Every field defaults to the
+//! value the daemon used before the section existed.
+
+use capacitor_daemon_protocol::{EventType, SessionPolicyView};
+use serde::Deserialize;
+
+use crate::reducer::SessionState;
+
+pub const SESSION_TTL_ACTIVE_SECS: u64 = 20 * 60; // Working/Waiting/Compacting
+pub const SESSION_TTL_READY_SECS: u64 = 30 * 60;
+pub const SESSION_TTL_IDLE_SECS: u64 = 10 * 60;
+/// Auto-ready eligible states -> Ready when inactive and no tools are in flight.
+pub const SESSION_AUTO_READY_SECS: u64 = 60;
+/// Long-stale post_tool_use can auto-ready even if tools leaked.
+pub const SESSION_POST_TOOL_USE_STALE_SECS: u64 = 5 * 60;
+/// Short-lived guard for false Stop->Ready while session is still actively finishing.
+pub const STOP_GATE_WORKING_GRACE_SECS: u64 = 20;
+pub const STALE_EVENT_GRACE_SECS: u64 = 5;
+/// Upper bound for every duration, so timestamps stay in range.
+const MAX_POLICY_SECS: u64 = 30 * 24 * 60 * 60;
+
+fn default_ttl_active_secs() -> u64 {
+    SESSION_TTL_ACTIVE_SECS
+}
+
+fn default_ttl_ready_secs() -> u64 {
+    SESSION_TTL_READY_SECS
+}
+
+fn default_ttl_idle_secs() -> u64 {
+    SESSION_TTL_IDLE_SECS
+}
+
+fn default_inactivity_fallback() -> bool {
+    true
+}
+
+fn default_inactivity_fallback_secs() -> u64 {
+    SESSION_AUTO_READY_SECS
+}
+
+fn default_inactivity_fallback_events() -> Vec<String> {
+    vec!["task_completed".to_string()]
+}
+
+fn default_post_tool_use_stale_secs() -> u64 {
+    SESSION_POST_TOOL_USE_STALE_SECS
+}
+
+fn default_stop_gate_grace_secs() -> u64 {
+    STOP_GATE_WORKING_GRACE_SECS
+}
+
+fn default_stale_event_grace_secs() -> u64 {
+    STALE_EVENT_GRACE_SECS
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct SessionPolicy {
+    /// TTL for `working`, `waiting` and `compacting` sessions.
+    #[serde(default = "default_ttl_active_secs")]
+    pub ttl_active_secs: u64,
+    /// `ready` does not auto-idle; it remains until this TTL or session end.
+    #[serde(default = "default_ttl_ready_secs")]
+    pub ttl_ready_secs: u64,
+    #[serde(default = "default_ttl_idle_secs")]
+    pub ttl_idle_secs: u64,
+    /// Whether quiet `working` sessions fall back to `ready`.
+    #[serde(default = "default_inactivity_fallback")]
+    pub inactivity_fallback: bool,
+    #[serde(default = "default_inactivity_fallback_secs")]
+    pub inactivity_fallback_secs: u64,
+    /// Last events after which a quiet `working` session may fall back.
+    #[serde(default = "default_inactivity_fallback_events")]
+    pub inactivity_fallback_events: Vec<String>,
+    /// A `post_tool_use` this old falls back even with untracked tools in
+    /// flight, since the Pre/Post counter leaks when a post event is lost.
+    #[serde(default = "default_post_tool_use_stale_secs")]
+    pub post_tool_use_stale_secs: u64,
+    #[serde(default = "default_stop_gate_grace_secs")]
+    pub stop_gate_grace_secs: u64,
+    /// Events older than the session's last update by more than this are
+    /// dropped as out of order.
+    #[serde(default = "default_stale_event_grace_secs")]
+    pub stale_event_grace_secs: u64,
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self {
+            ttl_active_secs: default_ttl_active_secs(),
+            ttl_ready_secs: default_ttl_ready_secs(),
+            ttl_idle_secs: default_ttl_idle_secs(),
+            inactivity_fallback: default_inactivity_fallback(),
+            inactivity_fallback_secs: default_inactivity_fallback_secs(),
+            inactivity_fallback_events: default_inactivity_fallback_events(),
+            post_tool_use_stale_secs: default_post_tool_use_stale_secs(),
+            stop_gate_grace_secs: default_stop_gate_grace_secs(),
+            stale_event_grace_secs: default_stale_event_grace_secs(),
+        }
+    }
+}
+
+impl SessionPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value, min) in [
+            ("ttl_active_secs", self.ttl_active_secs, 1),
+            ("ttl_ready_secs", self.ttl_ready_secs, 1),
+            ("ttl_idle_secs", self.ttl_idle_secs, 1),
+            ("inactivity_fallback_secs", self.inactivity_fallback_secs, 1),
+            ("post_tool_use_stale_secs", self.post_tool_use_stale_secs, 1),
+            ("stop_gate_grace_secs", self.stop_gate_grace_secs, 0),
+            ("stale_event_grace_secs", self.stale_event_grace_secs, 0),
+        ] {
+            if !(min..=MAX_POLICY_SECS).contains(&value) {
+                return Err(format!(
+                    "session_policy.{} must be between {} and {} seconds, got {}",
+                    name, min, MAX_POLICY_SECS, value
+                ));
+            }
+        }
+        if self.post_tool_use_stale_secs < self.inactivity_fallback_secs {
+            return Err(format!(
+                "session_policy.post_tool_use_stale_secs ({}) must not be shorter than \
+                 inactivity_fallback_secs ({})",
+                self.post_tool_use_stale_secs, self.inactivity_fallback_secs
+            ));
+        }
+        if self.stop_gate_grace_secs >= self.ttl_ready_secs {
+            return Err(format!(
+                "session_policy.stop_gate_grace_secs ({}) must be shorter than ttl_ready_secs ({})",
+                self.stop_gate_grace_secs, self.ttl_ready_secs
+            ));
+        }
+        for event in &self.inactivity_fallback_events {
+            if serde_json::from_value::<EventType>(serde_json::Value::String(event.clone()))
+                .is_err()
+            {
+                return Err(format!(
+                    "session_policy.inactivity_fallback_events: unknown event type {:?}",
+                    event
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ttl_secs(&self, state: &SessionState) -> i64 {
+        let ttl = match state {
+            SessionState::Working | SessionState::Waiting | SessionState::Compacting => {
+                self.ttl_active_secs
+            }
+            SessionState::Ready => self.ttl_ready_secs,
+            SessionState::Idle => self.ttl_idle_secs,
+        };
+        ttl as i64
+    }
+
+    pub fn view(&self) -> SessionPolicyView {
+        SessionPolicyView {
+            ttl_active_secs: self.ttl_active_secs,
+            ttl_ready_secs: self.ttl_ready_secs,
+            ttl_idle_secs: self.ttl_idle_secs,
+            inactivity_fallback: self.inactivity_fallback,
+            inactivity_fallback_secs: self.inactivity_fallback_secs,
+            inactivity_fallback_events: self.inactivity_fallback_events.clone(),
+            post_tool_use_stale_secs: self.post_tool_use_stale_secs,
+            stop_gate_grace_secs: self.stop_gate_grace_secs,
+            stale_event_grace_secs: self.stale_event_grace_secs,
+        }
+    }
+}
@@ -5,6 +5,7 @@ use tracing::debug;
 
 use crate::db::Db;
 use crate::reducer::{reduce_session, SessionRecord, SessionUpdate};
+use crate::session_policy::SessionPolicy;
 
 const TOMBSTONE_TTL_SECS: i64 = 60;
 
@@ -12,6 +13,7 @@ pub fn handle_session_event(
     db: &Db,
     current: Option<&SessionRecord>,
     event: &EventEnvelope,
+    policy: &SessionPolicy,
 ) -> Result<SessionUpdate, String> {
     let session_id = match event.session_id.as_ref() {
         Some(value) => value.as_str(),
@@ -58,7 +60,7 @@ pub fn handle_session_event(
         }
     }
 
-    Ok(reduce_session(current, event))
+    Ok(reduce_session(current, event, policy))
 }
 
 fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
@@ -101,7 +103,8 @@ mod tests {
             .expect("insert tombstone");
 
         let event = event_base(EventType::UserPromptSubmit, "2026-01-31T00:00:30Z");
-        let update = handle_session_event(&db, None, &event).expect("handle event");
+        let update = handle_session_event(&db, None, &event, &SessionPolicy::default())
+            .expect("handle event");
         assert_eq!(update, SessionUpdate::Skip);
     }
 
@@ -115,7 +118,8 @@ mod tests {
             .expect("insert tombstone");
 
         let event = event_base(EventType::SessionStart, "2026-01-31T00:00:30Z");
-        let update = handle_session_event(&db, None, &event).expect("handle event");
+        let update = handle_session_event(&db, None, &event, &SessionPolicy::default())
+            .expect("handle event");
 
         assert!(matches!(update, SessionUpdate::Upsert(_)));
         assert!(db
@@ -131,7 +135,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
 
         let event = event_base(EventType::SessionEnd, "2026-01-31T00:00:00Z");
-        let update = handle_session_event(&db, None, &event).expect("handle event");
+        let update = handle_session_event(&db, None, &event, &SessionPolicy::default())
+            .expect("handle event");
         assert_eq!(
             update,
             SessionUpdate::Delete {
@@ -4,40 +4,50 @@
 //! table, keeping shell state fast to query while other state remains event-only.
 
 use capacitor_daemon_protocol::{
-    EventEnvelope, EventType, RoutingConfigView, RoutingDiagnostics, RoutingSnapshot,
-    RoutingStatus, RoutingTarget, RoutingTargetKind,
+    ActivityEntry, ConfigChangeRecord, ConfigChangesQuery, DeadSessionReconcileHealth,
+    EnrichedSession, EventEnvelope, EventType, EventsQuery, HemShadowHealth, IntegrityHealth,
+    PendingRequest, ProcessLiveness, ProjectDayTime, ProjectStateRecord, ProjectTimeQuery,
+    ProjectViewHealth, ProjectWorktreeState, RoutingConfigView, RoutingDiagnostics,
+    RoutingSnapshot, RoutingStatus, RoutingTarget, RoutingTargetKind, SessionTimeline,
+    SessionTimelineRequest, ShellEntry, ShellState, SnapshotDelta, SubagentRecord,
+    SubscribeRequest, SubscriptionTopic, TeamState, Tombstone, ToolCallsQuery, ToolCallsResponse,
+    WorktreeRecord, WorktreesQuery,
 };
 use chrono::{DateTime, Duration, Utc};
 use serde::Serialize;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Instant;
 
-use crate::activity::{reduce_activity, ActivityEntry};
-use crate::db::{Db, HemShadowMismatch, TombstoneRow};
+use crate::activity::reduce_activity;
+use crate::config_changes::{self, SettingsRead};
+use crate::db::{Db, HemShadowMismatch, PROJECT_STATES_REVISION_KIND, SESSIONS_REVISION_KIND};
 use crate::hem::{
     HemCapabilityStatus, HemCapabilityTracker, HemEffectiveCapabilities, HemMode, HemProjectState,
     HemRuntimeConfig,
 };
+use crate::metrics::MetricsConfig;
 use crate::process::get_process_start_time;
 use crate::project_identity::workspace_id;
 use crate::project_state_policy::{reduce_project_sessions, SessionProjection};
+use crate::project_view::{PendingRefresh, ProjectStatesView, ProjectViewDelta};
 use crate::reducer::{SessionRecord, SessionUpdate};
 use crate::replay::catch_up_sessions_from_events;
+use crate::retention::{self, RetentionRun, RetentionStatus};
+use crate::session_policy::SessionPolicy;
 use crate::session_store::handle_session_event;
+use crate::subagents::apply_subagent_event;
+use crate::subscriptions::{Subscription, SubscriptionHub};
+use crate::teams::{self, Teams};
+use crate::time_accounting;
+use crate::tool_calls;
+use crate::transitions;
+use crate::worktrees;
 
 const PROCESS_LIVENESS_MAX_AGE_HOURS: i64 = 24;
 const SHELL_MAX_AGE_HOURS: i64 = 24;
 const SHELL_RECENT_THRESHOLD_SECS: i64 = 5 * 60; // 5 minutes
 
-// Session TTL policies (seconds).
-// These intentionally err on the side of clearing stale states.
-const SESSION_TTL_ACTIVE_SECS: i64 = 20 * 60; // Working/Waiting/Compacting
-const SESSION_TTL_READY_SECS: i64 = 30 * 60; // Ready
-const SESSION_TTL_IDLE_SECS: i64 = 10 * 60; // Idle
-                                            // Ready does not auto-idle; it remains until TTL or session end.
-const SESSION_AUTO_READY_SECS: i64 = 60; // Auto-ready eligible states -> Ready when inactive and no tools are in flight.
-const SESSION_POST_TOOL_USE_STALE_SECS: i64 = 5 * 60; // Long-stale post_tool_use can auto-ready even if tools leaked.
-const STOP_GATE_WORKING_GRACE_SECS: i64 = 20; // Short-lived guard for false Stop->Ready while session is still actively finishing.
 const HEM_SHADOW_MISMATCH_RETENTION_DAYS: i64 = 14;
 const HEM_SHADOW_MISMATCH_PERSIST_LIMIT_PER_EVENT: usize = 4;
 const HEM_STABLE_STATE_TRANSITION_EXCLUSION_SECS: i64 = 20;
@@ -53,10 +63,19 @@ pub struct SharedState {
     routing_shell_registry: Mutex<crate::are::registry::ShellRegistry>,
     routing_tmux_registry: Mutex<crate::are::registry::TmuxRegistry>,
     routing_process_registry: Mutex<crate::are::registry::ProcessRegistry>,
-    dead_session_reconcile: Mutex<HashMap<String, DeadSessionReconcileMetrics>>,
+    dead_session_reconcile: Mutex<HashMap<String, DeadSessionReconcileHealth>>,
+    retention_status: Mutex<RetentionStatus>,
+    integrity_report: Mutex<Option<IntegrityHealth>>,
+    project_view: Mutex<ProjectStatesView>,
+    /// Set, and `project_view_wake` signalled, when a write leaves a
+    /// whole-view refresh to the refresher thread.
+    project_view_requested: Mutex<bool>,
+    project_view_wake: Condvar,
     hem_shadow_metrics: Mutex<HemShadowMetrics>,
     hem_capability_tracker: Mutex<HemCapabilityTracker>,
+    subscriptions: SubscriptionHub,
     mutation_lock: Mutex<()>,
+    pinned_now: Mutex<Option<DateTime<Utc>>>,
 }
 
 impl SharedState {
@@ -131,7 +150,7 @@ impl SharedState {
         }
 
         if needs_catch_up {
-            if let Err(err) = catch_up_sessions_from_events(&db) {
+            if let Err(err) = catch_up_sessions_from_events(&db, &hem_config.session_policy) {
                 tracing::warn!(
                     error = %err,
                     "Failed to catch up session state from event cursor"
@@ -203,6 +222,15 @@ impl SharedState {
                 None
             }
         };
+        let mut retention_status = RetentionStatus::new(&hem_config.retention);
+        match db.load_retention_snapshot() {
+            Ok(Some(snapshot)) => {
+                retention_status.snapshot_event_rowid = Some(snapshot.event_rowid);
+                retention_status.snapshot_at = Some(snapshot.created_at);
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!(error = %err, "Failed to load retention snapshot"),
+        }
 
         let shared = Self {
             db,
@@ -218,9 +246,16 @@ impl SharedState {
             routing_tmux_registry: Mutex::new(crate::are::registry::TmuxRegistry::default()),
             routing_process_registry: Mutex::new(crate::are::registry::ProcessRegistry::default()),
             dead_session_reconcile: Mutex::new(HashMap::new()),
+            retention_status: Mutex::new(retention_status),
+            integrity_report: Mutex::new(None),
+            project_view: Mutex::new(ProjectStatesView::default()),
+            project_view_requested: Mutex::new(false),
+            project_view_wake: Condvar::new(),
             hem_shadow_metrics: Mutex::new(HemShadowMetrics::new(&hem_config)),
             hem_capability_tracker: Mutex::new(HemCapabilityTracker::new()),
+            subscriptions: SubscriptionHub::default(),
             mutation_lock: Mutex::new(()),
+            pinned_now: Mutex::new(None),
         };
 
         if let Err(err) = shared.reconcile_dead_non_idle_sessions("startup") {
@@ -233,124 +268,310 @@ impl SharedState {
         shared
     }
 
+    /// Freezes the clock used for TTL, inactivity fallbacks and HEM synthesis
+    /// at `now`, and stops probing live processes (liveness reads as unknown).
+    /// Offline replay pins the clock to each event's `recorded_at` so a trace
+    /// evaluates the same way on any machine.
+    pub fn pin_clock(&self, now: DateTime<Utc>) {
+        *self
+            .pinned_now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(now);
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.pinned_now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .unwrap_or_else(Utc::now)
+    }
+
+    fn clock_is_pinned(&self) -> bool {
+        self.pinned_now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .is_some()
+    }
+
+    #[cfg(test)]
     pub fn update_from_event(&self, event: &EventEnvelope) {
+        if let Err(err) = self.update_from_events(std::slice::from_ref(event)) {
+            tracing::warn!(error = %err, "Failed to persist daemon event");
+        }
+    }
+
+    /// Persists `events` and applies the new ones in order, all in one write
+    /// transaction; see [`Self::update_from_event_jobs`].
+    pub fn update_from_events(
+        &self,
+        events: &[EventEnvelope],
+    ) -> Result<Vec<EventOutcome>, String> {
+        Ok(self
+            .update_from_event_jobs(&[events])?
+            .pop()
+            .unwrap_or_default())
+    }
+
+    /// Persists and applies several submissions in one write transaction,
+    /// reporting an [`EventOutcome`] per event, per submission. Each event is
+    /// stored under its own savepoint, so one that cannot be stored is
+    /// rejected alone. A stored event stays stored: when deriving state from
+    /// it fails, its derived writes are rolled back, logged and skipped.
+    /// `Err` means the transaction itself failed and nothing was kept.
+    /// Expired sessions are pruned, and the project view refreshed, once per
+    /// call. Subscribers and in-memory caches see the changes only once they
+    /// are committed.
+    pub fn update_from_event_jobs(
+        &self,
+        jobs: &[&[EventEnvelope]],
+    ) -> Result<Vec<Vec<EventOutcome>>, String> {
+        // Settings files are read before the transaction so the filesystem
+        // never holds the writer.
+        let settings: HashMap<&str, SettingsRead> = jobs
+            .iter()
+            .flat_map(|events| events.iter())
+            .filter_map(|event| {
+                config_changes::read_config_change(event)
+                    .map(|read| (event.event_id.as_str(), read))
+            })
+            .collect();
         let _mutation_guard = self
             .mutation_lock
             .lock()
             .unwrap_or_else(|poisoned| poisoned.into_inner());
 
-        let inserted_rowid = match self.db.insert_event_with_rowid(event) {
+        let mut effects = PendingEffects::default();
+        let result = self.db.write_batch(|| {
+            let mut stored_rowid = None;
+            let outcomes: Vec<Vec<EventOutcome>> = jobs
+                .iter()
+                .map(|events| {
+                    events
+                        .iter()
+                        .map(|event| {
+                            let settings = settings.get(event.event_id.as_str());
+                            let (outcome, rowid) =
+                                self.store_event_locked(event, settings, &mut effects);
+                            stored_rowid = rowid.or(stored_rowid);
+                            outcome
+                        })
+                        .collect()
+                })
+                .collect();
+
+            let now = self.now();
+            let mut prune_effects = effects.nested();
+            let pruned = self
+                .db
+                .write_batch(|| self.prune_expired_sessions_locked(now, &mut prune_effects));
+            match pruned {
+                Ok(_) => effects.absorb(prune_effects),
+                Err(err) => {
+                    tracing::warn!(error = %err, "Failed to prune expired sessions after event apply");
+                    // Leave the cursor so catch-up revisits these events.
+                    return Ok(outcomes);
+                }
+            }
+
+            if let Some(rowid) = stored_rowid {
+                if let Err(err) = self.db.set_last_applied_event_rowid(rowid) {
+                    tracing::warn!(
+                        error = %err,
+                        rowid,
+                        "Failed to advance replay cursor after event apply"
+                    );
+                }
+            }
+            Ok(outcomes)
+        });
+        match &result {
+            Ok(_) => self.apply_effects(effects),
+            // Marks from a rolled-back batch may not cover what was undone.
+            Err(_) => self.lock_project_view().mark_all(),
+        }
+        self.refresh_project_view_after_write_locked();
+        result
+    }
+
+    /// Stores `event` and, when it is new, applies it. Returns its outcome
+    /// and, when it was stored, its rowid. Caller holds the mutation lock
+    /// inside a write batch.
+    fn store_event_locked(
+        &self,
+        event: &EventEnvelope,
+        settings: Option<&SettingsRead>,
+        effects: &mut PendingEffects,
+    ) -> (EventOutcome, Option<i64>) {
+        let inserted = self.db.write_batch(|| {
+            self.db
+                .insert_events_with_rowids(std::slice::from_ref(event))
+        });
+        let rowid = match inserted.map(|rowids| rowids.first().copied().flatten()) {
             Ok(Some(rowid)) => rowid,
             Ok(None) => {
                 tracing::debug!(event_id = %event.event_id, "Skipping duplicate daemon event");
-                return;
+                return (EventOutcome::Duplicate, None);
             }
             Err(err) => {
-                tracing::warn!(error = %err, "Failed to persist daemon event");
-                return;
+                tracing::warn!(event_id = %event.event_id, error = %err, "Rejected daemon event");
+                return (EventOutcome::Rejected(err), None);
             }
         };
 
-        let mut had_error = false;
+        let mut event_effects = effects.nested();
+        let applied = self
+            .db
+            .write_batch(|| self.apply_event_locked(event, settings, &mut event_effects));
+        let outcome = match applied {
+            Ok(()) => {
+                effects.absorb(event_effects);
+                EventOutcome::Applied
+            }
+            Err(err) => {
+                tracing::warn!(
+                    event_id = %event.event_id,
+                    rowid,
+                    error = %err,
+                    "Stored event but skipped its derived state"
+                );
+                EventOutcome::Skipped(err)
+            }
+        };
+        (outcome, Some(rowid))
+    }
 
-        if let Err(err) = self.db.upsert_process_liveness(event) {
-            tracing::warn!(error = %err, "Failed to update process liveness");
-            had_error = true;
+    /// Applies a freshly persisted event to derived state, queueing what it
+    /// changes outside the database on `effects`. `settings` is what
+    /// [`config_changes::read_config_change`] read for it before the
+    /// transaction. Caller holds the mutation lock.
+    fn apply_event_locked(
+        &self,
+        event: &EventEnvelope,
+        settings: Option<&SettingsRead>,
+        effects: &mut PendingEffects,
+    ) -> Result<(), String> {
+        self.db.upsert_process_liveness(event)?;
+        let subagent_changed = apply_subagent_event(&self.db, event)?.is_some();
+        tool_calls::record_tool_call(&self.db, event)?;
+        if worktrees::apply_worktree_event(&self.db, event)? {
+            self.lock_project_view().mark_all();
+        }
+        if let Some(settings) = settings {
+            config_changes::record_config_change(&self.db, event, settings)?;
         }
-        self.update_routing_process_registry(event);
+        if teams::apply_team_event(&self.db, event)? {
+            self.lock_project_view().mark_all();
+        }
+        let mut session_published = false;
 
         let current_session = match event.session_id.as_ref() {
-            Some(session_id) => self.db.get_session(session_id).ok().flatten(),
+            Some(session_id) => self.db.get_session(session_id)?,
             None => None,
         };
+        if let Some(current) = current_session.as_ref() {
+            self.lock_project_view().mark_project(&current.project_path);
+        }
 
-        match handle_session_event(&self.db, current_session.as_ref(), event) {
-            Ok(update) => match update {
-                SessionUpdate::Upsert(record) => {
-                    tracing::info!(
-                        session_id = %record.session_id,
-                        state = ?record.state,
-                        project_path = %record.project_path,
-                        pid = record.pid,
-                        "Session upsert"
-                    );
-                    let mut upserted = false;
-                    if let Err(err) = self.db.upsert_session(&record) {
-                        tracing::warn!(error = %err, "Failed to upsert session");
-                        had_error = true;
-                    } else {
-                        upserted = true;
-                    }
-                    if upserted && event.event_type == EventType::SessionStart {
-                        if let Err(err) = self.prune_superseded_sessions_for_pid_locked(&record) {
-                            tracing::warn!(
-                                error = %err,
-                                session_id = %record.session_id,
-                                pid = record.pid,
-                                "Failed to prune superseded same-pid sessions"
-                            );
-                            had_error = true;
-                        }
-                    }
-                    if let Some(entry) = reduce_activity(event) {
-                        if let Err(err) = self.db.insert_activity(&entry) {
-                            tracing::warn!(error = %err, "Failed to insert activity");
-                            had_error = true;
-                        }
-                    }
+        match handle_session_event(
+            &self.db,
+            current_session.as_ref(),
+            event,
+            &self.hem_config.session_policy,
+        )? {
+            SessionUpdate::Upsert(mut record) => {
+                tool_calls::sync_tools_in_flight(&self.db, &mut record)?;
+                tracing::info!(
+                    session_id = %record.session_id,
+                    state = ?record.state,
+                    project_path = %record.project_path,
+                    pid = record.pid,
+                    "Session upsert"
+                );
+                self.lock_project_view().mark_project(&record.project_path);
+                self.db.upsert_session(&record)?;
+                transitions::record_upsert(
+                    &self.db,
+                    current_session.as_ref(),
+                    &record,
+                    Some(&event.event_id),
+                )?;
+                effects.push(Effect::SessionUpsert(record.clone()));
+                session_published = true;
+                if event.event_type == EventType::SessionStart {
+                    self.prune_superseded_sessions_for_pid_locked(&record, effects)?;
                 }
-                SessionUpdate::Delete { session_id } => {
-                    tracing::info!(session_id = %session_id, "Session delete");
-                    if let Err(err) = self.db.delete_session(&session_id) {
-                        tracing::warn!(error = %err, "Failed to delete session");
-                        had_error = true;
-                    }
-                    if let Err(err) = self.db.delete_activity_for_session(&session_id) {
-                        tracing::warn!(error = %err, "Failed to delete activity");
-                        had_error = true;
-                    }
+                if let Some(entry) = reduce_activity(event) {
+                    self.db.insert_activity(&entry)?;
                 }
-                SessionUpdate::Skip => {}
-            },
-            Err(err) => {
-                tracing::warn!(error = %err, "Failed to apply session event");
-                had_error = true;
             }
-        }
-
-        if event.event_type == EventType::ShellCwd {
-            if let Err(err) = self.db.upsert_shell_state(event) {
-                tracing::warn!(error = %err, "Failed to update shell_state table");
-                had_error = true;
-            } else {
-                self.update_shell_state_cache(event);
-                self.update_routing_shell_registry(event);
+            SessionUpdate::Delete { session_id } => {
+                tracing::info!(session_id = %session_id, "Session delete");
+                self.db.delete_session(&session_id)?;
+                if let Some(current) = current_session.as_ref() {
+                    transitions::record_end(
+                        &self.db,
+                        current,
+                        Some(&event.event_id),
+                        &event.recorded_at,
+                    )?;
+                }
+                self.db.delete_activity_for_session(&session_id)?;
+                self.db.delete_subagents_for_session(&session_id)?;
+                self.db.delete_teams_led_by(&session_id)?;
+                effects.push(Effect::SessionDelete(session_id));
+                session_published = true;
             }
+            SessionUpdate::Skip => {}
         }
 
-        self.evaluate_hem_shadow(event);
+        time_accounting::record_human_wait(&self.db, event)?;
 
-        if let Err(err) = self.prune_expired_sessions_locked(Utc::now()) {
-            tracing::warn!(error = %err, "Failed to prune expired sessions after event apply");
-            had_error = true;
+        // Subagent traffic rarely changes the parent record, but the parent's
+        // `active_subagents` changed; republish it.
+        if subagent_changed && !session_published {
+            if let Some(record) = current_session {
+                effects.push(Effect::SessionUpsert(record));
+            }
         }
 
-        if had_error {
-            tracing::warn!(
-                event_id = %event.event_id,
-                rowid = inserted_rowid,
-                "Event applied with derived-state errors; cursor not advanced to force replay reconciliation"
-            );
-            return;
+        if event.event_type == EventType::ShellCwd {
+            self.db.upsert_shell_state(event)?;
         }
+        effects.push(Effect::EventApplied(event.clone()));
 
-        if let Err(err) = self.db.set_last_applied_event_rowid(inserted_rowid) {
-            tracing::warn!(
-                error = %err,
-                event_id = %event.event_id,
-                rowid = inserted_rowid,
-                "Failed to advance replay cursor after event apply"
-            );
+        self.evaluate_hem_shadow(event, effects);
+        Ok(())
+    }
+
+    /// Publishes and caches what a committed write changed.
+    fn apply_effects(&self, effects: PendingEffects) {
+        if let Some(tracker) = effects.hem_capability_tracker {
+            *self
+                .hem_capability_tracker
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = tracker;
+        }
+        for effect in effects.effects {
+            match effect {
+                Effect::SessionUpsert(record) => self.publish_session_upsert(record),
+                Effect::SessionDelete(session_id) => self
+                    .subscriptions
+                    .publish_delete(SubscriptionTopic::Sessions, &session_id),
+                Effect::EventApplied(event) => {
+                    self.update_routing_process_registry(&event);
+                    if event.event_type == EventType::ShellCwd {
+                        self.update_shell_state_cache(&event);
+                        self.update_routing_shell_registry(&event);
+                    }
+                }
+                Effect::HemShadow(sample) => self.record_hem_shadow_metrics(
+                    &sample.observed_at,
+                    sample.projects_evaluated,
+                    &sample.mismatches,
+                    sample.stable_state_agreement,
+                    sample.capability_status,
+                ),
+            }
         }
     }
 
@@ -399,6 +620,10 @@ impl SharedState {
                 cache.shells.remove(pid);
             }
         }
+        for pid in &dead_pids {
+            self.subscriptions
+                .publish_delete(SubscriptionTopic::Shells, pid);
+        }
 
         // Remove from DB (best-effort, don't fail the snapshot)
         if let Err(err) = self.db.delete_shells(&dead_pids) {
@@ -440,38 +665,346 @@ impl SharedState {
 
     pub fn sessions_snapshot(&self) -> Result<Vec<EnrichedSession>, String> {
         let sessions = self.db.list_sessions()?;
-        let now = Utc::now();
+        let mut subagents = self.db.list_running_subagents(None)?;
+        let now = self.now();
         let mut enriched = Vec::new();
 
         for record in sessions {
             if self.is_session_expired(&record, now) {
                 continue;
             }
+            let active_subagents = subagents.remove(&record.session_id).unwrap_or_default();
+            enriched.push(self.enrich_session_with(record, active_subagents));
+        }
 
-            let is_alive = self.session_is_alive(record.pid);
+        Ok(enriched)
+    }
 
-            let project_id = record.project_id.clone();
-            let computed_workspace_id = workspace_id(&project_id, &record.project_path);
+    pub fn state_revision(&self) -> Result<u64, String> {
+        self.db.state_revision()
+    }
 
-            enriched.push(EnrichedSession {
-                session_id: record.session_id,
-                pid: record.pid,
-                state: record.state,
-                cwd: record.cwd,
-                project_id,
-                workspace_id: computed_workspace_id,
-                project_path: record.project_path,
-                updated_at: record.updated_at,
-                state_changed_at: record.state_changed_at,
-                last_event: record.last_event,
-                last_activity_at: record.last_activity_at,
-                tools_in_flight: record.tools_in_flight,
-                ready_reason: record.ready_reason,
-                is_alive,
+    /// Sessions snapshot stamped with the state revision. With `since`, only
+    /// entries changed or removed after that revision are returned. Session
+    /// writes stamp their revisions, so this only reads.
+    pub fn sessions_snapshot_since(
+        &self,
+        since: Option<u64>,
+    ) -> Result<SnapshotDelta<EnrichedSession>, String> {
+        self.db.read_batch(|| {
+            let revision = self.db.state_revision()?;
+            let delta = match since {
+                Some(since) => Some(self.db.entry_revisions_since(
+                    SESSIONS_REVISION_KIND,
+                    since,
+                    None,
+                )?),
+                None => None,
+            };
+            // A revision from before pruned removal history, or from a
+            // different database, cannot be diffed; fall back to the full
+            // snapshot.
+            let Some((since_revision, delta)) = since
+                .zip(delta)
+                .filter(|(since, delta)| *since >= delta.floor && *since <= delta.revision)
+            else {
+                return Ok(SnapshotDelta {
+                    revision,
+                    since_revision: since,
+                    full: true,
+                    changed: self.sessions_snapshot()?,
+                    removed: Vec::new(),
+                });
+            };
+
+            let now = self.now();
+            let mut removed = delta.removed_keys;
+            let mut changed_keys: Vec<String> = delta.changed_keys.into_iter().collect();
+            changed_keys.sort();
+            let mut records: HashMap<String, SessionRecord> = self
+                .db
+                .get_sessions(&changed_keys)?
+                .into_iter()
+                .map(|record| (record.session_id.clone(), record))
+                .collect();
+            let mut subagents = self.db.list_running_subagents(Some(&changed_keys))?;
+            let mut changed = Vec::new();
+            for session_id in changed_keys {
+                match records.remove(&session_id) {
+                    Some(record) if !self.is_session_expired(&record, now) => {
+                        let active_subagents = subagents.remove(&session_id).unwrap_or_default();
+                        changed.push(self.enrich_session_with(record, active_subagents));
+                    }
+                    _ => removed.push(session_id),
+                }
+            }
+            changed.sort_by(|left, right| right.updated_at.cmp(&left.updated_at));
+            Ok(SnapshotDelta {
+                revision: delta.revision,
+                since_revision: Some(since_revision),
+                full: false,
+                changed,
+                removed,
+            })
+        })
+    }
+
+    /// Project states snapshot stamped with the revision the project view
+    /// was refreshed at; see [`Self::sessions_snapshot_since`]. Deltas come
+    /// from the revisions the view keeps per project, without a query.
+    pub fn project_states_snapshot_since(
+        &self,
+        since: Option<u64>,
+    ) -> Result<SnapshotDelta<ProjectStateRecord>, String> {
+        let delta = match self.project_view_read(since) {
+            Some(delta) => delta,
+            None => ProjectViewDelta {
+                revision: self.db.state_revision()?,
+                full: true,
+                changed: self.project_states_snapshot()?,
+                removed: Vec::new(),
+            },
+        };
+        Ok(SnapshotDelta {
+            revision: delta.revision,
+            since_revision: since,
+            full: delta.full,
+            changed: delta.changed.iter().map(ProjectState::to_record).collect(),
+            removed: delta.removed,
+        })
+    }
+
+    fn enrich_session(&self, record: SessionRecord) -> EnrichedSession {
+        let active_subagents = self
+            .db
+            .list_subagents(&record.session_id, true)
+            .unwrap_or_else(|err| {
+                tracing::warn!(error = %err, "Failed to load active subagents");
+                Vec::new()
             });
+        self.enrich_session_with(record, active_subagents)
+    }
+
+    /// Snapshots load running subagents for all their sessions at once.
+    fn enrich_session_with(
+        &self,
+        record: SessionRecord,
+        active_subagents: Vec<SubagentRecord>,
+    ) -> EnrichedSession {
+        let is_alive = self.session_is_alive(record.pid);
+        let project_id = record.project_id.clone();
+        let computed_workspace_id = workspace_id(&project_id, &record.project_path);
+
+        EnrichedSession {
+            session_id: record.session_id,
+            pid: record.pid,
+            state: record.state.into(),
+            cwd: record.cwd,
+            project_id,
+            workspace_id: computed_workspace_id,
+            project_path: record.project_path,
+            updated_at: record.updated_at,
+            state_changed_at: record.state_changed_at,
+            last_event: record.last_event,
+            last_activity_at: record.last_activity_at,
+            tools_in_flight: record.tools_in_flight,
+            ready_reason: record.ready_reason,
+            is_alive,
+            active_subagents,
+            pending_request: record.pending_request,
         }
+    }
 
-        Ok(enriched)
+    pub fn subscribe(&self, request: SubscribeRequest) -> Option<Subscription> {
+        self.subscriptions.subscribe(request)
+    }
+
+    pub fn unsubscribe(&self, id: u64) {
+        self.subscriptions.unsubscribe(id);
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscriptions.subscriber_count()
+    }
+
+    fn publish_session_upsert(&self, record: SessionRecord) {
+        if !self.subscriptions.wants(SubscriptionTopic::Sessions) {
+            return;
+        }
+        let session = self.enrich_session(record);
+        self.subscriptions.publish_upsert(
+            SubscriptionTopic::Sessions,
+            &session.session_id,
+            &session,
+        );
+    }
+
+    fn lock_project_view(&self) -> MutexGuard<'_, ProjectStatesView> {
+        self.project_view
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Recomputes whatever the project view has marked stale, publishes the
+    /// projects that changed and records their revisions. Caller holds the
+    /// mutation lock.
+    fn refresh_project_view_locked(&self) {
+        if let Err(err) = self.try_refresh_project_view_locked() {
+            tracing::warn!(error = %err, "Failed to refresh project states");
+        }
+    }
+
+    /// Refreshes the projects a write touched right away. Recomputing the
+    /// whole view (HEM primary mode, registry changes) is left to the
+    /// refresher thread so bursts of writes share one; reads in between
+    /// refresh on demand. Caller holds the mutation lock.
+    fn refresh_project_view_after_write_locked(&self) {
+        let whole = match self.lock_project_view().pending() {
+            Some(PendingRefresh::All) => true,
+            Some(PendingRefresh::Projects(_)) => self.hem_primary(),
+            None => return,
+        };
+        if !whole {
+            self.refresh_project_view_locked();
+            return;
+        }
+        *self
+            .project_view_requested
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+        self.project_view_wake.notify_one();
+    }
+
+    fn try_refresh_project_view_locked(&self) -> Result<(), String> {
+        let (mut pending, populated) = {
+            let view = self.lock_project_view();
+            match view.pending() {
+                Some(pending) => (pending, view.is_populated()),
+                None => return Ok(()),
+            }
+        };
+        // HEM groups sessions across projects, so it is always synthesized whole.
+        if self.hem_primary() {
+            pending = PendingRefresh::All;
+        }
+
+        let started = Instant::now();
+        let states = match &pending {
+            PendingRefresh::All => self.compute_project_states(None)?,
+            PendingRefresh::Projects(paths) => self.compute_project_states(Some(paths))?,
+        };
+        let update = self.lock_project_view().plan(&pending, states);
+
+        // The first refresh after startup also retires projects recorded by
+        // the previous run that no longer exist.
+        let mut removed: Vec<String> = update.changes.removed.clone();
+        if !populated {
+            let present: HashSet<&str> = update
+                .changes
+                .upserted
+                .iter()
+                .map(|state| state.project_path.as_str())
+                .collect();
+            removed.extend(
+                self.db
+                    .live_entry_keys(PROJECT_STATES_REVISION_KIND)?
+                    .into_iter()
+                    .filter(|key| !present.contains(key.as_str())),
+            );
+        }
+        let changed: Vec<&str> = update
+            .changes
+            .upserted
+            .iter()
+            .map(|state| state.project_path.as_str())
+            .collect();
+        let removed: Vec<&str> = removed.iter().map(String::as_str).collect();
+        let revision = if update.changes.is_empty() && removed.is_empty() {
+            self.db.state_revision()?
+        } else {
+            self.db
+                .record_entry_revisions(PROJECT_STATES_REVISION_KIND, &changed, &removed)?
+        };
+
+        let changes =
+            self.lock_project_view()
+                .apply(update, revision, self.now(), started.elapsed());
+        if self.subscriptions.wants(SubscriptionTopic::ProjectStates) {
+            for state in &changes.upserted {
+                self.subscriptions.publish_upsert(
+                    SubscriptionTopic::ProjectStates,
+                    &state.project_path,
+                    &state.to_record(),
+                );
+            }
+            for project_path in &changes.removed {
+                self.subscriptions
+                    .publish_delete(SubscriptionTopic::ProjectStates, project_path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Refresher thread step: waits up to `interval` for a write to leave a
+    /// whole-view refresh behind and runs it, or re-evaluates every project
+    /// when the interval passes; see [`Self::refresh_project_view`].
+    pub fn wait_and_refresh_project_view(&self, interval: std::time::Duration) {
+        let requested = self
+            .project_view_requested
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (mut requested, _) = self
+            .project_view_wake
+            .wait_timeout_while(requested, interval, |requested| !*requested)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let woken = std::mem::take(&mut *requested);
+        drop(requested);
+
+        if !woken {
+            self.refresh_project_view();
+            return;
+        }
+        let _mutation_guard = self
+            .mutation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.refresh_project_view_locked();
+    }
+
+    /// Re-evaluates every project, picking up liveness changes and time-based
+    /// fallbacks that no event announced.
+    pub fn refresh_project_view(&self) {
+        let _mutation_guard = self
+            .mutation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        self.lock_project_view().mark_all();
+        self.refresh_project_view_locked();
+    }
+
+    pub fn project_view_status(&self) -> ProjectViewHealth {
+        self.lock_project_view().status()
+    }
+
+    /// Project states from the view, changed after `since` when given. A
+    /// read that finds the view stale runs the pending refresh itself, after
+    /// any write in progress. `None` if the view is still stale after that
+    /// (the refresh failed) or the clock is pinned, which bypasses the view.
+    fn project_view_read(&self, since: Option<u64>) -> Option<ProjectViewDelta> {
+        if self.clock_is_pinned() {
+            return None;
+        }
+        if let Some(delta) = self.lock_project_view().read_since(since) {
+            return Some(delta);
+        }
+
+        let _mutation_guard = self
+            .mutation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.refresh_project_view_locked();
+        self.lock_project_view().read_since(since)
     }
 
     pub fn reconcile_dead_non_idle_sessions(&self, source: &str) -> Result<usize, String> {
@@ -493,13 +1026,25 @@ impl SharedState {
                 continue;
             }
 
+            let current = record.clone();
             record.state = crate::reducer::SessionState::Idle;
             record.updated_at = now.clone();
             record.state_changed_at = now.clone();
             record.last_event = Some(marker.clone());
             record.tools_in_flight = 0;
             record.ready_reason = None;
-            self.db.upsert_session(record)?;
+            record.pending_request = None;
+            // The same bookkeeping an event-driven change gets: the timeline
+            // sees the demotion, and a dead process no longer waits on a human
+            // or runs tools.
+            self.db.write_batch(|| {
+                self.db.upsert_session(record)?;
+                transitions::record_upsert(&self.db, Some(&current), record, None)?;
+                self.db.close_human_wait(&record.session_id, &now)?;
+                self.db.interrupt_tool_calls(&record.session_id, None, &now)
+            })?;
+            self.lock_project_view().mark_project(&record.project_path);
+            self.publish_session_upsert(record.clone());
             repaired += 1;
         }
 
@@ -511,13 +1056,92 @@ impl SharedState {
             );
         }
 
-        self.prune_expired_sessions_locked(Utc::now())?;
+        let mut effects = PendingEffects::default();
+        let pruned = self
+            .db
+            .write_batch(|| self.prune_expired_sessions_locked(Utc::now(), &mut effects));
+        if pruned.is_ok() {
+            self.apply_effects(effects);
+        }
+        self.refresh_project_view_locked();
+        pruned?;
         self.record_dead_session_reconcile(source, repaired as u64, &now);
 
         Ok(repaired)
     }
 
-    pub fn dead_session_reconcile_snapshot(&self) -> HashMap<String, DeadSessionReconcileMetrics> {
+    /// Drops registered worktrees whose directory is gone.
+    pub fn prune_missing_worktrees(&self) -> Result<usize, String> {
+        let _mutation_guard = self
+            .mutation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let pruned = worktrees::prune_missing_worktrees(&self.db, self.now())?;
+        if pruned > 0 {
+            tracing::info!(count = pruned, "Pruned missing worktrees");
+            self.lock_project_view().mark_all();
+            self.refresh_project_view_locked();
+        }
+        Ok(pruned)
+    }
+
+    pub fn retention_enabled(&self) -> bool {
+        self.hem_config.retention.enabled
+    }
+
+    pub fn retention_interval_secs(&self) -> u64 {
+        self.hem_config.retention.interval_secs
+    }
+
+    pub fn metrics_config(&self) -> &MetricsConfig {
+        &self.hem_config.metrics
+    }
+
+    /// Prunes events and activity past the retention horizons. Runs under the
+    /// mutation lock so the snapshot matches the event cursor.
+    pub fn run_retention(&self) -> Result<RetentionRun, String> {
+        let _mutation_guard = self
+            .mutation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let now = self.now();
+        let result = retention::run_retention(&self.db, &self.hem_config.retention, now);
+        if let Ok(mut status) = self.retention_status.lock() {
+            match &result {
+                Ok(run) => status.record_run(run, now),
+                Err(err) => status.record_error(err, now),
+            }
+        }
+        result
+    }
+
+    pub fn retention_status_snapshot(&self) -> RetentionStatus {
+        let mut status = self
+            .retention_status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default();
+        status.event_count = self.db.event_count().unwrap_or_default();
+        status.oldest_event_at = self.db.oldest_event_time().unwrap_or_default();
+        status
+    }
+
+    pub fn record_integrity_report(&self, report: IntegrityHealth) {
+        if let Ok(mut slot) = self.integrity_report.lock() {
+            *slot = Some(report);
+        }
+    }
+
+    pub fn integrity_report(&self) -> Option<IntegrityHealth> {
+        self.integrity_report
+            .lock()
+            .ok()
+            .and_then(|report| report.clone())
+    }
+
+    pub fn dead_session_reconcile_snapshot(&self) -> HashMap<String, DeadSessionReconcileHealth> {
         self.dead_session_reconcile
             .lock()
             .map(|metrics| metrics.clone())
@@ -539,7 +1163,10 @@ impl SharedState {
     }
 
     pub fn routing_config_view(&self) -> RoutingConfigView {
-        self.routing_config.view()
+        RoutingConfigView {
+            session_policy: Some(self.hem_config.session_policy.view()),
+            ..self.routing_config.view()
+        }
     }
 
     pub fn routing_poller_enabled(&self) -> bool {
@@ -605,6 +1232,21 @@ impl SharedState {
             return;
         }
 
+        if diff != crate::are::tmux_poller::TmuxDiff::default() {
+            self.subscriptions.publish_upsert(
+                SubscriptionTopic::Routing,
+                "tmux",
+                &serde_json::json!({
+                    "clients_added": diff.clients_added,
+                    "clients_removed": diff.clients_removed,
+                    "clients_updated": diff.clients_updated,
+                    "sessions_added": diff.sessions_added,
+                    "sessions_removed": diff.sessions_removed,
+                    "sessions_updated": diff.sessions_updated,
+                }),
+            );
+        }
+
         tracing::debug!(
             clients_added = diff.clients_added,
             clients_removed = diff.clients_removed,
@@ -688,7 +1330,7 @@ impl SharedState {
     }
 
     fn session_is_alive(&self, pid: u32) -> Option<bool> {
-        if pid == 0 {
+        if pid == 0 || self.clock_is_pinned() {
             return None;
         }
 
@@ -707,28 +1349,97 @@ impl SharedState {
         }
     }
 
+    /// Project states computed from the database, bypassing the view.
     pub fn project_states_snapshot(&self) -> Result<Vec<ProjectState>, String> {
-        if self.hem_config.engine.enabled && matches!(self.hem_config.engine.mode, HemMode::Primary)
-        {
+        self.compute_project_states(None)
+    }
+
+    fn hem_primary(&self) -> bool {
+        self.hem_config.engine.enabled && matches!(self.hem_config.engine.mode, HemMode::Primary)
+    }
+
+    /// Aggregates the projects in `only`, or all of them. HEM primary mode
+    /// always synthesizes every project.
+    fn compute_project_states(
+        &self,
+        only: Option<&HashSet<String>>,
+    ) -> Result<Vec<ProjectState>, String> {
+        if self.hem_primary() {
             return self.project_states_snapshot_hem_primary();
         }
 
-        let sessions = self.db.list_sessions()?;
-        let now = Utc::now();
+        // A partial refresh reads only the rows of the projects it covers,
+        // plus the sessions their teams list, which may live elsewhere.
+        let (mut sessions, registered_worktrees, teams) = match only {
+            Some(paths) => {
+                let paths: Vec<String> = paths.iter().cloned().collect();
+                let mut worktrees = Vec::new();
+                for path in &paths {
+                    worktrees.extend(self.db.list_worktrees(Some(path))?);
+                }
+                (
+                    self.db.list_sessions_for_projects(&paths)?,
+                    worktrees,
+                    Teams::load(&self.db, Some(&paths))?,
+                )
+            }
+            None => (
+                self.db.list_sessions()?,
+                self.db.list_worktrees(None)?,
+                Teams::load(&self.db, None)?,
+            ),
+        };
+        if only.is_some() {
+            let loaded: HashSet<&str> = sessions
+                .iter()
+                .map(|record| record.session_id.as_str())
+                .collect();
+            let team_sessions: Vec<String> = teams
+                .session_ids()
+                .filter(|session_id| !loaded.contains(session_id))
+                .map(str::to_string)
+                .collect();
+            sessions.extend(self.db.get_sessions(&team_sessions)?);
+        }
+        let session_ids: Vec<String> = sessions
+            .iter()
+            .map(|record| record.session_id.clone())
+            .collect();
+        let tools_tracked = self.db.sessions_with_tracked_tools(&session_ids)?;
+        let now = self.now();
         let mut sessions_by_project: HashMap<String, Vec<SessionProjection>> = HashMap::new();
+        let mut pending_requests: HashMap<String, PendingRequest> = HashMap::new();
+        let mut session_cwds: HashMap<String, String> = HashMap::new();
+        let mut session_states: HashMap<String, crate::reducer::SessionState> = HashMap::new();
 
         for record in sessions {
             if record.project_path.trim().is_empty() {
                 continue;
             }
-
             if self.is_session_expired(&record, now) {
                 continue;
             }
 
             let is_alive = self.session_is_alive(record.pid);
-            let effective_state = effective_session_state(&record, now, is_alive);
+            let effective_state = effective_session_state(
+                &record,
+                now,
+                is_alive,
+                tools_tracked.contains(&record.session_id),
+                &self.hem_config.session_policy,
+            );
+            session_states.insert(record.session_id.clone(), effective_state.clone());
+            if only.is_some_and(|paths| !paths.contains(&record.project_path)) {
+                continue;
+            }
+
+            if effective_state == crate::reducer::SessionState::Waiting {
+                if let Some(pending_request) = record.pending_request.clone() {
+                    pending_requests.insert(record.session_id.clone(), pending_request);
+                }
+            }
             let session_time = session_timestamp(&record).unwrap_or(now);
+            session_cwds.insert(record.session_id.clone(), record.cwd.clone());
             sessions_by_project
                 .entry(record.project_path.clone())
                 .or_default()
@@ -749,6 +1460,14 @@ impl SharedState {
             };
             let has_session = aggregate.state != crate::reducer::SessionState::Idle;
             let computed_workspace_id = workspace_id(&aggregate.project_id, &project_path);
+            let pending_request = project_pending_request(
+                &aggregate.state,
+                aggregate.representative_session_id.as_deref(),
+                &pending_requests,
+            );
+            let worktrees =
+                worktrees::project_worktrees(&registered_worktrees, &projections, &session_cwds);
+            let teams = teams.project_teams(&project_path, &session_states, now);
             results.push(ProjectState {
                 project_id: aggregate.project_id.clone(),
                 workspace_id: computed_workspace_id,
@@ -761,6 +1480,9 @@ impl SharedState {
                 session_count: aggregate.session_count,
                 active_count: aggregate.active_count,
                 has_session,
+                pending_request,
+                worktrees,
+                teams,
             });
         }
         results.sort_by(|left, right| {
@@ -792,9 +1514,26 @@ impl SharedState {
         Ok(results)
     }
 
-    fn project_states_snapshot_hem_primary(&self) -> Result<Vec<ProjectState>, String> {
+    /// HEM's synthesized project states for the current sessions, whatever
+    /// the engine mode. Primary mode serves these through
+    /// `project_states_snapshot`; offline replay shows them in every mode.
+    pub fn hem_project_states_snapshot(&self) -> Result<Vec<HemProjectState>, String> {
+        let now = self.now();
+        let eligible_sessions = self.hem_eligible_sessions(now)?;
+        Ok(crate::hem::synthesize_project_states_shadow(
+            &eligible_sessions,
+            now,
+            &self.hem_config,
+        ))
+    }
+
+    fn hem_eligible_sessions(&self, now: DateTime<Utc>) -> Result<Vec<SessionRecord>, String> {
         let sessions = self.db.list_sessions()?;
-        let now = Utc::now();
+        let session_ids: Vec<String> = sessions
+            .iter()
+            .map(|record| record.session_id.clone())
+            .collect();
+        let tools_tracked = self.db.sessions_with_tracked_tools(&session_ids)?;
         let mut eligible_sessions = Vec::new();
 
         for record in sessions {
@@ -805,13 +1544,46 @@ impl SharedState {
                 continue;
             }
             let is_alive = self.session_is_alive(record.pid);
+            let tools_tracked = tools_tracked.contains(&record.session_id);
             let mut normalized = record;
-            normalized.state = effective_session_state(&normalized, now, is_alive);
+            normalized.state = effective_session_state(
+                &normalized,
+                now,
+                is_alive,
+                tools_tracked,
+                &self.hem_config.session_policy,
+            );
             eligible_sessions.push(normalized);
         }
+        Ok(eligible_sessions)
+    }
+
+    fn project_states_snapshot_hem_primary(&self) -> Result<Vec<ProjectState>, String> {
+        let now = self.now();
+        let eligible_sessions = self.hem_eligible_sessions(now)?;
 
         let hem_states =
             crate::hem::synthesize_project_states_shadow(&eligible_sessions, now, &self.hem_config);
+        let registered_worktrees = self.db.list_worktrees(None)?;
+        let teams = Teams::load(&self.db, None)?;
+        let session_cwds: HashMap<String, String> = eligible_sessions
+            .iter()
+            .map(|record| (record.session_id.clone(), record.cwd.clone()))
+            .collect();
+        let session_states: HashMap<String, crate::reducer::SessionState> = eligible_sessions
+            .iter()
+            .map(|record| (record.session_id.clone(), record.state.clone()))
+            .collect();
+        let pending_requests: HashMap<String, PendingRequest> = eligible_sessions
+            .iter()
+            .filter(|record| record.state == crate::reducer::SessionState::Waiting)
+            .filter_map(|record| {
+                record
+                    .pending_request
+                    .clone()
+                    .map(|pending_request| (record.session_id.clone(), pending_request))
+            })
+            .collect();
 
         let mut results = Vec::new();
         for hem_state in hem_states {
@@ -836,6 +1608,8 @@ impl SharedState {
                 .collect::<Vec<_>>();
 
             let reduced = reduce_project_sessions(&projections);
+            let worktrees =
+                worktrees::project_worktrees(&registered_worktrees, &projections, &session_cwds);
             let project_id = reduced
                 .as_ref()
                 .map(|aggregate| aggregate.project_id.clone())
@@ -875,6 +1649,9 @@ impl SharedState {
                 }
             };
 
+            let pending_request =
+                project_pending_request(&state, session_id.as_deref(), &pending_requests);
+            let teams = teams.project_teams(&hem_state.project_path, &session_states, now);
             results.push(ProjectState {
                 project_id,
                 workspace_id: computed_workspace_id,
@@ -887,6 +1664,9 @@ impl SharedState {
                 session_count,
                 active_count,
                 has_session: state != crate::reducer::SessionState::Idle,
+                pending_request,
+                worktrees,
+                teams,
             });
         }
 
@@ -929,7 +1709,7 @@ impl SharedState {
             return false;
         };
 
-        let ttl_secs = session_ttl_seconds(&record.state);
+        let ttl_secs = self.hem_config.session_policy.ttl_secs(&record.state);
         let expires_at = last_seen + Duration::seconds(ttl_secs);
         if now > expires_at {
             tracing::info!(
@@ -943,16 +1723,30 @@ impl SharedState {
         false
     }
 
-    fn prune_session(&self, session_id: &str) -> Result<(), String> {
+    fn prune_session(
+        &self,
+        record: &SessionRecord,
+        effects: &mut PendingEffects,
+    ) -> Result<(), String> {
+        let session_id = record.session_id.as_str();
         tracing::info!(session_id = %session_id, "Pruning session");
         self.db.delete_session(session_id)?;
+        self.lock_project_view().mark_project(&record.project_path);
+        let now = self.now().to_rfc3339();
+        transitions::record_end(&self.db, record, None, &now)?;
+        self.db.close_human_wait(session_id, &now)?;
+        self.db.interrupt_tool_calls(session_id, None, &now)?;
         self.db.delete_activity_for_session(session_id)?;
+        self.db.delete_subagents_for_session(session_id)?;
+        self.db.delete_teams_led_by(session_id)?;
+        effects.push(Effect::SessionDelete(session_id.to_string()));
         Ok(())
     }
 
     fn prune_superseded_sessions_for_pid_locked(
         &self,
         current: &SessionRecord,
+        effects: &mut PendingEffects,
     ) -> Result<(), String> {
         if current.pid == 0 {
             return Ok(());
@@ -970,18 +1764,22 @@ impl SharedState {
                 _ => true,
             };
             if should_prune {
-                self.prune_session(&record.session_id)?;
+                self.prune_session(&record, effects)?;
             }
         }
         Ok(())
     }
 
-    fn prune_expired_sessions_locked(&self, now: DateTime<Utc>) -> Result<usize, String> {
+    fn prune_expired_sessions_locked(
+        &self,
+        now: DateTime<Utc>,
+        effects: &mut PendingEffects,
+    ) -> Result<usize, String> {
         let sessions = self.db.list_sessions()?;
         let mut pruned = 0usize;
         for record in sessions {
             if self.is_session_expired(&record, now) {
-                self.prune_session(&record.session_id)?;
+                self.prune_session(&record, effects)?;
                 pruned += 1;
             }
         }
@@ -1002,7 +1800,59 @@ impl SharedState {
         }
     }
 
-    pub fn tombstones_snapshot(&self) -> Result<Vec<TombstoneRow>, String> {
+    pub fn subagents_snapshot(&self, session_id: &str) -> Result<Vec<SubagentRecord>, String> {
+        self.db.list_subagents(session_id, false)
+    }
+
+    pub fn session_timeline(
+        &self,
+        request: &SessionTimelineRequest,
+    ) -> Result<SessionTimeline, String> {
+        let parse = |value: &Option<String>| {
+            value
+                .as_deref()
+                .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+                .map(|value| value.with_timezone(&Utc))
+        };
+        let history = self.db.list_session_transitions(&request.session_id)?;
+        let live = self.db.get_session(&request.session_id)?.is_some();
+        Ok(transitions::build_timeline(
+            &request.session_id,
+            history,
+            parse(&request.since),
+            parse(&request.until),
+            self.now(),
+            live,
+        ))
+    }
+
+    pub fn project_time(&self, query: &ProjectTimeQuery) -> Result<Vec<ProjectDayTime>, String> {
+        time_accounting::project_time(&self.db, query, self.now())
+    }
+
+    pub fn tool_calls(&self, query: &ToolCallsQuery) -> Result<ToolCallsResponse, String> {
+        tool_calls::tool_calls(&self.db, query, self.now())
+    }
+
+    pub fn worktrees(&self, query: &WorktreesQuery) -> Result<Vec<WorktreeRecord>, String> {
+        worktrees::worktrees(&self.db, query)
+    }
+
+    pub fn config_changes(
+        &self,
+        query: &ConfigChangesQuery,
+    ) -> Result<Vec<ConfigChangeRecord>, String> {
+        config_changes::config_changes(&self.db, query)
+    }
+
+    pub fn events_page(
+        &self,
+        query: &EventsQuery,
+    ) -> Result<(Vec<EventEnvelope>, Option<u64>), String> {
+        self.db.list_events_page(query)
+    }
+
+    pub fn tombstones_snapshot(&self) -> Result<Vec<Tombstone>, String> {
         self.db.list_tombstones()
     }
 
@@ -1021,6 +1871,9 @@ impl SharedState {
             updated_at: event.recorded_at.clone(),
         };
 
+        self.subscriptions
+            .publish_upsert(SubscriptionTopic::Shells, &pid.to_string(), &entry);
+
         if let Ok(mut state) = self.shell_state.lock() {
             state.shells.insert(pid.to_string(), entry);
             tracing::debug!(
@@ -1235,7 +2088,7 @@ impl SharedState {
         if let Ok(mut metrics) = self.dead_session_reconcile.lock() {
             let entry = metrics
                 .entry(source.to_string())
-                .or_insert_with(DeadSessionReconcileMetrics::default);
+                .or_insert_with(DeadSessionReconcileHealth::default);
             entry.runs = entry.runs.saturating_add(1);
             entry.last_run_at = Some(now.to_string());
             if repaired > 0 {
@@ -1247,7 +2100,9 @@ impl SharedState {
         }
     }
 
-    fn evaluate_hem_shadow(&self, event: &EventEnvelope) {
+    /// Compares HEM against the reducer for `event`. Mismatches are stored
+    /// with the event's writes; the metrics update waits for the commit.
+    fn evaluate_hem_shadow(&self, event: &EventEnvelope, effects: &mut PendingEffects) {
         let enabled = self
             .hem_shadow_metrics
             .lock()
@@ -1260,12 +2115,14 @@ impl SharedState {
             return;
         }
 
-        let capability_assessment = if let Ok(mut tracker) = self.hem_capability_tracker.lock() {
-            tracker.observe_event(event);
-            tracker.assess(&self.hem_config, event.recorded_at.as_str())
-        } else {
-            crate::hem::HemCapabilityAssessment::from_config(&self.hem_config)
-        };
+        let tracker = effects.hem_capability_tracker.get_or_insert_with(|| {
+            self.hem_capability_tracker
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+        });
+        tracker.observe_event(event);
+        let capability_assessment = tracker.assess(&self.hem_config, event.recorded_at.as_str());
         if capability_assessment.status.warning_count > 0 && capability_assessment.warnings_changed
         {
             tracing::warn!(
@@ -1299,7 +2156,7 @@ impl SharedState {
         };
         let hem_states = crate::hem::synthesize_project_states_shadow_with_capabilities(
             &sessions,
-            Utc::now(),
+            self.now(),
             &self.hem_config,
             &effective_capabilities,
         );
@@ -1318,13 +2175,13 @@ impl SharedState {
                 tracing::warn!(error = %err, "Failed to persist HEM shadow mismatch");
             }
         }
-        self.record_hem_shadow_metrics(
-            event.recorded_at.as_str(),
-            reducer_states.len(),
-            &mismatches,
+        effects.push(Effect::HemShadow(HemShadowSample {
+            observed_at: event.recorded_at.clone(),
+            projects_evaluated: reducer_states.len(),
+            mismatches,
             stable_state_agreement,
-            capability_assessment.status,
-        );
+            capability_status: capability_assessment.status,
+        }));
     }
 
     fn record_hem_shadow_metrics(
@@ -1405,17 +2262,75 @@ fn should_evaluate_hem_shadow_for_event(event_type: EventType) -> bool {
     )
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
-pub struct DeadSessionReconcileMetrics {
-    pub runs: u64,
-    pub repaired_sessions: u64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_run_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_repair_at: Option<String>,
+/// What happened to one submitted event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventOutcome {
+    /// Stored and applied.
+    Applied,
+    /// Stored, but deriving state from it failed; its derived writes were
+    /// rolled back and skipped.
+    Skipped(String),
+    /// Already stored (same `event_id`); nothing was applied.
+    Duplicate,
+    /// Could not be stored; nothing from it was kept.
+    Rejected(String),
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl EventOutcome {
+    /// Whether the event was new and is now stored.
+    pub fn is_stored(&self) -> bool {
+        matches!(self, EventOutcome::Applied | EventOutcome::Skipped(_))
+    }
+}
+
+/// What a write changes outside the database (subscriber publishes, caches,
+/// HEM shadow metrics), held back until its transaction commits so nothing
+/// sees state a rollback undoes.
+#[derive(Default)]
+struct PendingEffects {
+    effects: Vec<Effect>,
+    /// The capability tracker as the write's events left it.
+    hem_capability_tracker: Option<HemCapabilityTracker>,
+}
+
+impl PendingEffects {
+    fn push(&mut self, effect: Effect) {
+        self.effects.push(effect);
+    }
+
+    /// Effects of a nested write, kept only if [`Self::absorb`]ed.
+    fn nested(&self) -> Self {
+        Self {
+            effects: Vec::new(),
+            hem_capability_tracker: self.hem_capability_tracker.clone(),
+        }
+    }
+
+    fn absorb(&mut self, nested: Self) {
+        self.effects.extend(nested.effects);
+        if nested.hem_capability_tracker.is_some() {
+            self.hem_capability_tracker = nested.hem_capability_tracker;
+        }
+    }
+}
+
+enum Effect {
+    SessionUpsert(SessionRecord),
+    SessionDelete(String),
+    /// Updates the shell cache and routing registries.
+    EventApplied(EventEnvelope),
+    HemShadow(HemShadowSample),
+}
+
+struct HemShadowSample {
+    observed_at: String,
+    projects_evaluated: usize,
+    mismatches: Vec<HemShadowMismatch>,
+    stable_state_agreement: StableStateAgreementSample,
+    capability_status: HemCapabilityStatus,
+}
+
+#[derive(Debug, Clone)]
 pub struct HemShadowMetrics {
     pub enabled: bool,
     pub mode: String,
@@ -1435,11 +2350,8 @@ pub struct HemShadowMetrics {
     pub stable_state_agreement_gate_met: bool,
     pub shadow_gate_ready: bool,
     pub blocking_mismatch_rate: f64,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_evaluated_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_mismatch_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_blocking_mismatch_at: Option<String>,
 }
 
@@ -1475,6 +2387,32 @@ impl HemShadowMetrics {
         }
     }
 
+    pub fn health(&self) -> HemShadowHealth {
+        HemShadowHealth {
+            enabled: self.enabled,
+            mode: self.mode.clone(),
+            capability_status: self.capability_status.health(),
+            events_evaluated: self.events_evaluated,
+            projects_evaluated: self.projects_evaluated,
+            mismatches_total: self.mismatches_total,
+            mismatches_by_category: self.mismatches_by_category.clone(),
+            mismatches_by_severity: self.mismatches_by_severity.clone(),
+            gate_blocking_mismatches: self.gate_blocking_mismatches,
+            gate_critical_mismatches: self.gate_critical_mismatches,
+            gate_important_mismatches: self.gate_important_mismatches,
+            stable_state_samples: self.stable_state_samples,
+            stable_state_matches: self.stable_state_matches,
+            stable_state_agreement_rate: self.stable_state_agreement_rate,
+            stable_state_agreement_gate_target: self.stable_state_agreement_gate_target,
+            stable_state_agreement_gate_met: self.stable_state_agreement_gate_met,
+            shadow_gate_ready: self.shadow_gate_ready,
+            blocking_mismatch_rate: self.blocking_mismatch_rate,
+            last_evaluated_at: self.last_evaluated_at.clone(),
+            last_mismatch_at: self.last_mismatch_at.clone(),
+            last_blocking_mismatch_at: self.last_blocking_mismatch_at.clone(),
+        }
+    }
+
     fn refresh_cutover_summary(&mut self) {
         self.shadow_gate_ready =
             self.enabled && self.events_evaluated > 0 && self.gate_blocking_mismatches == 0;
@@ -1523,7 +2461,7 @@ impl Default for HemShadowMetrics {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ProjectState {
     pub project_id: String,
     pub workspace_id: String,
@@ -1536,6 +2474,30 @@ pub struct ProjectState {
     pub session_count: usize,
     pub active_count: usize,
     pub has_session: bool,
+    pub pending_request: Option<PendingRequest>,
+    pub worktrees: Vec<ProjectWorktreeState>,
+    pub teams: Vec<TeamState>,
+}
+
+impl ProjectState {
+    pub fn to_record(&self) -> ProjectStateRecord {
+        ProjectStateRecord {
+            project_id: self.project_id.clone(),
+            workspace_id: self.workspace_id.clone(),
+            project_path: self.project_path.clone(),
+            state: self.state.clone().into(),
+            state_changed_at: self.state_changed_at.clone(),
+            updated_at: self.updated_at.clone(),
+            session_id: self.session_id.clone(),
+            latest_session_id: self.latest_session_id.clone(),
+            session_count: self.session_count,
+            active_count: self.active_count,
+            has_session: self.has_session,
+            pending_request: self.pending_request.clone(),
+            worktrees: self.worktrees.clone(),
+            teams: self.teams.clone(),
+        }
+    }
 }
 
 fn build_hem_shadow_mismatches(
@@ -1703,10 +2665,14 @@ fn select_mismatches_for_persistence(
     selected
 }
 
+/// `tools_tracked` says the session's calls are paired by `tool_use_id`, so
+/// `record.tools_in_flight` is exact rather than a Pre/Post counter.
 fn effective_session_state(
     record: &SessionRecord,
     now: DateTime<Utc>,
     is_alive: Option<bool>,
+    tools_tracked: bool,
+    policy: &SessionPolicy,
 ) -> crate::reducer::SessionState {
     if matches!(record.state, crate::reducer::SessionState::Ready)
         && record.ready_reason.as_deref() == Some("stop_gate")
@@ -1715,7 +2681,9 @@ fn effective_session_state(
         let stop_time =
             parse_rfc3339(&record.state_changed_at).or_else(|| parse_rfc3339(&record.updated_at));
         if let Some(stop_time) = stop_time {
-            if now.signed_duration_since(stop_time).num_seconds() <= STOP_GATE_WORKING_GRACE_SECS {
+            if now.signed_duration_since(stop_time).num_seconds()
+                <= policy.stop_gate_grace_secs as i64
+            {
                 return crate::reducer::SessionState::Working;
             }
         }
@@ -1725,20 +2693,22 @@ fn effective_session_state(
         return crate::reducer::SessionState::Idle;
     }
 
-    if let Some(state) = inactivity_fallback_state(record, now) {
+    if let Some(state) = inactivity_fallback_state(record, now, tools_tracked, policy) {
         return state;
     }
     record.state.clone()
 }
 
-fn session_ttl_seconds(state: &crate::reducer::SessionState) -> i64 {
-    match state {
-        crate::reducer::SessionState::Working
-        | crate::reducer::SessionState::Waiting
-        | crate::reducer::SessionState::Compacting => SESSION_TTL_ACTIVE_SECS,
-        crate::reducer::SessionState::Ready => SESSION_TTL_READY_SECS,
-        crate::reducer::SessionState::Idle => SESSION_TTL_IDLE_SECS,
+/// The representative session's pending request while the project waits.
+fn project_pending_request(
+    state: &crate::reducer::SessionState,
+    session_id: Option<&str>,
+    pending_requests: &HashMap<String, PendingRequest>,
+) -> Option<PendingRequest> {
+    if *state != crate::reducer::SessionState::Waiting {
+        return None;
     }
+    session_id.and_then(|session_id| pending_requests.get(session_id).cloned())
 }
 
 fn session_timestamp(record: &SessionRecord) -> Option<DateTime<Utc>> {
@@ -1746,17 +2716,18 @@ fn session_timestamp(record: &SessionRecord) -> Option<DateTime<Utc>> {
 }
 
 #[derive(Clone, Copy)]
-enum InactivityFallbackGuard {
-    RequireLastEvent(&'static [&'static str]),
+enum InactivityFallbackGuard<'a> {
+    RequireLastEvent(&'a [String]),
 }
 
-fn inactivity_fallback_policy(
+fn inactivity_fallback_policy<'a>(
     state: &crate::reducer::SessionState,
-) -> Option<(crate::reducer::SessionState, InactivityFallbackGuard)> {
+    policy: &'a SessionPolicy,
+) -> Option<(crate::reducer::SessionState, InactivityFallbackGuard<'a>)> {
     match state {
-        crate::reducer::SessionState::Working => Some((
+        crate::reducer::SessionState::Working if policy.inactivity_fallback => Some((
             crate::reducer::SessionState::Ready,
-            InactivityFallbackGuard::RequireLastEvent(&["task_completed"]),
+            InactivityFallbackGuard::RequireLastEvent(&policy.inactivity_fallback_events),
         )),
         _ => None,
     }
@@ -1765,52 +2736,53 @@ fn inactivity_fallback_policy(
 fn inactivity_fallback_state(
     record: &SessionRecord,
     now: DateTime<Utc>,
+    tools_tracked: bool,
+    policy: &SessionPolicy,
 ) -> Option<crate::reducer::SessionState> {
-    let (target_state, fallback_guard) = inactivity_fallback_policy(&record.state)?;
-    if should_apply_inactivity_fallback(record, fallback_guard, now) {
+    let (target_state, fallback_guard) = inactivity_fallback_policy(&record.state, policy)?;
+    if should_apply_inactivity_fallback(record, fallback_guard, now, tools_tracked, policy) {
         return Some(target_state);
     }
     None
 }
 
+/// A quiet session falls back once both its last activity and its last update
+/// are `inactivity_fallback_secs` old, if it ended on one of the expected
+/// completion events or on a `post_tool_use` older than
+/// `post_tool_use_stale_secs` (the `Stop` after it was lost).
+///
+/// Running tools block the fallback. The stale `post_tool_use` exception stays
+/// for sessions whose hooks send no `tool_use_id`: their count is the reducer's
+/// Pre/Post counter, which leaks whenever a post event is lost. Counts from
+/// tracked calls are exact and always block.
 fn should_apply_inactivity_fallback(
     record: &SessionRecord,
-    fallback_guard: InactivityFallbackGuard,
+    fallback_guard: InactivityFallbackGuard<'_>,
     now: DateTime<Utc>,
+    tools_tracked: bool,
+    policy: &SessionPolicy,
 ) -> bool {
     let InactivityFallbackGuard::RequireLastEvent(expected_events) = fallback_guard;
-    let last_event = record.last_event.as_deref().unwrap_or_default();
-    let Some(last_activity) = record.last_activity_at.as_deref().and_then(parse_rfc3339) else {
+    let age_secs =
+        |value: &str| parse_rfc3339(value).map(|at| now.signed_duration_since(at).num_seconds());
+    let (Some(activity_age_secs), Some(update_age_secs)) = (
+        record.last_activity_at.as_deref().and_then(age_secs),
+        age_secs(&record.updated_at),
+    ) else {
         return false;
     };
-    let activity_age_secs = now.signed_duration_since(last_activity).num_seconds();
-    if activity_age_secs < SESSION_AUTO_READY_SECS {
+    let quiet_secs = activity_age_secs.min(update_age_secs);
+    if quiet_secs < policy.inactivity_fallback_secs as i64 {
         return false;
     }
 
-    let Some(last_session_update) = parse_rfc3339(&record.updated_at) else {
-        return false;
-    };
-    let update_age_secs = now.signed_duration_since(last_session_update).num_seconds();
-    if update_age_secs < SESSION_AUTO_READY_SECS {
-        return false;
-    }
-
-    let long_stale_post_tool_use = last_event == "post_tool_use"
-        && activity_age_secs >= SESSION_POST_TOOL_USE_STALE_SECS
-        && update_age_secs >= SESSION_POST_TOOL_USE_STALE_SECS;
-
-    // Working auto-ready should run on explicit completion markers.
-    // A long-stale post_tool_use also qualifies to recover leaked tool counters.
-    if !expected_events.contains(&last_event) && !long_stale_post_tool_use {
-        return false;
-    }
-
-    if record.tools_in_flight > 0 && !long_stale_post_tool_use {
+    let last_event = record.last_event.as_deref().unwrap_or_default();
+    let long_stale_post_tool_use =
+        last_event == "post_tool_use" && quiet_secs >= policy.post_tool_use_stale_secs as i64;
+    if !long_stale_post_tool_use && !expected_events.iter().any(|event| event == last_event) {
         return false;
     }
-
-    true
+    record.tools_in_flight == 0 || (long_stale_post_tool_use && !tools_tracked)
 }
 
 fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
@@ -1916,24 +2888,15 @@ mod tests {
     use super::*;
     use crate::db::Db;
     use crate::reducer::SessionState;
+    use crate::session_policy::{
+        SESSION_AUTO_READY_SECS, SESSION_POST_TOOL_USE_STALE_SECS, SESSION_TTL_READY_SECS,
+        STOP_GATE_WORKING_GRACE_SECS,
+    };
 
     fn event_base(event_id: &str, event_type: EventType, recorded_at: &str) -> EventEnvelope {
         EventEnvelope {
-            event_id: event_id.to_string(),
-            recorded_at: recorded_at.to_string(),
-            event_type,
-            session_id: Some("session-1".to_string()),
-            pid: Some(1234),
-            cwd: Some("/repo".to_string()),
             tool: Some("Read".to_string()),
-            file_path: None,
-            parent_app: None,
-            tty: None,
-            tmux_session: None,
-            tmux_client_tty: None,
-            notification_type: None,
-            stop_hook_active: None,
-            metadata: None,
+            ..crate::test_support::event(event_id, event_type, recorded_at)
         }
     }
 
@@ -1956,6 +2919,7 @@ mod tests {
             last_activity_at: None,
             tools_in_flight: 0,
             ready_reason: None,
+            pending_request: None,
         }
     }
 
@@ -1972,6 +2936,9 @@ mod tests {
             session_count: 1,
             active_count: 0,
             has_session: true,
+            pending_request: None,
+            worktrees: Vec::new(),
+            teams: Vec::new(),
         }
     }
 
@@ -2110,7 +3077,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS as i64 + 5)).to_rfc3339();
         let mut record = make_record("session-stale", "/repo", SessionState::Working, stale_time);
         record.last_activity_at = Some(record.updated_at.clone());
         record.last_event = Some("post_tool_use".to_string());
@@ -2130,7 +3098,7 @@ mod tests {
         let state = SharedState::new(db);
 
         let stale_activity =
-            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS + 5)).to_rfc3339();
+            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS as i64 + 5)).to_rfc3339();
         let fresh_update = (Utc::now() - Duration::seconds(2)).to_rfc3339();
         let mut record = make_record(
             "session-stale",
@@ -2155,7 +3123,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS as i64 + 5)).to_rfc3339();
         let mut record = make_record("session-stale", "/repo", SessionState::Working, stale_time);
         record.last_activity_at = Some(record.updated_at.clone());
         record.last_event = Some("post_tool_use".to_string());
@@ -2186,6 +3155,39 @@ mod tests {
         assert_eq!(aggregates[0].state, SessionState::Ready);
     }
 
+    #[test]
+    fn project_states_stay_working_while_tracked_tool_runs() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+
+        let start_time =
+            Utc::now() - Duration::seconds(SESSION_POST_TOOL_USE_STALE_SECS as i64 + 10);
+        let at = |seconds: i64| (start_time + Duration::seconds(seconds)).to_rfc3339();
+        for (event_id, event_type, tool_use_id, offset) in [
+            ("evt-pre-1", EventType::PreToolUse, "toolu_1", 0),
+            ("evt-pre-2", EventType::PreToolUse, "toolu_2", 1),
+            ("evt-post-2", EventType::PostToolUse, "toolu_2", 2),
+            ("evt-post-2-again", EventType::PostToolUse, "toolu_2", 3),
+        ] {
+            let mut event = event_base(event_id, event_type, &at(offset));
+            event.metadata = Some(serde_json::json!({ "tool_use_id": tool_use_id }));
+            state.update_from_event(&event);
+        }
+
+        let session = state
+            .db
+            .get_session("session-1")
+            .expect("get session")
+            .expect("session");
+        assert_eq!(session.last_event.as_deref(), Some("post_tool_use"));
+        assert_eq!(session.tools_in_flight, 1);
+
+        let aggregates = state.project_states_snapshot().expect("project states");
+        assert_eq!(aggregates.len(), 1);
+        assert_eq!(aggregates[0].state, SessionState::Working);
+    }
+
     #[test]
     fn project_states_auto_ready_after_inactive_task_completed() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -2193,7 +3195,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS as i64 + 5)).to_rfc3339();
         let mut record = make_record(
             "session-working",
             "/repo",
@@ -2217,7 +3220,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS as i64 + 5)).to_rfc3339();
         let mut record = make_record(
             "session-compacting",
             "/repo",
@@ -2277,7 +3281,7 @@ mod tests {
         let state = SharedState::new(db);
 
         let old_stop =
-            (Utc::now() - Duration::seconds(STOP_GATE_WORKING_GRACE_SECS + 5)).to_rfc3339();
+            (Utc::now() - Duration::seconds(STOP_GATE_WORKING_GRACE_SECS as i64 + 5)).to_rfc3339();
         let mut record = make_record(
             "session-stop-gate-old",
             "/repo",
@@ -2302,7 +3306,7 @@ mod tests {
         let state = SharedState::new(db);
 
         let state_changed_at =
-            (Utc::now() - Duration::seconds(STOP_GATE_WORKING_GRACE_SECS + 5)).to_rfc3339();
+            (Utc::now() - Duration::seconds(STOP_GATE_WORKING_GRACE_SECS as i64 + 5)).to_rfc3339();
         let updated_at = Utc::now().to_rfc3339();
         let mut record = make_record(
             "session-stop-gate-repeat",
@@ -2328,7 +3332,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS as i64 + 5)).to_rfc3339();
         let record = make_record("session-stale", "/repo", SessionState::Ready, stale_time);
         state.db.upsert_session(&record).expect("insert session");
 
@@ -2339,6 +3344,33 @@ mod tests {
         assert_eq!(remaining[0].session_id, "session-stale");
     }
 
+    #[test]
+    fn session_policy_ttls_and_view_come_from_config() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let hem_config = HemRuntimeConfig {
+            session_policy: SessionPolicy {
+                ttl_ready_secs: 4 * 60 * 60,
+                ..SessionPolicy::default()
+            },
+            ..HemRuntimeConfig::default()
+        };
+        let state = SharedState::new_with_hem_config(db, hem_config);
+
+        let quiet_time =
+            (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS as i64 + 5)).to_rfc3339();
+        let record = make_record("session-long", "/repo", SessionState::Ready, quiet_time);
+        state.db.upsert_session(&record).expect("insert session");
+
+        let sessions = state.sessions_snapshot().expect("snapshot");
+        assert_eq!(sessions.len(), 1);
+        let view = state.routing_config_view();
+        assert_eq!(
+            view.session_policy.map(|policy| policy.ttl_ready_secs),
+            Some(4 * 60 * 60)
+        );
+    }
+
     #[test]
     fn aggregates_project_state() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -2492,7 +3524,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS as i64 + 5)).to_rfc3339();
         let record = make_record("session-stale", "/repo", SessionState::Ready, stale_time);
         state.db.upsert_session(&record).expect("insert session");
 
@@ -2510,7 +3543,8 @@ mod tests {
         let db = Db::new(db_path).expect("db init");
         let state = SharedState::new(db);
 
-        let stale_time = (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS + 5)).to_rfc3339();
+        let stale_time =
+            (Utc::now() - Duration::seconds(SESSION_TTL_READY_SECS as i64 + 5)).to_rfc3339();
         let stale = make_record("session-stale", "/repo", SessionState::Ready, stale_time);
         state.db.upsert_session(&stale).expect("insert stale");
 
@@ -2736,7 +3770,10 @@ mod tests {
         let sessions = state.sessions_snapshot().expect("sessions snapshot");
         assert_eq!(sessions.len(), 1);
         assert_eq!(sessions[0].session_id, "session-dead-pid");
-        assert_eq!(sessions[0].state, SessionState::Working);
+        assert_eq!(
+            sessions[0].state,
+            capacitor_daemon_protocol::SessionState::Working
+        );
     }
 
     #[test]
@@ -2910,6 +3947,75 @@ mod tests {
         assert!(periodic.last_repair_at.is_some());
     }
 
+    #[test]
+    fn reconcile_records_transition_and_closes_waits_and_tool_calls() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db_path = temp_dir.path().join("state.db");
+        let db = Db::new(db_path).expect("db init");
+        let state = SharedState::new(db);
+
+        let mut record = make_record(
+            "session-dead-waiting",
+            "/repo",
+            SessionState::Waiting,
+            Utc::now().to_rfc3339(),
+        );
+        record.pid = 999_999;
+        state.db.upsert_session(&record).expect("insert session");
+        let started_at = (Utc::now() - Duration::minutes(1)).to_rfc3339();
+        state
+            .db
+            .open_human_wait(&record.session_id, "/repo", &started_at)
+            .expect("open wait");
+        state
+            .db
+            .start_tool_call(&capacitor_daemon_protocol::ToolCall {
+                session_id: record.session_id.clone(),
+                tool_use_id: "toolu_1".to_string(),
+                tool_name: "Bash".to_string(),
+                file_path: None,
+                project_path: "/repo".to_string(),
+                agent_id: None,
+                started_at: started_at.clone(),
+                ended_at: None,
+                duration_ms: None,
+                outcome: None,
+            })
+            .expect("start tool call");
+
+        let repaired = state
+            .reconcile_dead_non_idle_sessions("periodic")
+            .expect("reconcile");
+        assert_eq!(repaired, 1);
+
+        let transitions = state
+            .db
+            .list_session_transitions(&record.session_id)
+            .expect("transitions");
+        let last = transitions.last().expect("reconcile transition");
+        assert_eq!(last.from_state, Some(SessionState::Waiting.into()));
+        assert_eq!(last.to_state, Some(SessionState::Idle.into()));
+        assert_eq!(last.event_id, None);
+
+        let until = (Utc::now() + Duration::minutes(1)).to_rfc3339();
+        let waits = state
+            .db
+            .list_human_waits(None, &started_at, &until)
+            .expect("waits");
+        assert_eq!(waits.len(), 1);
+        assert!(waits[0].ended_at.is_some(), "wait left open");
+
+        let calls = state
+            .db
+            .list_tool_calls(&ToolCallsQuery::default())
+            .expect("tool calls");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0].outcome,
+            Some(capacitor_daemon_protocol::ToolCallOutcome::Interrupted)
+        );
+    }
+
     #[test]
     fn startup_with_no_sessions_does_not_replay_old_history() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -2971,10 +4077,604 @@ mod tests {
             .expect("session exists");
         assert_eq!(session.tools_in_flight, 1);
 
-        let events = state.db.list_events().expect("list events");
+        let events = state.db.list_events(None).expect("list events");
         assert_eq!(events.len(), 2);
     }
 
+    #[test]
+    fn update_from_events_applies_new_events_in_order_and_skips_duplicates() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db_path = temp_dir.path().join("state.db");
+        let db = Db::new(db_path).expect("db init");
+        let state = SharedState::new(db);
+
+        let start_time = Utc::now();
+        let start = event_base(
+            "evt-start",
+            EventType::SessionStart,
+            &start_time.to_rfc3339(),
+        );
+        let pre_tool = event_base(
+            "evt-pretool",
+            EventType::PreToolUse,
+            &(start_time + Duration::seconds(1)).to_rfc3339(),
+        );
+        state.update_from_event(&start);
+
+        let inserted = state
+            .update_from_events(&[start.clone(), pre_tool.clone(), pre_tool])
+            .expect("apply batch");
+        assert_eq!(
+            inserted,
+            vec![
+                EventOutcome::Duplicate,
+                EventOutcome::Applied,
+                EventOutcome::Duplicate
+            ]
+        );
+
+        let session = state
+            .db
+            .get_session("session-1")
+            .expect("query session")
+            .expect("session exists");
+        assert_eq!(session.tools_in_flight, 1);
+        assert_eq!(state.db.list_events(None).expect("list events").len(), 2);
+        assert_eq!(
+            state.db.last_applied_event_rowid().expect("cursor"),
+            state.db.max_event_rowid().expect("max rowid")
+        );
+    }
+
+    #[test]
+    fn failed_events_are_isolated_and_publish_nothing() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db_path = temp_dir.path().join("state.db");
+        let state = SharedState::new(Db::new(db_path.clone()).expect("db init"));
+        let sessions = state
+            .subscribe(SubscribeRequest {
+                topics: vec![SubscriptionTopic::Sessions],
+            })
+            .expect("subscribe sessions");
+        rusqlite::Connection::open(&db_path)
+            .expect("open raw sqlite")
+            .execute_batch(
+                "CREATE TRIGGER reject_bad_session BEFORE INSERT ON sessions
+                 WHEN NEW.session_id = 'session-bad'
+                 BEGIN SELECT RAISE(ABORT, 'rejected session'); END;
+                 CREATE TRIGGER reject_bad_event BEFORE INSERT ON events
+                 WHEN NEW.id = 'evt-unstorable'
+                 BEGIN SELECT RAISE(ABORT, 'rejected event'); END;",
+            )
+            .expect("create triggers");
+
+        let now = Utc::now().to_rfc3339();
+        let good = event_base("evt-good", EventType::SessionStart, &now);
+        let bad = EventEnvelope {
+            session_id: Some("session-bad".to_string()),
+            pid: Some(5678),
+            ..event_base("evt-bad", EventType::SessionStart, &now)
+        };
+        let unstorable = EventEnvelope {
+            session_id: Some("session-2".to_string()),
+            ..event_base("evt-unstorable", EventType::SessionStart, &now)
+        };
+        let results = state
+            .update_from_event_jobs(&[&[good], &[bad, unstorable]])
+            .expect("batch commits");
+        assert_eq!(results[0], vec![EventOutcome::Applied]);
+        assert!(matches!(
+            &results[1][..],
+            [EventOutcome::Skipped(skipped), EventOutcome::Rejected(rejected)]
+                if skipped.contains("rejected session") && rejected.contains("rejected event")
+        ));
+
+        // The skipped event stays stored; what it derived is gone. The
+        // rejected event left nothing behind.
+        let mut event_ids: Vec<String> = state
+            .db
+            .list_events(None)
+            .expect("list events")
+            .into_iter()
+            .map(|event| event.event_id)
+            .collect();
+        event_ids.sort();
+        assert_eq!(event_ids, vec!["evt-bad", "evt-good"]);
+        assert!(state
+            .db
+            .get_session("session-bad")
+            .expect("query session")
+            .is_none());
+        assert!(state
+            .db
+            .get_process_liveness(5678)
+            .expect("query liveness")
+            .is_none());
+        assert!(state
+            .db
+            .get_session("session-2")
+            .expect("query session")
+            .is_none());
+        assert_eq!(
+            state.db.last_applied_event_rowid().expect("cursor"),
+            state.db.max_event_rowid().expect("max rowid")
+        );
+
+        let keys: Vec<String> = sessions
+            .receiver
+            .try_iter()
+            .map(|change| change.key)
+            .collect();
+        assert_eq!(keys, vec!["session-1".to_string()]);
+    }
+
+    #[test]
+    fn subagents_appear_on_parent_session_until_stopped() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+
+        let start_time = Utc::now();
+        let at = |seconds: i64| (start_time + Duration::seconds(seconds)).to_rfc3339();
+        let subagent = |event_id: &str, event_type: EventType, seconds: i64| {
+            let mut event = event_base(event_id, event_type, &at(seconds));
+            event.metadata = Some(serde_json::json!({
+                "agent_id": "agent-1",
+                "agent_type": "Explore",
+                "agent_transcript_path": "/tmp/agent-1.jsonl"
+            }));
+            event
+        };
+
+        state.update_from_event(&event_base("evt-start", EventType::SessionStart, &at(0)));
+        let baseline = state.state_revision().expect("revision");
+        state.update_from_event(&event_base(
+            "evt-prompt",
+            EventType::UserPromptSubmit,
+            &at(1),
+        ));
+        state.update_from_event(&subagent("evt-sub-start", EventType::SubagentStart, 2));
+        state.update_from_event(&subagent("evt-sub-tool", EventType::PreToolUse, 3));
+
+        let sessions = state.sessions_snapshot().expect("sessions");
+        assert_eq!(sessions[0].active_subagents.len(), 1);
+        let running = &sessions[0].active_subagents[0];
+        assert_eq!(running.agent_type.as_deref(), Some("Explore"));
+        assert_eq!(running.tools, vec!["Read".to_string()]);
+
+        let delta = state
+            .sessions_snapshot_since(Some(baseline))
+            .expect("delta snapshot");
+        assert!(!delta.full);
+        assert_eq!(delta.changed.len(), 1);
+        assert_eq!(delta.changed[0].active_subagents.len(), 1);
+        assert_eq!(delta.changed[0].active_subagents[0].agent_id, "agent-1");
+
+        state.update_from_event(&subagent("evt-sub-stop", EventType::SubagentStop, 4));
+        let sessions = state.sessions_snapshot().expect("sessions");
+        assert!(sessions[0].active_subagents.is_empty());
+
+        let subagents = state.subagents_snapshot("session-1").expect("subagents");
+        assert_eq!(subagents.len(), 1);
+        assert_eq!(
+            subagents[0].transcript_path.as_deref(),
+            Some("/tmp/agent-1.jsonl")
+        );
+        assert_eq!(subagents[0].tool_calls, 1);
+    }
+
+    #[test]
+    fn update_from_event_records_state_transitions() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+
+        let start_time = Utc::now() - Duration::seconds(60);
+        let at = |seconds: i64| (start_time + Duration::seconds(seconds)).to_rfc3339();
+        state.update_from_event(&event_base("evt-start", EventType::SessionStart, &at(0)));
+        state.update_from_event(&event_base(
+            "evt-prompt",
+            EventType::UserPromptSubmit,
+            &at(1),
+        ));
+        state.update_from_event(&event_base("evt-tool", EventType::PreToolUse, &at(2)));
+        let mut permission = event_base("evt-permission", EventType::PermissionRequest, &at(3));
+        permission.tool = None;
+        state.update_from_event(&permission);
+        state.update_from_event(&event_base("evt-end", EventType::SessionEnd, &at(10)));
+
+        let timeline = state
+            .session_timeline(&SessionTimelineRequest {
+                session_id: "session-1".to_string(),
+                since: None,
+                until: None,
+            })
+            .expect("timeline");
+        let steps: Vec<(Option<&str>, Option<&str>, Option<&str>)> = timeline
+            .transitions
+            .iter()
+            .map(|transition| {
+                (
+                    transition.from_state.map(|state| state.as_str()),
+                    transition.to_state.map(|state| state.as_str()),
+                    transition.event_id.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (None, Some("ready"), Some("evt-start")),
+                (Some("ready"), Some("working"), Some("evt-prompt")),
+                (Some("working"), Some("waiting"), Some("evt-permission")),
+                (Some("waiting"), None, Some("evt-end")),
+            ]
+        );
+        assert_eq!(
+            timeline.seconds_in_state[&capacitor_daemon_protocol::SessionState::Waiting],
+            7
+        );
+
+        let project_time = state
+            .project_time(&ProjectTimeQuery {
+                days: Some(2),
+                ..ProjectTimeQuery::default()
+            })
+            .expect("project time");
+        let waiting_on_human: u64 = project_time
+            .iter()
+            .map(|row| row.waiting_on_human_secs)
+            .sum();
+        assert_eq!(waiting_on_human, 7);
+    }
+
+    #[test]
+    fn waiting_sessions_carry_pending_request_until_resumed() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+
+        let start_time = Utc::now() - Duration::seconds(10);
+        let at = |seconds: i64| (start_time + Duration::seconds(seconds)).to_rfc3339();
+        state.update_from_event(&event_base("evt-start", EventType::SessionStart, &at(0)));
+        let mut permission = event_base("evt-permission", EventType::PermissionRequest, &at(1));
+        permission.tool = Some("Bash".to_string());
+        permission.metadata = Some(serde_json::json!({
+            "tool_input": { "command": "cargo publish" }
+        }));
+        state.update_from_event(&permission);
+
+        let sessions = state.sessions_snapshot().expect("snapshot");
+        let pending = sessions[0]
+            .pending_request
+            .as_ref()
+            .expect("pending request");
+        assert_eq!(pending.tool_name.as_deref(), Some("Bash"));
+        assert_eq!(pending.detail.as_deref(), Some("cargo publish"));
+        let projects = state.project_states_snapshot().expect("project states");
+        assert_eq!(
+            projects[0]
+                .pending_request
+                .as_ref()
+                .and_then(|pending| pending.detail.as_deref()),
+            Some("cargo publish")
+        );
+
+        state.update_from_event(&event_base("evt-post", EventType::PostToolUse, &at(2)));
+        let sessions = state.sessions_snapshot().expect("snapshot");
+        assert!(sessions[0].pending_request.is_none());
+        let projects = state.project_states_snapshot().expect("project states");
+        assert!(projects[0].pending_request.is_none());
+    }
+
+    #[test]
+    fn update_from_event_publishes_changes_to_subscribers() {
+        use capacitor_daemon_protocol::ChangeKind;
+
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+        let sessions = state
+            .subscribe(SubscribeRequest {
+                topics: vec![SubscriptionTopic::Sessions],
+            })
+            .expect("subscribe sessions");
+        let projects = state
+            .subscribe(SubscribeRequest {
+                topics: vec![SubscriptionTopic::ProjectStates],
+            })
+            .expect("subscribe projects");
+
+        let start_time = Utc::now();
+        let start = event_base(
+            "evt-start",
+            EventType::SessionStart,
+            &start_time.to_rfc3339(),
+        );
+        state.update_from_event(&start);
+        // Duplicates are not re-applied and must not be re-announced.
+        state.update_from_event(&start);
+        // HEM primary mode leaves project states to the refresher thread.
+        state.wait_and_refresh_project_view(std::time::Duration::ZERO);
+
+        let session_changes = sessions.receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(session_changes.len(), 1);
+        assert_eq!(session_changes[0].change, ChangeKind::Upsert);
+        assert_eq!(session_changes[0].key, "session-1");
+        let data = session_changes[0].data.as_ref().expect("session payload");
+        assert_eq!(data["state"], "ready");
+
+        let project_changes = projects.receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(project_changes.len(), 1);
+        assert_eq!(project_changes[0].change, ChangeKind::Upsert);
+
+        let mut end = event_base(
+            "evt-end",
+            EventType::SessionEnd,
+            &(start_time + Duration::seconds(1)).to_rfc3339(),
+        );
+        end.tool = None;
+        state.update_from_event(&end);
+        state.wait_and_refresh_project_view(std::time::Duration::ZERO);
+
+        let session_changes = sessions.receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(session_changes.len(), 1);
+        assert_eq!(session_changes[0].change, ChangeKind::Delete);
+        assert!(session_changes[0].data.is_none());
+        let project_changes = projects.receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(project_changes.len(), 1);
+        assert_eq!(project_changes[0].change, ChangeKind::Delete);
+
+        state.unsubscribe(sessions.id);
+        state.unsubscribe(projects.id);
+        assert_eq!(state.subscriber_count(), 0);
+    }
+
+    #[test]
+    fn sessions_snapshot_since_returns_only_changed_and_removed_entries() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+        let now = Utc::now().to_rfc3339();
+        for session_id in ["session-a", "session-b"] {
+            state
+                .db
+                .upsert_session(&make_record(
+                    session_id,
+                    &format!("/repo/{}", session_id),
+                    SessionState::Working,
+                    now.clone(),
+                ))
+                .expect("insert session");
+        }
+
+        let full = state.sessions_snapshot_since(None).expect("full snapshot");
+        assert!(full.full);
+        assert_eq!(full.changed.len(), 2);
+        let baseline = full.revision;
+
+        let unchanged = state
+            .sessions_snapshot_since(Some(baseline))
+            .expect("unchanged delta");
+        assert!(!unchanged.full);
+        assert_eq!(unchanged.revision, baseline);
+        assert!(unchanged.changed.is_empty());
+        assert!(unchanged.removed.is_empty());
+
+        let mut updated = make_record(
+            "session-a",
+            "/repo/session-a",
+            SessionState::Ready,
+            Utc::now().to_rfc3339(),
+        );
+        updated.ready_reason = Some("stop".to_string());
+        state.db.upsert_session(&updated).expect("update session");
+        state
+            .db
+            .delete_session("session-b")
+            .expect("delete session");
+
+        let delta = state
+            .sessions_snapshot_since(Some(baseline))
+            .expect("delta snapshot");
+        assert!(!delta.full);
+        assert!(delta.revision > baseline);
+        assert_eq!(delta.changed.len(), 1);
+        assert_eq!(delta.changed[0].session_id, "session-a");
+        assert_eq!(delta.removed, vec!["session-b".to_string()]);
+
+        let ahead = state
+            .sessions_snapshot_since(Some(delta.revision + 10))
+            .expect("revision from another database");
+        assert!(ahead.full);
+        assert_eq!(ahead.changed.len(), 1);
+    }
+
+    #[test]
+    fn snapshot_reads_do_not_take_the_write_lock() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db_path = temp_dir.path().join("state.db");
+        let state = SharedState::new(Db::new(db_path.clone()).expect("db init"));
+        state.update_from_event(&event_base(
+            "evt-start",
+            EventType::SessionStart,
+            &Utc::now().to_rfc3339(),
+        ));
+        // The first project read runs the refresh the write left behind.
+        let projects = state
+            .project_states_snapshot_since(None)
+            .expect("project states");
+        let sessions = state.sessions_snapshot_since(None).expect("sessions");
+
+        // A writer holding the lock would stall any read that tried to write.
+        let writer = rusqlite::Connection::open(&db_path).expect("open writer");
+        writer
+            .execute_batch("BEGIN IMMEDIATE")
+            .expect("hold write lock");
+        let started = Instant::now();
+        let session_delta = state
+            .sessions_snapshot_since(Some(sessions.revision))
+            .expect("session delta");
+        let project_delta = state
+            .project_states_snapshot_since(Some(projects.revision))
+            .expect("project delta");
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        writer
+            .execute_batch("ROLLBACK")
+            .expect("release write lock");
+
+        assert!(!session_delta.full);
+        assert!(session_delta.changed.is_empty());
+        assert_eq!(session_delta.revision, sessions.revision);
+        assert!(!project_delta.full);
+        assert!(project_delta.changed.is_empty());
+        assert_eq!(state.state_revision().expect("revision"), sessions.revision);
+    }
+
+    #[test]
+    fn project_states_are_served_from_view_until_refreshed() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let state = SharedState::new(db);
+        let start = event_base(
+            "evt-start",
+            EventType::SessionStart,
+            &Utc::now().to_rfc3339(),
+        );
+        state.update_from_event(&start);
+
+        let full = state
+            .project_states_snapshot_since(None)
+            .expect("full snapshot");
+        assert!(full.full);
+        assert_eq!(full.changed.len(), 1);
+        assert_eq!(full.changed[0].project_path, "/repo");
+        let unchanged = state
+            .project_states_snapshot_since(Some(full.revision))
+            .expect("unchanged delta");
+        assert!(!unchanged.full);
+        assert!(unchanged.changed.is_empty());
+        assert!(unchanged.removed.is_empty());
+
+        // Written behind the view's back: invisible until the next refresh.
+        state
+            .db
+            .upsert_session(&make_record(
+                "session-other",
+                "/other",
+                SessionState::Working,
+                Utc::now().to_rfc3339(),
+            ))
+            .expect("insert session");
+        let cached = state
+            .project_states_snapshot_since(None)
+            .expect("cached snapshot");
+        assert_eq!(cached.revision, full.revision);
+        assert_eq!(cached.changed.len(), 1);
+
+        state.refresh_project_view();
+        let delta = state
+            .project_states_snapshot_since(Some(full.revision))
+            .expect("delta after refresh");
+        assert!(!delta.full);
+        assert!(delta.revision > full.revision);
+        assert_eq!(delta.changed.len(), 1);
+        assert_eq!(delta.changed[0].project_path, "/other");
+
+        let status = state.project_view_status();
+        assert_eq!(status.projects, 2);
+        assert_eq!(status.revision, Some(delta.revision));
+        assert!(status.reads >= 4);
+    }
+
+    #[test]
+    fn partial_refresh_reads_only_the_projects_a_write_touched() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let mut hem_config = crate::hem::HemRuntimeConfig::default();
+        hem_config.engine.mode = crate::hem::HemMode::Shadow;
+        let state = SharedState::new_with_hem_config(db, hem_config);
+        let now = Utc::now();
+        state.update_from_event(&event_base(
+            "evt-start",
+            EventType::SessionStart,
+            &now.to_rfc3339(),
+        ));
+        let full = state
+            .project_states_snapshot_since(None)
+            .expect("full snapshot");
+
+        // Written behind the view's back; the next write only touches /repo.
+        state
+            .db
+            .upsert_session(&make_record(
+                "session-other",
+                "/other",
+                SessionState::Working,
+                now.to_rfc3339(),
+            ))
+            .expect("insert session");
+        state.update_from_event(&event_base(
+            "evt-prompt",
+            EventType::UserPromptSubmit,
+            &(now + Duration::seconds(1)).to_rfc3339(),
+        ));
+
+        let delta = state
+            .project_states_snapshot_since(Some(full.revision))
+            .expect("delta");
+        let changed: Vec<(&str, capacitor_daemon_protocol::SessionState)> = delta
+            .changed
+            .iter()
+            .map(|project| (project.project_path.as_str(), project.state))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![("/repo", capacitor_daemon_protocol::SessionState::Working)]
+        );
+
+        state.refresh_project_view();
+        assert_eq!(state.project_view_status().projects, 2);
+    }
+
+    #[test]
+    fn hem_primary_writes_leave_the_view_refresh_to_the_refresher() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db init");
+        let mut hem_config = crate::hem::HemRuntimeConfig::default();
+        hem_config.engine.enabled = true;
+        hem_config.engine.mode = crate::hem::HemMode::Primary;
+        let state = SharedState::new_with_hem_config(db, hem_config);
+        let projects = state
+            .subscribe(SubscribeRequest {
+                topics: vec![SubscriptionTopic::ProjectStates],
+            })
+            .expect("subscribe projects");
+        let full_refreshes = state.project_view_status().full_refreshes;
+
+        state.update_from_event(&event_base(
+            "evt-start",
+            EventType::SessionStart,
+            &Utc::now().to_rfc3339(),
+        ));
+        assert_eq!(projects.receiver.try_iter().count(), 0);
+        assert_eq!(state.project_view_status().full_refreshes, full_refreshes);
+
+        let started = Instant::now();
+        state.wait_and_refresh_project_view(std::time::Duration::from_secs(30));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        let keys: Vec<String> = projects
+            .receiver
+            .try_iter()
+            .map(|change| change.key)
+            .collect();
+        assert_eq!(keys, vec!["/repo".to_string()]);
+        assert_eq!(
+            state.project_view_status().full_refreshes,
+            full_refreshes + 1
+        );
+    }
+
     #[test]
     fn session_start_prunes_older_sessions_with_same_pid() {
         let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -3261,7 +4961,8 @@ mod tests {
         hem_config.engine.mode = crate::hem::HemMode::Shadow;
         let state = SharedState::new_with_hem_config(db, hem_config);
 
-        let stale = (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS + 5)).to_rfc3339();
+        let stale =
+            (Utc::now() - Duration::seconds(SESSION_AUTO_READY_SECS as i64 + 5)).to_rfc3339();
         let mut record = make_record(
             "session-shadow-mismatch",
             "/Users/petepetrash/Code/shadow-mismatch",
@@ -3457,6 +5158,9 @@ mod tests {
                 session_count: 1,
                 active_count: 0,
                 has_session: true,
+                pending_request: None,
+                worktrees: Vec::new(),
+                teams: Vec::new(),
             },
             ProjectState {
                 project_id: "beta".to_string(),
@@ -3470,6 +5174,9 @@ mod tests {
                 session_count: 1,
                 active_count: 0,
                 has_session: true,
+                pending_request: None,
+                worktrees: Vec::new(),
+                teams: Vec::new(),
             },
             ProjectState {
                 project_id: "gamma".to_string(),
@@ -3483,6 +5190,9 @@ mod tests {
                 session_count: 1,
                 active_count: 1,
                 has_session: true,
+                pending_request: None,
+                worktrees: Vec::new(),
+                teams: Vec::new(),
             },
         ];
         let hem = vec![
@@ -3510,6 +5220,9 @@ mod tests {
             session_count: 1,
             active_count: 0,
             has_session: false,
+            pending_request: None,
+            worktrees: Vec::new(),
+            teams: Vec::new(),
         }];
         let hem = vec![make_hem_project_state("/alpha", SessionState::Ready)];
 
@@ -3525,69 +5238,3 @@ pub struct ProcessLivenessRow {
     pub proc_started: Option<i64>,
     pub last_seen_at: String,
 }
-
-#[derive(Debug, Clone, Serialize)]
-pub struct ProcessLiveness {
-    pub pid: u32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub proc_started: Option<u64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub current_start_time: Option<u64>,
-    pub last_seen_at: String,
-    pub is_alive: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub identity_matches: Option<bool>,
-}
-
-/// Session record enriched with liveness info for IPC responses.
-#[derive(Debug, Clone, Serialize)]
-pub struct EnrichedSession {
-    pub session_id: String,
-    pub pid: u32,
-    pub state: crate::reducer::SessionState,
-    pub cwd: String,
-    pub project_id: String,
-    pub workspace_id: String,
-    pub project_path: String,
-    pub updated_at: String,
-    pub state_changed_at: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_event: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_activity_at: Option<String>,
-    pub tools_in_flight: u32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ready_reason: Option<String>,
-    /// Whether the session's process is still alive.
-    /// None if pid is 0 (unknown), Some(true) if alive, Some(false) if dead.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub is_alive: Option<bool>,
-}
-
-#[derive(Debug, Clone, Serialize)]
-pub struct ShellState {
-    pub version: u32,
-    pub shells: HashMap<String, ShellEntry>,
-}
-
-impl Default for ShellState {
-    fn default() -> Self {
-        Self {
-            version: 1,
-            shells: HashMap::new(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Serialize)]
-pub struct ShellEntry {
-    pub cwd: String,
-    pub tty: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub parent_app: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tmux_session: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tmux_client_tty: Option<String>,
-    pub updated_at: String,
-}
@@ -0,0 +1,205 @@
+//! Subagent child records.
+//!
+//! Subagents share their parent's `session_id` and are told apart by the
+//! `agent_id` hook metadata. `reduce_session` keeps their traffic from changing
+//! the parent's state; this module records them separately so a long-running
+//! parent can show which subagents are doing the work.
+
+use capacitor_daemon_protocol::{EventEnvelope, EventType, SubagentRecord};
+
+use crate::db::Db;
+
+/// Applies `event` to the subagent it belongs to, if any, and returns the
+/// updated record.
+pub fn apply_subagent_event(
+    db: &Db,
+    event: &EventEnvelope,
+) -> Result<Option<SubagentRecord>, String> {
+    let (Some(session_id), Some(agent_id)) =
+        (event.session_id.as_deref(), metadata_str(event, "agent_id"))
+    else {
+        return Ok(None);
+    };
+    if !is_subagent_event(event.event_type) {
+        return Ok(None);
+    }
+
+    let current = db.get_subagent(session_id, agent_id)?;
+    let Some(record) = reduce_subagent(current.as_ref(), event) else {
+        return Ok(None);
+    };
+    db.upsert_subagent(&record)?;
+    Ok(Some(record))
+}
+
+fn is_subagent_event(event_type: EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::SubagentStart
+            | EventType::SubagentStop
+            | EventType::PreToolUse
+            | EventType::PostToolUse
+            | EventType::PostToolUseFailure
+    )
+}
+
+pub fn reduce_subagent(
+    current: Option<&SubagentRecord>,
+    event: &EventEnvelope,
+) -> Option<SubagentRecord> {
+    let session_id = event.session_id.clone()?;
+    let agent_id = metadata_str(event, "agent_id")?.to_string();
+    let mut record = current.cloned().unwrap_or_else(|| SubagentRecord {
+        session_id,
+        agent_id,
+        agent_type: None,
+        started_at: None,
+        stopped_at: None,
+        transcript_path: None,
+        tools: Vec::new(),
+        tool_calls: 0,
+        updated_at: event.recorded_at.clone(),
+    });
+
+    if let Some(agent_type) = metadata_str(event, "agent_type") {
+        record.agent_type = Some(agent_type.to_string());
+    }
+    match event.event_type {
+        EventType::SubagentStart => {
+            if record.started_at.is_none() {
+                record.started_at = Some(event.recorded_at.clone());
+            }
+        }
+        EventType::SubagentStop => {
+            record.stopped_at = Some(event.recorded_at.clone());
+            if let Some(path) = metadata_str(event, "agent_transcript_path") {
+                record.transcript_path = Some(path.to_string());
+            }
+        }
+        EventType::PreToolUse => {
+            record.tool_calls = record.tool_calls.saturating_add(1);
+            if let Some(tool) = event.tool.as_deref().filter(|tool| !tool.is_empty()) {
+                if !record.tools.iter().any(|known| known == tool) {
+                    record.tools.push(tool.to_string());
+                }
+            }
+        }
+        EventType::PostToolUse | EventType::PostToolUseFailure => {}
+        _ => return None,
+    }
+    record.updated_at = event.recorded_at.clone();
+    Some(record)
+}
+
+fn metadata_str<'a>(event: &'a EventEnvelope, key: &str) -> Option<&'a str> {
+    event
+        .metadata
+        .as_ref()
+        .and_then(|value| value.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(
+        event_type: EventType,
+        recorded_at: &str,
+        metadata: serde_json::Value,
+    ) -> EventEnvelope {
+        EventEnvelope {
+            metadata: Some(metadata),
+            ..crate::test_support::event(&format!("evt-{}", recorded_at), event_type, recorded_at)
+        }
+    }
+
+    #[test]
+    fn tracks_subagent_lifecycle_and_tools() {
+        let start = event(
+            EventType::SubagentStart,
+            "2026-01-31T00:00:00Z",
+            serde_json::json!({ "agent_id": "agent-1", "agent_type": "Explore" }),
+        );
+        let record = reduce_subagent(None, &start).expect("start");
+        assert!(record.is_running());
+        assert_eq!(record.agent_type.as_deref(), Some("Explore"));
+
+        let mut read = event(
+            EventType::PreToolUse,
+            "2026-01-31T00:00:01Z",
+            serde_json::json!({ "agent_id": "agent-1" }),
+        );
+        read.tool = Some("Read".to_string());
+        let record = reduce_subagent(Some(&record), &read).expect("tool");
+        let record = reduce_subagent(Some(&record), &read).expect("tool again");
+        assert_eq!(record.tools, vec!["Read".to_string()]);
+        assert_eq!(record.tool_calls, 2);
+
+        let stop = event(
+            EventType::SubagentStop,
+            "2026-01-31T00:00:05Z",
+            serde_json::json!({
+                "agent_id": "agent-1",
+                "agent_transcript_path": "/tmp/agent-1.jsonl"
+            }),
+        );
+        let record = reduce_subagent(Some(&record), &stop).expect("stop");
+        assert!(!record.is_running());
+        assert_eq!(record.started_at.as_deref(), Some("2026-01-31T00:00:00Z"));
+        assert_eq!(
+            record.transcript_path.as_deref(),
+            Some("/tmp/agent-1.jsonl")
+        );
+        assert_eq!(record.updated_at, "2026-01-31T00:00:05Z");
+    }
+
+    #[test]
+    fn ignores_events_without_agent_id() {
+        let mut tool = event(
+            EventType::PreToolUse,
+            "2026-01-31T00:00:01Z",
+            serde_json::json!({}),
+        );
+        tool.tool = Some("Read".to_string());
+        assert!(reduce_subagent(None, &tool).is_none());
+
+        let blank = event(
+            EventType::SubagentStart,
+            "2026-01-31T00:00:01Z",
+            serde_json::json!({ "agent_id": "  " }),
+        );
+        assert!(reduce_subagent(None, &blank).is_none());
+
+        let stop = event(
+            EventType::Stop,
+            "2026-01-31T00:00:01Z",
+            serde_json::json!({ "agent_id": "agent-1" }),
+        );
+        assert!(reduce_subagent(None, &stop).is_none());
+    }
+
+    #[test]
+    fn stop_without_start_and_repeated_stop() {
+        let stop = event(
+            EventType::SubagentStop,
+            "2026-01-31T00:00:05Z",
+            serde_json::json!({ "agent_id": "agent-1" }),
+        );
+        let record = reduce_subagent(None, &stop).expect("stop creates the record");
+        assert!(!record.is_running());
+        assert_eq!(record.started_at, None);
+        assert_eq!(record.tool_calls, 0);
+
+        let again = event(
+            EventType::SubagentStop,
+            "2026-01-31T00:00:09Z",
+            serde_json::json!({ "agent_id": "agent-1" }),
+        );
+        let record = reduce_subagent(Some(&record), &again).expect("second stop");
+        assert_eq!(record.stopped_at.as_deref(), Some("2026-01-31T00:00:09Z"));
+        assert_eq!(record.transcript_path, None);
+    }
+}
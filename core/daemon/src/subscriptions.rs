@@ -0,0 +1,202 @@
+//! Fan-out of state changes to long-lived `subscribe` connections.
+//!
+//! Each subscriber owns a bounded queue. Publishing never blocks the writer:
+//! a subscriber whose queue is full is dropped so a stalled client cannot hold
+//! back event application. Dropping the sender closes the subscriber's stream,
+//! and well-behaved clients resync with a snapshot before subscribing again.
+
+use capacitor_daemon_protocol::{ChangeKind, StateChange, SubscribeRequest, SubscriptionTopic};
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+
+pub const MAX_SUBSCRIBERS: usize = 16;
+const SUBSCRIBER_QUEUE_CAPACITY: usize = 256;
+
+pub struct Subscription {
+    pub id: u64,
+    pub receiver: Receiver<StateChange>,
+}
+
+struct Subscriber {
+    id: u64,
+    request: SubscribeRequest,
+    sender: SyncSender<StateChange>,
+}
+
+#[derive(Default)]
+pub struct SubscriptionHub {
+    subscribers: Mutex<Vec<Subscriber>>,
+    next_id: AtomicU64,
+}
+
+impl SubscriptionHub {
+    /// Registers a subscriber, or returns `None` when the subscriber cap is reached.
+    pub fn subscribe(&self, request: SubscribeRequest) -> Option<Subscription> {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if subscribers.len() >= MAX_SUBSCRIBERS {
+            return None;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let (sender, receiver) = sync_channel(SUBSCRIBER_QUEUE_CAPACITY);
+        subscribers.push(Subscriber {
+            id,
+            request,
+            sender,
+        });
+        Some(Subscription { id, receiver })
+    }
+
+    pub fn unsubscribe(&self, id: u64) {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        subscribers.retain(|subscriber| subscriber.id != id);
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers
+            .lock()
+            .map(|subscribers| subscribers.len())
+            .unwrap_or(0)
+    }
+
+    /// Cheap check used to skip building change payloads nobody will read.
+    pub fn wants(&self, topic: SubscriptionTopic) -> bool {
+        self.subscribers
+            .lock()
+            .map(|subscribers| {
+                subscribers
+                    .iter()
+                    .any(|subscriber| subscriber.request.wants(topic))
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn publish_upsert<T: Serialize>(&self, topic: SubscriptionTopic, key: &str, data: &T) {
+        if !self.wants(topic) {
+            return;
+        }
+        let data = match serde_json::to_value(data) {
+            Ok(value) => value,
+            Err(err) => {
+                tracing::warn!(error = %err, ?topic, key, "Failed to serialize state change");
+                return;
+            }
+        };
+        self.publish(StateChange {
+            topic,
+            change: ChangeKind::Upsert,
+            key: key.to_string(),
+            data: Some(data),
+            emitted_at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    pub fn publish_delete(&self, topic: SubscriptionTopic, key: &str) {
+        if !self.wants(topic) {
+            return;
+        }
+        self.publish(StateChange {
+            topic,
+            change: ChangeKind::Delete,
+            key: key.to_string(),
+            data: None,
+            emitted_at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    fn publish(&self, change: StateChange) {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        subscribers.retain(|subscriber| {
+            if !subscriber.request.wants(change.topic) {
+                return true;
+            }
+            match subscriber.sender.try_send(change.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!(
+                        subscriber_id = subscriber.id,
+                        "Dropping subscriber that fell behind"
+                    );
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(topics: Vec<SubscriptionTopic>) -> SubscribeRequest {
+        SubscribeRequest { topics }
+    }
+
+    #[test]
+    fn publish_respects_topic_filters() {
+        let hub = SubscriptionHub::default();
+        let sessions = hub
+            .subscribe(request(vec![SubscriptionTopic::Sessions]))
+            .expect("subscribe sessions");
+        let everything = hub.subscribe(request(Vec::new())).expect("subscribe all");
+
+        hub.publish_delete(SubscriptionTopic::Shells, "1234");
+        hub.publish_upsert(
+            SubscriptionTopic::Sessions,
+            "session-1",
+            &serde_json::json!({ "state": "working" }),
+        );
+
+        let received = sessions.receiver.try_recv().expect("session change");
+        assert_eq!(received.topic, SubscriptionTopic::Sessions);
+        assert_eq!(received.key, "session-1");
+        assert!(sessions.receiver.try_recv().is_err());
+
+        let first = everything.receiver.try_recv().expect("shell change");
+        assert_eq!(first.topic, SubscriptionTopic::Shells);
+        assert_eq!(first.change, ChangeKind::Delete);
+        let second = everything.receiver.try_recv().expect("session change");
+        assert_eq!(second.topic, SubscriptionTopic::Sessions);
+    }
+
+    #[test]
+    fn subscriber_cap_is_enforced() {
+        let hub = SubscriptionHub::default();
+        let subscriptions = (0..MAX_SUBSCRIBERS)
+            .map(|_| hub.subscribe(request(Vec::new())).expect("subscribe"))
+            .collect::<Vec<_>>();
+        assert!(hub.subscribe(request(Vec::new())).is_none());
+
+        hub.unsubscribe(subscriptions[0].id);
+        assert!(hub.subscribe(request(Vec::new())).is_some());
+    }
+
+    #[test]
+    fn slow_and_disconnected_subscribers_are_dropped() {
+        let hub = SubscriptionHub::default();
+        let slow = hub.subscribe(request(Vec::new())).expect("subscribe slow");
+        let gone = hub.subscribe(request(Vec::new())).expect("subscribe gone");
+        drop(gone.receiver);
+
+        for index in 0..=SUBSCRIBER_QUEUE_CAPACITY {
+            hub.publish_delete(SubscriptionTopic::Sessions, &format!("session-{}", index));
+        }
+
+        assert_eq!(hub.subscriber_count(), 0);
+        assert!(!hub.wants(SubscriptionTopic::Sessions));
+        assert_eq!(slow.receiver.try_iter().count(), SUBSCRIBER_QUEUE_CAPACITY);
+    }
+}
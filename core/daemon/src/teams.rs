@@ -0,0 +1,455 @@
+//! Agent teams from events carrying `team_name` metadata.
+//!
+//! The lead's own events name the team only; a teammate's events also name
+//! the teammate, whether they arrive relayed through the lead's session or
+//! from the teammate's own session. Teammates with a session of their own
+//! take that session's state; the others keep a state derived from their
+//! last event. `task_completed` events add to the team's completed tasks.
+
+use capacitor_daemon_protocol::{EventEnvelope, EventType, TeamState, TeamTask, TeammateState};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+use crate::db::Db;
+use crate::project_identity::resolve_project_identity;
+use crate::project_state_policy::state_priority;
+use crate::reducer::{event_type_string, SessionState};
+
+/// Teams with neither a live lead nor a live teammate session stay in
+/// project states this long after their last event.
+const TEAM_STALE_SECS: i64 = 30 * 60;
+/// Completed tasks listed per team in project states.
+const MAX_TEAM_TASKS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeamRecord {
+    pub team_name: String,
+    pub lead_session_id: Option<String>,
+    pub project_path: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeammateRecord {
+    pub team_name: String,
+    pub name: String,
+    pub session_id: Option<String>,
+    pub state: SessionState,
+    pub last_event: Option<String>,
+    pub updated_at: String,
+}
+
+/// Updates the team named by `event`. Returns whether anything changed.
+pub fn apply_team_event(db: &Db, event: &EventEnvelope) -> Result<bool, String> {
+    let Some(team_name) = metadata_str(event, "team_name") else {
+        return Ok(false);
+    };
+    let teammate_name = metadata_str(event, "teammate_name");
+    let session_id = event.session_id.as_deref();
+    let existing = db.get_team(team_name)?;
+
+    let lead_session_id = existing
+        .as_ref()
+        .and_then(|team| team.lead_session_id.clone())
+        .or_else(|| {
+            teammate_name
+                .is_none()
+                .then(|| session_id.map(str::to_string))
+                .flatten()
+        });
+    let project_path = match existing
+        .as_ref()
+        .map(|team| team.project_path.clone())
+        .filter(|path| !path.is_empty())
+    {
+        Some(path) => path,
+        None => project_path(db, event)?.unwrap_or_default(),
+    };
+    db.upsert_team(&TeamRecord {
+        team_name: team_name.to_string(),
+        lead_session_id: lead_session_id.clone(),
+        project_path,
+        updated_at: event.recorded_at.clone(),
+    })?;
+    // Teammate events relayed before the lead was known look like they came
+    // from the teammate's own session.
+    if let Some(lead_session_id) = lead_session_id.as_deref() {
+        db.clear_teammate_session(team_name, lead_session_id)?;
+    }
+
+    if let Some(name) = teammate_name {
+        let current = db.get_teammate(team_name, name)?;
+        let own_session_id = session_id
+            .filter(|session_id| Some(*session_id) != lead_session_id.as_deref())
+            .map(str::to_string);
+        db.upsert_teammate(&TeammateRecord {
+            team_name: team_name.to_string(),
+            name: name.to_string(),
+            session_id: own_session_id.or_else(|| {
+                current
+                    .as_ref()
+                    .and_then(|record| record.session_id.clone())
+            }),
+            state: teammate_state(event, current.map(|record| record.state)),
+            last_event: Some(event_type_string(&event.event_type)),
+            updated_at: event.recorded_at.clone(),
+        })?;
+    }
+
+    if event.event_type == EventType::TaskCompleted {
+        db.insert_team_task(
+            &event.event_id,
+            team_name,
+            &TeamTask {
+                task_id: metadata_str(event, "task_id").map(str::to_string),
+                task_subject: metadata_str(event, "task_subject").map(str::to_string),
+                teammate_name: teammate_name.map(str::to_string),
+                completed_at: event.recorded_at.clone(),
+            },
+        )?;
+    }
+    Ok(true)
+}
+
+/// Stored teams, loaded once per project-state snapshot.
+pub struct Teams {
+    teams: Vec<TeamRecord>,
+    teammates: Vec<TeammateRecord>,
+    tasks: Vec<(String, TeamTask)>,
+}
+
+impl Teams {
+    /// Loads every team, or only those of `project_paths`.
+    pub fn load(db: &Db, project_paths: Option<&[String]>) -> Result<Self, String> {
+        let teams = db.list_teams(project_paths)?;
+        let team_names: Option<Vec<String>> =
+            project_paths.map(|_| teams.iter().map(|team| team.team_name.clone()).collect());
+        Ok(Self {
+            teammates: db.list_teammates(team_names.as_deref())?,
+            tasks: db.list_team_tasks(team_names.as_deref())?,
+            teams,
+        })
+    }
+
+    /// Sessions whose live state the loaded teams report.
+    pub fn session_ids(&self) -> impl Iterator<Item = &str> {
+        self.teams
+            .iter()
+            .filter_map(|team| team.lead_session_id.as_deref())
+            .chain(
+                self.teammates
+                    .iter()
+                    .filter_map(|teammate| teammate.session_id.as_deref()),
+            )
+    }
+
+    /// Teams of `project_path`, with sessions in `live_states` (by session id)
+    /// overriding the stored teammate states.
+    pub fn project_teams(
+        &self,
+        project_path: &str,
+        live_states: &HashMap<String, SessionState>,
+        now: DateTime<Utc>,
+    ) -> Vec<TeamState> {
+        let cutoff = now - Duration::seconds(TEAM_STALE_SECS);
+        self.teams
+            .iter()
+            .filter(|team| team.project_path == project_path)
+            .filter_map(|team| {
+                let lead_state = team
+                    .lead_session_id
+                    .as_ref()
+                    .and_then(|session_id| live_states.get(session_id));
+                let mut any_live = lead_state.is_some();
+                let mut states: Vec<SessionState> = lead_state.cloned().into_iter().collect();
+                let teammates: Vec<TeammateState> = self
+                    .teammates
+                    .iter()
+                    .filter(|teammate| teammate.team_name == team.team_name)
+                    .map(|teammate| {
+                        let state = match teammate.session_id.as_ref() {
+                            Some(session_id) => match live_states.get(session_id) {
+                                Some(state) => {
+                                    any_live = true;
+                                    state.clone()
+                                }
+                                None => SessionState::Idle,
+                            },
+                            None => teammate.state.clone(),
+                        };
+                        states.push(state.clone());
+                        TeammateState {
+                            name: teammate.name.clone(),
+                            session_id: teammate.session_id.clone(),
+                            state: state.into(),
+                            last_event: teammate.last_event.clone(),
+                            tasks_completed: self
+                                .tasks
+                                .iter()
+                                .filter(|(team_name, task)| {
+                                    *team_name == team.team_name
+                                        && task.teammate_name.as_deref()
+                                            == Some(teammate.name.as_str())
+                                })
+                                .count() as u32,
+                            updated_at: teammate.updated_at.clone(),
+                        }
+                    })
+                    .collect();
+
+                let is_recent = DateTime::parse_from_rfc3339(&team.updated_at)
+                    .is_ok_and(|updated_at| updated_at.with_timezone(&Utc) > cutoff);
+                if !any_live && !is_recent {
+                    return None;
+                }
+
+                let state = states
+                    .into_iter()
+                    .max_by_key(state_priority)
+                    .unwrap_or(SessionState::Idle);
+                let completed_tasks = self
+                    .tasks
+                    .iter()
+                    .filter(|(team_name, _)| *team_name == team.team_name)
+                    .take(MAX_TEAM_TASKS)
+                    .map(|(_, task)| task.clone())
+                    .collect();
+                Some(TeamState {
+                    team_name: team.team_name.clone(),
+                    lead_session_id: team.lead_session_id.clone(),
+                    state: state.into(),
+                    teammates,
+                    completed_tasks,
+                    updated_at: team.updated_at.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A relayed teammate's state after `event`.
+fn teammate_state(event: &EventEnvelope, current: Option<SessionState>) -> SessionState {
+    match event.event_type {
+        EventType::TeammateIdle | EventType::SessionEnd => SessionState::Idle,
+        EventType::Stop => SessionState::Ready,
+        EventType::PreCompact => SessionState::Compacting,
+        EventType::PermissionRequest => SessionState::Waiting,
+        EventType::Notification
+            if matches!(
+                event.notification_type.as_deref(),
+                Some("permission_prompt" | "elicitation_dialog")
+            ) =>
+        {
+            SessionState::Waiting
+        }
+        EventType::TaskCompleted | EventType::Notification => {
+            current.unwrap_or(SessionState::Working)
+        }
+        _ => SessionState::Working,
+    }
+}
+
+fn project_path(db: &Db, event: &EventEnvelope) -> Result<Option<String>, String> {
+    if let Some(session_id) = event.session_id.as_deref() {
+        if let Some(session) = db.get_session(session_id)? {
+            if !session.project_path.is_empty() {
+                return Ok(Some(session.project_path));
+            }
+        }
+    }
+    Ok(event
+        .cwd
+        .as_deref()
+        .and_then(resolve_project_identity)
+        .map(|identity| identity.project_path))
+}
+
+fn metadata_str<'a>(event: &'a EventEnvelope, key: &str) -> Option<&'a str> {
+    event
+        .metadata
+        .as_ref()
+        .and_then(|value| value.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn event(
+        event_id: &str,
+        event_type: EventType,
+        session_id: &str,
+        cwd: &str,
+        metadata: serde_json::Value,
+    ) -> EventEnvelope {
+        EventEnvelope {
+            session_id: Some(session_id.to_string()),
+            cwd: Some(cwd.to_string()),
+            metadata: Some(metadata),
+            ..test_support::event(
+                event_id,
+                event_type,
+                &format!("2026-01-31T00:00:0{}Z", &event_id[event_id.len() - 1..]),
+            )
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-01-31T00:01:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn groups_lead_teammates_and_completed_tasks() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let repo = temp_dir.path().join("repo");
+        std::fs::create_dir_all(&repo).expect("repo dir");
+        std::fs::write(repo.join("Cargo.toml"), "").expect("repo marker");
+        let cwd = repo.to_string_lossy().to_string();
+
+        // A teammate event relayed through the lead before the lead's own.
+        let events = [
+            event(
+                "evt-1",
+                EventType::TaskCompleted,
+                "lead",
+                &cwd,
+                serde_json::json!({
+                    "team_name": "release",
+                    "teammate_name": "reviewer",
+                    "task_id": "3",
+                    "task_subject": "Review the changelog"
+                }),
+            ),
+            event(
+                "evt-2",
+                EventType::TaskCompleted,
+                "lead",
+                &cwd,
+                serde_json::json!({ "team_name": "release", "task_id": "1", "task_subject": "Plan" }),
+            ),
+            event(
+                "evt-3",
+                EventType::TeammateIdle,
+                "lead",
+                &cwd,
+                serde_json::json!({ "team_name": "release", "teammate_name": "reviewer" }),
+            ),
+            event(
+                "evt-4",
+                EventType::PreToolUse,
+                "writer-session",
+                &cwd,
+                serde_json::json!({ "team_name": "release", "teammate_name": "writer" }),
+            ),
+        ];
+        for event in &events {
+            assert!(apply_team_event(&db, event).expect("apply"));
+        }
+        assert!(!apply_team_event(
+            &db,
+            &event(
+                "evt-5",
+                EventType::Stop,
+                "lead",
+                &cwd,
+                serde_json::json!({})
+            )
+        )
+        .expect("no team"));
+
+        let project_path = db.get_team("release").unwrap().unwrap().project_path;
+        assert!(!project_path.is_empty());
+        let live = HashMap::from([
+            ("lead".to_string(), SessionState::Ready),
+            ("writer-session".to_string(), SessionState::Waiting),
+        ]);
+        let teams = Teams::load(&db, None).expect("load");
+        let project_teams = teams.project_teams(&project_path, &live, now());
+        assert_eq!(project_teams.len(), 1);
+        let scoped =
+            Teams::load(&db, Some(std::slice::from_ref(&project_path))).expect("scoped load");
+        assert_eq!(
+            scoped.project_teams(&project_path, &live, now()),
+            project_teams
+        );
+        let mut session_ids: Vec<&str> = scoped.session_ids().collect();
+        session_ids.sort();
+        assert_eq!(session_ids, vec!["lead", "writer-session"]);
+        assert!(Teams::load(&db, Some(&["/elsewhere".to_string()]))
+            .expect("load elsewhere")
+            .session_ids()
+            .next()
+            .is_none());
+        let team = &project_teams[0];
+        assert_eq!(team.lead_session_id.as_deref(), Some("lead"));
+        assert_eq!(team.state, capacitor_daemon_protocol::SessionState::Waiting);
+
+        let reviewer = &team.teammates[0];
+        assert_eq!(reviewer.name, "reviewer");
+        assert_eq!(reviewer.session_id, None);
+        assert_eq!(
+            reviewer.state,
+            capacitor_daemon_protocol::SessionState::Idle
+        );
+        assert_eq!(reviewer.last_event.as_deref(), Some("teammate_idle"));
+        assert_eq!(reviewer.tasks_completed, 1);
+        let writer = &team.teammates[1];
+        assert_eq!(writer.session_id.as_deref(), Some("writer-session"));
+        assert_eq!(
+            writer.state,
+            capacitor_daemon_protocol::SessionState::Waiting
+        );
+
+        let subjects: Vec<(Option<&str>, Option<&str>)> = team
+            .completed_tasks
+            .iter()
+            .map(|task| (task.task_subject.as_deref(), task.teammate_name.as_deref()))
+            .collect();
+        assert_eq!(
+            subjects,
+            vec![
+                (Some("Plan"), None),
+                (Some("Review the changelog"), Some("reviewer"))
+            ]
+        );
+
+        db.delete_teams_led_by("lead").expect("delete");
+        assert!(Teams::load(&db, None)
+            .expect("load")
+            .project_teams(&project_path, &live, now())
+            .is_empty());
+    }
+
+    #[test]
+    fn stale_teams_without_live_sessions_are_hidden() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        db.upsert_team(&TeamRecord {
+            team_name: "release".to_string(),
+            lead_session_id: Some("lead".to_string()),
+            project_path: "/repo".to_string(),
+            updated_at: "2026-01-30T00:00:00Z".to_string(),
+        })
+        .expect("team");
+        let teams = Teams::load(&db, None).expect("load");
+
+        assert!(teams
+            .project_teams("/repo", &HashMap::new(), now())
+            .is_empty());
+        let live = HashMap::from([("lead".to_string(), SessionState::Working)]);
+        let project_teams = teams.project_teams("/repo", &live, now());
+        assert_eq!(project_teams.len(), 1);
+        assert_eq!(
+            project_teams[0].state,
+            capacitor_daemon_protocol::SessionState::Working
+        );
+        assert!(teams.project_teams("/other", &live, now()).is_empty());
+    }
+}
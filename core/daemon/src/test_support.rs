@@ -0,0 +1,25 @@
+//! Fixtures shared by the unit tests.
+
+use capacitor_daemon_protocol::{EventEnvelope, EventType};
+
+/// A `session-1` event from pid 1234 in `/repo` with every optional field
+/// unset. Tests override what they exercise with struct update syntax.
+pub fn event(event_id: &str, event_type: EventType, recorded_at: &str) -> EventEnvelope {
+    EventEnvelope {
+        event_id: event_id.to_string(),
+        recorded_at: recorded_at.to_string(),
+        event_type,
+        session_id: Some("session-1".to_string()),
+        pid: Some(1234),
+        cwd: Some("/repo".to_string()),
+        tool: None,
+        file_path: None,
+        parent_app: None,
+        tty: None,
+        tmux_session: None,
+        tmux_client_tty: None,
+        notification_type: None,
+        stop_hook_active: None,
+        metadata: None,
+    }
+}
@@ -0,0 +1,372 @@
+//! Per-project, per-day time accounting.
+//!
+//! State time is derived from `session_transitions`. Time waiting on the human
+//! is tracked in `human_waits`: a wait opens on `PermissionRequest` or a
+//! `permission_prompt` / `elicitation_dialog` notification and closes on the
+//! next `UserPromptSubmit`. Approving a permission prompt does not submit a
+//! prompt, so a tool result, `Stop` or `SessionEnd` also closes the wait.
+
+use capacitor_daemon_protocol::{
+    EventEnvelope, EventType, ProjectDayTime, ProjectTimeQuery, SessionTransition,
+};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, Utc};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::db::Db;
+use crate::transitions::state_intervals;
+
+/// Opens or closes the session's human wait for `event`. Call after the
+/// session update so a new session already has its project path.
+pub fn record_human_wait(db: &Db, event: &EventEnvelope) -> Result<(), String> {
+    let Some(session_id) = event.session_id.as_deref() else {
+        return Ok(());
+    };
+    if opens_human_wait(event) {
+        if let Some(session) = db.get_session(session_id)? {
+            db.open_human_wait(session_id, &session.project_path, &event.recorded_at)?;
+        }
+    } else if closes_human_wait(event.event_type) {
+        db.close_human_wait(session_id, &event.recorded_at)?;
+    }
+    Ok(())
+}
+
+fn opens_human_wait(event: &EventEnvelope) -> bool {
+    match event.event_type {
+        EventType::PermissionRequest => true,
+        EventType::Notification => matches!(
+            event.notification_type.as_deref(),
+            Some("permission_prompt") | Some("elicitation_dialog")
+        ),
+        _ => false,
+    }
+}
+
+fn closes_human_wait(event_type: EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::UserPromptSubmit
+            | EventType::PostToolUse
+            | EventType::PostToolUseFailure
+            | EventType::Stop
+            | EventType::SessionEnd
+    )
+}
+
+/// Totals per project and calendar day, oldest day first. Days are cut at
+/// `query.utc_offset_minutes` and the window runs from the start of the first
+/// day to `now`.
+pub fn project_time(
+    db: &Db,
+    query: &ProjectTimeQuery,
+    now: DateTime<Utc>,
+) -> Result<Vec<ProjectDayTime>, String> {
+    let offset = FixedOffset::east_opt(query.utc_offset_minutes.unwrap_or(0) * 60)
+        .ok_or_else(|| "utc_offset_minutes is out of range".to_string())?;
+    let today = now.with_timezone(&offset).date_naive();
+    let first_day = today - Duration::days(i64::from(query.days()) - 1);
+    let window = (day_start(first_day, offset), now);
+    let project_path = query.project_path.as_deref();
+
+    let live: HashSet<String> = db
+        .list_sessions()?
+        .into_iter()
+        .map(|session| session.session_id)
+        .collect();
+    let mut totals = Totals::new();
+
+    let transitions = db.list_session_transitions_in_window(
+        project_path,
+        &window.0.to_rfc3339(),
+        &window.1.to_rfc3339(),
+    )?;
+    for session in transitions.chunk_by(|left, right| left.session_id == right.session_id) {
+        let Some(last) = session.last() else {
+            continue;
+        };
+        let is_live = live.contains(&last.session_id);
+        for interval in state_intervals(session, is_live, now) {
+            let interval_project = project_for(session, interval.start);
+            if project_path.is_some_and(|wanted| wanted != interval_project) {
+                continue;
+            }
+            for_each_day(
+                interval.start,
+                interval.end,
+                window,
+                offset,
+                |day, seconds| {
+                    *entry(&mut totals, day, interval_project)
+                        .seconds_in_state
+                        .entry(interval.state)
+                        .or_default() += seconds;
+                },
+            );
+        }
+    }
+
+    let waits =
+        db.list_human_waits(project_path, &window.0.to_rfc3339(), &window.1.to_rfc3339())?;
+    for wait in waits {
+        let Some(start) = parse_rfc3339(&wait.started_at) else {
+            continue;
+        };
+        let end = match wait.ended_at.as_deref() {
+            Some(ended_at) => match parse_rfc3339(ended_at) {
+                Some(end) => end,
+                None => continue,
+            },
+            // A session that vanished without an answer has no known end.
+            None if live.contains(&wait.session_id) => now,
+            None => continue,
+        };
+        for_each_day(start, end, window, offset, |day, seconds| {
+            entry(&mut totals, day, &wait.project_path).waiting_on_human_secs += seconds;
+        });
+    }
+
+    Ok(totals.into_values().collect())
+}
+
+type Totals = BTreeMap<(NaiveDate, String), ProjectDayTime>;
+
+fn entry<'a>(totals: &'a mut Totals, day: NaiveDate, project_path: &str) -> &'a mut ProjectDayTime {
+    totals
+        .entry((day, project_path.to_string()))
+        .or_insert_with(|| ProjectDayTime {
+            project_path: project_path.to_string(),
+            day: day.format("%Y-%m-%d").to_string(),
+            seconds_in_state: Default::default(),
+            waiting_on_human_secs: 0,
+        })
+}
+
+/// Project of the transition in effect at `at`.
+fn project_for(session: &[SessionTransition], at: DateTime<Utc>) -> &str {
+    session
+        .iter()
+        .rev()
+        .find(|transition| parse_rfc3339(&transition.transitioned_at).is_some_and(|t| t <= at))
+        .or(session.first())
+        .map(|transition| transition.project_path.as_str())
+        .unwrap_or_default()
+}
+
+/// Splits `[start, end)` clipped to `window` at local midnights and reports
+/// whole seconds per day.
+fn for_each_day(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    window: (DateTime<Utc>, DateTime<Utc>),
+    offset: FixedOffset,
+    mut add: impl FnMut(NaiveDate, u64),
+) {
+    let mut cursor = start.max(window.0);
+    let end = end.min(window.1);
+    while cursor < end {
+        let day = cursor.with_timezone(&offset).date_naive();
+        let next_day = day
+            .succ_opt()
+            .map(|next| day_start(next, offset))
+            .unwrap_or(end);
+        let chunk_end = next_day.min(end);
+        let seconds = (chunk_end - cursor).num_seconds();
+        if seconds > 0 {
+            add(day, seconds as u64);
+        }
+        cursor = chunk_end;
+    }
+}
+
+fn day_start(day: NaiveDate, offset: FixedOffset) -> DateTime<Utc> {
+    (day.and_time(NaiveTime::MIN) - Duration::seconds(i64::from(offset.local_minus_utc())))
+        .and_utc()
+}
+
+fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use capacitor_daemon_protocol::SessionState;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        parse_rfc3339(value).expect("timestamp")
+    }
+
+    #[test]
+    fn splits_intervals_at_local_midnight() {
+        let offset = FixedOffset::east_opt(-8 * 3600).unwrap();
+        let mut days = Vec::new();
+        // 23:30 to 00:45 Pacific.
+        for_each_day(
+            at("2026-01-31T07:30:00Z"),
+            at("2026-01-31T08:45:00Z"),
+            (at("2026-01-01T00:00:00Z"), at("2026-02-02T00:00:00Z")),
+            offset,
+            |day, seconds| days.push((day.to_string(), seconds)),
+        );
+        assert_eq!(
+            days,
+            vec![
+                ("2026-01-30".to_string(), 1800),
+                ("2026-01-31".to_string(), 2700),
+            ]
+        );
+    }
+
+    #[test]
+    fn totals_state_and_human_wait_per_project_day() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let transitions = [
+            (None, Some(SessionState::Working), "2026-01-31T10:00:00Z"),
+            (
+                Some(SessionState::Working),
+                Some(SessionState::Waiting),
+                "2026-01-31T10:20:00Z",
+            ),
+            (
+                Some(SessionState::Waiting),
+                Some(SessionState::Working),
+                "2026-01-31T10:25:00Z",
+            ),
+            (Some(SessionState::Working), None, "2026-01-31T10:30:00Z"),
+        ];
+        for (from_state, to_state, transitioned_at) in transitions {
+            db.insert_session_transition(&SessionTransition {
+                session_id: "session-1".to_string(),
+                project_path: "/repo".to_string(),
+                from_state,
+                to_state,
+                event_id: None,
+                transitioned_at: transitioned_at.to_string(),
+            })
+            .expect("insert transition");
+        }
+        db.open_human_wait("session-1", "/repo", "2026-01-31T10:20:00Z")
+            .expect("open wait");
+        assert!(!db
+            .open_human_wait("session-1", "/repo", "2026-01-31T10:21:00Z")
+            .expect("already open"));
+        db.close_human_wait("session-1", "2026-01-31T10:24:00Z")
+            .expect("close wait");
+
+        let rows = project_time(
+            &db,
+            &ProjectTimeQuery {
+                days: Some(2),
+                ..ProjectTimeQuery::default()
+            },
+            at("2026-01-31T12:00:00Z"),
+        )
+        .expect("project time");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].day, "2026-01-31");
+        assert_eq!(rows[0].seconds_in_state[&SessionState::Working], 1500);
+        assert_eq!(rows[0].seconds_in_state[&SessionState::Waiting], 300);
+        assert_eq!(rows[0].waiting_on_human_secs, 240);
+
+        let other = project_time(
+            &db,
+            &ProjectTimeQuery {
+                project_path: Some("/other".to_string()),
+                ..ProjectTimeQuery::default()
+            },
+            at("2026-01-31T12:00:00Z"),
+        )
+        .expect("project time");
+        assert!(other.is_empty());
+    }
+
+    fn insert_transitions(db: &Db, session_id: &str, rows: &[(&str, Option<SessionState>, &str)]) {
+        let mut from_state = None;
+        for (project_path, to_state, transitioned_at) in rows {
+            db.insert_session_transition(&SessionTransition {
+                session_id: session_id.to_string(),
+                project_path: project_path.to_string(),
+                from_state,
+                to_state: *to_state,
+                event_id: None,
+                transitioned_at: transitioned_at.to_string(),
+            })
+            .expect("insert transition");
+            from_state = *to_state;
+        }
+    }
+
+    #[test]
+    fn session_that_changed_project_is_split_between_them() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        insert_transitions(
+            &db,
+            "session-1",
+            &[
+                ("/a", Some(SessionState::Working), "2026-01-31T10:00:00Z"),
+                ("/b", Some(SessionState::Ready), "2026-01-31T10:10:00Z"),
+                ("/b", None, "2026-01-31T10:15:00Z"),
+            ],
+        );
+
+        let query = |project: &str| {
+            project_time(
+                &db,
+                &ProjectTimeQuery {
+                    project_path: Some(project.to_string()),
+                    ..ProjectTimeQuery::default()
+                },
+                at("2026-01-31T12:00:00Z"),
+            )
+            .expect("project time")
+        };
+        let a = query("/a");
+        assert_eq!(a.len(), 1);
+        assert_eq!(a[0].seconds_in_state[&SessionState::Working], 600);
+        assert!(!a[0].seconds_in_state.contains_key(&SessionState::Ready));
+        let b = query("/b");
+        assert_eq!(b.len(), 1);
+        assert_eq!(b[0].seconds_in_state[&SessionState::Ready], 300);
+        assert!(!b[0].seconds_in_state.contains_key(&SessionState::Working));
+    }
+
+    #[test]
+    fn window_query_skips_history_before_the_window() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        insert_transitions(
+            &db,
+            "session-old",
+            &[
+                ("/repo", Some(SessionState::Working), "2026-01-01T10:00:00Z"),
+                ("/repo", None, "2026-01-01T11:00:00Z"),
+            ],
+        );
+        insert_transitions(
+            &db,
+            "session-1",
+            &[
+                ("/repo", Some(SessionState::Working), "2026-01-20T10:00:00Z"),
+                ("/repo", Some(SessionState::Ready), "2026-01-25T10:00:00Z"),
+                ("/repo", Some(SessionState::Working), "2026-01-31T09:00:00Z"),
+            ],
+        );
+
+        let rows = db
+            .list_session_transitions_in_window(
+                Some("/repo"),
+                "2026-01-30T00:00:00+00:00",
+                "2026-01-31T12:00:00+00:00",
+            )
+            .expect("transitions");
+        let times = rows
+            .iter()
+            .map(|row| row.transitioned_at.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(times, vec!["2026-01-25T10:00:00Z", "2026-01-31T09:00:00Z"]);
+    }
+}
@@ -0,0 +1,270 @@
+//! Offline replay of an event trace into a per-event state timeline.
+//!
+//! Each event goes through the daemon's own apply path (`reduce_session`,
+//! TTL pruning, HEM synthesis) against a scratch database. The clock is pinned
+//! to the event's `recorded_at` and process liveness reads as unknown, so a
+//! trace produces the same timeline on any machine. Time-based fallbacks are
+//! therefore evaluated at event times only, not between events.
+
+use crate::db::Db;
+use crate::hem::HemRuntimeConfig;
+use crate::reducer::SessionState;
+use crate::state::{EventOutcome, SharedState};
+use capacitor_daemon_protocol::{EnrichedSession, EventEnvelope, EventType, ProjectStateRecord};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+/// State after one event of the trace.
+#[derive(Debug, Serialize)]
+pub struct TimelineEntry {
+    /// 1-based position in the trace.
+    pub index: usize,
+    pub event_id: String,
+    pub recorded_at: String,
+    pub event_type: EventType,
+    pub session_id: Option<String>,
+    /// The event id was already seen; nothing was applied.
+    pub duplicate: bool,
+    pub sessions: Vec<EnrichedSession>,
+    pub project_states: Vec<ProjectStateRecord>,
+    pub hem_project_states: Vec<HemProjectStateView>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HemProjectStateView {
+    pub project_path: String,
+    pub state: SessionState,
+    pub confidence: f64,
+    pub evidence_count: usize,
+}
+
+/// Replays `events` in order against a fresh database under `scratch_dir`.
+pub fn replay_timeline(
+    events: &[EventEnvelope],
+    hem_config: HemRuntimeConfig,
+    scratch_dir: &Path,
+) -> Result<Vec<TimelineEntry>, String> {
+    let db = Db::new(scratch_dir.join("replay.db"))?;
+    if db.max_event_rowid()?.is_some() {
+        return Err(format!(
+            "Scratch database under {} is not empty",
+            scratch_dir.display()
+        ));
+    }
+    let state = SharedState::new_with_hem_config(db, hem_config);
+
+    let mut entries = Vec::with_capacity(events.len());
+    for (index, event) in events.iter().enumerate() {
+        let recorded_at = DateTime::parse_from_rfc3339(&event.recorded_at)
+            .map_err(|err| format!("Invalid recorded_at on {}: {}", event.event_id, err))?
+            .with_timezone(&Utc);
+        state.pin_clock(recorded_at);
+        let outcomes = state.update_from_events(std::slice::from_ref(event))?;
+        if let Some(EventOutcome::Rejected(err)) = outcomes.first() {
+            return Err(format!("Failed to store {}: {}", event.event_id, err));
+        }
+
+        entries.push(TimelineEntry {
+            index: index + 1,
+            event_id: event.event_id.clone(),
+            recorded_at: event.recorded_at.clone(),
+            event_type: event.event_type,
+            session_id: event.session_id.clone(),
+            duplicate: !outcomes.first().is_some_and(EventOutcome::is_stored),
+            sessions: state.sessions_snapshot()?,
+            project_states: state
+                .project_states_snapshot()?
+                .iter()
+                .map(|project| project.to_record())
+                .collect(),
+            hem_project_states: state
+                .hem_project_states_snapshot()?
+                .into_iter()
+                .map(|hem| HemProjectStateView {
+                    project_path: hem.project_path,
+                    state: hem.state,
+                    confidence: hem.confidence,
+                    evidence_count: hem.evidence_count,
+                })
+                .collect(),
+        });
+    }
+    Ok(entries)
+}
+
+/// One JSON object per entry.
+pub fn write_json(entries: &[TimelineEntry], out: &mut impl Write) -> Result<(), String> {
+    for entry in entries {
+        let line = serde_json::to_string(entry)
+            .map_err(|err| format!("Failed to serialize timeline entry: {}", err))?;
+        writeln!(out, "{}", line).map_err(|err| format!("Failed to write timeline: {}", err))?;
+    }
+    Ok(())
+}
+
+/// One header line per event followed by the states it changed, e.g.
+/// `  session s1: working -> ready`. `-` marks an absent entry.
+pub fn write_text(entries: &[TimelineEntry], out: &mut impl Write) -> Result<(), String> {
+    let mut previous = StateLines::default();
+    for entry in entries {
+        let event_type = serde_json::to_value(entry.event_type)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let mut header = format!("[{}] {} {}", entry.index, entry.recorded_at, event_type);
+        if let Some(session_id) = &entry.session_id {
+            header.push_str(&format!(" session={}", session_id));
+        }
+        if entry.duplicate {
+            header.push_str(" (duplicate, skipped)");
+        }
+        let current = StateLines::from_entry(entry);
+        let mut lines = vec![header];
+        lines.extend(diff("session", &previous.sessions, &current.sessions));
+        lines.extend(diff("project", &previous.projects, &current.projects));
+        lines.extend(diff("hem", &previous.hem, &current.hem));
+        for line in lines {
+            writeln!(out, "{}", line)
+                .map_err(|err| format!("Failed to write timeline: {}", err))?;
+        }
+        previous = current;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct StateLines {
+    sessions: BTreeMap<String, String>,
+    projects: BTreeMap<String, String>,
+    hem: BTreeMap<String, String>,
+}
+
+impl StateLines {
+    fn from_entry(entry: &TimelineEntry) -> Self {
+        Self {
+            sessions: entry
+                .sessions
+                .iter()
+                .map(|session| {
+                    (
+                        session.session_id.clone(),
+                        session.state.as_str().to_string(),
+                    )
+                })
+                .collect(),
+            projects: entry
+                .project_states
+                .iter()
+                .map(|project| {
+                    (
+                        project.project_path.clone(),
+                        project.state.as_str().to_string(),
+                    )
+                })
+                .collect(),
+            hem: entry
+                .hem_project_states
+                .iter()
+                .map(|hem| {
+                    (
+                        hem.project_path.clone(),
+                        format!("{} ({:.2})", hem.state.as_str(), hem.confidence),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+fn diff(
+    label: &str,
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = before.get(key).map(String::as_str).unwrap_or("-");
+            let new = after.get(key).map(String::as_str).unwrap_or("-");
+            (old != new).then(|| format!("  {} {}: {} -> {}", label, key, old, new))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_id: &str, event_type: EventType, recorded_at: &str) -> EventEnvelope {
+        EventEnvelope {
+            event_id: event_id.to_string(),
+            recorded_at: recorded_at.to_string(),
+            event_type,
+            session_id: Some("session-1".to_string()),
+            pid: Some(4242),
+            cwd: Some("/repo".to_string()),
+            tool: None,
+            file_path: None,
+            parent_app: None,
+            tty: None,
+            tmux_session: None,
+            tmux_client_tty: None,
+            notification_type: None,
+            stop_hook_active: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn replay_evaluates_ttl_at_event_time() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let mut stop = event("evt-3", EventType::Stop, "2026-01-31T00:00:05Z");
+        stop.stop_hook_active = Some(false);
+        let events = vec![
+            event("evt-1", EventType::SessionStart, "2026-01-31T00:00:00Z"),
+            event("evt-2", EventType::UserPromptSubmit, "2026-01-31T00:00:01Z"),
+            stop,
+            event("evt-2", EventType::UserPromptSubmit, "2026-01-31T00:00:01Z"),
+            // Hours later: the ready session has outlived its TTL.
+            {
+                let mut other = event("evt-4", EventType::SessionStart, "2026-01-31T03:00:00Z");
+                other.session_id = Some("session-2".to_string());
+                other.pid = Some(4343);
+                other
+            },
+        ];
+
+        let entries =
+            replay_timeline(&events, HemRuntimeConfig::default(), temp_dir.path()).expect("replay");
+        let states: Vec<Vec<(&str, &str)>> = entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .sessions
+                    .iter()
+                    .map(|session| (session.session_id.as_str(), session.state.as_str()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(states[1], vec![("session-1", "working")]);
+        assert_eq!(states[2], vec![("session-1", "ready")]);
+        assert!(entries[3].duplicate);
+        assert_eq!(states[4], vec![("session-2", "ready")]);
+        assert_eq!(entries[2].project_states[0].project_path, "/repo");
+
+        let mut text = Vec::new();
+        write_text(&entries, &mut text).expect("text");
+        let text = String::from_utf8(text).unwrap();
+        assert!(
+            text.contains("  session session-1: working -> ready\n"),
+            "{text}"
+        );
+        assert!(text.contains("  session session-1: ready -> -\n"), "{text}");
+        assert!(text.contains("(duplicate, skipped)"), "{text}");
+    }
+}
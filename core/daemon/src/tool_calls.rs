@@ -0,0 +1,409 @@
+//! Tool invocations paired by `tool_use_id`.
+//!
+//! `pre_tool_use` and its `post_tool_use` / `post_tool_use_failure` carry the
+//! same `tool_use_id`, so each invocation becomes one `tool_calls` row with a
+//! duration and outcome. Calls still running when the turn ends are marked
+//! interrupted, which also gives sessions an exact in-flight count instead of
+//! the reducer's Pre/Post counter.
+
+use capacitor_daemon_protocol::{
+    EventEnvelope, EventType, ToolCall, ToolCallOutcome, ToolCallsQuery, ToolCallsResponse,
+    TOOL_CALLS_DEFAULT_WINDOW_HOURS,
+};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::db::Db;
+use crate::reducer::SessionRecord;
+
+/// Starts, finishes or interrupts tool calls for `event`. Call before the
+/// session update so `sync_tools_in_flight` sees the result.
+pub fn record_tool_call(db: &Db, event: &EventEnvelope) -> Result<(), String> {
+    let Some(session_id) = event.session_id.as_deref() else {
+        return Ok(());
+    };
+    let agent_id = metadata_str(event, "agent_id");
+    match event.event_type {
+        EventType::PreToolUse => {
+            let Some(tool_use_id) = metadata_str(event, "tool_use_id") else {
+                return Ok(());
+            };
+            let project_path = match db.get_session(session_id)? {
+                Some(session) if !session.project_path.is_empty() => session.project_path,
+                _ => event.cwd.clone().unwrap_or_default(),
+            };
+            db.start_tool_call(&ToolCall {
+                session_id: session_id.to_string(),
+                tool_use_id: tool_use_id.to_string(),
+                tool_name: event
+                    .tool
+                    .clone()
+                    .filter(|tool| !tool.is_empty())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                file_path: event.file_path.clone(),
+                project_path,
+                agent_id: agent_id.map(str::to_string),
+                started_at: event.recorded_at.clone(),
+                ended_at: None,
+                duration_ms: None,
+                outcome: None,
+            })
+        }
+        EventType::PostToolUse | EventType::PostToolUseFailure => {
+            let Some(tool_use_id) = metadata_str(event, "tool_use_id") else {
+                return Ok(());
+            };
+            let outcome = if event.event_type == EventType::PostToolUse {
+                ToolCallOutcome::Success
+            } else {
+                ToolCallOutcome::Failure
+            };
+            db.finish_tool_call(session_id, tool_use_id, &event.recorded_at, outcome)?;
+            Ok(())
+        }
+        EventType::SubagentStop => match agent_id {
+            Some(agent_id) => {
+                db.interrupt_tool_calls(session_id, Some(agent_id), &event.recorded_at)
+            }
+            None => Ok(()),
+        },
+        // Same resets as the reducer's in-flight counter, plus a new prompt
+        // (the user interrupted the turn) and the session ending.
+        EventType::Stop | EventType::TaskCompleted => {
+            if metadata_str(event, "teammate_name").is_some() && agent_id.is_none() {
+                return Ok(());
+            }
+            db.interrupt_tool_calls(session_id, agent_id, &event.recorded_at)
+        }
+        EventType::SessionStart
+        | EventType::PreCompact
+        | EventType::UserPromptSubmit
+        | EventType::SessionEnd => db.interrupt_tool_calls(session_id, None, &event.recorded_at),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the reducer's counter with the number of running calls when the
+/// session's calls are tracked by `tool_use_id`.
+pub fn sync_tools_in_flight(db: &Db, record: &mut SessionRecord) -> Result<(), String> {
+    if let Some(running) = db.tracked_tools_in_flight(&record.session_id)? {
+        record.tools_in_flight = running;
+    }
+    Ok(())
+}
+
+/// Lists calls and per-tool totals for `query`. Without `since` only the last
+/// `TOOL_CALLS_DEFAULT_WINDOW_HOURS` before `now` are covered.
+pub fn tool_calls(
+    db: &Db,
+    query: &ToolCallsQuery,
+    now: DateTime<Utc>,
+) -> Result<ToolCallsResponse, String> {
+    let query = ToolCallsQuery {
+        since: Some(query.since.clone().unwrap_or_else(|| {
+            (now - Duration::hours(TOOL_CALLS_DEFAULT_WINDOW_HOURS)).to_rfc3339()
+        })),
+        ..query.clone()
+    };
+    Ok(ToolCallsResponse {
+        calls: db.list_tool_calls(&query)?,
+        tools: db.tool_stats(&query)?,
+    })
+}
+
+fn metadata_str<'a>(event: &'a EventEnvelope, key: &str) -> Option<&'a str> {
+    event
+        .metadata
+        .as_ref()
+        .and_then(|value| value.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducer::SessionState;
+    use crate::test_support;
+
+    fn event(
+        event_type: EventType,
+        recorded_at: &str,
+        tool: Option<&str>,
+        metadata: serde_json::Value,
+    ) -> EventEnvelope {
+        EventEnvelope {
+            tool: tool.map(str::to_string),
+            metadata: Some(metadata),
+            ..test_support::event(
+                &format!("evt-{}-{:?}", recorded_at, event_type),
+                event_type,
+                recorded_at,
+            )
+        }
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .expect("timestamp")
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn pairs_calls_by_tool_use_id_and_aggregates() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let id = |value: &str| serde_json::json!({ "tool_use_id": value });
+        let events = [
+            event(
+                EventType::PreToolUse,
+                "2026-01-31T00:00:00Z",
+                Some("Bash"),
+                id("t1"),
+            ),
+            event(
+                EventType::PreToolUse,
+                "2026-01-31T00:00:01Z",
+                Some("Read"),
+                id("t2"),
+            ),
+            event(
+                EventType::PostToolUse,
+                "2026-01-31T00:00:01.500Z",
+                Some("Read"),
+                id("t2"),
+            ),
+            event(
+                EventType::PostToolUseFailure,
+                "2026-01-31T00:00:04Z",
+                Some("Bash"),
+                id("t1"),
+            ),
+            event(
+                EventType::PreToolUse,
+                "2026-01-31T00:00:05Z",
+                Some("Bash"),
+                id("t3"),
+            ),
+            event(
+                EventType::PostToolUse,
+                "2026-01-31T00:00:06Z",
+                Some("Bash"),
+                id("t3"),
+            ),
+            event(
+                EventType::PreToolUse,
+                "2026-01-31T00:00:07Z",
+                Some("Read"),
+                id("t4"),
+            ),
+        ];
+        for event in &events {
+            record_tool_call(&db, event).expect("record");
+        }
+        assert_eq!(db.tracked_tools_in_flight("session-1").unwrap(), Some(1));
+        assert_eq!(db.tracked_tools_in_flight("session-2").unwrap(), None);
+
+        record_tool_call(
+            &db,
+            &event(
+                EventType::Stop,
+                "2026-01-31T00:00:09Z",
+                None,
+                serde_json::json!({}),
+            ),
+        )
+        .expect("stop");
+        assert_eq!(db.tracked_tools_in_flight("session-1").unwrap(), Some(0));
+
+        let response = tool_calls(
+            &db,
+            &ToolCallsQuery {
+                limit: Some(2),
+                ..ToolCallsQuery::default()
+            },
+            at("2026-01-31T01:00:00Z"),
+        )
+        .expect("tool calls");
+        assert_eq!(response.calls.len(), 2);
+        assert_eq!(response.calls[0].tool_use_id, "t4");
+        assert_eq!(
+            response.calls[0].outcome,
+            Some(ToolCallOutcome::Interrupted)
+        );
+        assert_eq!(response.calls[1].duration_ms, Some(1000));
+
+        let bash = &response.tools[0];
+        assert_eq!(bash.tool_name, "Bash");
+        assert_eq!(bash.calls, 2);
+        assert_eq!(bash.failures, 1);
+        assert_eq!(bash.failure_rate_percent, 50);
+        assert_eq!(bash.avg_duration_ms, 2500);
+        assert_eq!(bash.max_duration_ms, 4000);
+        assert_eq!(bash.slowest_tool_use_id.as_deref(), Some("t1"));
+        let read = &response.tools[1];
+        assert_eq!(read.calls, 2);
+        assert_eq!(read.interrupted, 1);
+        assert_eq!(read.avg_duration_ms, 500);
+        assert_eq!(read.failure_rate_percent, 0);
+    }
+
+    #[test]
+    fn subagent_stop_only_interrupts_its_own_calls() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        record_tool_call(
+            &db,
+            &event(
+                EventType::PreToolUse,
+                "2026-01-31T00:00:00Z",
+                Some("Bash"),
+                serde_json::json!({ "tool_use_id": "t1" }),
+            ),
+        )
+        .expect("parent call");
+        record_tool_call(
+            &db,
+            &event(
+                EventType::PreToolUse,
+                "2026-01-31T00:00:01Z",
+                Some("Grep"),
+                serde_json::json!({ "tool_use_id": "t2", "agent_id": "agent-1" }),
+            ),
+        )
+        .expect("subagent call");
+        record_tool_call(
+            &db,
+            &event(
+                EventType::SubagentStop,
+                "2026-01-31T00:00:02Z",
+                None,
+                serde_json::json!({ "agent_id": "agent-1" }),
+            ),
+        )
+        .expect("subagent stop");
+
+        assert_eq!(db.tracked_tools_in_flight("session-1").unwrap(), Some(1));
+        let mut record = SessionRecord {
+            session_id: "session-1".to_string(),
+            pid: 1234,
+            state: SessionState::Working,
+            cwd: "/repo".to_string(),
+            project_id: "/repo".to_string(),
+            project_path: "/repo".to_string(),
+            updated_at: "2026-01-31T00:00:02Z".to_string(),
+            state_changed_at: "2026-01-31T00:00:00Z".to_string(),
+            last_event: Some("pre_tool_use".to_string()),
+            last_activity_at: None,
+            tools_in_flight: 5,
+            ready_reason: None,
+            pending_request: None,
+        };
+        sync_tools_in_flight(&db, &mut record).expect("sync");
+        assert_eq!(record.tools_in_flight, 1);
+    }
+
+    #[test]
+    fn calls_without_tool_use_id_are_not_tracked() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        for event_type in [EventType::PreToolUse, EventType::PostToolUse] {
+            record_tool_call(
+                &db,
+                &event(
+                    event_type,
+                    "2026-01-31T00:00:00Z",
+                    Some("Bash"),
+                    serde_json::json!({ "tool_use_id": "  " }),
+                ),
+            )
+            .expect("blank id");
+        }
+        record_tool_call(
+            &db,
+            &EventEnvelope {
+                metadata: None,
+                ..event(
+                    EventType::PreToolUse,
+                    "2026-01-31T00:00:01Z",
+                    Some("Bash"),
+                    serde_json::json!({}),
+                )
+            },
+        )
+        .expect("no metadata");
+
+        // The reducer's counter stays in charge for these sessions.
+        assert_eq!(db.tracked_tools_in_flight("session-1").unwrap(), None);
+        let response = tool_calls(&db, &ToolCallsQuery::default(), at("2026-01-31T01:00:00Z"))
+            .expect("tool calls");
+        assert!(response.calls.is_empty());
+        assert!(response.tools.is_empty());
+    }
+
+    #[test]
+    fn default_window_skips_old_calls_and_totals_ignore_limit() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let calls = [
+            ("old", "2026-01-29T00:00:00Z", "2026-01-29T00:00:09Z"),
+            ("t1", "2026-01-31T00:00:00Z", "2026-01-31T00:00:02Z"),
+            ("t2", "2026-01-31T00:01:00Z", "2026-01-31T00:01:02Z"),
+            ("t3", "2026-01-31T00:02:00Z", "2026-01-31T00:02:01Z"),
+        ];
+        for (id, started_at, ended_at) in calls {
+            let metadata = serde_json::json!({ "tool_use_id": id });
+            record_tool_call(
+                &db,
+                &event(
+                    EventType::PreToolUse,
+                    started_at,
+                    Some("Bash"),
+                    metadata.clone(),
+                ),
+            )
+            .expect("start");
+            record_tool_call(
+                &db,
+                &event(EventType::PostToolUse, ended_at, Some("Bash"), metadata),
+            )
+            .expect("finish");
+        }
+
+        let now = at("2026-01-31T12:00:00Z");
+        let response = tool_calls(
+            &db,
+            &ToolCallsQuery {
+                limit: Some(1),
+                ..ToolCallsQuery::default()
+            },
+            now,
+        )
+        .expect("tool calls");
+        assert_eq!(response.calls.len(), 1);
+        assert_eq!(response.calls[0].tool_use_id, "t3");
+        let bash = &response.tools[0];
+        assert_eq!(bash.calls, 3);
+        assert_eq!(bash.avg_duration_ms, 1666);
+        assert_eq!(bash.max_duration_ms, 2000);
+        // Equal durations: the newest call is reported as the slowest.
+        assert_eq!(bash.slowest_tool_use_id.as_deref(), Some("t2"));
+
+        let response = tool_calls(
+            &db,
+            &ToolCallsQuery {
+                since: Some("2026-01-28T00:00:00Z".to_string()),
+                limit: Some(0),
+                ..ToolCallsQuery::default()
+            },
+            now,
+        )
+        .expect("tool calls since");
+        assert!(response.calls.is_empty());
+        assert_eq!(response.tools[0].calls, 4);
+        assert_eq!(
+            response.tools[0].slowest_tool_use_id.as_deref(),
+            Some("old")
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Session state transition history.
+//!
+//! `sessions` only holds the current state, so every change the daemon applies
+//! is also appended to `session_transitions`. Rows outlive their session: the
+//! history is what answers "how long did this sit in `waiting` today".
+
+use capacitor_daemon_protocol::{SessionState, SessionTimeline, SessionTransition};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use crate::db::Db;
+use crate::reducer::SessionRecord;
+
+/// Records the change from `current` to `record`, if the state changed.
+/// `event_id` is `None` when the daemon changed the state itself, e.g. when
+/// reconciling a dead process.
+pub fn record_upsert(
+    db: &Db,
+    current: Option<&SessionRecord>,
+    record: &SessionRecord,
+    event_id: Option<&str>,
+) -> Result<(), String> {
+    let from_state = current.map(|session| session.state.clone().into());
+    let to_state: SessionState = record.state.clone().into();
+    if from_state == Some(to_state) {
+        return Ok(());
+    }
+    db.insert_session_transition(&SessionTransition {
+        session_id: record.session_id.clone(),
+        project_path: record.project_path.clone(),
+        from_state,
+        to_state: Some(to_state),
+        event_id: event_id.map(str::to_string),
+        transitioned_at: record.state_changed_at.clone(),
+    })
+}
+
+/// Records the end of `current`. `event_id` is `None` when the daemon pruned
+/// the session rather than an event ending it.
+pub fn record_end(
+    db: &Db,
+    current: &SessionRecord,
+    event_id: Option<&str>,
+    ended_at: &str,
+) -> Result<(), String> {
+    db.insert_session_transition(&SessionTransition {
+        session_id: current.session_id.clone(),
+        project_path: current.project_path.clone(),
+        from_state: Some(current.state.clone().into()),
+        to_state: None,
+        event_id: event_id.map(str::to_string),
+        transitioned_at: ended_at.to_string(),
+    })
+}
+
+/// A stretch of time one session spent in one state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateInterval {
+    pub state: SessionState,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Turns one session's transitions (oldest first) into state intervals. A
+/// session that is still `live` stays in its last state until `now`; for an
+/// ended session the last interval closes at its end transition.
+pub fn state_intervals(
+    transitions: &[SessionTransition],
+    live: bool,
+    now: DateTime<Utc>,
+) -> Vec<StateInterval> {
+    let times: Vec<Option<DateTime<Utc>>> = transitions
+        .iter()
+        .map(|transition| parse_rfc3339(&transition.transitioned_at))
+        .collect();
+
+    let mut intervals = Vec::new();
+    for (index, transition) in transitions.iter().enumerate() {
+        let (Some(state), Some(start)) = (transition.to_state, times[index]) else {
+            continue;
+        };
+        let end = match times.get(index + 1) {
+            Some(Some(next)) => *next,
+            None if live => now,
+            _ => continue,
+        };
+        if end > start {
+            intervals.push(StateInterval { state, start, end });
+        }
+    }
+    intervals
+}
+
+/// Clips `transitions` (oldest first) to `[since, until)` and totals the time
+/// spent in each state. A session that is still `live` stays in its last state
+/// until `until`, or `now` when no upper bound was given.
+pub fn build_timeline(
+    session_id: &str,
+    transitions: Vec<SessionTransition>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    live: bool,
+) -> SessionTimeline {
+    let window_end = until.map_or(now, |until| until.min(now));
+    let mut seconds_in_state: HashMap<SessionState, u64> = HashMap::new();
+    for interval in state_intervals(&transitions, live, now) {
+        let start = since.map_or(interval.start, |since| interval.start.max(since));
+        let end = interval.end.min(window_end);
+        if end > start {
+            *seconds_in_state.entry(interval.state).or_default() +=
+                (end - start).num_seconds() as u64;
+        }
+    }
+
+    let transitions = transitions
+        .into_iter()
+        .filter(|transition| {
+            parse_rfc3339(&transition.transitioned_at).is_some_and(|at| {
+                since.map_or(true, |since| at >= since) && until.map_or(true, |until| at < until)
+            })
+        })
+        .collect();
+
+    SessionTimeline {
+        session_id: session_id.to_string(),
+        transitions,
+        seconds_in_state,
+    }
+}
+
+fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(
+        from_state: Option<SessionState>,
+        to_state: Option<SessionState>,
+        at: &str,
+    ) -> SessionTransition {
+        SessionTransition {
+            session_id: "session-1".to_string(),
+            project_path: "/repo".to_string(),
+            from_state,
+            to_state,
+            event_id: Some(format!("evt-{}", at)),
+            transitioned_at: at.to_string(),
+        }
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        parse_rfc3339(value).expect("timestamp")
+    }
+
+    fn history() -> Vec<SessionTransition> {
+        vec![
+            transition(None, Some(SessionState::Ready), "2026-01-31T09:00:00Z"),
+            transition(
+                Some(SessionState::Ready),
+                Some(SessionState::Working),
+                "2026-01-31T09:10:00Z",
+            ),
+            transition(
+                Some(SessionState::Working),
+                Some(SessionState::Waiting),
+                "2026-01-31T09:40:00Z",
+            ),
+            transition(
+                Some(SessionState::Waiting),
+                Some(SessionState::Working),
+                "2026-01-31T09:45:00Z",
+            ),
+        ]
+    }
+
+    #[test]
+    fn totals_time_per_state_until_now_for_live_sessions() {
+        let timeline = build_timeline(
+            "session-1",
+            history(),
+            None,
+            None,
+            at("2026-01-31T10:00:00Z"),
+            true,
+        );
+        assert_eq!(timeline.transitions.len(), 4);
+        assert_eq!(timeline.seconds_in_state[&SessionState::Ready], 600);
+        assert_eq!(timeline.seconds_in_state[&SessionState::Working], 2700);
+        assert_eq!(timeline.seconds_in_state[&SessionState::Waiting], 300);
+    }
+
+    #[test]
+    fn clips_to_window_and_stops_at_session_end() {
+        let mut transitions = history();
+        transitions.push(transition(
+            Some(SessionState::Working),
+            None,
+            "2026-01-31T09:50:00Z",
+        ));
+        let timeline = build_timeline(
+            "session-1",
+            transitions,
+            Some(at("2026-01-31T09:30:00Z")),
+            None,
+            at("2026-01-31T12:00:00Z"),
+            false,
+        );
+        // Working since 09:10 counts from the window start.
+        assert_eq!(timeline.seconds_in_state[&SessionState::Working], 900);
+        assert_eq!(timeline.seconds_in_state[&SessionState::Waiting], 300);
+        assert!(!timeline.seconds_in_state.contains_key(&SessionState::Ready));
+        assert_eq!(timeline.transitions.len(), 3);
+        assert_eq!(timeline.transitions[2].to_state, None);
+    }
+}
@@ -0,0 +1,359 @@
+//! Git worktrees registered from `worktree_create` / `worktree_remove`.
+//!
+//! Sessions running in a linked worktree resolve to the main checkout's
+//! project (see `project_identity`), so parallel agents in worktrees of one
+//! repository share a project state. The registry records each worktree's
+//! path, branch and creating session so project states can group those
+//! sessions by the worktree they run in.
+
+use capacitor_daemon_protocol::{
+    EventEnvelope, EventType, ProjectWorktreeState, WorktreeRecord, WorktreesQuery,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::db::Db;
+use crate::project_identity::{repo_root, resolve_project_identity, worktree_branch};
+use crate::project_state_policy::{reduce_project_sessions, SessionProjection};
+
+/// Where Claude creates a worktree when the hook input only names it.
+const DEFAULT_WORKTREES_DIR: &str = ".claude/worktrees";
+/// A worktree is registered as it is being created, so its directory may
+/// not exist yet; only prune missing directories older than this.
+const MISSING_WORKTREE_GRACE_SECS: i64 = 300;
+
+/// Registers or drops the worktree named by `event`. Returns whether the
+/// registry changed.
+pub fn apply_worktree_event(db: &Db, event: &EventEnvelope) -> Result<bool, String> {
+    match event.event_type {
+        EventType::WorktreeCreate => match worktree_from_event(db, event)? {
+            Some(record) => {
+                db.upsert_worktree(&record)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        },
+        EventType::WorktreeRemove => match worktree_path(event) {
+            Some(path) => db.delete_worktree(&path),
+            None => Ok(false),
+        },
+        _ => Ok(false),
+    }
+}
+
+pub fn worktrees(db: &Db, query: &WorktreesQuery) -> Result<Vec<WorktreeRecord>, String> {
+    db.list_worktrees(query.project_path.as_deref())
+}
+
+/// Drops worktrees whose directory was removed without a `worktree_remove`
+/// (e.g. `git worktree remove` from a shell).
+pub fn prune_missing_worktrees(db: &Db, now: DateTime<Utc>) -> Result<usize, String> {
+    let cutoff = now - Duration::seconds(MISSING_WORKTREE_GRACE_SECS);
+    let mut pruned = 0;
+    for worktree in db.list_worktrees(None)? {
+        if Path::new(&worktree.path).exists() {
+            continue;
+        }
+        let created_at = DateTime::parse_from_rfc3339(&worktree.created_at)
+            .map(|value| value.with_timezone(&Utc))
+            .ok();
+        if created_at.is_some_and(|created_at| created_at > cutoff) {
+            continue;
+        }
+        if db.delete_worktree(&worktree.path)? {
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+/// Groups a project's sessions by the registered worktree containing their
+/// cwd. Sessions outside every worktree (the main checkout) are left out.
+pub fn project_worktrees(
+    worktrees: &[WorktreeRecord],
+    sessions: &[SessionProjection],
+    session_cwds: &HashMap<String, String>,
+) -> Vec<ProjectWorktreeState> {
+    let mut grouped: BTreeMap<&str, (&WorktreeRecord, Vec<SessionProjection>)> = BTreeMap::new();
+    for session in sessions {
+        let Some(cwd) = session_cwds.get(&session.session_id) else {
+            continue;
+        };
+        let Some(worktree) = worktree_containing(worktrees, cwd) else {
+            continue;
+        };
+        grouped
+            .entry(worktree.path.as_str())
+            .or_insert_with(|| (worktree, Vec::new()))
+            .1
+            .push(session.clone());
+    }
+
+    grouped
+        .into_values()
+        .filter_map(|(worktree, sessions)| {
+            let reduced = reduce_project_sessions(&sessions)?;
+            let mut session_ids: Vec<String> = sessions
+                .iter()
+                .map(|session| session.session_id.clone())
+                .collect();
+            session_ids.sort();
+            Some(ProjectWorktreeState {
+                path: worktree.path.clone(),
+                branch: worktree.branch.clone(),
+                state: reduced.state.into(),
+                session_id: reduced.representative_session_id,
+                session_ids,
+                active_count: reduced.active_count,
+            })
+        })
+        .collect()
+}
+
+/// The innermost registered worktree at or above `cwd`.
+fn worktree_containing<'a>(
+    worktrees: &'a [WorktreeRecord],
+    cwd: &str,
+) -> Option<&'a WorktreeRecord> {
+    let cwd = normalize_path(cwd);
+    worktrees
+        .iter()
+        .filter(|worktree| cwd == worktree.path || cwd.starts_with(&format!("{}/", worktree.path)))
+        .max_by_key(|worktree| worktree.path.len())
+}
+
+fn worktree_from_event(db: &Db, event: &EventEnvelope) -> Result<Option<WorktreeRecord>, String> {
+    let cwd = event.cwd.as_deref().unwrap_or_default();
+    let name = metadata_str(event, "name");
+    let path = match (worktree_path(event), name) {
+        (Some(path), _) => path,
+        (None, Some(name)) => {
+            let Some(root) = repo_root(cwd) else {
+                return Ok(None);
+            };
+            normalize_path(&format!("{}/{}/{}", root, DEFAULT_WORKTREES_DIR, name))
+        }
+        (None, None) => return Ok(None),
+    };
+
+    let resolved = resolve_project_identity(&path).or_else(|| resolve_project_identity(cwd));
+    let identity = match resolved {
+        Some(identity) => Some((identity.project_id, identity.project_path)),
+        None => match event.session_id.as_deref() {
+            Some(session_id) => db
+                .get_session(session_id)?
+                .filter(|session| !session.project_path.is_empty())
+                .map(|session| (session.project_id, session.project_path)),
+            None => None,
+        },
+    };
+    let Some((project_id, project_path)) = identity else {
+        return Ok(None);
+    };
+
+    let name = name.map(str::to_string).or_else(|| {
+        Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    });
+    let branch = metadata_str(event, "branch")
+        .map(str::to_string)
+        .or_else(|| worktree_branch(&path));
+    Ok(Some(WorktreeRecord {
+        path,
+        project_id,
+        project_path,
+        name,
+        branch,
+        created_by_session_id: event.session_id.clone(),
+        created_at: event.recorded_at.clone(),
+    }))
+}
+
+fn worktree_path(event: &EventEnvelope) -> Option<String> {
+    metadata_str(event, "worktree_path")
+        .or_else(|| metadata_str(event, "path"))
+        .map(normalize_path)
+}
+
+fn normalize_path(path: &str) -> String {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn metadata_str<'a>(event: &'a EventEnvelope, key: &str) -> Option<&'a str> {
+    event
+        .metadata
+        .as_ref()
+        .and_then(|value| value.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducer::SessionState;
+    use crate::test_support;
+
+    fn event(event_type: EventType, metadata: serde_json::Value) -> EventEnvelope {
+        EventEnvelope {
+            cwd: None,
+            metadata: Some(metadata),
+            ..test_support::event(
+                &format!("evt-{:?}", event_type),
+                event_type,
+                "2026-01-31T00:00:00Z",
+            )
+        }
+    }
+
+    #[test]
+    fn registers_and_removes_worktrees_with_branch() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let repo = temp_dir.path().join("repo");
+        let repo_git = repo.join(".git");
+        let worktree = repo.join(".claude").join("worktrees").join("feature-a");
+        let worktree_gitdir = repo_git.join("worktrees").join("feature-a");
+        std::fs::create_dir_all(&worktree).expect("worktree dir");
+        std::fs::create_dir_all(&worktree_gitdir).expect("worktree gitdir");
+        std::fs::write(repo.join("Cargo.toml"), "").expect("repo marker");
+        std::fs::write(worktree.join("Cargo.toml"), "").expect("worktree marker");
+        std::fs::write(worktree_gitdir.join("commondir"), "../..").expect("commondir");
+        std::fs::write(worktree_gitdir.join("HEAD"), "ref: refs/heads/feature-a\n").expect("head");
+        std::fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", worktree_gitdir.to_string_lossy()),
+        )
+        .expect("git file");
+
+        let mut create = event(
+            EventType::WorktreeCreate,
+            serde_json::json!({ "name": "feature-a" }),
+        );
+        create.cwd = Some(repo.to_string_lossy().to_string());
+        assert!(apply_worktree_event(&db, &create).expect("create"));
+
+        let registered = worktrees(&db, &WorktreesQuery::default()).expect("list");
+        assert_eq!(registered.len(), 1);
+        let canonical_worktree = std::fs::canonicalize(&worktree).expect("canonical");
+        assert_eq!(Path::new(&registered[0].path), canonical_worktree.as_path());
+        assert_eq!(registered[0].branch.as_deref(), Some("feature-a"));
+        assert_eq!(registered[0].name.as_deref(), Some("feature-a"));
+        assert_eq!(
+            registered[0].created_by_session_id.as_deref(),
+            Some("session-1")
+        );
+        let identity =
+            resolve_project_identity(repo.to_string_lossy().as_ref()).expect("repo identity");
+        assert_eq!(registered[0].project_id, identity.project_id);
+        assert_eq!(registered[0].project_path, identity.project_path);
+
+        let remove = event(
+            EventType::WorktreeRemove,
+            serde_json::json!({ "worktree_path": format!("{}/", registered[0].path) }),
+        );
+        assert!(apply_worktree_event(&db, &remove).expect("remove"));
+        assert!(db.list_worktrees(None).expect("list").is_empty());
+    }
+
+    #[test]
+    fn repeated_or_pathless_remove_changes_nothing() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        db.upsert_worktree(&WorktreeRecord {
+            path: "/repo/.claude/worktrees/a".to_string(),
+            project_id: "/repo/.git".to_string(),
+            project_path: "/repo".to_string(),
+            name: Some("a".to_string()),
+            branch: None,
+            created_by_session_id: None,
+            created_at: "2026-01-31T00:00:00Z".to_string(),
+        })
+        .expect("upsert");
+
+        let pathless = event(EventType::WorktreeRemove, serde_json::json!({}));
+        assert!(!apply_worktree_event(&db, &pathless).expect("pathless remove"));
+        assert_eq!(db.list_worktrees(None).expect("list").len(), 1);
+
+        let remove = event(
+            EventType::WorktreeRemove,
+            serde_json::json!({ "worktree_path": "/repo/.claude/worktrees/a" }),
+        );
+        assert!(apply_worktree_event(&db, &remove).expect("remove"));
+        assert!(!apply_worktree_event(&db, &remove).expect("repeated remove"));
+        assert!(db.list_worktrees(None).expect("list").is_empty());
+    }
+
+    #[test]
+    fn groups_sessions_by_innermost_worktree_and_prunes_missing_ones() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let db = Db::new(temp_dir.path().join("state.db")).expect("db");
+        let worktree = |path: &str, created_at: &str| WorktreeRecord {
+            path: path.to_string(),
+            project_id: "/repo/.git".to_string(),
+            project_path: "/repo".to_string(),
+            name: None,
+            branch: Some("feature".to_string()),
+            created_by_session_id: None,
+            created_at: created_at.to_string(),
+        };
+        let registered = vec![
+            worktree("/repo/.claude/worktrees/a", "2026-01-31T00:00:00Z"),
+            worktree("/repo/.claude/worktrees/b", "2026-01-31T00:00:00Z"),
+        ];
+        let now = Utc::now();
+        let session = |session_id: &str, state: SessionState| SessionProjection {
+            session_id: session_id.to_string(),
+            project_id: "/repo/.git".to_string(),
+            state,
+            session_time: now,
+            updated_at: now.to_rfc3339(),
+            state_changed_at: now.to_rfc3339(),
+        };
+        let sessions = vec![
+            session("main", SessionState::Working),
+            session("a-1", SessionState::Ready),
+            session("a-2", SessionState::Waiting),
+            session("b-1", SessionState::Working),
+        ];
+        let cwds = HashMap::from([
+            ("main".to_string(), "/repo".to_string()),
+            (
+                "a-1".to_string(),
+                "/repo/.claude/worktrees/a/src".to_string(),
+            ),
+            ("a-2".to_string(), "/repo/.claude/worktrees/a".to_string()),
+            ("b-1".to_string(), "/repo/.claude/worktrees/b/".to_string()),
+        ]);
+
+        let grouped = project_worktrees(&registered, &sessions, &cwds);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].path, "/repo/.claude/worktrees/a");
+        assert_eq!(grouped[0].session_ids, vec!["a-1", "a-2"]);
+        assert_eq!(grouped[0].session_id.as_deref(), Some("a-2"));
+        assert_eq!(
+            grouped[0].state,
+            capacitor_daemon_protocol::SessionState::Waiting
+        );
+        assert_eq!(grouped[0].active_count, 1);
+        assert_eq!(grouped[1].session_ids, vec!["b-1"]);
+
+        for record in &registered {
+            db.upsert_worktree(record).expect("upsert");
+        }
+        db.upsert_worktree(&worktree("/repo/.claude/worktrees/new", &now.to_rfc3339()))
+            .expect("upsert recent");
+        assert_eq!(prune_missing_worktrees(&db, now).expect("prune"), 2);
+        let remaining = db.list_worktrees(Some("/repo")).expect("list");
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].path, "/repo/.claude/worktrees/new");
+    }
+}
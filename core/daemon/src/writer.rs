@@ -0,0 +1,369 @@
+//! Writer thread for event ingestion.
+//!
+//! Connection handlers hand events to one writer thread over a bounded queue
+//! instead of each taking the mutation lock and writing on its own. The writer
+//! drains whatever is queued, up to `MAX_BATCH_EVENTS`, and stores and applies
+//! it in one transaction through `SharedState::update_from_event_jobs`, so a
+//! burst of `pre_tool_use`/`post_tool_use` heartbeats costs one commit instead
+//! of one per event. Each event gets its own savepoint, so one that cannot be
+//! stored is rejected without taking the rest of the batch with it, and one
+//! whose derived state fails to apply is still stored.
+//! When the queue stays full for `ENQUEUE_TIMEOUT`, submitters get
+//! `WriteError::Busy`, which the socket reports as `writer_busy`.
+//!
+//! Maintenance (dead-session reconciliation, retention, worktree pruning,
+//! project view refreshes) writes from its own threads; the mutation lock
+//! keeps those writes from interleaving with a batch.
+
+use capacitor_daemon_protocol::{EventEnvelope, EventType, WriterHealth};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::state::{EventOutcome, SharedState};
+
+/// Submissions (single events or batches) waiting for the writer.
+pub const WRITER_QUEUE_CAPACITY: usize = 256;
+/// Events committed per transaction at most.
+const MAX_BATCH_EVENTS: usize = 512;
+/// How long a heartbeat-only batch waits for more heartbeats before writing.
+const HEARTBEAT_LINGER: Duration = Duration::from_millis(2);
+/// How long a submitter waits for queue space before giving up.
+const ENQUEUE_TIMEOUT: Duration = Duration::from_secs(2);
+const ENQUEUE_RETRY: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    /// The queue stayed full; the caller should retry or spool.
+    Busy,
+    /// The writer thread is gone.
+    Stopped,
+    /// The batch's transaction rolled back; nothing from it was stored.
+    Failed(String),
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Busy => write!(f, "event writer queue is full"),
+            WriteError::Stopped => write!(f, "event writer has stopped"),
+            WriteError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+struct WriteJob {
+    events: Vec<EventEnvelope>,
+    reply: SyncSender<Result<Vec<EventOutcome>, String>>,
+}
+
+#[derive(Default)]
+struct WriterStats {
+    queued: AtomicUsize,
+    batches: AtomicU64,
+    events_written: AtomicU64,
+    largest_batch: AtomicUsize,
+    busy_rejections: AtomicU64,
+    failed_batches: AtomicU64,
+    rejected_events: AtomicU64,
+    skipped_events: AtomicU64,
+    last_batch_micros: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct EventWriter {
+    sender: SyncSender<WriteJob>,
+    stats: Arc<WriterStats>,
+    enqueue_timeout: Duration,
+}
+
+impl EventWriter {
+    pub fn spawn(state: Arc<SharedState>) -> Result<Self, String> {
+        let (sender, receiver) = mpsc::sync_channel(WRITER_QUEUE_CAPACITY);
+        let stats = Arc::new(WriterStats::default());
+        let writer_stats = Arc::clone(&stats);
+        thread::Builder::new()
+            .name("event-writer".to_string())
+            .spawn(move || run_writer(&state, &receiver, &writer_stats))
+            .map_err(|err| format!("Failed to start event writer thread: {}", err))?;
+        Ok(Self {
+            sender,
+            stats,
+            enqueue_timeout: ENQUEUE_TIMEOUT,
+        })
+    }
+
+    /// Queues `events` and waits until they are committed. Returns each
+    /// event's outcome, like `SharedState::update_from_events`.
+    pub fn submit(&self, events: Vec<EventEnvelope>) -> Result<Vec<EventOutcome>, WriteError> {
+        if events.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (reply, response) = mpsc::sync_channel(1);
+        let mut job = WriteJob { events, reply };
+        let deadline = Instant::now() + self.enqueue_timeout;
+        self.stats.queued.fetch_add(1, Ordering::SeqCst);
+        loop {
+            match self.sender.try_send(job) {
+                Ok(()) => break,
+                Err(TrySendError::Full(returned)) if Instant::now() < deadline => {
+                    job = returned;
+                    thread::sleep(ENQUEUE_RETRY);
+                }
+                Err(TrySendError::Full(_)) => {
+                    self.stats.queued.fetch_sub(1, Ordering::SeqCst);
+                    self.stats.busy_rejections.fetch_add(1, Ordering::SeqCst);
+                    return Err(WriteError::Busy);
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    self.stats.queued.fetch_sub(1, Ordering::SeqCst);
+                    return Err(WriteError::Stopped);
+                }
+            }
+        }
+
+        match response.recv() {
+            Ok(result) => result.map_err(WriteError::Failed),
+            Err(_) => Err(WriteError::Stopped),
+        }
+    }
+
+    pub fn snapshot(&self) -> WriterHealth {
+        let stats = &self.stats;
+        WriterHealth {
+            queue_capacity: WRITER_QUEUE_CAPACITY,
+            queued: stats.queued.load(Ordering::SeqCst),
+            max_batch_events: MAX_BATCH_EVENTS,
+            batches: stats.batches.load(Ordering::SeqCst),
+            events_written: stats.events_written.load(Ordering::SeqCst),
+            largest_batch: stats.largest_batch.load(Ordering::SeqCst),
+            busy_rejections: stats.busy_rejections.load(Ordering::SeqCst),
+            failed_batches: stats.failed_batches.load(Ordering::SeqCst),
+            rejected_events: stats.rejected_events.load(Ordering::SeqCst),
+            skipped_events: stats.skipped_events.load(Ordering::SeqCst),
+            last_batch_micros: stats.last_batch_micros.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn run_writer(state: &SharedState, receiver: &Receiver<WriteJob>, stats: &WriterStats) {
+    while let Ok(first) = receiver.recv() {
+        let jobs = collect_batch(first, receiver);
+        stats.queued.fetch_sub(jobs.len(), Ordering::SeqCst);
+
+        let submissions: Vec<&[EventEnvelope]> =
+            jobs.iter().map(|job| job.events.as_slice()).collect();
+        let event_count: usize = submissions.iter().map(|events| events.len()).sum();
+        let started = Instant::now();
+        let result = state.update_from_event_jobs(&submissions);
+        stats
+            .last_batch_micros
+            .store(started.elapsed().as_micros() as u64, Ordering::SeqCst);
+        stats.batches.fetch_add(1, Ordering::SeqCst);
+        stats.largest_batch.fetch_max(event_count, Ordering::SeqCst);
+
+        match result {
+            Ok(results) => {
+                for (job, outcomes) in jobs.into_iter().zip(results) {
+                    for outcome in &outcomes {
+                        let counter = match outcome {
+                            EventOutcome::Applied => &stats.events_written,
+                            EventOutcome::Skipped(_) => {
+                                stats.events_written.fetch_add(1, Ordering::SeqCst);
+                                &stats.skipped_events
+                            }
+                            EventOutcome::Rejected(_) => &stats.rejected_events,
+                            EventOutcome::Duplicate => continue,
+                        };
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    }
+                    let _ = job.reply.send(Ok(outcomes));
+                }
+            }
+            Err(err) => {
+                stats.failed_batches.fetch_add(1, Ordering::SeqCst);
+                tracing::warn!(error = %err, events = event_count, "Event writer batch failed");
+                for job in jobs {
+                    let _ = job.reply.send(Err(err.clone()));
+                }
+            }
+        }
+    }
+}
+
+/// Takes everything already queued behind `first`, up to `MAX_BATCH_EVENTS`.
+/// A batch of nothing but tool heartbeats waits briefly for the rest of the
+/// burst.
+fn collect_batch(first: WriteJob, receiver: &Receiver<WriteJob>) -> Vec<WriteJob> {
+    let mut event_count = first.events.len();
+    let mut jobs = vec![first];
+    let mut lingered = false;
+    while event_count < MAX_BATCH_EVENTS {
+        let next = match receiver.try_recv() {
+            Ok(job) => Some(job),
+            Err(_) if !lingered && jobs.iter().all(is_heartbeat_job) => {
+                lingered = true;
+                receiver.recv_timeout(HEARTBEAT_LINGER).ok()
+            }
+            Err(_) => None,
+        };
+        match next {
+            Some(job) => {
+                event_count += job.events.len();
+                jobs.push(job);
+            }
+            None => break,
+        }
+    }
+    jobs
+}
+
+fn is_heartbeat_job(job: &WriteJob) -> bool {
+    job.events.iter().all(|event| {
+        matches!(
+            event.event_type,
+            EventType::PreToolUse | EventType::PostToolUse
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use crate::test_support::event;
+    use chrono::{Duration as ChronoDuration, Utc};
+
+    fn at(offset_ms: i64) -> String {
+        (Utc::now() + ChronoDuration::milliseconds(offset_ms)).to_rfc3339()
+    }
+
+    #[test]
+    fn concurrent_heartbeats_are_committed_in_shared_batches() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let state = Arc::new(SharedState::new(Db::new(path.clone()).expect("db")));
+        let writer = EventWriter::spawn(state).expect("writer");
+
+        let inserted = writer
+            .submit(vec![event("evt-start", EventType::SessionStart, &at(0))])
+            .expect("start");
+        assert_eq!(inserted, vec![EventOutcome::Applied]);
+
+        let handles: Vec<_> = (0..40)
+            .map(|index| {
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    let event_type = if index % 2 == 0 {
+                        EventType::PreToolUse
+                    } else {
+                        EventType::PostToolUse
+                    };
+                    writer
+                        .submit(vec![event(
+                            &format!("evt-{}", index),
+                            event_type,
+                            &at(1 + index),
+                        )])
+                        .expect("heartbeat")
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().expect("join"), vec![EventOutcome::Applied]);
+        }
+        let duplicate = writer
+            .submit(vec![event("evt-start", EventType::SessionStart, &at(0))])
+            .expect("duplicate");
+        assert_eq!(duplicate, vec![EventOutcome::Duplicate]);
+
+        let snapshot = writer.snapshot();
+        assert_eq!(snapshot.events_written, 41);
+        assert!(snapshot.batches < 42, "batches: {}", snapshot.batches);
+        assert_eq!(snapshot.queued, 0);
+        let db = Db::new(path).expect("db");
+        assert_eq!(
+            db.last_applied_event_rowid().expect("cursor"),
+            db.max_event_rowid().expect("max rowid")
+        );
+    }
+
+    #[test]
+    fn failing_event_does_not_take_the_batch_with_it() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("state.db");
+        let state = Arc::new(SharedState::new(Db::new(path.clone()).expect("db")));
+        rusqlite::Connection::open(&path)
+            .expect("open raw sqlite")
+            .execute_batch(
+                "CREATE TRIGGER reject_bad_session BEFORE INSERT ON sessions
+                 WHEN NEW.session_id = 'session-bad'
+                 BEGIN SELECT RAISE(ABORT, 'rejected session'); END;",
+            )
+            .expect("create trigger");
+        let writer = EventWriter::spawn(state).expect("writer");
+
+        let bad = EventEnvelope {
+            session_id: Some("session-bad".to_string()),
+            pid: Some(5678),
+            ..event("evt-bad", EventType::SessionStart, &at(1))
+        };
+        let outcomes = writer
+            .submit(vec![
+                event("evt-start", EventType::SessionStart, &at(0)),
+                bad,
+                event("evt-tool", EventType::PreToolUse, &at(2)),
+            ])
+            .expect("batch commits");
+        assert_eq!(outcomes[0], EventOutcome::Applied);
+        assert!(matches!(&outcomes[1], EventOutcome::Skipped(err) if err.contains("rejected")));
+        assert_eq!(outcomes[2], EventOutcome::Applied);
+
+        let snapshot = writer.snapshot();
+        assert_eq!(snapshot.events_written, 3);
+        assert_eq!(snapshot.skipped_events, 1);
+        assert_eq!(snapshot.failed_batches, 0);
+        let db = Db::new(path).expect("db");
+        assert_eq!(db.list_events(None).expect("events").len(), 3);
+        let session = db
+            .get_session("session-1")
+            .expect("query session")
+            .expect("session exists");
+        assert_eq!(session.tools_in_flight, 1);
+        assert!(db.get_session("session-bad").expect("query").is_none());
+        assert_eq!(
+            db.last_applied_event_rowid().expect("cursor"),
+            db.max_event_rowid().expect("max rowid")
+        );
+    }
+
+    #[test]
+    fn full_queue_rejects_with_busy() {
+        // No writer thread drains this queue.
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let writer = EventWriter {
+            sender,
+            stats: Arc::new(WriterStats::default()),
+            enqueue_timeout: Duration::from_millis(20),
+        };
+
+        let (reply, _response) = mpsc::sync_channel(1);
+        writer
+            .sender
+            .try_send(WriteJob {
+                events: vec![event("evt-0", EventType::PreToolUse, &at(0))],
+                reply,
+            })
+            .expect("fill queue");
+
+        let error = writer
+            .submit(vec![event("evt-1", EventType::PreToolUse, &at(1))])
+            .expect_err("queue full");
+        assert_eq!(error, WriteError::Busy);
+        assert_eq!(writer.snapshot().busy_rejections, 1);
+        assert_eq!(writer.snapshot().queued, 0);
+    }
+}
@@ -1,9 +1,11 @@
 use capacitor_daemon_protocol::{
-    EventEnvelope, EventType, Method, Request, Response, PROTOCOL_VERSION,
+    ChangeKind, EventBatchAck, EventEnvelope, EventStatus, EventType, EventsPage, HelloResponse,
+    Method, Request, Response, SubscriptionMessage, SubscriptionTopic, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION,
 };
 use chrono::{Duration as ChronoDuration, Utc};
 use std::fs;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
@@ -793,6 +795,9 @@ fn daemon_ipc_health_and_liveness_smoke() {
         },
     );
     assert!(sessions.ok, "sessions response was not ok");
+    let sessions_revision = sessions
+        .revision
+        .expect("sessions response carries revision");
     let sessions_value = sessions.data.expect("sessions payload");
     let sessions_array = sessions_value
         .as_array()
@@ -953,6 +958,27 @@ fn daemon_ipc_health_and_liveness_smoke() {
         .expect("sessions payload is array");
     assert!(sessions_after_array.is_empty());
 
+    let sessions_delta = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetSessions,
+            id: Some("sessions-delta".to_string()),
+            params: Some(serde_json::json!({ "since_revision": sessions_revision })),
+        },
+    );
+    assert!(sessions_delta.ok, "sessions delta response was not ok");
+    assert!(sessions_delta
+        .revision
+        .is_some_and(|revision| revision > sessions_revision));
+    let sessions_delta_value = sessions_delta.data.expect("sessions delta payload");
+    assert_eq!(sessions_delta_value["full"], false);
+    assert_eq!(sessions_delta_value["changed"], serde_json::json!([]));
+    assert_eq!(
+        sessions_delta_value["removed"],
+        serde_json::json!([session_id])
+    );
+
     let projects_after = send_request(
         &socket,
         Request {
@@ -1008,3 +1034,491 @@ fn daemon_ipc_health_and_liveness_smoke() {
     );
     drop(guard.take());
 }
+
+#[test]
+fn daemon_event_batch_reports_per_event_results() {
+    let home = tempfile::Builder::new()
+        .prefix("capacitor-daemon-batch")
+        .tempdir_in("/tmp")
+        .expect("Failed to create temp HOME");
+    let socket = socket_path(home.path());
+    if !can_bind_socket(home.path()) {
+        eprintln!(
+            "Skipping event batch smoke test: unix socket binding not permitted in this environment."
+        );
+        return;
+    }
+    let child = spawn_daemon(home.path());
+    let mut guard = Some(DaemonGuard { child });
+    wait_for_socket(&socket, Duration::from_secs(5));
+
+    let repo_root = home.path().join("repo");
+    fs::create_dir_all(&repo_root).expect("create repo dir");
+    let now = Utc::now();
+    let event = |event_id: &str, event_type: EventType, offset_secs: i64| EventEnvelope {
+        event_id: event_id.to_string(),
+        recorded_at: (now + ChronoDuration::seconds(offset_secs)).to_rfc3339(),
+        event_type,
+        session_id: Some("session-batch".to_string()),
+        pid: Some(std::process::id()),
+        cwd: Some(repo_root.to_string_lossy().to_string()),
+        tool: None,
+        file_path: None,
+        parent_app: None,
+        tty: None,
+        tmux_session: None,
+        tmux_client_tty: None,
+        notification_type: None,
+        stop_hook_active: None,
+        metadata: None,
+    };
+    let start = event("evt-batch-1", EventType::SessionStart, 0);
+    let prompt = event("evt-batch-2", EventType::UserPromptSubmit, 1);
+    let mut invalid = serde_json::to_value(event("evt-batch-3", EventType::Stop, 2)).unwrap();
+    invalid["stop_hook_active"] = serde_json::Value::Null;
+
+    let response = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::EventBatch,
+            id: Some("batch".to_string()),
+            params: Some(serde_json::json!({
+                "events": [start, prompt, invalid, start]
+            })),
+        },
+    );
+    assert!(response.ok, "event batch failed: {:?}", response.error);
+    let ack: EventBatchAck =
+        serde_json::from_value(response.data.expect("batch data")).expect("parse batch ack");
+    assert_eq!((ack.accepted, ack.duplicates, ack.rejected), (2, 1, 1));
+    let statuses: Vec<EventStatus> = ack.results.iter().map(|result| result.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            EventStatus::Accepted,
+            EventStatus::Accepted,
+            EventStatus::Rejected,
+            EventStatus::Duplicate
+        ]
+    );
+    assert_eq!(ack.results[2].event_id.as_deref(), Some("evt-batch-3"));
+    assert_eq!(
+        ack.results[2]
+            .error
+            .as_ref()
+            .map(|error| error.code.as_str()),
+        Some("missing_field")
+    );
+
+    let sessions = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetSessions,
+            id: None,
+            params: None,
+        },
+    );
+    let sessions = sessions.data.expect("sessions data");
+    assert_eq!(sessions[0]["session_id"], "session-batch");
+    assert_eq!(sessions[0]["state"], "working");
+
+    let events = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetEvents,
+            id: None,
+            params: Some(serde_json::json!({
+                "session_id": "session-batch",
+                "project_path": repo_root.to_string_lossy(),
+                "limit": 1
+            })),
+        },
+    );
+    assert!(events.ok, "get_events failed: {:?}", events.error);
+    let page: EventsPage =
+        serde_json::from_value(events.data.expect("events data")).expect("parse events page");
+    assert_eq!(page.events[0].event_id, "evt-batch-1");
+    let events = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetEvents,
+            id: None,
+            params: Some(serde_json::json!({
+                "event_types": ["user_prompt_submit"],
+                "cursor": page.next_cursor.expect("next cursor")
+            })),
+        },
+    );
+    let page: EventsPage =
+        serde_json::from_value(events.data.expect("events data")).expect("parse events page");
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].event_id, "evt-batch-2");
+    assert_eq!(page.next_cursor, None);
+
+    drop(guard.take());
+}
+
+#[test]
+fn daemon_event_batch_isolates_events_that_fail_to_store_or_apply() {
+    let home = tempfile::Builder::new()
+        .prefix("capacitor-daemon-batch-fail")
+        .tempdir_in("/tmp")
+        .expect("Failed to create temp HOME");
+    let socket = socket_path(home.path());
+    if !can_bind_socket(home.path()) {
+        eprintln!(
+            "Skipping event batch failure smoke test: unix socket binding not permitted in this environment."
+        );
+        return;
+    }
+    let child = spawn_daemon(home.path());
+    let mut guard = Some(DaemonGuard { child });
+    wait_for_socket(&socket, Duration::from_secs(5));
+    // The socket is bound before the database is opened; a served request
+    // means the schema exists.
+    let health = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetHealth,
+            id: None,
+            params: None,
+        },
+    );
+    assert!(health.ok, "get_health failed: {:?}", health.error);
+
+    rusqlite::Connection::open(home.path().join(".capacitor/daemon/state.db"))
+        .expect("open daemon db")
+        .execute_batch(
+            "CREATE TRIGGER reject_bad_event BEFORE INSERT ON events
+             WHEN NEW.id = 'evt-unstorable'
+             BEGIN SELECT RAISE(ABORT, 'rejected event'); END;
+             CREATE TRIGGER reject_bad_session BEFORE INSERT ON sessions
+             WHEN NEW.session_id = 'session-bad'
+             BEGIN SELECT RAISE(ABORT, 'rejected session'); END;",
+        )
+        .expect("create triggers");
+
+    let repo_root = home.path().join("repo");
+    fs::create_dir_all(&repo_root).expect("create repo dir");
+    let now = Utc::now();
+    let event = |event_id: &str, session_id: &str, event_type: EventType| EventEnvelope {
+        event_id: event_id.to_string(),
+        recorded_at: now.to_rfc3339(),
+        event_type,
+        session_id: Some(session_id.to_string()),
+        pid: Some(std::process::id()),
+        cwd: Some(repo_root.to_string_lossy().to_string()),
+        tool: None,
+        file_path: None,
+        parent_app: None,
+        tty: None,
+        tmux_session: None,
+        tmux_client_tty: None,
+        notification_type: None,
+        stop_hook_active: None,
+        metadata: None,
+    };
+
+    let response = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::EventBatch,
+            id: Some("batch".to_string()),
+            params: Some(serde_json::json!({
+                "events": [
+                    event("evt-good", "session-good", EventType::SessionStart),
+                    event("evt-unstorable", "session-good", EventType::UserPromptSubmit),
+                    event("evt-bad", "session-bad", EventType::SessionStart),
+                ]
+            })),
+        },
+    );
+    assert!(response.ok, "event batch failed: {:?}", response.error);
+    let ack: EventBatchAck =
+        serde_json::from_value(response.data.expect("batch data")).expect("parse batch ack");
+    assert_eq!((ack.accepted, ack.duplicates, ack.rejected), (2, 0, 1));
+    let outcomes: Vec<(EventStatus, Option<&str>)> = ack
+        .results
+        .iter()
+        .map(|result| {
+            (
+                result.status,
+                result.error.as_ref().map(|error| error.code.as_str()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            (EventStatus::Accepted, None),
+            (EventStatus::Rejected, Some("event_error")),
+            (EventStatus::Accepted, Some("apply_failed")),
+        ]
+    );
+
+    let events = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetEvents,
+            id: None,
+            params: None,
+        },
+    );
+    let page: EventsPage =
+        serde_json::from_value(events.data.expect("events data")).expect("parse events page");
+    let mut event_ids: Vec<&str> = page
+        .events
+        .iter()
+        .map(|event| event.event_id.as_str())
+        .collect();
+    event_ids.sort();
+    assert_eq!(event_ids, vec!["evt-bad", "evt-good"]);
+
+    drop(guard.take());
+}
+
+#[test]
+fn daemon_drains_event_spool_on_startup() {
+    let home = tempfile::Builder::new()
+        .prefix("capacitor-daemon-spool")
+        .tempdir_in("/tmp")
+        .expect("Failed to create temp HOME");
+    let socket = socket_path(home.path());
+    if !can_bind_socket(home.path()) {
+        eprintln!(
+            "Skipping spool smoke test: unix socket binding not permitted in this environment."
+        );
+        return;
+    }
+
+    let repo_root = home.path().join("repo");
+    fs::create_dir_all(&repo_root).expect("create repo dir");
+    let spool_dir = home.path().join(".capacitor").join("spool");
+    fs::create_dir_all(&spool_dir).expect("create spool dir");
+    let event = serde_json::json!({
+        "event_id": "evt-spooled-1",
+        "recorded_at": Utc::now().to_rfc3339(),
+        "event_type": "user_prompt_submit",
+        "session_id": "session-spooled",
+        "pid": std::process::id(),
+        "cwd": repo_root.to_string_lossy(),
+    });
+    fs::write(spool_dir.join("events.ndjson"), format!("{event}\n")).expect("write spool");
+
+    let child = spawn_daemon(home.path());
+    let mut guard = Some(DaemonGuard { child });
+    wait_for_socket(&socket, Duration::from_secs(5));
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let sessions = loop {
+        let response = send_request(
+            &socket,
+            Request {
+                protocol_version: PROTOCOL_VERSION,
+                method: Method::GetSessions,
+                id: None,
+                params: None,
+            },
+        );
+        let sessions = response.data.expect("sessions data");
+        if sessions.as_array().is_some_and(|list| !list.is_empty()) || Instant::now() > deadline {
+            break sessions;
+        }
+        sleep(Duration::from_millis(25));
+    };
+    assert_eq!(sessions[0]["session_id"], "session-spooled");
+    assert!(!spool_dir.join("events.ndjson").exists());
+
+    drop(guard.take());
+}
+
+#[test]
+fn daemon_negotiates_protocol_version_and_serves_older_clients() {
+    let home = tempfile::Builder::new()
+        .prefix("capacitor-daemon-hello")
+        .tempdir_in("/tmp")
+        .expect("Failed to create temp HOME");
+    let socket = socket_path(home.path());
+    if !can_bind_socket(home.path()) {
+        eprintln!(
+            "Skipping hello smoke test: unix socket binding not permitted in this environment."
+        );
+        return;
+    }
+    let child = spawn_daemon(home.path());
+    let mut guard = Some(DaemonGuard { child });
+    wait_for_socket(&socket, Duration::from_secs(5));
+
+    // A newer client stamps the handshake with a version this daemon has never
+    // heard of; hello is answered anyway.
+    let hello = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION + 1,
+            method: Method::Hello,
+            id: Some("hello".to_string()),
+            params: Some(serde_json::json!({
+                "min_protocol_version": MIN_PROTOCOL_VERSION,
+                "max_protocol_version": PROTOCOL_VERSION + 1,
+                "features": ["subscribe", "from_the_future"],
+                "client": "ipc-smoke"
+            })),
+        },
+    );
+    assert!(hello.ok, "hello failed: {:?}", hello.error);
+    let hello: HelloResponse =
+        serde_json::from_value(hello.data.expect("hello data")).expect("parse hello");
+    assert_eq!(hello.protocol_version, PROTOCOL_VERSION);
+    assert_eq!(hello.min_protocol_version, MIN_PROTOCOL_VERSION);
+    assert!(hello.supports("subscribe"));
+
+    let disjoint = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::Hello,
+            id: None,
+            params: Some(serde_json::json!({
+                "min_protocol_version": PROTOCOL_VERSION + 1,
+                "max_protocol_version": PROTOCOL_VERSION + 2
+            })),
+        },
+    );
+    assert!(!disjoint.ok);
+    assert_eq!(disjoint.error.expect("error").code, "protocol_mismatch");
+
+    // Clients built against the oldest supported version keep working.
+    let legacy = send_request(
+        &socket,
+        Request {
+            protocol_version: MIN_PROTOCOL_VERSION,
+            method: Method::GetHealth,
+            id: None,
+            params: None,
+        },
+    );
+    assert!(legacy.ok, "legacy health failed: {:?}", legacy.error);
+
+    let unsupported = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION + 1,
+            method: Method::GetHealth,
+            id: None,
+            params: None,
+        },
+    );
+    assert!(!unsupported.ok);
+    assert_eq!(unsupported.error.expect("error").code, "protocol_mismatch");
+
+    drop(guard.take());
+}
+
+#[test]
+fn daemon_subscribe_streams_session_changes() {
+    let home = tempfile::Builder::new()
+        .prefix("capacitor-daemon-subscribe")
+        .tempdir_in("/tmp")
+        .expect("Failed to create temp HOME");
+    let socket = socket_path(home.path());
+    if !can_bind_socket(home.path()) {
+        eprintln!(
+            "Skipping subscribe smoke test: unix socket binding not permitted in this environment."
+        );
+        return;
+    }
+    let child = spawn_daemon(home.path());
+    let mut guard = Some(DaemonGuard { child });
+    wait_for_socket(&socket, Duration::from_secs(5));
+
+    let mut subscriber = UnixStream::connect(&socket).expect("Failed to connect subscriber");
+    let request = Request {
+        protocol_version: PROTOCOL_VERSION,
+        method: Method::Subscribe,
+        id: Some("subscribe".to_string()),
+        params: Some(serde_json::json!({ "topics": ["sessions"] })),
+    };
+    serde_json::to_writer(&mut subscriber, &request).expect("Failed to serialize subscribe");
+    subscriber
+        .write_all(b"\n")
+        .expect("Failed to write subscribe request");
+    subscriber
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("set subscriber read timeout");
+    let mut lines = BufReader::new(subscriber.try_clone().expect("clone subscriber"));
+
+    let mut ack_line = String::new();
+    lines.read_line(&mut ack_line).expect("read subscribe ack");
+    let ack: Response = serde_json::from_str(&ack_line).expect("parse subscribe ack");
+    assert!(ack.ok, "subscribe ack was not ok");
+    assert_eq!(ack.id.as_deref(), Some("subscribe"));
+
+    let project_dir = home.path().join("subscribed-project");
+    fs::create_dir_all(&project_dir).expect("create project dir");
+    let event = EventEnvelope {
+        event_id: "evt-subscribe-start".to_string(),
+        recorded_at: Utc::now().to_rfc3339(),
+        event_type: EventType::SessionStart,
+        session_id: Some("session-subscribed".to_string()),
+        pid: Some(std::process::id()),
+        cwd: Some(project_dir.to_string_lossy().to_string()),
+        tool: None,
+        file_path: None,
+        parent_app: None,
+        tty: None,
+        tmux_session: None,
+        tmux_client_tty: None,
+        notification_type: None,
+        stop_hook_active: None,
+        metadata: None,
+    };
+    let response = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::Event,
+            id: Some("subscribe-event".to_string()),
+            params: Some(serde_json::to_value(&event).expect("serialize event")),
+        },
+    );
+    assert!(response.ok, "event response was not ok");
+
+    let mut change_line = String::new();
+    lines.read_line(&mut change_line).expect("read change line");
+    let message: SubscriptionMessage =
+        serde_json::from_str(&change_line).expect("parse subscription message");
+    let SubscriptionMessage::Change(change) = message else {
+        panic!("expected change notification, got {}", change_line);
+    };
+    assert_eq!(change.topic, SubscriptionTopic::Sessions);
+    assert_eq!(change.change, ChangeKind::Upsert);
+    assert_eq!(change.key, "session-subscribed");
+
+    let health = send_request(
+        &socket,
+        Request {
+            protocol_version: PROTOCOL_VERSION,
+            method: Method::GetHealth,
+            id: Some("subscribe-health".to_string()),
+            params: None,
+        },
+    );
+    let subscribers = health
+        .data
+        .as_ref()
+        .and_then(|data| data.get("runtime"))
+        .and_then(|runtime| runtime.get("subscribers"))
+        .and_then(|value| value.as_u64());
+    assert_eq!(subscribers, Some(1));
+
+    drop(lines);
+    drop(subscriber);
+    drop(guard.take());
+}
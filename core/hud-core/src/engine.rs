@@ -30,7 +30,8 @@ use crate::setup::{DependencyStatus, HookStatus, InstallResult, SetupChecker, Se
 use crate::storage::StorageConfig;
 use crate::types::{
     Artifact, DashboardData, GlobalConfig, HookDiagnosticReport, HookIssue, HookTestResult,
-    HudConfig, Plugin, PluginManifest, Project, ProjectSessionState, SuggestedProject,
+    HudConfig, Plugin, PluginManifest, Project, ProjectSessionState, ProjectTimeAccounting,
+    SuggestedProject,
 };
 use crate::validation::{create_claude_md, validate_project_path, ValidationResultFfi};
 use fs_err as fs;
@@ -100,7 +101,7 @@ fn has_active_daemon_session(
 
     snapshot.sessions().iter().any(|record| {
         let is_alive = record.is_alive.unwrap_or(true);
-        let is_active = !matches!(record.state.to_ascii_lowercase().as_str(), "idle");
+        let is_active = !matches!(record.state.as_str(), "idle");
         is_alive && is_active
     })
 }
@@ -259,7 +260,7 @@ impl HudEngine {
             }
         }
 
-        suggestions.sort_by(|a, b| b.1.cmp(&a.1));
+        suggestions.sort_by_key(|b| std::cmp::Reverse(b.1));
         Ok(suggestions.into_iter().take(8).map(|(s, _)| s).collect())
     }
 
@@ -290,6 +291,16 @@ impl HudEngine {
         read_project_status(&project_path)
     }
 
+    /// Time per project and local calendar day spent in each session state,
+    /// plus time spent waiting on the human, for the last `days` days
+    /// including today. Days are cut at the current local UTC offset.
+    ///
+    /// Requires the daemon; errors when it is disabled or unreachable.
+    pub fn get_project_time(&self, days: u32) -> Result<Vec<ProjectTimeAccounting>, HudFfiError> {
+        let utc_offset_minutes = chrono::Local::now().offset().local_minus_utc() / 60;
+        crate::state::daemon::project_time(days, utc_offset_minutes).map_err(HudFfiError::from)
+    }
+
     // ─────────────────────────────────────────────────────────────────────────────
     // Multi-Agent API
     // ─────────────────────────────────────────────────────────────────────────────
@@ -485,7 +496,7 @@ impl HudEngine {
             }
         }
 
-        plugins.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        plugins.sort_by_key(|a| a.name.to_lowercase());
         Ok(plugins)
     }
 
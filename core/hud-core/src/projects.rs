@@ -289,7 +289,7 @@ pub fn load_projects_with_storage(storage: &StorageConfig) -> Result<Vec<Project
 
     let _ = save_stats_cache_with_storage(storage, &stats_cache);
 
-    projects.sort_by(|a, b| b.1.cmp(&a.1));
+    projects.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     Ok(projects.into_iter().map(|(p, _)| p).collect())
 }
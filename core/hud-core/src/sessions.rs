@@ -72,7 +72,7 @@ pub fn get_all_session_states_with_storage(
 }
 
 fn project_state_from_daemon(record: &DaemonSessionRecord) -> ProjectSessionState {
-    let state = map_daemon_state(&record.state);
+    let state = map_daemon_state(record.state.as_str());
     let is_alive = record.is_alive.unwrap_or(true);
     let has_session = is_alive && state != SessionState::Idle;
     let is_working = state == SessionState::Working;
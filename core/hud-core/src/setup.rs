@@ -29,7 +29,7 @@ const HOOK_COMMAND: &str = "CAPACITOR_HOOK_MARKER=1 $HOME/.local/bin/hud-hook ha
 ///   need `matcher: "*"` to match all tools.
 /// - `is_async`: If true, hook runs in background without blocking Claude Code
 ///   SessionEnd is sync to ensure cleanup completes before session exits
-const HUD_HOOK_EVENTS: [(&str, bool, bool); 14] = [
+const HUD_HOOK_EVENTS: [(&str, bool, bool); 15] = [
     ("SessionStart", false, true),
     ("SessionEnd", false, false), // Keep sync for guaranteed cleanup
     ("UserPromptSubmit", false, true),
@@ -44,6 +44,7 @@ const HUD_HOOK_EVENTS: [(&str, bool, bool); 14] = [
     ("SubagentStop", false, true),
     ("TeammateIdle", false, true),
     ("TaskCompleted", false, true),
+    ("ConfigChange", false, true),
 ];
 
 const HOOK_TIMEOUT_SECONDS: u32 = 30;
@@ -862,6 +863,7 @@ mod tests {
         assert!(settings["hooks"]["PostToolUseFailure"].is_array());
         assert!(settings["hooks"]["TaskCompleted"].is_array());
         assert!(settings["hooks"]["SubagentStop"].is_array());
+        assert!(settings["hooks"]["ConfigChange"].is_array());
 
         let post_tool_use = &settings["hooks"]["PostToolUse"][0];
         assert_eq!(post_tool_use["matcher"], "*");
@@ -2,42 +2,18 @@
 //!
 //! The daemon is authoritative; callers should not fall back to local checks.
 
-use capacitor_daemon_protocol::{Method, Request, Response, MAX_REQUEST_BYTES, PROTOCOL_VERSION};
+use capacitor_daemon_client::{ClientConfig, DaemonClient, ProjectDayTime, ProjectTimeQuery};
+use capacitor_daemon_protocol::{Method, SessionState};
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use std::env;
-use std::io::{Read, Write};
-use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
 use std::time::Duration;
 
-const ENABLE_ENV: &str = "CAPACITOR_DAEMON_ENABLED";
-const SOCKET_ENV: &str = "CAPACITOR_DAEMON_SOCKET";
-const SOCKET_NAME: &str = "daemon.sock";
-const READ_TIMEOUT_MS: u64 = 150;
-const WRITE_TIMEOUT_MS: u64 = 150;
-
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct DaemonSessionRecord {
-    pub session_id: String,
-    pub pid: u32,
-    pub state: String,
-    pub cwd: String,
-    pub project_path: String,
-    pub updated_at: String,
-    pub state_changed_at: String,
-    #[serde(default)]
-    pub last_event: Option<String>,
-    #[serde(default)]
-    pub last_activity_at: Option<String>,
-    #[serde(default)]
-    pub tools_in_flight: u32,
-    #[serde(default)]
-    pub ready_reason: Option<String>,
-    #[serde(default)]
-    pub is_alive: Option<bool>,
-}
+use crate::types::ProjectTimeAccounting;
+
+pub use capacitor_daemon_client::EnrichedSession as DaemonSessionRecord;
+
+const TIMEOUT_MS: u64 = 150;
+/// Reports scan history, so they get more time than snapshot reads.
+const REPORT_TIMEOUT_MS: u64 = 2_000;
 
 pub struct DaemonSessionsSnapshot {
     sessions: Vec<DaemonSessionRecord>,
@@ -82,21 +58,40 @@ pub fn sessions_snapshot() -> Option<DaemonSessionsSnapshot> {
         return None;
     }
 
-    let request = Request {
-        protocol_version: PROTOCOL_VERSION,
-        method: Method::GetSessions,
-        id: Some("sessions-snapshot".to_string()),
-        params: None,
-    };
+    let sessions = client()?.sessions().ok()?;
+    Some(DaemonSessionsSnapshot { sessions })
+}
 
-    let response = send_request(request).ok()?;
-    if !response.ok {
-        return None;
+pub(crate) fn project_time(
+    days: u32,
+    utc_offset_minutes: i32,
+) -> Result<Vec<ProjectTimeAccounting>, String> {
+    if !daemon_enabled() {
+        return Err("Daemon is disabled".to_string());
     }
+    let client = client_with_timeout(REPORT_TIMEOUT_MS).ok_or("Daemon is not configured")?;
+    let rows = client
+        .project_time(&ProjectTimeQuery {
+            project_path: None,
+            days: Some(days),
+            utc_offset_minutes: Some(utc_offset_minutes),
+        })
+        .map_err(|err| err.to_string())?;
+    Ok(rows.into_iter().map(to_time_accounting).collect())
+}
 
-    let data = response.data?;
-    let sessions: Vec<DaemonSessionRecord> = serde_json::from_value(data).ok()?;
-    Some(DaemonSessionsSnapshot { sessions })
+fn to_time_accounting(row: ProjectDayTime) -> ProjectTimeAccounting {
+    let seconds = |state| row.seconds_in_state.get(&state).copied().unwrap_or(0);
+    ProjectTimeAccounting {
+        working_secs: seconds(SessionState::Working),
+        waiting_secs: seconds(SessionState::Waiting),
+        ready_secs: seconds(SessionState::Ready),
+        idle_secs: seconds(SessionState::Idle),
+        compacting_secs: seconds(SessionState::Compacting),
+        waiting_on_human_secs: row.waiting_on_human_secs,
+        project_path: row.project_path,
+        day: row.day,
+    }
 }
 
 pub(crate) fn daemon_health() -> Option<bool> {
@@ -104,84 +99,27 @@ pub(crate) fn daemon_health() -> Option<bool> {
         return None;
     }
 
-    let request = Request {
-        protocol_version: PROTOCOL_VERSION,
-        method: Method::GetHealth,
-        id: Some("daemon-health".to_string()),
-        params: None,
-    };
-
-    let response = send_request(request).ok()?;
-    Some(response.ok)
-}
-
-pub(crate) fn daemon_enabled() -> bool {
-    match env::var(ENABLE_ENV) {
-        Ok(value) => matches!(value.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
-        Err(_) => true,
+    let client = client()?;
+    match client.request(Method::GetHealth, None, None) {
+        Ok(_) => Some(true),
+        Err(err) if err.daemon_code().is_some() => Some(false),
+        Err(_) => None,
     }
 }
 
-fn socket_path() -> Result<PathBuf, String> {
-    if let Ok(path) = env::var(SOCKET_ENV) {
-        return Ok(PathBuf::from(path));
-    }
-    let home = dirs::home_dir().ok_or_else(|| "Home directory not found".to_string())?;
-    Ok(home.join(".capacitor").join(SOCKET_NAME))
+pub(crate) fn daemon_enabled() -> bool {
+    capacitor_daemon_client::daemon_enabled()
 }
 
-fn send_request(request: Request) -> Result<Response, String> {
-    let socket = socket_path()?;
-    let mut stream = UnixStream::connect(&socket)
-        .map_err(|err| format!("Failed to connect to daemon socket: {}", err))?;
-    let _ = stream.set_read_timeout(Some(Duration::from_millis(READ_TIMEOUT_MS)));
-    let _ = stream.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT_MS)));
-
-    serde_json::to_writer(&mut stream, &request)
-        .map_err(|err| format!("Failed to write request: {}", err))?;
-    stream
-        .write_all(b"\n")
-        .map_err(|err| format!("Failed to flush request: {}", err))?;
-    stream.flush().ok();
-
-    read_response(&mut stream)
+fn client() -> Option<DaemonClient> {
+    client_with_timeout(TIMEOUT_MS)
 }
 
-fn read_response(stream: &mut UnixStream) -> Result<Response, String> {
-    let mut buffer = Vec::new();
-    let mut chunk = [0u8; 4096];
-
-    loop {
-        match stream.read(&mut chunk) {
-            Ok(0) => break,
-            Ok(n) => {
-                buffer.extend_from_slice(&chunk[..n]);
-                if buffer.len() > MAX_REQUEST_BYTES {
-                    return Err("Response exceeded maximum size".to_string());
-                }
-                if chunk[..n].contains(&b'\n') {
-                    break;
-                }
-            }
-            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
-                return Err("Timed out waiting for daemon response".to_string());
-            }
-            Err(err) => return Err(format!("Failed to read response: {}", err)),
-        }
-    }
-
-    let newline_index = buffer.iter().position(|b| *b == b'\n');
-    let response_bytes = match newline_index {
-        Some(index) => &buffer[..index],
-        None => buffer.as_slice(),
-    };
-
-    if response_bytes.is_empty() {
-        return Err("Daemon response was empty".to_string());
-    }
-
-    serde_json::from_slice(response_bytes)
-        .map_err(|err| format!("Failed to parse response JSON: {}", err))
+fn client_with_timeout(timeout_ms: u64) -> Option<DaemonClient> {
+    let config = ClientConfig::from_env()
+        .ok()?
+        .with_timeout(Duration::from_millis(timeout_ms));
+    Some(DaemonClient::new(config))
 }
 
 fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
@@ -206,6 +144,7 @@ fn is_more_recent(left: &DaemonSessionRecord, right: &DaemonSessionRecord) -> bo
 #[cfg(test)]
 mod tests {
     use super::*;
+    use capacitor_daemon_client::{SessionState, ENABLE_ENV};
     use std::sync::{Mutex, OnceLock};
 
     static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
@@ -251,8 +190,10 @@ mod tests {
         DaemonSessionRecord {
             session_id: session_id.to_string(),
             pid: 123,
-            state: "working".to_string(),
+            state: SessionState::Working,
             cwd: project_path.to_string(),
+            project_id: project_path.to_string(),
+            workspace_id: String::new(),
             project_path: project_path.to_string(),
             updated_at: updated_at.to_string(),
             state_changed_at: updated_at.to_string(),
@@ -261,9 +202,29 @@ mod tests {
             tools_in_flight: 0,
             ready_reason: None,
             is_alive: Some(true),
+            active_subagents: Vec::new(),
+            pending_request: None,
         }
     }
 
+    #[test]
+    fn project_time_rows_flatten_state_seconds() {
+        let row: ProjectDayTime = serde_json::from_value(serde_json::json!({
+            "project_path": "/repo",
+            "day": "2026-01-31",
+            "seconds_in_state": { "working": 1800, "waiting": 60 },
+            "waiting_on_human_secs": 45
+        }))
+        .expect("parse row");
+
+        let accounting = to_time_accounting(row);
+        assert_eq!(accounting.working_secs, 1800);
+        assert_eq!(accounting.waiting_secs, 60);
+        assert_eq!(accounting.idle_secs, 0);
+        assert_eq!(accounting.waiting_on_human_secs, 45);
+        assert_eq!(accounting.day, "2026-01-31");
+    }
+
     #[test]
     fn sessions_snapshot_parses_entries() {
         let value = serde_json::json!([
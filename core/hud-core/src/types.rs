@@ -74,6 +74,22 @@ pub struct ProjectStats {
     pub last_activity: Option<String>,
 }
 
+/// Wall time one project's sessions spent in each state on one day.
+/// Sourced from the daemon's session transition history.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct ProjectTimeAccounting {
+    pub project_path: String,
+    /// Local calendar day, `YYYY-MM-DD`.
+    pub day: String,
+    pub working_secs: u64,
+    pub waiting_secs: u64,
+    pub ready_secs: u64,
+    pub idle_secs: u64,
+    pub compacting_secs: u64,
+    /// Time from a permission prompt or waiting notification until it was answered.
+    pub waiting_on_human_secs: u64,
+}
+
 /// Cached file metadata for cache invalidation.
 #[derive(Debug, Serialize, Deserialize, Clone, Default, uniffi::Record)]
 pub struct CachedFileInfo {
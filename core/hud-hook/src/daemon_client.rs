@@ -1,43 +1,56 @@
 //! Client helper for sending hook events to the capacitor daemon.
 //!
-//! The daemon is the only writer. Failures should be surfaced to the caller
-//! (no legacy file-based fallback).
+//! The daemon is the only writer. Hook events it cannot receive are kept in the
+//! event spool (`~/.capacitor/spool/`) and replayed, in order, ahead of the
+//! next hook event; the daemon also drains the spool when it starts. Shell cwd
+//! events are not spooled: the next prompt refreshes them anyway.
+//!
+//! A hook replays at most `HOOK_REPLAY_MAX_EVENTS` per invocation so a large
+//! backlog cannot stall the agent. While older events remain, the hook's own
+//! event joins the back of the spool instead of overtaking them.
 
-use capacitor_daemon_protocol::{
-    EventEnvelope, EventType, Method, Request, Response, MAX_REQUEST_BYTES, PROTOCOL_VERSION,
-};
+use capacitor_daemon_client::spool::{EventSpool, Flush};
+use capacitor_daemon_client::{ClientConfig, DaemonClient, RetryPolicy};
+use capacitor_daemon_protocol::{EventEnvelope, EventType};
 use chrono::Utc;
 use hud_core::state::{HookEvent, HookInput};
 use hud_core::ParentApp;
 use rand::RngCore;
-use std::env;
-use std::io::{Read, Write};
-use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
+use std::path::Path;
 use std::time::Duration;
 
-const ENABLE_ENV: &str = "CAPACITOR_DAEMON_ENABLED";
-const SOCKET_ENV: &str = "CAPACITOR_DAEMON_SOCKET";
-const SOCKET_NAME: &str = "daemon.sock";
-const READ_TIMEOUT_MS: u64 = 600;
-const WRITE_TIMEOUT_MS: u64 = 600;
+pub use capacitor_daemon_client::daemon_enabled;
+
+const TIMEOUT_MS: u64 = 600;
+const SEND_ATTEMPTS: u32 = 2;
 const RETRY_DELAY_MS: u64 = 50;
+/// Spooled events replayed ahead of a hook event; one `event_batch` round trip.
+const HOOK_REPLAY_MAX_EVENTS: usize = 100;
 const CAPABILITY_TOOL_USE_ID_CONSISTENCY: &str = "partial";
 
+/// How a hook event reached (or will reach) the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// The daemon was unavailable; the event waits in the spool.
+    Spooled,
+}
+
 pub fn send_handle_event(
     event: &HookEvent,
     hook_input: &HookInput,
     session_id: &str,
     pid: Option<u32>,
     cwd: &str,
-) -> bool {
+    home: &Path,
+) -> Result<Delivery, String> {
     if !daemon_enabled() {
-        return false;
+        return Err("Daemon disabled".to_string());
     }
 
     let event_type = match event_type_for_hook(event) {
         Some(event_type) => event_type,
-        None => return false,
+        None => return Err("Hook event has no daemon event type".to_string()),
     };
 
     let hook_input_file_path = hook_input
@@ -74,25 +87,75 @@ pub fn send_handle_event(
     let event_id = make_event_id(pid.unwrap_or(0));
     let recorded_at = Utc::now().to_rfc3339();
     let metadata = build_runtime_capability_metadata(Some(hook_input));
-    let build_envelope = || EventEnvelope {
-        event_id: event_id.clone(),
-        recorded_at: recorded_at.clone(),
+    let envelope = EventEnvelope {
+        event_id,
+        recorded_at,
         event_type,
         session_id: Some(session_id.to_string()),
         pid,
         cwd: Some(cwd.to_string()),
-        tool: tool.clone(),
-        file_path: file_path.clone(),
+        tool,
+        file_path,
         parent_app: None,
         tty: None,
         tmux_session: None,
         tmux_client_tty: None,
-        notification_type: notification_type.clone(),
+        notification_type,
         stop_hook_active,
-        metadata: metadata.clone(),
+        metadata,
     };
 
-    send_event_with_retry(build_envelope, "session event").is_ok()
+    deliver_or_spool(&envelope, &EventSpool::in_home(home))
+}
+
+/// Sends `event` unless older events are still spooled, in which case those
+/// are replayed first so the daemon sees hook events in order. If the daemon
+/// is unavailable the event joins the spool instead.
+fn deliver_or_spool(event: &EventEnvelope, spool: &EventSpool) -> Result<Delivery, String> {
+    let client = client()?;
+    let spool_event = |reason: &str| {
+        spool
+            .append(event)
+            .map(|_| {
+                tracing::info!(event_id = %event.event_id, reason, "Spooled hook event");
+                Delivery::Spooled
+            })
+            .map_err(|err| {
+                tracing::warn!(error = %err, "Failed to spool hook event");
+                err.to_string()
+            })
+    };
+
+    if !spool.is_empty() {
+        match spool.flush_some(&client, HOOK_REPLAY_MAX_EVENTS) {
+            Ok(Flush::Done {
+                replayed,
+                discarded,
+            }) => {
+                tracing::info!(replayed, discarded, "Replayed spooled hook events");
+            }
+            Ok(Flush::Busy) => return spool_event("spool replay in progress"),
+            Err(err) => {
+                tracing::warn!(error = %err, "Failed to replay spooled hook events");
+                return spool_event("spool replay failed");
+            }
+        }
+        if !spool.is_empty() {
+            return spool_event("spool backlog");
+        }
+    }
+
+    match client.send_event(event) {
+        Ok(_) => Ok(Delivery::Sent),
+        Err(err) if err.is_unavailable() => {
+            tracing::warn!(error = %err, "Daemon unavailable for session event");
+            spool_event("daemon unavailable")
+        }
+        Err(err) => {
+            tracing::warn!(error = %err, "Failed to send session event to daemon");
+            Err(err.to_string())
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -112,9 +175,9 @@ pub fn send_shell_cwd_event(
 
     let event_id = make_event_id(pid);
     let recorded_at = Utc::now().to_rfc3339();
-    let build_envelope = || EventEnvelope {
-        event_id: event_id.clone(),
-        recorded_at: recorded_at.clone(),
+    let envelope = EventEnvelope {
+        event_id,
+        recorded_at,
         event_type: EventType::ShellCwd,
         session_id: None,
         pid: Some(pid),
@@ -123,14 +186,14 @@ pub fn send_shell_cwd_event(
         file_path: None,
         parent_app: Some(parent_app_string(parent_app)),
         tty: Some(tty.to_string()),
-        tmux_session: tmux_session.clone(),
-        tmux_client_tty: tmux_client_tty.clone(),
+        tmux_session,
+        tmux_client_tty,
         notification_type: None,
         stop_hook_active: None,
-        metadata: build_shell_cwd_metadata(proc_start, tmux_pane.clone()),
+        metadata: build_shell_cwd_metadata(proc_start, tmux_pane),
     };
 
-    send_event_with_retry(build_envelope, "shell-cwd event")
+    send_event(&envelope, "shell-cwd event")
 }
 
 fn build_shell_cwd_metadata(
@@ -182,138 +245,33 @@ pub fn daemon_health() -> Option<bool> {
         return None;
     }
 
-    let request = Request {
-        protocol_version: PROTOCOL_VERSION,
-        method: Method::GetHealth,
-        id: Some("health-check".to_string()),
-        params: None,
-    };
-
-    let response = send_request(request).ok()?;
-    if !response.ok {
-        return Some(false);
+    let client = client().ok()?;
+    match client.health() {
+        Ok(health) => Some(health.is_ok()),
+        Err(err) if err.daemon_code().is_some() => Some(false),
+        Err(_) => None,
     }
-
-    let status = response
-        .data
-        .as_ref()
-        .and_then(|data| data.get("status"))
-        .and_then(|value| value.as_str());
-
-    Some(matches!(status, Some("ok")))
 }
 
-pub fn daemon_enabled() -> bool {
-    match env::var(ENABLE_ENV) {
-        Ok(value) => matches!(value.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
-        Err(_) => true,
-    }
-}
-
-fn socket_path() -> Result<PathBuf, String> {
-    if let Ok(path) = env::var(SOCKET_ENV) {
-        return Ok(PathBuf::from(path));
-    }
-    let home = dirs::home_dir().ok_or_else(|| "Home directory not found".to_string())?;
-    Ok(home.join(".capacitor").join(SOCKET_NAME))
-}
-
-fn send_event(event: EventEnvelope) -> Result<(), String> {
-    let request = Request {
-        protocol_version: PROTOCOL_VERSION,
-        method: Method::Event,
-        id: Some(event.event_id.clone()),
-        params: Some(
-            serde_json::to_value(event)
-                .map_err(|err| format!("Failed to serialize event: {}", err))?,
-        ),
-    };
-
-    let response = send_request(request)?;
-    if response.ok {
-        Ok(())
-    } else {
-        let message = response
-            .error
-            .map(|err| format!("{}: {}", err.code, err.message))
-            .unwrap_or_else(|| "Unknown daemon error".to_string());
-        Err(message)
-    }
-}
-
-fn send_event_with_retry<F>(mut build: F, label: &str) -> Result<(), String>
-where
-    F: FnMut() -> EventEnvelope,
-{
-    match send_event(build()) {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            tracing::warn!(error = %err, "Failed to send {} to daemon", label);
-            std::thread::sleep(Duration::from_millis(RETRY_DELAY_MS));
-            send_event(build()).map_err(|retry_err| {
-                tracing::warn!(
-                    error = %retry_err,
-                    "Retry failed sending {} to daemon",
-                    label
-                );
-                retry_err
-            })
-        }
-    }
+fn client() -> Result<DaemonClient, String> {
+    let config = ClientConfig::from_env()
+        .map_err(|err| err.to_string())?
+        .with_timeout(Duration::from_millis(TIMEOUT_MS))
+        .with_retry(RetryPolicy::new(
+            SEND_ATTEMPTS,
+            Duration::from_millis(RETRY_DELAY_MS),
+        ));
+    Ok(DaemonClient::new(config))
 }
 
-fn send_request(request: Request) -> Result<Response, String> {
-    let socket = socket_path()?;
-    let mut stream = UnixStream::connect(&socket)
-        .map_err(|err| format!("Failed to connect to daemon socket: {}", err))?;
-    let _ = stream.set_read_timeout(Some(Duration::from_millis(READ_TIMEOUT_MS)));
-    let _ = stream.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT_MS)));
-
-    serde_json::to_writer(&mut stream, &request)
-        .map_err(|err| format!("Failed to write request: {}", err))?;
-    stream
-        .write_all(b"\n")
-        .map_err(|err| format!("Failed to flush request: {}", err))?;
-    stream.flush().ok();
-
-    read_response(&mut stream)
-}
-
-fn read_response(stream: &mut UnixStream) -> Result<Response, String> {
-    let mut buffer = Vec::new();
-    let mut chunk = [0u8; 4096];
-
-    loop {
-        match stream.read(&mut chunk) {
-            Ok(0) => break,
-            Ok(n) => {
-                buffer.extend_from_slice(&chunk[..n]);
-                if buffer.len() > MAX_REQUEST_BYTES {
-                    return Err("Response exceeded maximum size".to_string());
-                }
-                if chunk[..n].contains(&b'\n') {
-                    break;
-                }
-            }
-            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
-                return Err("Timed out waiting for daemon response".to_string());
-            }
-            Err(err) => return Err(format!("Failed to read response: {}", err)),
-        }
-    }
-
-    let newline_index = buffer.iter().position(|b| *b == b'\n');
-    let response_bytes = match newline_index {
-        Some(index) => &buffer[..index],
-        None => buffer.as_slice(),
-    };
-
-    if response_bytes.is_empty() {
-        return Err("Daemon response was empty".to_string());
-    }
-
-    serde_json::from_slice(response_bytes)
-        .map_err(|err| format!("Failed to parse response JSON: {}", err))
+/// Sends with one retry. The retry resends the same event id, so the daemon
+/// drops it as a duplicate if the first attempt landed but the reply was lost.
+fn send_event(event: &EventEnvelope, label: &str) -> Result<(), String> {
+    let client = client()?;
+    client.send_event(event).map(|_| ()).map_err(|err| {
+        tracing::warn!(error = %err, "Failed to send {} to daemon", label);
+        err.to_string()
+    })
 }
 
 fn event_type_for_hook(event: &HookEvent) -> Option<EventType> {
@@ -356,12 +314,12 @@ fn parent_app_string(app: ParentApp) -> String {
 mod tests {
     use super::*;
     use crate::test_support::env_lock;
-    use capacitor_daemon_protocol::{Request, Response};
+    use capacitor_daemon_client::mock::{MockDaemon, MockReply};
+    use capacitor_daemon_client::{ENABLE_ENV, SOCKET_ENV};
     use std::sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
+        Arc,
     };
-    use std::time::Duration;
 
     struct EnvGuard {
         key: &'static str,
@@ -392,112 +350,53 @@ mod tests {
         }
     }
 
-    fn read_request(stream: &mut UnixStream) {
-        let mut buffer = Vec::new();
-        let mut chunk = [0u8; 1024];
-        loop {
-            match stream.read(&mut chunk) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buffer.extend_from_slice(&chunk[..n]);
-                    if buffer.contains(&b'\n') {
-                        break;
-                    }
-                }
-                Err(_) => break,
-            }
-        }
+    fn point_at(daemon: &MockDaemon) -> (EnvGuard, EnvGuard) {
+        (
+            EnvGuard::set(SOCKET_ENV, daemon.socket_path().to_str().unwrap()),
+            EnvGuard::set(ENABLE_ENV, "1"),
+        )
     }
 
-    fn read_request_id(stream: &mut UnixStream) -> Option<String> {
-        let mut buffer = Vec::new();
-        let mut chunk = [0u8; 1024];
-        loop {
-            match stream.read(&mut chunk) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buffer.extend_from_slice(&chunk[..n]);
-                    if buffer.contains(&b'\n') {
-                        break;
-                    }
-                }
-                Err(_) => return None,
-            }
-        }
+    fn accepting_daemon() -> MockDaemon {
+        MockDaemon::start(|_| MockReply::ok(serde_json::json!({"accepted": true})))
+    }
 
-        let newline_index = buffer.iter().position(|b| *b == b'\n');
-        let request_bytes = match newline_index {
-            Some(index) => &buffer[..index],
-            None => buffer.as_slice(),
-        };
-        let request: Request = serde_json::from_slice(request_bytes).ok()?;
-        request.id
+    fn temp_home() -> tempfile::TempDir {
+        tempfile::tempdir().expect("create temp home")
     }
 
-    fn read_request_event(stream: &mut UnixStream) -> Option<EventEnvelope> {
-        let mut buffer = Vec::new();
-        let mut chunk = [0u8; 1024];
-        loop {
-            match stream.read(&mut chunk) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buffer.extend_from_slice(&chunk[..n]);
-                    if buffer.contains(&b'\n') {
-                        break;
-                    }
-                }
-                Err(_) => return None,
-            }
-        }
+    fn prompt_input() -> HookInput {
+        serde_json::from_value(serde_json::json!({
+            "hook_event_name": "UserPromptSubmit",
+            "session_id": "session-1",
+            "cwd": "/repo"
+        }))
+        .expect("hook input")
+    }
 
-        let newline_index = buffer.iter().position(|b| *b == b'\n');
-        let request_bytes = match newline_index {
-            Some(index) => &buffer[..index],
-            None => buffer.as_slice(),
-        };
-        let request: Request = serde_json::from_slice(request_bytes).ok()?;
-        let params = request.params?;
-        serde_json::from_value(params).ok()
+    fn captured_event(daemon: &MockDaemon) -> EventEnvelope {
+        let request = daemon
+            .requests()
+            .into_iter()
+            .next()
+            .expect("captured request");
+        serde_json::from_value(request["params"].clone()).expect("captured event")
     }
 
     #[test]
     fn send_event_retries_after_daemon_error() {
         let _guard = env_lock();
 
-        let socket_dir = std::env::temp_dir().join(format!(
-            "hud-hook-test-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_millis(0))
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&socket_dir).unwrap();
-        let socket_path = socket_dir.join("daemon.sock");
-        let _ = std::fs::remove_file(&socket_path);
-
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
-
-        let attempt_count = std::sync::Arc::new(AtomicUsize::new(0));
-        let attempt_count_clone = attempt_count.clone();
-
-        let server = std::thread::spawn(move || {
-            for handled in 1..=2 {
-                let (mut stream, _) = listener.accept().expect("accept daemon request");
-                attempt_count_clone.fetch_add(1, Ordering::SeqCst);
-                read_request(&mut stream);
-                let response = if handled == 1 {
-                    Response::error(None, "test_error", "simulated")
-                } else {
-                    Response::ok(None, serde_json::json!({"status": "ok"}))
-                };
-                let mut payload = serde_json::to_vec(&response).unwrap();
-                payload.push(b'\n');
-                let _ = stream.write_all(&payload);
+        let attempt_count = Arc::new(AtomicUsize::new(0));
+        let attempt_count_clone = Arc::clone(&attempt_count);
+        let daemon = MockDaemon::start(move |_| {
+            if attempt_count_clone.fetch_add(1, Ordering::SeqCst) == 0 {
+                MockReply::error("test_error", "simulated")
+            } else {
+                MockReply::ok(serde_json::json!({"accepted": true}))
             }
         });
-
-        let _socket_guard = EnvGuard::set(SOCKET_ENV, socket_path.to_str().unwrap());
-        let _enabled_guard = EnvGuard::set(ENABLE_ENV, "1");
+        let _env = point_at(&daemon);
 
         let result = send_shell_cwd_event(
             4242,
@@ -511,9 +410,6 @@ mod tests {
         );
 
         assert!(result.is_ok(), "expected retry to succeed, got {result:?}");
-
-        server.join().unwrap();
-
         assert_eq!(attempt_count.load(Ordering::SeqCst), 2);
     }
 
@@ -521,39 +417,15 @@ mod tests {
     fn send_shell_cwd_retry_reuses_same_request_id_after_lost_response() {
         let _guard = env_lock();
 
-        let socket_dir = std::path::Path::new("/tmp").join(format!(
-            "hh-lost-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_millis(0))
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&socket_dir).unwrap();
-        let socket_path = socket_dir.join("daemon.sock");
-        let _ = std::fs::remove_file(&socket_path);
-
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
-
-        let attempt_ids: Arc<Mutex<Vec<Option<String>>>> = Arc::new(Mutex::new(Vec::new()));
-        let attempt_ids_clone = Arc::clone(&attempt_ids);
-
-        let server = std::thread::spawn(move || {
-            for handled in 1..=2 {
-                let (mut stream, _) = listener.accept().expect("accept daemon request");
-                let request_id = read_request_id(&mut stream);
-                attempt_ids_clone.lock().unwrap().push(request_id);
-
-                if handled == 2 {
-                    let response = Response::ok(None, serde_json::json!({"status": "ok"}));
-                    let mut payload = serde_json::to_vec(&response).unwrap();
-                    payload.push(b'\n');
-                    let _ = stream.write_all(&payload);
-                }
+        let attempt_count = AtomicUsize::new(0);
+        let daemon = MockDaemon::start(move |_| {
+            if attempt_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                MockReply::Close
+            } else {
+                MockReply::ok(serde_json::json!({"accepted": true}))
             }
         });
-
-        let _socket_guard = EnvGuard::set(SOCKET_ENV, socket_path.to_str().unwrap());
-        let _enabled_guard = EnvGuard::set(ENABLE_ENV, "1");
+        let _env = point_at(&daemon);
 
         let result = send_shell_cwd_event(
             4242,
@@ -567,13 +439,143 @@ mod tests {
         );
 
         assert!(result.is_ok());
-        server.join().unwrap();
 
-        let ids = attempt_ids.lock().unwrap();
+        let ids = daemon
+            .requests()
+            .into_iter()
+            .map(|request| request["id"].as_str().map(str::to_string))
+            .collect::<Vec<_>>();
         assert_eq!(ids.len(), 2);
         assert_eq!(ids[0], ids[1], "retry must reuse the same request/event id");
     }
 
+    #[test]
+    fn unreachable_daemon_spools_event_and_next_send_replays_it_first() {
+        let _guard = env_lock();
+        let home = temp_home();
+        let _enabled = EnvGuard::set(ENABLE_ENV, "1");
+        let missing = home.path().join("missing.sock");
+        let socket = EnvGuard::set(SOCKET_ENV, missing.to_str().unwrap());
+
+        let delivery = send_handle_event(
+            &HookEvent::UserPromptSubmit,
+            &prompt_input(),
+            "session-1",
+            Some(4242),
+            "/repo",
+            home.path(),
+        );
+        assert_eq!(delivery, Ok(Delivery::Spooled));
+        assert!(!EventSpool::in_home(home.path()).is_empty());
+        drop(socket);
+
+        let daemon = MockDaemon::start(|request| match request.method {
+            capacitor_daemon_protocol::Method::EventBatch => MockReply::ok(serde_json::json!({
+                "accepted": 1,
+                "duplicates": 0,
+                "rejected": 0,
+                "results": [{ "index": 0, "event_id": "spooled", "status": "accepted" }]
+            })),
+            _ => MockReply::ok(serde_json::json!({"accepted": true})),
+        });
+        let _env = point_at(&daemon);
+
+        let delivery = send_handle_event(
+            &HookEvent::UserPromptSubmit,
+            &prompt_input(),
+            "session-1",
+            Some(4242),
+            "/repo",
+            home.path(),
+        );
+        assert_eq!(delivery, Ok(Delivery::Sent));
+        assert!(EventSpool::in_home(home.path()).is_empty());
+
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["method"], "event_batch");
+        assert_eq!(requests[1]["method"], "event");
+        let spooled_id = &requests[0]["params"]["events"][0]["event_id"];
+        assert_ne!(spooled_id, &requests[1]["params"]["event_id"]);
+    }
+
+    #[test]
+    fn spool_backlog_is_replayed_in_bounded_slices() {
+        let _guard = env_lock();
+        let home = temp_home();
+        let spool = EventSpool::in_home(home.path());
+        let backlog = HOOK_REPLAY_MAX_EVENTS + 5;
+        for index in 0..backlog {
+            let event: EventEnvelope = serde_json::from_value(serde_json::json!({
+                "event_id": format!("spooled-{index}"),
+                "recorded_at": "2026-01-31T00:00:00Z",
+                "event_type": "user_prompt_submit",
+                "session_id": "session-1",
+                "pid": 4242,
+                "cwd": "/repo"
+            }))
+            .expect("event");
+            spool.append(&event).expect("append");
+        }
+
+        let daemon = MockDaemon::start(|request| match request.method {
+            capacitor_daemon_protocol::Method::EventBatch => MockReply::ok(serde_json::json!({
+                "accepted": 0,
+                "duplicates": 0,
+                "rejected": 0,
+                "results": []
+            })),
+            _ => MockReply::ok(serde_json::json!({"accepted": true})),
+        });
+        let _env = point_at(&daemon);
+
+        let delivery = send_handle_event(
+            &HookEvent::UserPromptSubmit,
+            &prompt_input(),
+            "session-1",
+            Some(4242),
+            "/repo",
+            home.path(),
+        );
+        assert_eq!(delivery, Ok(Delivery::Spooled));
+
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 1);
+        let replayed = requests[0]["params"]["events"].as_array().unwrap();
+        assert_eq!(replayed.len(), HOOK_REPLAY_MAX_EVENTS);
+        assert_eq!(replayed[0]["event_id"], "spooled-0");
+
+        let mut remaining = Vec::new();
+        spool
+            .flush_with(|events| {
+                remaining.extend(events.iter().map(|event| event.event_id.clone()));
+                Ok::<_, String>(())
+            })
+            .expect("drain");
+        assert_eq!(remaining.len(), 6);
+        assert_eq!(remaining[0], format!("spooled-{}", HOOK_REPLAY_MAX_EVENTS));
+        assert!(remaining[5].starts_with("evt-"), "hook event queued last");
+    }
+
+    #[test]
+    fn daemon_rejection_is_not_spooled() {
+        let _guard = env_lock();
+        let home = temp_home();
+        let daemon = MockDaemon::start(|_| MockReply::error("invalid_params", "bad event"));
+        let _env = point_at(&daemon);
+
+        let delivery = send_handle_event(
+            &HookEvent::UserPromptSubmit,
+            &prompt_input(),
+            "session-1",
+            Some(4242),
+            "/repo",
+            home.path(),
+        );
+        assert!(delivery.is_err());
+        assert!(EventSpool::in_home(home.path()).is_empty());
+    }
+
     #[test]
     fn daemon_enabled_defaults_to_true_when_env_missing() {
         let _guard = env_lock();
@@ -599,33 +601,8 @@ mod tests {
     fn send_handle_event_includes_runtime_capability_handshake_and_hook_metadata() {
         let _guard = env_lock();
 
-        let socket_dir = std::path::Path::new("/tmp").join(format!(
-            "hh-capability-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_millis(0))
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&socket_dir).unwrap();
-        let socket_path = socket_dir.join("daemon.sock");
-        let _ = std::fs::remove_file(&socket_path);
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
-
-        let captured = Arc::new(Mutex::new(None::<EventEnvelope>));
-        let captured_clone = Arc::clone(&captured);
-        let server = std::thread::spawn(move || {
-            if let Ok((mut stream, _)) = listener.accept() {
-                let event = read_request_event(&mut stream);
-                *captured_clone.lock().unwrap() = event;
-                let response = Response::ok(None, serde_json::json!({"status": "ok"}));
-                let mut payload = serde_json::to_vec(&response).unwrap();
-                payload.push(b'\n');
-                let _ = stream.write_all(&payload);
-            }
-        });
-
-        let _socket_guard = EnvGuard::set(SOCKET_ENV, socket_path.to_str().unwrap());
-        let _enabled_guard = EnvGuard::set(ENABLE_ENV, "1");
+        let daemon = accepting_daemon();
+        let _env = point_at(&daemon);
         let event = HookEvent::PostToolUse {
             tool_name: Some("Read".to_string()),
             file_path: Some("src/main.rs".to_string()),
@@ -657,10 +634,11 @@ mod tests {
             "session-1",
             Some(4242),
             "/repo",
-        ));
-        server.join().unwrap();
+            temp_home().path(),
+        )
+        .is_ok());
 
-        let event = captured.lock().unwrap().take().expect("captured event");
+        let event = captured_event(&daemon);
         let metadata = event.metadata.expect("metadata");
         let caps = metadata
             .get("capabilities")
@@ -724,33 +702,8 @@ mod tests {
     fn send_permission_request_event_includes_tool_and_file_path() {
         let _guard = env_lock();
 
-        let socket_dir = std::path::Path::new("/tmp").join(format!(
-            "hh-permission-request-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_millis(0))
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&socket_dir).unwrap();
-        let socket_path = socket_dir.join("daemon.sock");
-        let _ = std::fs::remove_file(&socket_path);
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
-
-        let captured = Arc::new(Mutex::new(None::<EventEnvelope>));
-        let captured_clone = Arc::clone(&captured);
-        let server = std::thread::spawn(move || {
-            if let Ok((mut stream, _)) = listener.accept() {
-                let event = read_request_event(&mut stream);
-                *captured_clone.lock().unwrap() = event;
-                let response = Response::ok(None, serde_json::json!({"status": "ok"}));
-                let mut payload = serde_json::to_vec(&response).unwrap();
-                payload.push(b'\n');
-                let _ = stream.write_all(&payload);
-            }
-        });
-
-        let _socket_guard = EnvGuard::set(SOCKET_ENV, socket_path.to_str().unwrap());
-        let _enabled_guard = EnvGuard::set(ENABLE_ENV, "1");
+        let daemon = accepting_daemon();
+        let _env = point_at(&daemon);
 
         let event = HookEvent::PermissionRequest;
         let hook_input: HookInput = serde_json::from_value(serde_json::json!({
@@ -770,10 +723,11 @@ mod tests {
             "session-1",
             Some(4242),
             "/repo",
-        ));
-        server.join().unwrap();
+            temp_home().path(),
+        )
+        .is_ok());
 
-        let event = captured.lock().unwrap().take().expect("captured event");
+        let event = captured_event(&daemon);
         assert_eq!(event.event_type, EventType::PermissionRequest);
         assert_eq!(event.tool.as_deref(), Some("Edit"));
         assert_eq!(event.file_path.as_deref(), Some("src/main.rs"));
@@ -783,33 +737,8 @@ mod tests {
     fn send_shell_cwd_event_includes_runtime_capability_handshake() {
         let _guard = env_lock();
 
-        let socket_dir = std::path::Path::new("/tmp").join(format!(
-            "hh-capability-shell-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_millis(0))
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&socket_dir).unwrap();
-        let socket_path = socket_dir.join("daemon.sock");
-        let _ = std::fs::remove_file(&socket_path);
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
-
-        let captured = Arc::new(Mutex::new(None::<EventEnvelope>));
-        let captured_clone = Arc::clone(&captured);
-        let server = std::thread::spawn(move || {
-            if let Ok((mut stream, _)) = listener.accept() {
-                let event = read_request_event(&mut stream);
-                *captured_clone.lock().unwrap() = event;
-                let response = Response::ok(None, serde_json::json!({"status": "ok"}));
-                let mut payload = serde_json::to_vec(&response).unwrap();
-                payload.push(b'\n');
-                let _ = stream.write_all(&payload);
-            }
-        });
-
-        let _socket_guard = EnvGuard::set(SOCKET_ENV, socket_path.to_str().unwrap());
-        let _enabled_guard = EnvGuard::set(ENABLE_ENV, "1");
+        let daemon = accepting_daemon();
+        let _env = point_at(&daemon);
         assert!(send_shell_cwd_event(
             4242,
             "/repo",
@@ -821,9 +750,8 @@ mod tests {
             None
         )
         .is_ok());
-        server.join().unwrap();
 
-        let event = captured.lock().unwrap().take().expect("captured event");
+        let event = captured_event(&daemon);
         let metadata = event.metadata.expect("metadata");
         let caps = metadata
             .get("capabilities")
@@ -849,33 +777,8 @@ mod tests {
     fn send_shell_cwd_event_includes_proc_start_and_tmux_pane_metadata() {
         let _guard = env_lock();
 
-        let socket_dir = std::path::Path::new("/tmp").join(format!(
-            "hh-shell-metadata-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_millis(0))
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&socket_dir).unwrap();
-        let socket_path = socket_dir.join("daemon.sock");
-        let _ = std::fs::remove_file(&socket_path);
-        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
-
-        let captured = Arc::new(Mutex::new(None::<EventEnvelope>));
-        let captured_clone = Arc::clone(&captured);
-        let server = std::thread::spawn(move || {
-            if let Ok((mut stream, _)) = listener.accept() {
-                let event = read_request_event(&mut stream);
-                *captured_clone.lock().unwrap() = event;
-                let response = Response::ok(None, serde_json::json!({"status": "ok"}));
-                let mut payload = serde_json::to_vec(&response).unwrap();
-                payload.push(b'\n');
-                let _ = stream.write_all(&payload);
-            }
-        });
-
-        let _socket_guard = EnvGuard::set(SOCKET_ENV, socket_path.to_str().unwrap());
-        let _enabled_guard = EnvGuard::set(ENABLE_ENV, "1");
+        let daemon = accepting_daemon();
+        let _env = point_at(&daemon);
         assert!(send_shell_cwd_event(
             4242,
             "/repo",
@@ -887,9 +790,8 @@ mod tests {
             Some("%1".to_string()),
         )
         .is_ok());
-        server.join().unwrap();
 
-        let event = captured.lock().unwrap().take().expect("captured event");
+        let event = captured_event(&daemon);
         let metadata = event.metadata.expect("metadata");
         assert_eq!(
             metadata.get("proc_start").and_then(|value| value.as_u64()),
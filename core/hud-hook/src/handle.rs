@@ -16,6 +16,7 @@
 //! SessionEnd             → removes session record
 //! ```
 
+use crate::daemon_client::Delivery;
 use chrono::Utc;
 use fs_err as fs;
 use hud_core::state::{HookEvent, HookInput};
@@ -109,24 +110,36 @@ fn handle_hook_input_with_home(hook_input: HookInput, home: &Path) -> Result<(),
     let claude_pid = std::process::id();
     let session_pid = resolve_session_pid(get_ppid(), claude_pid);
 
-    let daemon_sent = crate::daemon_client::send_handle_event(
+    match crate::daemon_client::send_handle_event(
         &event,
         &hook_input,
         &session_id,
         session_pid,
         &cwd,
-    );
-    if daemon_sent {
-        touch_heartbeat(home);
-        tracing::debug!(
-            event = ?hook_input.hook_event_name,
-            session = %session_id,
-            "Daemon accepted event"
-        );
-        return Ok(());
+        home,
+    ) {
+        Ok(Delivery::Sent) => {
+            touch_heartbeat(home);
+            tracing::debug!(
+                event = ?hook_input.hook_event_name,
+                session = %session_id,
+                "Daemon accepted event"
+            );
+            Ok(())
+        }
+        Ok(Delivery::Spooled) => {
+            tracing::debug!(
+                event = ?hook_input.hook_event_name,
+                session = %session_id,
+                "Daemon unavailable; event spooled"
+            );
+            Ok(())
+        }
+        Err(err) => {
+            tracing::debug!(error = %err, "Hook event was not delivered");
+            Err("Failed to send hook event to daemon".to_string())
+        }
     }
-
-    Err("Failed to send hook event to daemon".to_string())
 }
 
 #[derive(Debug, PartialEq)]